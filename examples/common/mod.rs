@@ -0,0 +1,141 @@
+// Command line options shared by the examples, so every one of them can be run as a test of the engine:
+//
+//     cargo run --example lighting -- --frames 120 --screenshot target/lighting.png
+//     cargo run --example lighting -- --frames 120 --compare examples/reference/lighting.png
+//
+// `--frames N` renders N frames with a fixed 1/60s step & closes the window, `--screenshot PATH` saves the last one.
+// `--compare REFERENCE` runs the example again in a child process & compares its last frame with REFERENCE:
+// the exit code is 1 if their mean difference is over `--tolerance` (0.0 ~ 1.0, 0.01 by default).
+// The other arguments are left to the example. `tests/examples.rs` compares all of them with `examples/reference/`.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use eyengine::AppConfig;
+
+// seconds per frame of the automated runs
+const FRAME_DELTA: f32 = 1.0 / 60.0;
+// frames rendered by `--compare` without `--frames`
+const DEFAULT_FRAMES: u64 = 60;
+
+pub struct Harness {
+    frames: Option<u64>,
+    screenshot: Option<PathBuf>,
+    // the arguments which aren't the harness's
+    args: Vec<String>
+}
+
+impl Harness {
+    // Read the options of the command line.
+    // With `--compare`, the example is run & compared in a child process, then this process exits with its result.
+    pub fn from_args() -> Self {
+        match Self::parse(std::env::args().skip(1).collect()) {
+            Ok((harness, None)) => harness,
+            Ok((harness, Some((reference, tolerance)))) => {
+                let code = match harness.compare(&reference, tolerance) {
+                    Ok(true) => 0,
+                    Ok(false) => 1,
+                    Err(error) => {
+                        eprintln!("{:?}", error);
+                        2
+                    }
+                };
+                std::process::exit(code);
+            },
+            Err(error) => {
+                eprintln!("{:?}", error);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // the harness & the reference to compare with, if any
+    fn parse(args: Vec<String>) -> Result<(Self, Option<(PathBuf, f32)>)> {
+        let mut harness = Self {
+            frames: None,
+            screenshot: None,
+            args: Vec::new()
+        };
+        let mut reference = None;
+        let mut tolerance = 0.01;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{} expects a value", arg));
+            match arg.as_str() {
+                "--frames" => harness.frames = Some(value()?.parse().context("--frames expects a number of frames")?),
+                "--screenshot" => harness.screenshot = Some(PathBuf::from(value()?)),
+                "--compare" => reference = Some(PathBuf::from(value()?)),
+                "--tolerance" => tolerance = value()?.parse().context("--tolerance expects a number")?,
+                _ => harness.args.push(arg)
+            }
+        }
+        Ok((harness, reference.map(|reference| (reference, tolerance))))
+    }
+
+    // the arguments left to the example, e.g. a model to load
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    // `config` with the frame limit & the screenshot of the command line
+    pub fn config(&self, config: AppConfig) -> AppConfig {
+        let config = match self.frames {
+            Some(frames) => config.with_frame_limit(Some(frames)).with_fixed_frame_delta(Some(FRAME_DELTA)),
+            None => config
+        };
+        match &self.screenshot {
+            Some(screenshot) => config.with_final_screenshot(screenshot),
+            None => config
+        }
+    }
+
+    // run this example in a child process & compare its last frame with `reference`
+    fn compare(&self, reference: &Path, tolerance: f32) -> Result<bool> {
+        let screenshot = self.screenshot
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join(format!("eyengine-{}.png", std::process::id())));
+        let executable = std::env::current_exe().context("failed to find the example's executable")?;
+        let status = Command::new(executable)
+            .arg("--frames")
+            .arg(self.frames.unwrap_or(DEFAULT_FRAMES).to_string())
+            .arg("--screenshot")
+            .arg(&screenshot)
+            .args(&self.args)
+            .status()
+            .context("failed to run the example")?;
+        if !status.success() {
+            bail!("the example failed: {}", status);
+        }
+
+        let difference = image_difference(&screenshot, reference)?;
+        let matches = difference <= tolerance;
+        println!(
+            "{} {} {}: mean difference {:.4} (tolerance {})",
+            screenshot.display(),
+            if matches { "matches" } else { "differs from" },
+            reference.display(),
+            difference,
+            tolerance
+        );
+        Ok(matches)
+    }
+}
+
+// 0.0 ~ 1.0 mean difference of the color channels of two images of the same size
+fn image_difference(a: &Path, b: &Path) -> Result<f32> {
+    let load = |path: &Path| -> Result<image::RgbaImage> {
+        Ok(image::open(path).with_context(|| format!("failed to load {}", path.display()))?.to_rgba8())
+    };
+    let (a, b) = (load(a)?, load(b)?);
+    if a.dimensions() != b.dimensions() {
+        bail!("the sizes differ: {:?} & {:?}", a.dimensions(), b.dimensions());
+    }
+    let total = a.pixels()
+        .zip(b.pixels())
+        .flat_map(|(a, b)| (0..3).map(move |channel| (a[channel] as i32 - b[channel] as i32).unsigned_abs() as u64))
+        .sum::<u64>();
+    let channels = (a.width() as u64 * a.height() as u64 * 3).max(1);
+    Ok(total as f32 / channels as f32 / 255.0)
+}
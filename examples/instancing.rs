@@ -0,0 +1,57 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::Harness;
+use eyengine::{AppConfig, Application, Input, Material, Mesh, Scene, Time, Transform};
+use legion::*;
+use nalgebra::Vector3;
+
+// cubes per side of the grid
+const GRID_SIZE: i32 = 40;
+
+// a cube of the grid, bobbing with a wave going through the grid
+struct Wave {
+    phase: f32
+}
+
+fn wave_system() -> impl systems::Runnable {
+    SystemBuilder::new("wave")
+        .read_resource::<Time>()
+        .with_query(<(&mut Transform, &Wave)>::query())
+        .build(|_, world, time, query| {
+            for (transform, wave) in query.iter_mut(world) {
+                let height = (wave.phase + time.elapsed() * 2.0).sin();
+                transform.translation.y = height * 0.5;
+                transform.scale.y = 1.0 + height * 0.4;
+            }
+        })
+}
+
+// Thousands of entities sharing a mesh & a material: their transforms go to a single instance buffer every frame.
+struct InstancingApp;
+
+impl Application for InstancingApp {
+    fn update(&self, _time: &Time, _input: &Input) {}
+}
+
+fn main() {
+    let harness = Harness::from_args();
+
+    let schedule = Schedule::builder().add_system(wave_system()).build();
+    let mut scene = Scene::new(World::default(), schedule);
+    let cube = scene.add_mesh(&Rc::new(Mesh::cube(0.4)));
+    let material = scene.add_material(&Rc::new(Material::new("cube").with_specular(0.5, 32.0)));
+    let half = GRID_SIZE as f32 * 0.5;
+    for x in 0..GRID_SIZE {
+        for z in 0..GRID_SIZE {
+            let position = Vector3::new(x as f32 - half, 0.0, z as f32 - half) * 0.5;
+            // a ring going out from the center
+            let phase = -position.norm();
+            scene.world.push((Transform::from_translation(position), cube, material, Wave { phase }));
+        }
+    }
+
+    let config = AppConfig::new("EyeEngine - instancing").with_msaa_samples(4);
+    InstancingApp.start_with_scene(harness.config(config), scene);
+}
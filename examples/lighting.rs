@@ -0,0 +1,87 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::Harness;
+use eyengine::{point_light_candela, spot_light_candela, AppConfig, Application, Environment, Exposure, Input, Light, Material, Mesh, Scene, Time, Transform};
+use legion::*;
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+
+// point lights circling the origin, `speed` in radians per second
+struct Orbit {
+    radius: f32,
+    height: f32,
+    speed: f32,
+    phase: f32
+}
+
+fn orbit_system() -> impl systems::Runnable {
+    SystemBuilder::new("orbit")
+        .read_resource::<Time>()
+        .with_query(<(&mut Transform, &Orbit)>::query())
+        .build(|_, world, time, query| {
+            for (transform, orbit) in query.iter_mut(world) {
+                let angle = orbit.phase + orbit.speed * time.elapsed();
+                transform.translation = Vector3::new(angle.cos() * orbit.radius, orbit.height, angle.sin() * orbit.radius);
+            }
+        })
+}
+
+// Point, spot & directional lights at night, on a few shiny & matte shapes.
+struct LightingApp {
+    environment: Environment
+}
+
+impl Application for LightingApp {
+    fn update(&self, _time: &Time, _input: &Input) {}
+
+    fn environment(&self) -> Environment {
+        self.environment
+    }
+}
+
+fn main() {
+    let harness = Harness::from_args();
+
+    let schedule = Schedule::builder().add_system(orbit_system()).build();
+    let mut scene = Scene::new(World::default(), schedule);
+
+    // a flat box as the ground
+    let cube = scene.add_mesh(&Rc::new(Mesh::cube(1.0)));
+    let sphere = scene.add_mesh(&Rc::new(Mesh::sphere(0.5, 32, 16)));
+    let ground = scene.add_material(&Rc::new(Material::new("ground").with_tint([0.5, 0.5, 0.5, 1.0])));
+    let matte = scene.add_material(&Rc::new(Material::new("matte").with_tint([0.9, 0.3, 0.2, 1.0])));
+    let shiny = scene.add_material(&Rc::new(Material::new("shiny").with_tint([0.2, 0.4, 0.9, 1.0]).with_specular(1.0, 128.0)));
+    scene.world.push((Transform::from_translation(Vector3::new(0.0, -0.05, 0.0)).with_scale(Vector3::new(12.0, 0.1, 12.0)), cube, ground));
+    for (i, x) in [-2.0, 0.0, 2.0].into_iter().enumerate() {
+        let material = if i % 2 == 0 { shiny } else { matte };
+        scene.world.push((Transform::from_translation(Vector3::new(x, 0.5, 0.0)), sphere, material));
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), x * 0.4);
+        scene.world.push((Transform::from_translation(Vector3::new(x, 0.5, -2.0)).with_rotation(rotation), cube, material));
+    }
+
+    // warm & cold bulbs circling the shapes
+    for (color, phase) in [([1.0, 0.6, 0.3], 0.0), ([0.3, 0.6, 1.0], std::f32::consts::PI)] {
+        let orbit = Orbit { radius: 3.0, height: 1.5, speed: 0.8, phase };
+        scene.world.push((Transform::new(), Light::point(color, point_light_candela(3000.0), 8.0), orbit));
+    }
+    // a spot light from above & moonlight
+    let (inner_angle, outer_angle) = (0.3, 0.45);
+    let mut spot = Transform::from_translation(Vector3::new(0.0, 4.0, 2.0));
+    spot.look_at(&Point3::new(0.0, 0.0, 0.0), &Vector3::y());
+    let spot_light = Light::spot(spot.forward(), [1.0, 1.0, 0.9], spot_light_candela(4000.0, outer_angle), 10.0, inner_angle, outer_angle);
+    scene.world.push((spot, spot_light));
+    scene.world.push((Transform::new(), Light::directional(Vector3::new(0.5, -1.0, 0.3), [0.6, 0.7, 1.0], 0.3)));
+
+    // no sun, a dim sky & a camera exposed for the night
+    let environment = Environment {
+        sky_color: [0.01, 0.01, 0.03],
+        sun_intensity: 0.0,
+        ambient_intensity: 0.5,
+        exposure: Exposure::Ev100(4.0),
+        ..Environment::new()
+    };
+    let app = LightingApp { environment };
+    let config = AppConfig::new("EyeEngine - lighting").with_msaa_samples(4);
+    app.start_with_scene(harness.config(config), scene);
+}
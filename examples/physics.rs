@@ -0,0 +1,94 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use common::Harness;
+use eyengine::{AppConfig, Application, Cloth, ClothCollider, DebrisSettings, Environment, FracturedMesh, Input, Material, Mesh, MeshDraw, Scene, Time, Transform};
+use legion::*;
+use nalgebra::{Matrix4, Point3, Vector3};
+
+// seconds between two crates breaking
+const BREAK_INTERVAL: f32 = 1.5;
+
+// Cloth blown by the wind over a ball, next to crates breaking into debris one after the other.
+struct PhysicsApp {
+    ball: Rc<Mesh>,
+    ball_center: Vector3<f32>,
+    environment: Environment,
+    // the crates left to break, in order
+    crates: RefCell<Vec<Entity>>
+}
+
+impl Application for PhysicsApp {
+    fn update(&self, _time: &Time, _input: &Input) {}
+
+    fn update_scene(&self, scene: &mut Scene) {
+        let elapsed = match scene.resources.get::<Time>() {
+            Some(time) => time.elapsed(),
+            None => return
+        };
+        let mut crates = self.crates.borrow_mut();
+        let broken = (elapsed / BREAK_INTERVAL) as usize;
+        while crates.len() > 3usize.saturating_sub(broken) {
+            let entity = crates.remove(0);
+            // hit from the front, near the ground
+            scene.destroy(entity, Point3::new(0.0, 0.0, 1.0), 4.0);
+        }
+    }
+
+    fn environment(&self) -> Environment {
+        self.environment
+    }
+
+    fn draw_meshes(&self, mesh_draws: &mut Vec<MeshDraw>) {
+        mesh_draws.push(MeshDraw::new(&self.ball, Matrix4::new_translation(&self.ball_center)));
+    }
+}
+
+fn main() {
+    let harness = Harness::from_args();
+
+    // a curtain hanging in front of a ball, blown onto it
+    let ball_radius = 0.5;
+    let ball_center = Vector3::new(-2.0, 1.0, -0.6);
+    let mut curtain = Cloth::new(Vector3::new(-3.0, 2.0, 0.0), 2.0, 1.6, 20, 16);
+    curtain.pin_top_row();
+    curtain.add_collider(ClothCollider::Sphere { center: ball_center, radius: ball_radius });
+    curtain.add_collider(ClothCollider::Plane { normal: Vector3::y(), distance: 0.0 });
+    let environment = Environment {
+        wind_direction: Vector3::new(0.0, 0.0, -1.0),
+        wind_strength: 6.0,
+        ..Environment::new()
+    };
+    curtain.wind = environment.wind_velocity();
+
+    // three crates stacked, breaking from the top
+    let mut scene = Scene::default();
+    let crate_mesh = Mesh::cube(1.0);
+    let fractured = FracturedMesh::new(&crate_mesh, 12, 7);
+    let destructible = scene.add_destructible(&fractured).with_debris(DebrisSettings {
+        lifetime: 4.0,
+        ..DebrisSettings::default()
+    });
+    let crate_handle = scene.add_mesh(&Rc::new(crate_mesh));
+    let wood = scene.add_material(&Rc::new(Material::new("wood").with_tint([0.7, 0.5, 0.3, 1.0])));
+    let mut crates = (0..3)
+        .map(|level| {
+            let transform = Transform::from_translation(Vector3::new(1.5, 0.5 + level as f32, 0.0));
+            scene.world.push((transform, crate_handle, wood, destructible.clone()))
+        })
+        .collect::<Vec<_>>();
+    crates.reverse();
+    // stepped & drawn by the scene
+    scene.world.push((curtain,));
+
+    let app = PhysicsApp {
+        ball: Rc::new(Mesh::sphere(ball_radius, 32, 16)),
+        ball_center,
+        environment,
+        crates: RefCell::new(crates)
+    };
+    let config = AppConfig::new("EyeEngine - physics").with_msaa_samples(4);
+    app.start_with_scene(harness.config(config), scene);
+}
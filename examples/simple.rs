@@ -1,19 +1,162 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use legion::*;
-use eyengine::{Application, Transform};
+use common::Harness;
+use eyengine::{AppConfig, Application, Cloth, DayNightCycle, DebugDraw, Environment, Fog, Input, LensFlare, LineWidth, Mesh, MeshDraw, Model, Polyline, Scene, SceneLoad, SceneLoader, Time, Transform, Transition};
+
+// radians per second around the Y axis
+struct Spin(f32);
+
+fn spin_system() -> impl systems::Runnable {
+    SystemBuilder::new("spin")
+        .read_resource::<Time>()
+        .with_query(<(&mut Transform, &Spin)>::query())
+        .build(|_, world, time, query| {
+            for (transform, spin) in query.iter_mut(world) {
+                transform.rotation *= nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::y_axis(), spin.0 * time.delta());
+            }
+        })
+}
 
-struct SimpleApp;
+struct SimpleApp {
+    flag: RefCell<Cloth>,
+    day_night: RefCell<DayNightCycle>,
+    environment: RefCell<Environment>,
+    sphere: Rc<Mesh>,
+    model: RefCell<Option<Model>>,
+    scene_load: RefCell<Option<SceneLoad>>,
+    transition: RefCell<Transition>
+}
 
 impl Application for SimpleApp {
-    fn update(&self) {}
+    fn fixed_update(&self, time: &Time) {
+        let wind_velocity = self.environment.borrow().wind_velocity();
+        self.flag.borrow_mut().step(time.fixed_timestep(), wind_velocity);
+    }
+
+    fn update(&self, time: &Time, _input: &Input) {
+        let mut day_night = self.day_night.borrow_mut();
+        for event in day_night.advance(time.delta()) {
+            println!("{}!", event);
+        }
+        day_night.apply(&mut self.environment.borrow_mut());
+
+        // keep the screen covered until the model is loaded
+        let mut transition = self.transition.borrow_mut();
+        if let Some(scene_load) = self.scene_load.borrow_mut().as_mut() {
+            transition.set_loading(Some(scene_load.progress().fraction()));
+            match scene_load.poll() {
+                Some(Ok(mut scene)) => *self.model.borrow_mut() = scene.take_model("model"),
+                Some(Err(error)) => eprintln!("{:?}", error),
+                None => {}
+            }
+            if scene_load.is_finished() {
+                transition.set_loading(None);
+                transition.reveal();
+            }
+        }
+        transition.advance(time.delta());
+    }
+
+    fn environment(&self) -> Environment {
+        *self.environment.borrow()
+    }
+
+    fn transition(&self) -> Option<Transition> {
+        Some(*self.transition.borrow())
+    }
+
+    fn lens_flare(&self) -> Option<LensFlare> {
+        Some(LensFlare::sun())
+    }
+
+    fn draw_meshes(&self, mesh_draws: &mut Vec<MeshDraw>) {
+        // a ball next to the flag pole, the crate is an entity of the scene
+        let translation = |x: f32, y: f32, z: f32| nalgebra::Matrix4::new_translation(&nalgebra::Vector3::new(x, y, z));
+        mesh_draws.push(MeshDraw::new(&self.sphere, translation(1.5, 0.5, 1.0)));
+        if let Some(model) = self.model.borrow().as_ref() {
+            model.draw(translation(0.0, 0.0, 3.0), mesh_draws);
+        }
+    }
+
+    fn draw_polylines(&self, polylines: &mut Vec<Polyline>) {
+        // a circle guide around the origin, 2px wide at any distance
+        let points = (0..64).map(|i| {
+            let angle = i as f32 / 64.0 * std::f32::consts::TAU;
+            [angle.cos() * 3.0, 0.0, angle.sin() * 3.0]
+        }).collect();
+        polylines.push(Polyline::new(points, [1.0, 0.8, 0.0, 1.0], LineWidth::Pixels(2.0)).closed());
+        // a flag pole, 0.05 unit wide
+        polylines.push(Polyline::segment([0.0, 0.0, 0.0], [0.0, 1.5, 0.0], [0.0, 1.0, 0.0, 1.0], LineWidth::World(0.05)));
+
+        // the flag as a wireframe, row by row and column by column
+        let flag = self.flag.borrow();
+        let (columns, rows) = (flag.columns(), flag.rows());
+        let point = |column: usize, row: usize| -> [f32; 3] { flag.positions()[row * columns + column].into() };
+        for row in 0..rows {
+            let points = (0..columns).map(|column| point(column, row)).collect();
+            polylines.push(Polyline::new(points, [1.0, 1.0, 1.0, 1.0], LineWidth::Pixels(1.0)));
+        }
+        for column in 0..columns {
+            let points = (0..rows).map(|row| point(column, row)).collect();
+            polylines.push(Polyline::new(points, [1.0, 1.0, 1.0, 1.0], LineWidth::Pixels(1.0)));
+        }
+    }
+
+    fn debug_draw(&self, debug_draw: &mut DebugDraw) {
+        // bounds of the tree instances, toggled with F1 & F2
+        debug_draw.aabb([-5.5, -0.5, -5.5], [4.5, 0.5, 4.5], [0.0, 1.0, 1.0, 1.0]);
+        debug_draw.sphere([-0.5, 0.0, -0.5], 7.1, [1.0, 0.0, 1.0, 1.0]);
+    }
 }
 
 fn main() {
-    let app = SimpleApp {};
+    let harness = Harness::from_args();
+
+    // a flag attached to its pole by its left side
+    let mut flag = Cloth::new(nalgebra::Vector3::new(0.0, 1.5, 0.0), 1.0, 0.6, 16, 10);
+    for row in 0..flag.rows() {
+        flag.pin(0, row);
+    }
+    // a windy & slightly foggy day
+    let environment = Environment {
+        wind_direction: nalgebra::Vector3::new(2.0, 0.0, 1.0),
+        wind_strength: 4.0,
+        fog: Fog::new([0.1, 0.2, 0.3], 0.08),
+        ..Environment::new()
+    };
+    // a whole day in 2 minutes
+    let day_night = DayNightCycle::new(5.0, 120.0)
+        .with_event(6.0, "dawn")
+        .with_event(18.0, "dusk");
+    // an OBJ model given on the command line, e.g. `cargo run --example simple -- model.obj`
+    let scene_load = harness.args().first().map(|path| SceneLoader::new().with_model("model", path).start());
+    // fade in from black, once loaded
+    let mut transition = Transition::fade().with_coverage(1.0);
+    if scene_load.is_some() {
+        transition.cover();
+    }
+    let app = SimpleApp {
+        flag: RefCell::new(flag),
+        day_night: RefCell::new(day_night),
+        environment: RefCell::new(environment),
+        sphere: Rc::new(Mesh::sphere(0.5, 32, 16)),
+        model: RefCell::new(None),
+        scene_load: RefCell::new(scene_load),
+        transition: RefCell::new(transition)
+    };
 
-    // Create a world to store our entities
-    let mut world = World::default();
-    let _entity = world.push(((), vec![Transform::new()]));
+    // Create a world to store our entities: a spinning crate next to the flag pole
+    let schedule = Schedule::builder().add_system(spin_system()).build();
+    let mut scene = Scene::new(World::default(), schedule);
+    let cube = scene.add_mesh(&Rc::new(Mesh::cube(1.0)));
+    let crate_transform = Transform::from_translation(nalgebra::Vector3::new(-1.5, 0.5, 1.0));
+    scene.world.push((crate_transform, cube, Spin(1.0)));
 
     // Start Application window event loop
-    app.start();
+    let config = AppConfig::new("EyeEngine - simple").with_msaa_samples(4);
+    app.start_with_scene(harness.config(config), scene);
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+mod common;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use common::Harness;
+use eyengine::{AppConfig, Application, Environment, Input, Material, Mesh, Scene, Time, Transform};
+use legion::*;
+use nalgebra::{UnitQuaternion, Vector3};
+
+// radians per second around the Y axis, changed from the debug UI
+struct Spin(f32);
+
+fn spin_system() -> impl systems::Runnable {
+    SystemBuilder::new("spin")
+        .read_resource::<Time>()
+        .read_resource::<Spin>()
+        .with_query(<&mut Transform>::query())
+        .build(|_, world, (time, spin), query| {
+            for transform in query.iter_mut(world) {
+                transform.rotation *= UnitQuaternion::from_axis_angle(&Vector3::y_axis(), spin.0 * time.delta());
+            }
+        })
+}
+
+// A debug UI panel tweaking the scene & the lighting while it runs (feature "egui").
+struct UiApp {
+    spin: Cell<f32>,
+    sun_intensity: Cell<f32>,
+    time_of_day: Cell<f32>
+}
+
+impl Application for UiApp {
+    fn update(&self, _time: &Time, _input: &Input) {}
+
+    fn ui(&self, ctx: &egui::Context) {
+        let (mut spin, mut sun_intensity, mut time_of_day) = (self.spin.get(), self.sun_intensity.get(), self.time_of_day.get());
+        egui::Window::new("Tweaks").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut spin, -5.0..=5.0).text("spin (rad/s)"));
+            ui.add(egui::Slider::new(&mut sun_intensity, 0.0..=150000.0).text("sun (lux)"));
+            ui.add(egui::Slider::new(&mut time_of_day, 0.0..=24.0).text("time of day"));
+            if ui.button("reset").clicked() {
+                spin = 1.0;
+                sun_intensity = Environment::new().sun_intensity;
+                time_of_day = 12.0;
+            }
+        });
+        self.spin.set(spin);
+        self.sun_intensity.set(sun_intensity);
+        self.time_of_day.set(time_of_day);
+    }
+
+    fn update_scene(&self, scene: &mut Scene) {
+        scene.resources.insert(Spin(self.spin.get()));
+    }
+
+    fn environment(&self) -> Environment {
+        // the sun goes around the x axis, at its highest at noon
+        let angle = (self.time_of_day.get() / 24.0) * std::f32::consts::TAU;
+        Environment {
+            sun_direction: Vector3::new(0.3, angle.cos(), angle.sin()),
+            sun_intensity: self.sun_intensity.get(),
+            time_of_day: self.time_of_day.get(),
+            ..Environment::new()
+        }
+    }
+}
+
+fn main() {
+    let harness = Harness::from_args();
+
+    let schedule = Schedule::builder().add_system(spin_system()).build();
+    let mut scene = Scene::new(World::default(), schedule);
+    scene.resources.insert(Spin(1.0));
+    let cube = scene.add_mesh(&Rc::new(Mesh::cube(1.0)));
+    let material = scene.add_material(&Rc::new(Material::new("cube").with_tint([0.3, 0.8, 0.4, 1.0]).with_specular(0.8, 64.0)));
+    scene.world.push((Transform::from_translation(Vector3::new(0.0, 0.5, 0.0)), cube, material));
+
+    let app = UiApp {
+        spin: Cell::new(1.0),
+        sun_intensity: Cell::new(Environment::new().sun_intensity),
+        time_of_day: Cell::new(12.0)
+    };
+    let config = AppConfig::new("EyeEngine - ui").with_msaa_samples(4);
+    app.start_with_scene(harness.config(config), scene);
+}
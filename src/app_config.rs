@@ -0,0 +1,299 @@
+use std::path::PathBuf;
+
+use winit::{
+    dpi::LogicalSize,
+    event_loop::EventLoop,
+    window::{Fullscreen, Window, WindowBuilder}
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    // a window covering the current monitor
+    BorderlessFullscreen,
+    // takes over the current monitor, with its largest video mode
+    Fullscreen
+}
+
+// How frames are synchronized with the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentMode {
+    // wait for the vertical blank, no tearing (always supported)
+    Vsync,
+    // present right away, may tear
+    Immediate,
+    // replace the queued frame, no tearing without waiting
+    Mailbox
+}
+
+impl PresentMode {
+    pub(crate) fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Vsync => wgpu::PresentMode::Fifo,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox
+        }
+    }
+}
+
+// Graphics API to render with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    // the best one available
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+    Gl
+}
+
+impl Backend {
+    pub(crate) fn to_wgpu(self) -> wgpu::Backends {
+        match self {
+            Backend::Auto => wgpu::Backends::all(),
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Dx11 => wgpu::Backends::DX11,
+            Backend::Gl => wgpu::Backends::GL
+        }
+    }
+}
+
+// How the depth buffer is laid out, see `AppConfig::depth_mode`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthMode {
+    // near plane at 0.0, far plane at 1.0
+    Standard,
+    // near plane at 1.0, far plane at 0.0: the float precision then spreads evenly over the distance,
+    // which removes most of the z-fighting far away in large scenes.
+    ReversedZ
+}
+
+impl DepthMode {
+    // depth of the near plane in the depth buffer
+    pub fn near_depth(self) -> f32 {
+        match self {
+            DepthMode::Standard => 0.0,
+            DepthMode::ReversedZ => 1.0
+        }
+    }
+
+    // depth of the far plane, which the depth buffer is cleared to
+    pub fn far_depth(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReversedZ => 0.0
+        }
+    }
+
+    // depth test: closer fragments pass
+    pub(crate) fn compare(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard => wgpu::CompareFunction::Less,
+            DepthMode::ReversedZ => wgpu::CompareFunction::Greater
+        }
+    }
+
+    // comparison sampler test: passes where the depth is at `far_depth()` or beyond
+    pub(crate) fn far_compare(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard => wgpu::CompareFunction::LessEqual,
+            DepthMode::ReversedZ => wgpu::CompareFunction::GreaterEqual
+        }
+    }
+
+    // biases are given for the standard depth (negative towards the camera), flipped with reversed-Z
+    pub(crate) fn depth_bias(self, depth_bias: wgpu::DepthBiasState) -> wgpu::DepthBiasState {
+        match self {
+            DepthMode::Standard => depth_bias,
+            DepthMode::ReversedZ => wgpu::DepthBiasState {
+                constant: -depth_bias.constant,
+                slope_scale: -depth_bias.slope_scale,
+                clamp: depth_bias.clamp
+            }
+        }
+    }
+}
+
+// Samples per pixel the scene can be rendered with on `adapter`.
+// tips: wgpu only guarantees 1 & 4 samples, and can't query the others: the native backends support 2 & 8 samples
+// for the surface & depth formats on desktop GPUs, while GL & the browser are limited to 1 & 4.
+fn supported_sample_counts(adapter: &wgpu::Adapter) -> &'static [u32] {
+    match adapter.get_info().backend {
+        wgpu::Backend::Vulkan | wgpu::Backend::Metal | wgpu::Backend::Dx12 | wgpu::Backend::Dx11 => &[1, 2, 4, 8],
+        _ => &[1, 4]
+    }
+}
+
+// Window & surface settings, passed to `Application::start()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppConfig {
+    pub title: String,
+    // inner size in logical pixels, scaled by the monitor's DPI
+    pub size: (u32, u32),
+    pub resizable: bool,
+    pub window_mode: WindowMode,
+    // tips: unsupported modes fall back to vsync.
+    pub present_mode: PresentMode,
+    // samples per pixel of the scene: 1 (no MSAA), 2, 4 or 8
+    // tips: falls back to 4 if the adapter doesn't support it.
+    pub msaa_samples: u32,
+    // tips: if no adapter of this backend can present to the window, any backend is used.
+    pub backend: Backend,
+    // tips: `Camera::projection_matrix()` follows it, so do shaders using the camera's matrices.
+    pub depth_mode: DepthMode,
+    // development mode: the engine's WGSL shaders are read from its source tree & rebuilt when they're saved,
+    // compile errors are printed & shown by the error overlay.
+    pub shader_hot_reload: bool,
+    // Wait for the next window frame before reading the input instead of after updating the frame (late latching):
+    // the camera is moved by input younger by up to a refresh interval, which makes mouse look feel tighter.
+    // tips: the update then runs in the time left before the frame is presented, turn it off if the frame rate drops.
+    pub late_latch: bool,
+    // Close the window after rendering this many frames, None runs until it's closed.
+    // e.g. to run the examples as tests, see `final_screenshot`.
+    pub frame_limit: Option<u64>,
+    // Seconds every frame advances the `Time` by, whatever the clock says: None follows the clock.
+    // tips: with a frame limit, the same frames are rendered whatever the frame rate, so their screenshots can be compared.
+    pub fixed_frame_delta: Option<f32>,
+    // the last frame of the frame limit is saved there (PNG), the window is closed once it's written
+    pub final_screenshot: Option<PathBuf>
+}
+
+impl AppConfig {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn with_window_mode(mut self, window_mode: WindowMode) -> Self {
+        self.window_mode = window_mode;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_msaa_samples(mut self, msaa_samples: u32) -> Self {
+        self.msaa_samples = msaa_samples;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    pub fn with_shader_hot_reload(mut self, shader_hot_reload: bool) -> Self {
+        self.shader_hot_reload = shader_hot_reload;
+        self
+    }
+
+    pub fn with_late_latch(mut self, late_latch: bool) -> Self {
+        self.late_latch = late_latch;
+        self
+    }
+
+    pub fn with_frame_limit(mut self, frame_limit: Option<u64>) -> Self {
+        self.frame_limit = frame_limit;
+        self
+    }
+
+    pub fn with_fixed_frame_delta(mut self, fixed_frame_delta: Option<f32>) -> Self {
+        self.fixed_frame_delta = fixed_frame_delta;
+        self
+    }
+
+    pub fn with_final_screenshot<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.final_screenshot = Some(path.into());
+        self
+    }
+
+    // the requested MSAA samples if the adapter supports them, else 4 (or 1 without MSAA)
+    pub(crate) fn sample_count(&self, adapter: &wgpu::Adapter) -> u32 {
+        let samples = match self.msaa_samples {
+            0 | 1 => return 1,
+            samples @ (2 | 4 | 8) => samples,
+            samples => {
+                eprintln!("{} MSAA samples aren't supported, using 4", samples);
+                return 4;
+            }
+        };
+        if supported_sample_counts(adapter).contains(&samples) {
+            samples
+        } else {
+            eprintln!("{} MSAA samples aren't supported by {}, using 4", samples, adapter.get_info().name);
+            4
+        }
+    }
+
+    pub(crate) fn build_window(&self, event_loop: &EventLoop<()>) -> Window {
+        let monitor = event_loop.primary_monitor();
+        let fullscreen = match self.window_mode {
+            WindowMode::Windowed => None,
+            WindowMode::BorderlessFullscreen => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Fullscreen => {
+                let video_mode = monitor.and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|video_mode| {
+                        let size = video_mode.size();
+                        (size.width * size.height, video_mode.refresh_rate())
+                    })
+                });
+                match video_mode {
+                    Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                    // e.g. on Wayland, which doesn't expose the video modes
+                    None => Some(Fullscreen::Borderless(None))
+                }
+            }
+        };
+        let (width, height) = self.size;
+        WindowBuilder::new()
+            .with_title(&self.title)
+            .with_inner_size(LogicalSize::new(width.max(1), height.max(1)))
+            .with_resizable(self.resizable)
+            .with_fullscreen(fullscreen)
+            .build(event_loop)
+            .unwrap()
+    }
+}
+
+impl Default for AppConfig {
+    // a resizable 800x600 window with vsync & standard depth
+    fn default() -> Self {
+        Self {
+            title: "EyeEngine".to_string(),
+            size: (800, 600),
+            resizable: true,
+            window_mode: WindowMode::Windowed,
+            present_mode: PresentMode::Vsync,
+            msaa_samples: 1,
+            backend: Backend::Auto,
+            depth_mode: DepthMode::Standard,
+            shader_hot_reload: false,
+            late_latch: false,
+            frame_limit: None,
+            fixed_frame_delta: None,
+            final_screenshot: None
+        }
+    }
+}
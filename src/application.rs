@@ -1,16 +1,41 @@
+use std::rc::Rc;
+
 use winit::{
     event::{Event, WindowEvent, KeyboardInput, ElementState, VirtualKeyCode},
-    event_loop::{ControlFlow, EventLoop},
-    window::Window
+    event_loop::{ControlFlow, EventLoop}
 };
 
+use super::app_config::AppConfig;
+use super::camera::CameraRig;
+use super::capture::{CaptureConfig, FrameStream};
+use super::debug_draw::{DebugDraw, DebugDrawSettings};
+use super::environment::Environment;
 use super::gpu::GPUState;
+use super::input::Input;
+use super::lens_flare::LensFlare;
+use super::mesh::MeshDraw;
+use super::paint::PaintCanvas;
+use super::polyline::Polyline;
+use super::post_process::PostProcessStack;
+use super::profiler::{profile_scope, Profiler};
+use super::render_state::RenderState;
+use super::scene::Scene;
+use super::texture::Cubemap;
+use super::time::Time;
+use super::transition::Transition;
 
 
 // ref: https://github.com/sotrh/learn-wgpu/blob/0.11/docs/beginner/
 // ref: https://github.com/bevyengine/bevy/blob/669849c4547f1fd0950d7f03f56f78d4681db7f1/src/application.rs
 pub trait Application {
-    fn start(&self) {
+    // Open the window described by `config` & run the application in it.
+    // tips: the event loop never returns and owns everything it uses, so the application is moved into it.
+    fn start(self, config: AppConfig) where Self: Sized + 'static {
+        self.start_with_scene(config, Scene::default());
+    }
+
+    // Start with an ECS world & the systems to run on it every frame, see `Scene`.
+    fn start_with_scene(self, config: AppConfig, mut scene: Scene) where Self: Sized + 'static {
         // When wgpu hits any error it panics with a generic message, while logging the real error via the env_logger crate. 
         // This means if you don't include env_logger::init() wgpu will fail silently, leaving you very confused!
         env_logger::init();
@@ -19,10 +44,30 @@ pub trait Application {
         let event_loop = EventLoop::new();
 
         // Create a Window
-        let window = Window::new(&event_loop).unwrap();
+        let window = config.build_window(&event_loop);
 
         // Init GPU States
-        let mut state = pollster::block_on(GPUState::new(&window)); // await until it's done.
+        let mut state = pollster::block_on(GPUState::new(&window, &config)); // await until it's done.
+        state.set_capture_config(self.capture_config());
+        state.set_frame_stream(self.frame_stream());
+        state.warm_up(&self.render_states());
+        // content errors of the scene, before they turn into missing objects
+        let report = scene.validate();
+        for issue in &report.issues {
+            eprintln!("scene: {}", issue);
+        }
+        // failed pipelines whose materials are reported already
+        let mut failed_pipelines = Vec::new();
+        let mut time = Time::new(self.fixed_timestep());
+        let mut input = Input::new();
+        // cursor grab of the camera controller's pointer lock
+        let mut pointer_locked = false;
+        let late_latch = config.late_latch;
+        // automated runs, e.g. the examples run as tests
+        let frame_limit = config.frame_limit;
+        let fixed_frame_delta = config.fixed_frame_delta;
+        let final_screenshot = config.final_screenshot.clone();
+        let mut rendered_frames: u64 = 0;
 
         // Event handling
         event_loop.run(move |event, _event_loop_window_target, control_flow| {
@@ -31,49 +76,182 @@ pub trait Application {
             // *control_flow = ControlFlow::Poll;
 
             match event {
+                // Emitted when new events arrive from the OS, before they're dispatched.
+                // With late latching the frame is waited for here, so the events received meanwhile are used by this frame rather than the next one.
+                // event ref: https://docs.rs/winit/0.26.0/winit/event/enum.Event.html#variant.NewEvents
+                Event::NewEvents(_) if late_latch => state.acquire_frame(),
                 // A Window object can generate WindowEvents when certain input events occur,
                 // such as a cursor moving over the window or a key getting pressed while the window is focused.
                 // event ref: https://docs.rs/winit/0.26.0/winit/event/enum.Event.html#variant.WindowEvent
                 Event::WindowEvent {
                     ref event,
                     window_id
-                } if window_id == window.id() => if !state.input(event) { // if this Window Event isn't processed by GPUState::input()
-                    match event {
-                        // if get "window close" or "keyboard input `ESC`" event, end loop. 
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input: KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
+                } if window_id == window.id() => {
+                    // the input state sees every event, even the ones used by the camera controller
+                    input.process_event(event);
+                    if !state.input(event) { // if this Window Event isn't processed by GPUState::input()
+                        match event {
+                            // if get "window close" or "keyboard input `ESC`" event, end loop. 
+                            WindowEvent::CloseRequested
+                            | WindowEvent::KeyboardInput {
+                                input: KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Escape),
+                                    ..
+                                },
                                 ..
+                            } => *control_flow = ControlFlow::Exit,
+                            // resized events: WindowEvent::Resized or WindowEvent::ScaleFactorChanged
+                            WindowEvent::Resized(physical_size) => {
+                                state.resize(*physical_size);
                             },
-                            ..
-                        } => *control_flow = ControlFlow::Exit,
-                        // resized events: WindowEvent::Resized or WindowEvent::ScaleFactorChanged
-                        WindowEvent::Resized(physical_size) => {
-                            state.resize(*physical_size);
-                        },
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            // new_inner_size is &&mut so we have to dereference it twice
-                            state.resize(**new_inner_size)
+                            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                                // new_inner_size is &&mut so we have to dereference it twice
+                                state.resize(**new_inner_size)
+                            }
+                            // ignore other Window Event
+                            _ => {}
                         }
-                        // ignore other Window Event
-                        _ => {}
                     }
                 },
+                // raw input from the devices (mouse motion...), for the input state & the camera controller
+                // event ref: https://docs.rs/winit/0.26.0/winit/event/enum.Event.html#variant.DeviceEvent
+                Event::DeviceEvent { ref event, .. } => {
+                    input.process_device_event(event);
+                    state.device_input(event);
+                },
                 // Emitted when all of the event loop’s input events have been processed and redraw processing is about to begin.
                 // event ref: https://docs.rs/winit/0.26.0/winit/event/enum.Event.html#variant.MainEventsCleared
                 Event::MainEventsCleared => {
+                    let locked = state.camera_rig().pointer_locked();
+                    if locked != pointer_locked {
+                        // tips: some platforms can't grab the cursor (e.g. Wayland without confinement), it's only hidden then.
+                        if let Err(error) = window.set_cursor_grab(locked) {
+                            eprintln!("failed to grab the cursor: {}", error);
+                        }
+                        window.set_cursor_visible(!locked);
+                        pointer_locked = locked;
+                    }
                     // RedrawRequested will only trigger once, unless we manually request it.
                     window.request_redraw();
                 },
                 // Emitted after MainEventsCleared **when a window should be redrawn**.
                 // event ref: https://docs.rs/winit/0.26.0/winit/event/enum.Event.html#variant.RedrawRequested
                 Event::RedrawEventsCleared => {
-                    state.update();
+                    Profiler::with(|profiler| profiler.begin_frame());
+
+                    match fixed_frame_delta {
+                        Some(delta) => time.tick_fixed(delta),
+                        None => time.tick()
+                    }
+                    {
+                        let _scope = profile_scope("fixed update");
+                        for _ in 0..time.take_fixed_steps() {
+                            self.fixed_update(&time);
+                        }
+                    }
+                    {
+                        let _scope = profile_scope("application update");
+                        self.update(&time, &input);
+                    }
+                    #[cfg(feature = "egui")]
+                    {
+                        let _scope = profile_scope("debug ui");
+                        state.run_debug_ui(|ctx| self.ui(ctx));
+                        if let Some(cursor_icon) = state.debug_ui().take_cursor_icon() {
+                            window.set_cursor_icon(cursor_icon);
+                        }
+                    }
+                    {
+                        let _scope = profile_scope("scene update");
+                        scene.resources.insert(time);
+                        scene.resources.insert(input.clone());
+                        scene.resources.insert(state.debug_draw_settings());
+                        scene.recenter(state.camera_rig());
+                        self.update_scene(&mut scene);
+                        scene.execute();
+                        // changed by the systems, or the application
+                        if let Some(debug_draw_settings) = scene.resources.get::<DebugDrawSettings>().map(|settings| *settings) {
+                            state.set_debug_draw_settings(debug_draw_settings);
+                        }
+                    }
+                    {
+                        let _scope = profile_scope("engine update");
+                        let environment = self.environment();
+                        state.set_environment(&environment);
+                        state.set_skybox(self.skybox(), &environment);
+                        let mut lights = Vec::new();
+                        scene.lights(&mut lights);
+                        let mut occluder_segments = Vec::new();
+                        scene.occluder_segments(&mut occluder_segments);
+                        state.set_lights(&lights, &occluder_segments, &environment);
+                        state.set_lens_flare(self.lens_flare());
+                        state.set_post_process(self.post_process());
+                        state.set_transition(self.transition());
+                        self.paint_texture(state.diffuse_canvas());
+                        self.update_camera(state.camera_rig());
+                        state.update(&time, &input);
+                    }
+                    if state.failed_pipelines() != failed_pipelines.as_slice() {
+                        let newly_failed = state.failed_pipelines()
+                            .iter()
+                            .filter(|pipeline_key| !failed_pipelines.contains(*pipeline_key))
+                            .copied()
+                            .collect::<Vec<_>>();
+                        for issue in scene.pipeline_issues(&newly_failed) {
+                            eprintln!("scene: {}", issue);
+                        }
+                        failed_pipelines = state.failed_pipelines().to_vec();
+                    }
+                    {
+                        let _scope = profile_scope("meshes");
+                        let mut mesh_draws = Vec::new();
+                        scene.draw_meshes(&mut mesh_draws);
+                        self.draw_meshes(&mut mesh_draws);
+                        state.prepare_meshes(&mesh_draws);
+                    }
+                    {
+                        let _scope = profile_scope("sprites");
+                        let mut sprites = Vec::new();
+                        scene.sprites(&mut sprites);
+                        state.prepare_sprites(&sprites);
+                    }
+                    {
+                        let _scope = profile_scope("parallax layers");
+                        let mut parallax_layers = Vec::new();
+                        scene.parallax_layers(&mut parallax_layers);
+                        state.prepare_parallax_layers(&parallax_layers);
+                    }
+                    {
+                        // polylines & debug shapes are immediate mode: collect them again every frame.
+                        let _scope = profile_scope("polylines");
+                        let debug_draw = state.begin_debug_draw();
+                        self.debug_draw(debug_draw);
+                        scene.debug_draw(debug_draw);
+                        let mut polylines = Vec::new();
+                        self.draw_polylines(&mut polylines);
+                        state.prepare_polylines(&polylines);
+                    }
 
-                    match state.render() {
-                        Ok(_) => {},
+                    // the last frame of the frame limit is saved before the window is closed
+                    let last_frame = frame_limit.is_some_and(|frame_limit| rendered_frames + 1 >= frame_limit);
+                    if let (true, Some(path)) = (last_frame, &final_screenshot) {
+                        state.request_screenshot_to(path.clone());
+                    }
+                    let result = {
+                        let _scope = profile_scope("render");
+                        state.render()
+                    };
+                    input.end_frame();
+                    Profiler::with(|profiler| profiler.end_frame());
+
+                    match result {
+                        Ok(_) => {
+                            rendered_frames += 1;
+                            if last_frame {
+                                *control_flow = ControlFlow::Exit;
+                            }
+                        },
                         // Reconfigure the surface if lost.
                         Err(wgpu::SurfaceError::Lost) => {
                             println!("lost!");
@@ -90,5 +268,85 @@ pub trait Application {
         });
     }
     
-    fn update(&self);
+    // Called once per frame, scale movements by `time.delta()` so they don't depend on the frame rate.
+    // `input` is the keyboard & mouse state of the frame, also in the scene's resources for its systems.
+    fn update(&self, time: &Time, input: &Input);
+
+    // Called every `fixed_timestep()` seconds of frame time, before `update()`: zero or more times per frame.
+    // tips: simulations (physics, cloth...) stay stable & deterministic with a fixed step.
+    fn fixed_update(&self, _time: &Time) {}
+
+    // Seconds simulated by each `fixed_update()` call, read once at startup.
+    fn fixed_timestep(&self) -> f32 {
+        1.0 / 60.0
+    }
+
+    // Build the debug UI of the frame (tweak panels, inspectors...), drawn over the scene (feature "egui").
+    // tips: the window events used by the UI, e.g. clicks on a panel, don't move the camera.
+    #[cfg(feature = "egui")]
+    fn ui(&self, _ctx: &egui::Context) {}
+
+    // Change the scene before its systems run this frame, the `Time`, `Input` & `DebugDrawSettings` of the frame are in its resources (spawn entities, swap the schedule...).
+    // tips: with a floating origin, the scene has already been shifted this frame, see `WorldOrigin::shifted()`.
+    fn update_scene(&self, _scene: &mut Scene) {}
+
+    // Where & how screenshots (F12) and clips (F9 / F10) are saved, read once at startup.
+    fn capture_config(&self) -> CaptureConfig {
+        CaptureConfig::default()
+    }
+
+    // Stream the presented frames to a channel, read once at startup, see `FrameStream::channel()`.
+    // tips: streamed frames are read back from the GPU, keep the rate low.
+    fn frame_stream(&self) -> Option<FrameStream> {
+        None
+    }
+
+    // Render states the meshes will be drawn with, read once at startup: their pipelines are compiled in the background right away,
+    // so a mesh using one doesn't wait for its pipeline when it first appears, see `MeshDraw::with_render_state()`.
+    fn render_states(&self) -> Vec<RenderState> {
+        Vec::new()
+    }
+
+    // Global environment settings (sun, ambient, wind, fog...) of this frame.
+    fn environment(&self) -> Environment {
+        Environment::default()
+    }
+
+    // Cubemap drawn behind the scene this frame, None to clear it to the environment's sky color.
+    // tips: keep the cubemap (`Rc<Cubemap>`) around, it's only uploaded the first time it's drawn.
+    fn skybox(&self) -> Option<Rc<Cubemap>> {
+        None
+    }
+
+    // Lens flare of the sun this frame, None to disable it.
+    fn lens_flare(&self) -> Option<LensFlare> {
+        None
+    }
+
+    // Post-processing effects applied to the scene this frame, in order, see `PostProcessStack`.
+    fn post_process(&self) -> PostProcessStack {
+        PostProcessStack::default()
+    }
+
+    // Screen transition drawn over everything this frame (fades, wipes, loading spinner), None when there's none.
+    fn transition(&self) -> Option<Transition> {
+        None
+    }
+
+    // Push the meshes to draw this frame, with their transforms, besides the entities of the scene.
+    // tips: keep the meshes (`Rc<Mesh>`) around, they're only uploaded the first time they're drawn.
+    fn draw_meshes(&self, _mesh_draws: &mut Vec<MeshDraw>) {}
+
+    // Move the camera or replace it & its controller (orbit, fly, 2D pan & zoom...), before the controller runs this frame.
+    fn update_camera(&self, _camera_rig: &mut CameraRig) {}
+
+    // Paint on the texture of the scene at runtime (fog of war, splat maps, decals...), only the changed pixels are uploaded.
+    fn paint_texture(&self, _canvas: &mut PaintCanvas) {}
+
+    // Push the polylines to draw this frame (trajectories, graphs, editor guides...).
+    fn draw_polylines(&self, _polylines: &mut Vec<Polyline>) {}
+
+    // Submit the debug shapes to draw this frame (bounding volumes, lights, frusta...).
+    // tips: F1 ~ F4 toggle the debug draw categories at runtime, so does the `DebugDrawSettings` resource of the scene.
+    fn debug_draw(&self, _debug_draw: &mut DebugDraw) {}
 }
@@ -0,0 +1,236 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{mpsc, Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+
+use super::assets::AssetRoot;
+use super::model::Model;
+use super::scene_loader::{AssetData, AssetRequest};
+
+// Where an asset of an `AssetLoader` is, e.g. to draw a placeholder until it's loaded.
+pub enum LoadState<T> {
+    Loading,
+    Loaded(Rc<T>),
+    // the error with its causes
+    Failed(String)
+}
+
+impl<T> Clone for LoadState<T> {
+    fn clone(&self) -> Self {
+        match self {
+            LoadState::Loading => LoadState::Loading,
+            LoadState::Loaded(asset) => LoadState::Loaded(asset.clone()),
+            LoadState::Failed(error) => LoadState::Failed(error.clone())
+        }
+    }
+}
+
+// Handle of an asset loading in the background, filled by `AssetLoader::update()`.
+pub struct AssetHandle<T> {
+    state: Rc<RefCell<LoadState<T>>>
+}
+
+impl<T> AssetHandle<T> {
+    fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(LoadState::Loading))
+        }
+    }
+
+    pub fn state(&self) -> LoadState<T> {
+        self.state.borrow().clone()
+    }
+
+    // None while loading or if it failed
+    pub fn get(&self) -> Option<Rc<T>> {
+        match &*self.state.borrow() {
+            LoadState::Loaded(asset) => Some(asset.clone()),
+            _ => None
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(&*self.state.borrow(), LoadState::Loading)
+    }
+
+    fn set(&self, state: LoadState<T>) {
+        *self.state.borrow_mut() = state;
+    }
+}
+
+impl<T> Clone for AssetHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone()
+        }
+    }
+}
+
+// the handle an asset goes to once loaded
+enum PendingAsset {
+    Model(AssetHandle<Model>),
+    Image(AssetHandle<image::RgbaImage>)
+}
+
+impl PendingAsset {
+    fn fail(self, error: String) {
+        match self {
+            PendingAsset::Model(handle) => handle.set(LoadState::Failed(error)),
+            PendingAsset::Image(handle) => handle.set(LoadState::Failed(error))
+        }
+    }
+}
+
+type Job = (u64, AssetRequest);
+type JobResult = (u64, Result<AssetData>);
+
+// Loads assets on a pool of worker threads while the game runs: the files are read & decoded (images, models...)
+// off the main thread, then `update()` hands them to their handles on the main thread.
+// Unlike `SceneLoader`, every asset is available as soon as it's loaded & the failures are reported one by one.
+// tips: the meshes & textures are uploaded to the GPU when they're first drawn, on the main thread too.
+pub struct AssetLoader {
+    // None: the paths are used as given, i.e. relative to the working directory
+    asset_root: Option<AssetRoot>,
+    threads: usize,
+    // None until the first load starts the workers
+    jobs: Option<mpsc::Sender<Job>>,
+    results: (mpsc::Sender<JobResult>, mpsc::Receiver<JobResult>),
+    pending: HashMap<u64, PendingAsset>,
+    next_id: u64
+}
+
+impl AssetLoader {
+    // a worker per core, but one for the main thread
+    pub fn new() -> Self {
+        let threads = std::thread::available_parallelism()
+            .map(|threads| threads.get().saturating_sub(1).max(1))
+            .unwrap_or(2);
+        Self {
+            asset_root: None,
+            threads,
+            jobs: None,
+            results: mpsc::channel(),
+            pending: HashMap::new(),
+            next_id: 0
+        }
+    }
+
+    // resolve the relative paths of the assets against `asset_root`, e.g. `AssetRoot::default()`
+    pub fn with_asset_root(mut self, asset_root: AssetRoot) -> Self {
+        self.asset_root = Some(asset_root);
+        self
+    }
+
+    // number of worker threads, started by the first load
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // an OBJ model, see `Model::load()`
+    pub fn load_model<P: Into<PathBuf>>(&mut self, path: P) -> AssetHandle<Model> {
+        let handle = AssetHandle::new();
+        self.request(AssetRequest::Model(String::new(), path.into()), PendingAsset::Model(handle.clone()));
+        handle
+    }
+
+    // an image decoded to RGBA, e.g. for `Material::with_albedo()`
+    pub fn load_image<P: Into<PathBuf>>(&mut self, path: P) -> AssetHandle<image::RgbaImage> {
+        let handle = AssetHandle::new();
+        self.request(AssetRequest::Image(String::new(), path.into()), PendingAsset::Image(handle.clone()));
+        handle
+    }
+
+    fn request(&mut self, request: AssetRequest, pending: PendingAsset) {
+        let request = match &self.asset_root {
+            Some(asset_root) => request.resolve(asset_root),
+            None => request
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let threads = self.threads;
+        let results = self.results.0.clone();
+        let jobs = self.jobs.get_or_insert_with(|| spawn_workers(threads, results));
+        // the workers only stop with the loader, unless none could be started
+        if jobs.send((id, request)).is_err() {
+            // tips: the next request tries to start them again.
+            self.jobs = None;
+            pending.fail("no asset loader thread is running".to_string());
+            return;
+        }
+        self.pending.insert(id, pending);
+    }
+
+    // Hand the assets loaded since the last call to their handles, once a frame (e.g. in `Application::update()`).
+    // The models are built here, they're shared with `Rc`.
+    pub fn update(&mut self) {
+        while let Ok((id, result)) = self.results.1.try_recv() {
+            let pending = match self.pending.remove(&id) {
+                Some(pending) => pending,
+                None => continue
+            };
+            match (pending, result) {
+                (PendingAsset::Model(handle), Ok(AssetData::Model(_, data))) => handle.set(LoadState::Loaded(Rc::new(Model::from_data(data)))),
+                (PendingAsset::Image(handle), Ok(AssetData::Image(_, image))) => handle.set(LoadState::Loaded(Rc::new(image))),
+                (pending, Err(error)) => pending.fail(format!("{:#}", error)),
+                // a request always gives the data it was made for
+                _ => unreachable!()
+            }
+        }
+    }
+
+    // number of assets not handed to their handles yet
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl Default for AssetLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the workers take the jobs one by one, they stop once the loader (holding the sender) is dropped
+fn spawn_workers(threads: usize, results: mpsc::Sender<JobResult>) -> mpsc::Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    for index in 0..threads {
+        let receiver = receiver.clone();
+        let results = results.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("asset loader {}", index))
+            .spawn(move || loop {
+                // the lock is released before loading
+                let job = receiver.lock().unwrap_or_else(|error| error.into_inner()).recv();
+                let (id, request) = match job {
+                    Ok(job) => job,
+                    Err(_) => return
+                };
+                // a panic fails the asset rather than the worker, its handle would stay loading
+                let result = std::panic::catch_unwind(AssertUnwindSafe(|| request.load()))
+                    .unwrap_or_else(|panic| Err(anyhow!("loading panicked: {}", panic_message(&*panic))));
+                if results.send((id, result)).is_err() {
+                    return;
+                }
+            });
+        if let Err(error) = spawned {
+            eprintln!("failed to start an asset loader thread: {}", error);
+        }
+    }
+    sender
+}
+
+// the message given to `panic!()`, if any
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message,
+        _ => "unknown cause"
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+// Directory the game's files (textures, models...) are shipped in, relative asset paths are resolved against it.
+// By default `assets/` next to the executable, overridden by the `EYENGINE_ASSETS` environment variable or `AssetRoot::new()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetRoot {
+    path: PathBuf
+}
+
+impl AssetRoot {
+    pub const ENV_VAR: &'static str = "EYENGINE_ASSETS";
+
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into()
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // where the engine & its tools write derived files (e.g. thumbnails), `.cache/` in the asset root
+    // tips: it can be deleted at any time, everything in it is generated again.
+    pub fn cache_directory(&self) -> PathBuf {
+        self.path.join(".cache")
+    }
+
+    // tips: absolute paths are returned as-is.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+
+    // the bytes of a file, e.g. for `Texture::from_bytes()`
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = self.resolve(path);
+        std::fs::read(&path).with_context(|| format!("failed to read asset {}", path.display()))
+    }
+
+    // an image decoded to RGBA, e.g. for `Material::with_albedo()` or `PaintCanvas::from_image()`
+    pub fn load_image<P: AsRef<Path>>(&self, path: P) -> Result<image::RgbaImage> {
+        let path = self.resolve(path);
+        let image = image::open(&path).with_context(|| format!("failed to load image {}", path.display()))?;
+        Ok(image.to_rgba8())
+    }
+}
+
+impl Default for AssetRoot {
+    // `$EYENGINE_ASSETS`, or `assets/` next to the executable (in the working directory if its path is unknown)
+    fn default() -> Self {
+        if let Some(path) = std::env::var_os(Self::ENV_VAR) {
+            return Self::new(path);
+        }
+        let executable_directory = std::env::current_exe()
+            .ok()
+            .and_then(|executable| executable.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        Self::new(executable_directory.join("assets"))
+    }
+}
@@ -0,0 +1,327 @@
+use anyhow::{bail, Result};
+use wgpu::util::DeviceExt; // for `create_buffer_init`
+
+use super::error_overlay::catch_validation_error;
+use super::hot_reload::load_shader;
+use super::render_target::PingPongTargets;
+use super::texture::Texture;
+
+// widest kernel `BlurUniform` can hold
+const MAX_RADIUS: u32 = 32;
+const WORKGROUP_SIZE: u32 = 8;
+
+// Convolution kernels of `BlurPasses::blur()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlurKernel {
+    // standard deviation in pixels, the kernel covers 3 sigmas on each side (up to 32 pixels)
+    Gaussian { sigma: f32 },
+    // plain average of the pixels up to `radius` away (up to 32)
+    Box { radius: u32 }
+}
+
+impl BlurKernel {
+    // weights of the taps 0 ~ radius pixels away from the center, summing to 1.0 over both sides
+    fn weights(&self) -> Vec<f32> {
+        match *self {
+            Self::Gaussian { sigma } => {
+                let sigma = sigma.max(0.01);
+                let radius = ((sigma * 3.0).ceil() as u32).min(MAX_RADIUS);
+                let weights: Vec<f32> = (0..=radius)
+                    .map(|offset| (-((offset * offset) as f32) / (2.0 * sigma * sigma)).exp())
+                    .collect();
+                let total = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+                weights.iter().map(|weight| weight / total).collect()
+            },
+            Self::Box { radius } => {
+                let radius = radius.min(MAX_RADIUS);
+                vec![1.0 / (2 * radius + 1) as f32; radius as usize + 1]
+            }
+        }
+    }
+
+    fn to_uniform(self, direction: [i32; 2]) -> BlurUniform {
+        let weights = self.weights();
+        let mut packed = [[0.0; 4]; 9];
+        for (offset, weight) in weights.iter().enumerate() {
+            packed[offset / 4][offset % 4] = *weight;
+        }
+        BlurUniform {
+            direction,
+            radius: weights.len() as i32 - 1,
+            _padding: 0,
+            weights: packed
+        }
+    }
+}
+
+// `BlurKernel` layout in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurUniform {
+    direction: [i32; 2],
+    radius: i32,
+    _padding: i32,
+    weights: [[f32; 4]; 9]
+}
+
+// Reusable compute passes to blur & resample textures: separable gaussian/box blur and half/double resolution resampling,
+// shared by post-processing, bloom, SSAO, shadow filtering... or applied to any texture.
+// The destination textures are written as storage textures, so they need STORAGE_BINDING and the format given to `new()`.
+pub struct BlurPasses {
+    format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    blur_pipeline: wgpu::ComputePipeline,
+    downsample_pipeline: wgpu::ComputePipeline,
+    upsample_pipeline: wgpu::ComputePipeline,
+    sampler: wgpu::Sampler
+}
+
+impl BlurPasses {
+    // `format` of the destination textures: Rgba8Unorm, Rgba16Float or Rgba32Float.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Result<Self> {
+        let storage_format = match format {
+            wgpu::TextureFormat::Rgba8Unorm => "rgba8unorm",
+            wgpu::TextureFormat::Rgba16Float => "rgba16float",
+            wgpu::TextureFormat::Rgba32Float => "rgba32float",
+            _ => bail!("{:?} can't be written by the blur passes, use Rgba8Unorm, Rgba16Float or Rgba32Float", format)
+        };
+        let source = load_shader("blur.wgsl", include_str!("res/shaders/blur.wgsl")).replace("rgba8unorm, write", &format!("{}, write", storage_format));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blur Bind Group Layout"),
+            entries: &[
+                // source
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                },
+                // destination
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format,
+                        view_dimension: wgpu::TextureViewDimension::D2
+                    },
+                    count: None
+                },
+                // kernel
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+
+        let (blur_pipeline, downsample_pipeline, upsample_pipeline) = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Blur Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into())
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Blur Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[]
+            });
+            let create_pipeline = |label: &str, entry_point: &str| {
+                device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    module: &shader_module,
+                    entry_point
+                })
+            };
+            (
+                create_pipeline("Blur Compute Pipeline", "blur_main"),
+                create_pipeline("Downsample Compute Pipeline", "downsample_main"),
+                create_pipeline("Upsample Compute Pipeline", "upsample_main")
+            )
+        })?;
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            format,
+            bind_group_layout,
+            blur_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            sampler
+        })
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    fn dispatch(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::ComputePipeline,
+        (source, destination): (&wgpu::TextureView, &wgpu::TextureView),
+        (width, height): (u32, u32),
+        uniform: BlurUniform
+    ) {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Blur Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blur Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(destination)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler)
+                }
+            ]
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Blur Compute Pass")
+        });
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1
+        );
+    }
+
+    // Blur the last result of `targets` horizontally then vertically, the blurred image is their new `read_view()`.
+    // tips: several small gaussians in a row add up to a wider one (sigma = sqrt(sum of sigma²)).
+    pub fn blur(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, targets: &mut PingPongTargets, kernel: BlurKernel) {
+        let size = (targets.width(), targets.height());
+        for direction in [[1, 0], [0, 1]] {
+            self.dispatch(device, encoder, &self.blur_pipeline, (targets.read_view(), targets.write_view()), size, kernel.to_uniform(direction));
+            targets.swap();
+        }
+    }
+
+    // Blur `texture` in place, through `temporary` of the same size (both written, so both need STORAGE_BINDING).
+    pub fn blur_texture(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::TextureView,
+        temporary: &wgpu::TextureView,
+        size: (u32, u32),
+        kernel: BlurKernel
+    ) {
+        self.dispatch(device, encoder, &self.blur_pipeline, (texture, temporary), size, kernel.to_uniform([1, 0]));
+        self.dispatch(device, encoder, &self.blur_pipeline, (temporary, texture), size, kernel.to_uniform([0, 1]));
+    }
+
+    // Filter `source` into the smaller `destination` of `destination_size`, usually half of the source size.
+    pub fn downsample(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        destination: &wgpu::TextureView,
+        destination_size: (u32, u32)
+    ) {
+        self.dispatch(device, encoder, &self.downsample_pipeline, (source, destination), destination_size, bytemuck::Zeroable::zeroed());
+    }
+
+    // Filter `source` into the larger `destination` of `destination_size`, usually double of the source size.
+    pub fn upsample(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        destination: &wgpu::TextureView,
+        destination_size: (u32, u32)
+    ) {
+        self.dispatch(device, encoder, &self.upsample_pipeline, (source, destination), destination_size, bytemuck::Zeroable::zeroed());
+    }
+}
+
+// Successively halved copies of a texture (bloom, SSAO, wide blurs...), level 0 is half the source size.
+pub struct MipChain {
+    levels: Vec<(Texture, (u32, u32))>
+}
+
+impl MipChain {
+    // Up to `level_count` levels, stopping at 1x1. The levels get STORAGE_BINDING to be written by `BlurPasses`.
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, level_count: u32, format: wgpu::TextureFormat, label: &str) -> Self {
+        let mut levels = Vec::new();
+        let (mut width, mut height) = (width, height);
+        while (levels.len() as u32) < level_count && (width > 1 || height > 1) {
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+            let texture = Texture::create_render_target(
+                device,
+                width,
+                height,
+                format,
+                wgpu::TextureUsages::STORAGE_BINDING,
+                &format!("{} Level {}", label, levels.len())
+            );
+            levels.push((texture, (width, height)));
+        }
+        Self { levels }
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn level_view(&self, level: usize) -> &wgpu::TextureView {
+        &self.levels[level].0.view
+    }
+
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        self.levels[level].1
+    }
+
+    // Fill every level from `source`, each from the previous one.
+    pub fn downsample(&self, blur_passes: &BlurPasses, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, source: &wgpu::TextureView) {
+        for (level, (texture, size)) in self.levels.iter().enumerate() {
+            let previous = if level == 0 { source } else { &self.levels[level - 1].0.view };
+            blur_passes.downsample(device, encoder, previous, &texture.view, *size);
+        }
+    }
+}
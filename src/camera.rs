@@ -0,0 +1,883 @@
+use nalgebra::{Matrix4, Point3, Rotation3, Unit, Vector3};
+use winit::event::{DeviceEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+use super::app_config::DepthMode;
+
+// The coordinate system in Wgpu is based on DirectX, and Metal's coordinate systems.
+// So that in normalized device coordinates the z axis is 0.0 to +1.0.
+// But nalgebra crate are built for OpenGL's coordinate system whose z axis is -1.0 to +1.0.
+// Thus, we need to translate matrix from OpenGL to Wgpu coord system.
+// ref: https://github.com/gfx-rs/gfx/tree/master/src/backend/dx12
+// ref: https://github.com/gfx-rs/gfx/tree/master/src/backend/gl
+// tips: `Matrix4::new()` takes the elements row by row, z' = 0.5 * z + 0.5 * w.
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
+
+// Same with reversed-Z (see `DepthMode::ReversedZ`): z' = -0.5 * z + 0.5 * w, the near plane lands at 1.0 & the far plane at 0.0.
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_REVERSED_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PerspectiveCamera {
+    // vertical field of view, in radians
+    pub fovy: f32,
+    pub znear: f32,
+    // `f32::INFINITY` for an infinite far plane, e.g. for open worlds
+    // tips: pair it with `DepthMode::ReversedZ`, the precision of the standard depth runs out quickly far away.
+    pub zfar: f32
+}
+
+impl PerspectiveCamera {
+    // nothing is clipped in the distance
+    pub fn infinite(fovy: f32, znear: f32) -> Self {
+        Self {
+            fovy,
+            znear,
+            zfar: f32::INFINITY
+        }
+    }
+
+    pub fn has_infinite_far(&self) -> bool {
+        self.zfar.is_infinite()
+    }
+
+    // OpenGL style projection, like `nalgebra::Perspective3`
+    fn to_homogeneous(self, aspect: f32) -> Matrix4<f32> {
+        if !self.has_infinite_far() {
+            return nalgebra::Perspective3::new(aspect, self.fovy, self.znear, self.zfar).to_homogeneous();
+        }
+        // the limit of the perspective matrix when zfar goes to infinity
+        let f = 1.0 / (self.fovy * 0.5).tan();
+        Matrix4::new(
+            f / aspect, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, -1.0, -2.0 * self.znear,
+            0.0, 0.0, -1.0, 0.0,
+        )
+    }
+}
+
+impl Default for PerspectiveCamera {
+    fn default() -> Self {
+        Self {
+            fovy: 45f32.to_radians(),
+            znear: 0.1,
+            zfar: 100.0
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrthographicCamera {
+    // world units visible from the bottom to the top of the window, the width follows the aspect ratio
+    pub height: f32,
+    pub znear: f32,
+    pub zfar: f32
+}
+
+impl Default for OrthographicCamera {
+    fn default() -> Self {
+        Self {
+            height: 10.0,
+            znear: 0.1,
+            zfar: 100.0
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective(PerspectiveCamera),
+    Orthographic(OrthographicCamera)
+}
+
+// Look-at camera the scene is rendered from, see `CameraRig`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+    // window size in pixels, kept up to date by the engine
+    viewport: (u32, u32),
+    // from `AppConfig::depth_mode`, set by the engine
+    depth_mode: DepthMode
+}
+
+impl Camera {
+    pub fn perspective(eye: Point3<f32>, target: Point3<f32>) -> Self {
+        Self {
+            eye,
+            target,
+            up: Vector3::y(),
+            projection: Projection::Perspective(PerspectiveCamera::default()),
+            viewport: (1, 1),
+            depth_mode: DepthMode::Standard
+        }
+    }
+
+    // `height`: world units visible vertically
+    pub fn orthographic(eye: Point3<f32>, target: Point3<f32>, height: f32) -> Self {
+        Self {
+            projection: Projection::Orthographic(OrthographicCamera {
+                height,
+                ..OrthographicCamera::default()
+            }),
+            ..Self::perspective(eye, target)
+        }
+    }
+
+    // 2D camera looking along -z at `center` in the xy plane, `height` world units visible vertically,
+    // e.g. for sprites between z = -90.0 & z = 10.0
+    pub fn orthographic_2d(center: [f32; 2], height: f32) -> Self {
+        Self::orthographic([center[0], center[1], 10.0].into(), [center[0], center[1], 0.0].into(), height)
+    }
+
+    pub fn viewport(&self) -> (u32, u32) {
+        self.viewport
+    }
+
+    pub(crate) fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = (width.max(1), height.max(1));
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.viewport.0 as f32 / self.viewport.1 as f32
+    }
+
+    // unit vector from the eye to the target
+    pub fn forward(&self) -> Vector3<f32> {
+        (self.target - self.eye).normalize()
+    }
+
+    // unit vector to the right of the screen
+    pub fn right(&self) -> Vector3<f32> {
+        self.forward().cross(&self.up).normalize()
+    }
+
+    // size of a pixel at the target's distance, e.g. to pan by a mouse drag
+    pub fn world_units_per_pixel(&self) -> f32 {
+        let height = match self.projection {
+            Projection::Perspective(perspective) => 2.0 * (self.target - self.eye).magnitude() * (perspective.fovy * 0.5).tan(),
+            Projection::Orthographic(orthographic) => orthographic.height
+        };
+        height / self.viewport.1 as f32
+    }
+
+    // view tranform matrix (right-handed)
+    // right-handed: camera always look at -z after transform
+    // left-handed:  camera always look at +z after transform
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(&self.eye, &self.target, &self.up)
+    }
+
+    // projection to wgpu's clip space
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        let projection = match self.projection {
+            Projection::Perspective(perspective) => {
+                perspective.to_homogeneous(self.aspect())
+            },
+            Projection::Orthographic(orthographic) => {
+                let half_height = orthographic.height * 0.5;
+                let half_width = half_height * self.aspect();
+                nalgebra::Orthographic3::new(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    orthographic.znear,
+                    orthographic.zfar
+                ).to_homogeneous()
+            }
+        };
+        match self.depth_mode {
+            DepthMode::Standard => OPENGL_TO_WGPU_MATRIX * projection,
+            DepthMode::ReversedZ => OPENGL_TO_WGPU_REVERSED_Z_MATRIX * projection
+        }
+    }
+
+    // ref: https://nalgebra.org/docs/user_guide/cg_recipes/#build-a-mvp-matrix
+    pub fn view_projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+}
+
+impl Default for Camera {
+    // 2 units in front of the origin, looking at it
+    fn default() -> Self {
+        Self::perspective([0.0, 0.0, 2.0].into(), Point3::origin())
+    }
+}
+
+// Retro 2D rendering: the scene is rendered at a low resolution, then scaled up by a whole factor with nearest filtering,
+// so every texel of the scene covers the same square of screen pixels & nothing shimmers while the camera moves.
+// The low resolution is the window size divided by the largest zoom which still shows `resolution`,
+// the remaining few pixels of the window are left black.
+// Orthographic cameras show `pixels_per_unit` texels per world unit (their height is set accordingly)
+// & are snapped to the texel grid; sprites should be sized & placed in whole texels too.
+// tips: the lens flare & the depth view aren't drawn in this mode, `Camera::viewport()` is the low resolution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelPerfect {
+    // the smallest area of the scene shown (unless the window is smaller), in texels, e.g. 320x180
+    pub resolution: (u32, u32),
+    pub pixels_per_unit: f32
+}
+
+impl PixelPerfect {
+    pub fn new(width: u32, height: u32, pixels_per_unit: f32) -> Self {
+        Self {
+            resolution: (width.max(1), height.max(1)),
+            pixels_per_unit: pixels_per_unit.max(f32::EPSILON)
+        }
+    }
+
+    // the whole scale factor of a window of `width` x `height` pixels
+    pub fn zoom(&self, width: u32, height: u32) -> u32 {
+        (width / self.resolution.0).min(height / self.resolution.1).max(1)
+    }
+
+    // the size of the low resolution target for a window of `width` x `height` pixels
+    pub fn target_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let zoom = self.zoom(width, height);
+        ((width / zoom).max(1), (height / zoom).max(1))
+    }
+}
+
+// Area of the world the camera keeps to, in the xy plane (2D cameras looking along -z).
+// Orthographic cameras keep the whole view inside (centered if it's larger than the bounds),
+// perspective ones keep their target inside.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraBounds {
+    pub min: [f32; 2],
+    pub max: [f32; 2]
+}
+
+impl CameraBounds {
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self {
+            min: [min[0].min(max[0]), min[1].min(max[1])],
+            max: [min[0].max(max[0]), min[1].max(max[1])]
+        }
+    }
+
+    // move the eye & target together, so the camera keeps its direction
+    fn clamp(&self, camera: &mut Camera) {
+        let half_size = match camera.projection {
+            Projection::Orthographic(orthographic) => [orthographic.height * 0.5 * camera.aspect(), orthographic.height * 0.5],
+            Projection::Perspective(_) => [0.0, 0.0]
+        };
+        let clamp_axis = |position: f32, axis: usize| {
+            let (min, max) = (self.min[axis] + half_size[axis], self.max[axis] - half_size[axis]);
+            if min > max { (self.min[axis] + self.max[axis]) * 0.5 } else { position.clamp(min, max) }
+        };
+        let offset = Vector3::new(
+            clamp_axis(camera.target.x, 0) - camera.target.x,
+            clamp_axis(camera.target.y, 1) - camera.target.y,
+            0.0
+        );
+        camera.eye += offset;
+        camera.target += offset;
+    }
+}
+
+// Moves a camera from the window input, see `CameraRig::set_controller()`.
+pub trait CameraController {
+    // returns true if the event is used by the controller, it isn't processed any further then.
+    fn process_event(&mut self, event: &WindowEvent) -> bool;
+
+    // raw device input, e.g. the mouse motion which keeps coming while the pointer is locked.
+    // tips: device events aren't tied to the window, they're received even when it isn't focused.
+    fn process_device_event(&mut self, _event: &DeviceEvent) -> bool {
+        false
+    }
+
+    // whether the cursor is hidden & grabbed by the window (pointer lock), checked every frame
+    fn pointer_locked(&self) -> bool {
+        false
+    }
+
+    // move the camera by the input received since the last frame, `delta_time` in seconds.
+    fn update(&mut self, camera: &mut Camera, delta_time: f32);
+}
+
+// The active camera & its controller, owned by the engine & changed with `Application::update_camera()`.
+pub struct CameraRig {
+    camera: Camera,
+    controller: Option<Box<dyn CameraController>>,
+    // in pixels
+    window_size: (u32, u32),
+    pixel_perfect: Option<PixelPerfect>,
+    bounds: Option<CameraBounds>
+}
+
+impl CameraRig {
+    pub(crate) fn new(width: u32, height: u32, depth_mode: DepthMode) -> Self {
+        let mut camera = Camera::default();
+        camera.set_viewport(width, height);
+        camera.depth_mode = depth_mode;
+        Self {
+            camera,
+            controller: Some(Box::new(OrbitController::new(6.0))),
+            window_size: (width, height),
+            pixel_perfect: None,
+            bounds: None
+        }
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    // replace the active camera, it keeps the window size & depth mode
+    pub fn set_camera(&mut self, mut camera: Camera) {
+        camera.viewport = self.camera.viewport;
+        camera.depth_mode = self.camera.depth_mode;
+        self.camera = camera;
+    }
+
+    // replace the controller, None leaves the camera to the application
+    pub fn set_controller(&mut self, controller: Option<Box<dyn CameraController>>) {
+        self.controller = controller;
+    }
+
+    // render the scene in low resolution texels, None renders it at the window resolution
+    pub fn set_pixel_perfect(&mut self, pixel_perfect: Option<PixelPerfect>) {
+        self.pixel_perfect = pixel_perfect;
+        self.resize(self.window_size.0, self.window_size.1);
+    }
+
+    pub fn pixel_perfect(&self) -> Option<PixelPerfect> {
+        self.pixel_perfect
+    }
+
+    // keep the camera inside `bounds` after the controller moved it, None lets it go anywhere
+    pub fn set_bounds(&mut self, bounds: Option<CameraBounds>) {
+        self.bounds = bounds;
+    }
+
+    pub fn bounds(&self) -> Option<CameraBounds> {
+        self.bounds
+    }
+
+    // the size of the low resolution target & the zoom it's scaled up by, in pixel-perfect mode
+    pub(crate) fn pixel_target(&self) -> Option<((u32, u32), u32)> {
+        let pixel_perfect = self.pixel_perfect?;
+        let (width, height) = self.window_size;
+        Some((pixel_perfect.target_size(width, height), pixel_perfect.zoom(width, height)))
+    }
+
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        self.window_size = (width, height);
+        let (width, height) = match self.pixel_perfect {
+            Some(pixel_perfect) => pixel_perfect.target_size(width, height),
+            None => (width, height)
+        };
+        self.camera.set_viewport(width, height);
+    }
+
+    // The camera the frame is rendered from: in pixel-perfect mode, orthographic cameras are snapped to the texel grid.
+    // tips: only the rendered copy is snapped, so slow camera moves still add up.
+    pub(crate) fn render_camera(&self) -> Camera {
+        let mut camera = self.camera;
+        let pixel_perfect = match (self.pixel_perfect, camera.projection) {
+            (Some(pixel_perfect), Projection::Orthographic(_)) => pixel_perfect,
+            _ => return camera
+        };
+        // texel edges on whole multiples of a texel, odd sizes put the center in the middle of a texel
+        let texel = 1.0 / pixel_perfect.pixels_per_unit;
+        let snap = |position: f32, texels: u32| {
+            let half = texels as f32 * 0.5 * texel;
+            ((position - half) / texel).round() * texel + half
+        };
+        let offset = Vector3::new(
+            snap(camera.target.x, camera.viewport.0) - camera.target.x,
+            snap(camera.target.y, camera.viewport.1) - camera.target.y,
+            0.0
+        );
+        camera.eye += offset;
+        camera.target += offset;
+        camera
+    }
+
+    // the camera & its bounds follow the scene, see `WorldOrigin`
+    pub(crate) fn shift_origin(&mut self, shift: &Vector3<f32>) {
+        self.camera.eye -= shift;
+        self.camera.target -= shift;
+        if let Some(bounds) = &mut self.bounds {
+            for axis in 0..2 {
+                bounds.min[axis] -= shift[axis];
+                bounds.max[axis] -= shift[axis];
+            }
+        }
+    }
+
+    pub(crate) fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match &mut self.controller {
+            Some(controller) => controller.process_event(event),
+            None => false
+        }
+    }
+
+    pub(crate) fn process_device_event(&mut self, event: &DeviceEvent) -> bool {
+        match &mut self.controller {
+            Some(controller) => controller.process_device_event(event),
+            None => false
+        }
+    }
+
+    pub(crate) fn pointer_locked(&self) -> bool {
+        match &self.controller {
+            Some(controller) => controller.pointer_locked(),
+            None => false
+        }
+    }
+
+    pub(crate) fn update(&mut self, delta_time: f32) {
+        if let Some(controller) = &mut self.controller {
+            controller.update(&mut self.camera, delta_time);
+        }
+        // a whole number of texels per world unit
+        if let (Some(pixel_perfect), Projection::Orthographic(orthographic)) = (self.pixel_perfect, &mut self.camera.projection) {
+            orthographic.height = self.camera.viewport.1 as f32 / pixel_perfect.pixels_per_unit;
+        }
+        if let Some(bounds) = self.bounds {
+            bounds.clamp(&mut self.camera);
+        }
+    }
+}
+
+// pressed state of a key, the event is used if it's the key
+fn track_key(event: &WindowEvent, keys: &[VirtualKeyCode], pressed: &mut bool) -> bool {
+    match event {
+        WindowEvent::KeyboardInput {
+            input: KeyboardInput {
+                state,
+                virtual_keycode: Some(keycode),
+                ..
+            },
+            ..
+        } if keys.contains(keycode) => {
+            *pressed = *state == ElementState::Pressed;
+            true
+        },
+        _ => false
+    }
+}
+
+// Cursor movement while a mouse button is held, in pixels.
+#[derive(Clone, Copy, Debug)]
+struct MouseDrag {
+    button: MouseButton,
+    pressed: bool,
+    cursor: Option<(f64, f64)>,
+    delta: (f32, f32)
+}
+
+impl MouseDrag {
+    fn new(button: MouseButton) -> Self {
+        Self {
+            button,
+            pressed: false,
+            cursor: None,
+            delta: (0.0, 0.0)
+        }
+    }
+
+    fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } if *button == self.button => {
+                self.pressed = *state == ElementState::Pressed;
+                true
+            },
+            // the cursor is tracked for every controller: not used
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some((x, y))) = (self.pressed, self.cursor) {
+                    self.delta.0 += (position.x - x) as f32;
+                    self.delta.1 += (position.y - y) as f32;
+                }
+                self.cursor = Some((position.x, position.y));
+                false
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                false
+            },
+            _ => false
+        }
+    }
+
+    fn take_delta(&mut self) -> (f32, f32) {
+        std::mem::replace(&mut self.delta, (0.0, 0.0))
+    }
+}
+
+// wheel notches, pixel deltas (touchpads) are converted
+fn scroll_lines(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0
+    }
+}
+
+// rotate `vector` by `angle` radians around `axis`
+fn rotate(vector: Vector3<f32>, axis: Vector3<f32>, angle: f32) -> Vector3<f32> {
+    Rotation3::from_axis_angle(&Unit::new_normalize(axis), angle) * vector
+}
+
+// Turns around the target: W/S or the wheel get closer/further, A/D or a left mouse drag orbit, J/K move up/down.
+pub struct OrbitController {
+    // units per second
+    pub speed: f32,
+    // radians per dragged pixel
+    pub sensitivity: f32,
+    // share of the distance to the target per wheel notch
+    pub zoom_sensitivity: f32,
+    is_up_pressed: bool,
+    is_down_pressed: bool,
+    is_forward_pressed: bool,
+    is_backward_pressed: bool,
+    is_left_pressed: bool,
+    is_right_pressed: bool,
+    drag: MouseDrag,
+    scroll: f32
+}
+
+impl OrbitController {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            sensitivity: 0.005,
+            zoom_sensitivity: 0.1,
+            is_up_pressed: false,
+            is_down_pressed: false,
+            is_forward_pressed: false,
+            is_backward_pressed: false,
+            is_left_pressed: false,
+            is_right_pressed: false,
+            drag: MouseDrag::new(MouseButton::Left),
+            scroll: 0.0
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn process_event(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::MouseWheel { delta, .. } = event {
+            self.scroll += scroll_lines(delta);
+            return true;
+        }
+        track_key(event, &[VirtualKeyCode::J], &mut self.is_up_pressed)
+            || track_key(event, &[VirtualKeyCode::K], &mut self.is_down_pressed)
+            || track_key(event, &[VirtualKeyCode::W, VirtualKeyCode::Up], &mut self.is_forward_pressed)
+            || track_key(event, &[VirtualKeyCode::S, VirtualKeyCode::Down], &mut self.is_backward_pressed)
+            || track_key(event, &[VirtualKeyCode::A, VirtualKeyCode::Left], &mut self.is_left_pressed)
+            || track_key(event, &[VirtualKeyCode::D, VirtualKeyCode::Right], &mut self.is_right_pressed)
+            || self.drag.process_event(event)
+    }
+
+    fn update(&mut self, camera: &mut Camera, delta_time: f32) {
+        let forward = camera.target - camera.eye;
+        let forward_norm = forward.normalize();
+        let forward_mag = forward.magnitude();
+
+        // units per second, a wheel notch moves `zoom_sensitivity` of the distance
+        let step = self.speed * delta_time;
+        let zoom = std::mem::take(&mut self.scroll) * forward_mag * self.zoom_sensitivity;
+
+        let dolly = if self.is_forward_pressed { step } else { 0.0 } - if self.is_backward_pressed { step } else { 0.0 } + zoom;
+        if dolly < forward_mag {
+            camera.eye += forward_norm * dolly;
+        }
+
+        // new forward direction
+        let forward = camera.target - camera.eye;
+        let forward_mag = forward.magnitude();
+
+        let left = forward_norm.cross(&camera.up);
+        let right = -left;
+
+        // right/left move is rotation around the "target"
+        if self.is_right_pressed {
+            camera.eye = camera.target - (forward + right * step).normalize() * forward_mag;
+        }
+        if self.is_left_pressed {
+            camera.eye = camera.target - (forward +  left * step).normalize() * forward_mag;
+        }
+
+        // dragging rotates around the up axis & tilts, never over the poles
+        let (dx, dy) = self.drag.take_delta();
+        if dx != 0.0 || dy != 0.0 {
+            let offset = rotate(camera.eye - camera.target, camera.up, -dx * self.sensitivity);
+            let tilted = rotate(offset, offset.cross(&camera.up), dy * self.sensitivity);
+            let polar = tilted.angle(&camera.up);
+            let offset = if polar > 0.05 && polar < std::f32::consts::PI - 0.05 { tilted } else { offset };
+            camera.eye = camera.target + offset;
+        }
+
+        // up/down move is translation toward/backward "up"
+        if self.is_up_pressed {
+            camera.eye += camera.up * step;
+        }
+        if self.is_down_pressed {
+            camera.eye -= camera.up * step;
+        }
+    }
+}
+
+// First person fly camera: W/S/A/D move, Q/E go down/up, the mouse looks around while the right button is held
+// (or all the time with a pointer lock), the wheel zooms by narrowing the field of view.
+// tips: looking uses the raw mouse motion, so it isn't stopped by the window edges.
+pub struct FlyController {
+    // units per second
+    pub speed: f32,
+    // radians per mouse count (about a pixel)
+    pub sensitivity: f32,
+    // field of view factor per wheel notch
+    pub zoom_step: f32,
+    // field of view limits of the zoom, in radians
+    pub min_fovy: f32,
+    pub max_fovy: f32,
+    is_forward_pressed: bool,
+    is_backward_pressed: bool,
+    is_left_pressed: bool,
+    is_right_pressed: bool,
+    is_up_pressed: bool,
+    is_down_pressed: bool,
+    is_look_pressed: bool,
+    // None without pointer lock, else whether the pointer is locked now
+    pointer_lock: Option<bool>,
+    look: (f32, f32),
+    scroll: f32
+}
+
+impl FlyController {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            sensitivity: 0.003,
+            zoom_step: 1.1,
+            min_fovy: 10.0_f32.to_radians(),
+            max_fovy: 90.0_f32.to_radians(),
+            is_forward_pressed: false,
+            is_backward_pressed: false,
+            is_left_pressed: false,
+            is_right_pressed: false,
+            is_up_pressed: false,
+            is_down_pressed: false,
+            is_look_pressed: false,
+            pointer_lock: None,
+            look: (0.0, 0.0),
+            scroll: 0.0
+        }
+    }
+
+    // Look around without holding a button: a left click locks the pointer (hidden & kept in the window),
+    // Escape or leaving the window releases it.
+    pub fn with_pointer_lock(mut self) -> Self {
+        self.pointer_lock = Some(false);
+        self
+    }
+
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    fn is_looking(&self) -> bool {
+        self.is_look_pressed || self.pointer_lock == Some(true)
+    }
+}
+
+impl CameraController for FlyController {
+    fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += scroll_lines(delta);
+                return true;
+            },
+            WindowEvent::MouseInput { state, button: MouseButton::Right, .. } => {
+                self.is_look_pressed = *state == ElementState::Pressed;
+                return true;
+            },
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } if self.pointer_lock == Some(false) => {
+                self.pointer_lock = Some(true);
+                return true;
+            },
+            // the app isn't closed while the pointer is locked
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Escape),
+                    ..
+                },
+                ..
+            } if self.pointer_lock == Some(true) => {
+                self.pointer_lock = Some(false);
+                return true;
+            },
+            WindowEvent::Focused(false) => {
+                if self.pointer_lock.is_some() {
+                    self.pointer_lock = Some(false);
+                }
+                self.is_look_pressed = false;
+                return false;
+            },
+            _ => {}
+        }
+        track_key(event, &[VirtualKeyCode::W, VirtualKeyCode::Up], &mut self.is_forward_pressed)
+            || track_key(event, &[VirtualKeyCode::S, VirtualKeyCode::Down], &mut self.is_backward_pressed)
+            || track_key(event, &[VirtualKeyCode::A, VirtualKeyCode::Left], &mut self.is_left_pressed)
+            || track_key(event, &[VirtualKeyCode::D, VirtualKeyCode::Right], &mut self.is_right_pressed)
+            || track_key(event, &[VirtualKeyCode::E], &mut self.is_up_pressed)
+            || track_key(event, &[VirtualKeyCode::Q], &mut self.is_down_pressed)
+    }
+
+    fn process_device_event(&mut self, event: &DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::MouseMotion { delta } if self.is_looking() => {
+                self.look.0 += delta.0 as f32;
+                self.look.1 += delta.1 as f32;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn pointer_locked(&self) -> bool {
+        self.pointer_lock == Some(true)
+    }
+
+    fn update(&mut self, camera: &mut Camera, delta_time: f32) {
+        // look around: yaw around the up axis, then pitch without flipping over
+        let mut forward = camera.forward();
+        let (dx, dy) = std::mem::replace(&mut self.look, (0.0, 0.0));
+        if dx != 0.0 || dy != 0.0 {
+            forward = rotate(forward, camera.up, -dx * self.sensitivity);
+            let pitched = rotate(forward, forward.cross(&camera.up), -dy * self.sensitivity);
+            let polar = pitched.angle(&camera.up);
+            if polar > 0.05 && polar < std::f32::consts::PI - 0.05 {
+                forward = pitched;
+            }
+        }
+
+        // scrolling up zooms in, orthographic cameras have no field of view
+        let scroll = std::mem::take(&mut self.scroll);
+        if let Projection::Perspective(perspective) = &mut camera.projection {
+            if scroll != 0.0 {
+                perspective.fovy = (perspective.fovy * self.zoom_step.powf(-scroll)).clamp(self.min_fovy, self.max_fovy);
+            }
+        }
+
+        let axis = |positive: bool, negative: bool| (positive as i32 - negative as i32) as f32;
+        let right = forward.cross(&camera.up).normalize();
+        let movement = forward * axis(self.is_forward_pressed, self.is_backward_pressed)
+            + right * axis(self.is_right_pressed, self.is_left_pressed)
+            + camera.up * axis(self.is_up_pressed, self.is_down_pressed);
+        if movement.magnitude_squared() > 0.0 {
+            camera.eye += movement.normalize() * self.speed * delta_time;
+        }
+        // the target stays 1 unit ahead
+        camera.target = camera.eye + forward;
+    }
+}
+
+// 2D pan & zoom, for orthographic cameras looking at a plane: a left mouse drag or W/S/A/D pan, the wheel zooms.
+pub struct PanZoomController {
+    // screen heights per second, when panning with the keys
+    pub speed: f32,
+    // zoom factor per wheel notch
+    pub zoom_step: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    is_up_pressed: bool,
+    is_down_pressed: bool,
+    is_left_pressed: bool,
+    is_right_pressed: bool,
+    drag: MouseDrag,
+    scroll: f32
+}
+
+impl PanZoomController {
+    pub fn new() -> Self {
+        Self {
+            speed: 0.5,
+            zoom_step: 1.1,
+            min_height: 0.1,
+            max_height: 1000.0,
+            is_up_pressed: false,
+            is_down_pressed: false,
+            is_left_pressed: false,
+            is_right_pressed: false,
+            drag: MouseDrag::new(MouseButton::Left),
+            scroll: 0.0
+        }
+    }
+}
+
+impl Default for PanZoomController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CameraController for PanZoomController {
+    fn process_event(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::MouseWheel { delta, .. } = event {
+            self.scroll += scroll_lines(delta);
+            return true;
+        }
+        track_key(event, &[VirtualKeyCode::W, VirtualKeyCode::Up], &mut self.is_up_pressed)
+            || track_key(event, &[VirtualKeyCode::S, VirtualKeyCode::Down], &mut self.is_down_pressed)
+            || track_key(event, &[VirtualKeyCode::A, VirtualKeyCode::Left], &mut self.is_left_pressed)
+            || track_key(event, &[VirtualKeyCode::D, VirtualKeyCode::Right], &mut self.is_right_pressed)
+            || self.drag.process_event(event)
+    }
+
+    fn update(&mut self, camera: &mut Camera, delta_time: f32) {
+        // the content follows the cursor while dragging
+        let units_per_pixel = camera.world_units_per_pixel();
+        let (dx, dy) = self.drag.take_delta();
+        let axis = |positive: bool, negative: bool| (positive as i32 - negative as i32) as f32;
+        let key_step = self.speed * camera.viewport().1 as f32 * delta_time;
+        let pan_x = -dx + axis(self.is_right_pressed, self.is_left_pressed) * key_step;
+        let pan_y = dy + axis(self.is_up_pressed, self.is_down_pressed) * key_step;
+        let screen_up = camera.right().cross(&camera.forward());
+        let pan = (camera.right() * pan_x + screen_up * pan_y) * units_per_pixel;
+        camera.eye += pan;
+        camera.target += pan;
+
+        // scrolling up zooms in
+        let scroll = std::mem::take(&mut self.scroll);
+        if scroll != 0.0 {
+            let factor = self.zoom_step.powf(-scroll);
+            match &mut camera.projection {
+                Projection::Orthographic(orthographic) => {
+                    orthographic.height = (orthographic.height * factor).clamp(self.min_height, self.max_height);
+                },
+                Projection::Perspective(_) => {
+                    let offset = camera.eye - camera.target;
+                    let distance = (offset.magnitude() * factor).clamp(self.min_height, self.max_height);
+                    camera.eye = camera.target + offset.normalize() * distance;
+                }
+            }
+        }
+    }
+}
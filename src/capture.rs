@@ -0,0 +1,564 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+use super::texture::Texture;
+
+// Settings of the screenshots (F12) & clips (F9 records, F10 saves), returned by `Application::capture_config()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureConfig {
+    // where the screenshots & clips are saved, created if needed
+    pub directory: PathBuf,
+    // copy the screenshots to the clipboard as well
+    pub copy_to_clipboard: bool,
+    // length of the clips: the last seconds before F10
+    pub clip_seconds: f32,
+    // frames per second of the clips
+    pub clip_fps: f32,
+    // the clip frames are scaled down to this width, full size frames use a lot of memory
+    pub clip_max_width: u32,
+    // save the clips as a PNG sequence (a folder per clip) instead of an animated GIF
+    pub clip_as_png_sequence: bool
+}
+
+impl Default for CaptureConfig {
+    // 5 seconds GIFs at 15 fps, up to 480 pixels wide
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("captures"),
+            copy_to_clipboard: true,
+            clip_seconds: 5.0,
+            clip_fps: 15.0,
+            clip_max_width: 480,
+            clip_as_png_sequence: false
+        }
+    }
+}
+
+// A presented frame sent to a `FrameStream`'s receiver.
+#[derive(Clone, Debug)]
+pub struct StreamedFrame {
+    // counts the streamed frames, a gap means the receiver was too slow & frames were dropped
+    pub index: u64,
+    // since the stream started
+    pub timestamp: Duration,
+    pub image: image::RgbaImage
+}
+
+// Copies of the presented frames sent to a channel, e.g. to feed a video encoder or a computer vision pipeline.
+// Returned by `Application::frame_stream()`, the frames are received on any thread.
+pub struct FrameStream {
+    // frames per second sent at most
+    pub fps: f32,
+    // the frames are scaled down to this width
+    pub max_width: u32,
+    sender: mpsc::SyncSender<StreamedFrame>,
+    start: Option<Instant>,
+    sent: u64,
+    last_frame: Option<Instant>
+}
+
+impl FrameStream {
+    // Up to `capacity` frames wait in the channel: when it's full the new frames are dropped, the renderer never waits for the receiver.
+    // The stream stops once the receiver is dropped.
+    pub fn channel(fps: f32, max_width: u32, capacity: usize) -> (Self, mpsc::Receiver<StreamedFrame>) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let stream = Self {
+            fps,
+            max_width,
+            sender,
+            start: None,
+            sent: 0,
+            last_frame: None
+        };
+        (stream, receiver)
+    }
+
+    fn is_due(&self) -> bool {
+        match self.last_frame {
+            Some(last_frame) => last_frame.elapsed().as_secs_f32() >= 1.0 / self.fps.max(f32::EPSILON),
+            None => true
+        }
+    }
+
+    // false once the receiver is gone
+    fn send(&mut self, image: &image::RgbaImage) -> bool {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        self.last_frame = Some(now);
+        let frame = StreamedFrame {
+            index: self.sent,
+            timestamp: now.duration_since(start),
+            image: scale_to_width(image.clone(), self.max_width)
+        };
+        self.sent += 1;
+        !matches!(self.sender.try_send(frame), Err(mpsc::TrySendError::Disconnected(_)))
+    }
+}
+
+// What the frame being rendered is captured for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CaptureRequest {
+    screenshot: bool,
+    clip_frame: bool,
+    stream_frame: bool
+}
+
+// Screenshots & rolling clip recording from the readback path.
+// The surface texture can't be copied from, so a captured frame is rendered into `target`,
+// then copied to the surface (for display) & to `staging_buffer` (for the CPU).
+pub(crate) struct FrameCapture {
+    config: CaptureConfig,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    target: Option<(Texture, wgpu::BindGroup, wgpu::Buffer)>, // created on the first capture & on resize
+    bind_group_layout: wgpu::BindGroupLayout,
+    blit_pipeline: Option<wgpu::RenderPipeline>, // None if the pipeline failed to build
+    request: Option<CaptureRequest>, // the frame being rendered is captured
+    screenshot_requested: bool,
+    // where the requested screenshot is saved, None for the capture directory
+    screenshot_path: Option<PathBuf>,
+    clip_requested: bool,
+    recording: bool,
+    clip_frames: VecDeque<(Instant, image::RgbaImage)>,
+    last_clip_frame: Option<Instant>,
+    stream: Option<FrameStream>
+}
+
+impl FrameCapture {
+    pub(crate) fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, error_overlay: &mut ErrorOverlay) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blit BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let blit_pipeline = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Blit Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("blit.wgsl", include_str!("res/shaders/blit.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Blit Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the full-screen triangle is generated from the vertex index
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let blit_pipeline = blit_pipeline
+            .map_err(|error| error_overlay.report("Blit Render Pipeline", &error))
+            .ok();
+
+        Self {
+            config: CaptureConfig::default(),
+            width: config.width,
+            height: config.height,
+            format: config.format,
+            target: None,
+            bind_group_layout,
+            blit_pipeline,
+            request: None,
+            screenshot_requested: false,
+            screenshot_path: None,
+            clip_requested: false,
+            recording: false,
+            clip_frames: VecDeque::new(),
+            last_clip_frame: None,
+            stream: None
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: CaptureConfig) {
+        self.config = config;
+    }
+
+    // the target is recreated with the next capture, the recorded frames of the old size are dropped
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.target = None;
+        self.clip_frames.clear();
+    }
+
+    pub(crate) fn set_stream(&mut self, stream: Option<FrameStream>) {
+        self.stream = stream;
+    }
+
+    pub(crate) fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    // the next frame is saved to `path`, before `read_frame()` returns
+    pub(crate) fn request_screenshot_to(&mut self, path: PathBuf) {
+        self.screenshot_requested = true;
+        self.screenshot_path = Some(path);
+    }
+
+    // start or stop keeping the last `clip_seconds` of frames
+    pub(crate) fn toggle_recording(&mut self) {
+        self.recording = !self.recording;
+        if !self.recording {
+            self.clip_frames.clear();
+        }
+        eprintln!("clip recording {}", if self.recording { "on (F10 saves the last seconds)" } else { "off" });
+    }
+
+    pub(crate) fn request_clip(&mut self) {
+        if self.recording {
+            self.clip_requested = true;
+        } else {
+            eprintln!("clip recording is off, press F9 to start it");
+        }
+    }
+
+    // Decide whether the next frame is captured, and return the view it should be rendered into instead of the surface.
+    pub(crate) fn begin_frame(&mut self, device: &wgpu::Device) -> Option<&wgpu::TextureView> {
+        let clip_frame_due = self.recording && match self.last_clip_frame {
+            Some(last_clip_frame) => last_clip_frame.elapsed().as_secs_f32() >= 1.0 / self.config.clip_fps.max(1.0),
+            None => true
+        };
+        let stream_frame_due = self.stream.as_ref().is_some_and(FrameStream::is_due);
+        self.request = match (self.screenshot_requested || clip_frame_due || stream_frame_due, &self.blit_pipeline) {
+            (true, Some(_)) => Some(CaptureRequest {
+                screenshot: self.screenshot_requested,
+                clip_frame: clip_frame_due,
+                stream_frame: stream_frame_due
+            }),
+            _ => None
+        };
+        self.request?;
+        self.screenshot_requested = false;
+
+        if self.target.is_none() {
+            let texture = Texture::create_render_target(
+                device,
+                self.width,
+                self.height,
+                self.format,
+                wgpu::TextureUsages::COPY_SRC,
+                "Capture Target"
+            );
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Blit Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view)
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler)
+                    }
+                ]
+            });
+            let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Capture Staging Buffer"),
+                size: (self.padded_bytes_per_row() * self.height.max(1)) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false
+            });
+            self.target = Some((texture, bind_group, staging_buffer));
+        }
+        self.target.as_ref().map(|(texture, _, _)| &texture.view)
+    }
+
+    // buffer rows must be aligned to 256 bytes
+    fn padded_bytes_per_row(&self) -> u32 {
+        let bytes_per_row = self.width.max(1) * 4;
+        bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+    }
+
+    // show the captured frame on the surface & copy it for the CPU
+    pub(crate) fn end_frame(&self, surface_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) {
+        let ((texture, bind_group, staging_buffer), blit_pipeline) = match (&self.target, &self.blit_pipeline) {
+            (Some(target), Some(blit_pipeline)) if self.request.is_some() => (target, blit_pipeline),
+            _ => return
+        };
+
+        {
+            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Blit Render Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: surface_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true
+                    }
+                }],
+                depth_stencil_attachment: None
+            });
+            render_pass.set_pipeline(blit_pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        command_encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(self.padded_bytes_per_row()),
+                    rows_per_image: std::num::NonZeroU32::new(self.height.max(1))
+                }
+            },
+            wgpu::Extent3d {
+                width: self.width.max(1),
+                height: self.height.max(1),
+                depth_or_array_layers: 1
+            }
+        );
+    }
+
+    // After the frame is submitted: read it back & save what was asked for.
+    // tips: this waits for the GPU, captured frames cost a stall (clips are recorded at a lower frame rate for that reason).
+    pub(crate) fn read_frame(&mut self, device: &wgpu::Device) {
+        let request = match self.request.take() {
+            Some(request) => request,
+            None => return
+        };
+        let image = match self.read_image(device) {
+            Ok(image) => image,
+            Err(error) => {
+                eprintln!("{:?}", error);
+                return;
+            }
+        };
+
+        if request.screenshot {
+            match self.screenshot_path.take() {
+                Some(path) => save_screenshot_to(&image, &path),
+                None => self.save_screenshot(image.clone())
+            }
+        }
+        if request.stream_frame {
+            let open = self.stream.as_mut().is_some_and(|stream| stream.send(&image));
+            if !open {
+                self.stream = None;
+            }
+        }
+        if request.clip_frame {
+            let now = Instant::now();
+            self.last_clip_frame = Some(now);
+            self.clip_frames.push_back((now, scale_to_width(image, self.config.clip_max_width)));
+            let clip_length = Duration::from_secs_f32(self.config.clip_seconds.max(0.0));
+            while let Some((time, _)) = self.clip_frames.front() {
+                if now.duration_since(*time) <= clip_length {
+                    break;
+                }
+                self.clip_frames.pop_front();
+            }
+        }
+        if self.clip_requested {
+            self.clip_requested = false;
+            self.save_clip();
+        }
+    }
+
+    fn read_image(&self, device: &wgpu::Device) -> Result<image::RgbaImage> {
+        let (_, _, staging_buffer) = self.target.as_ref().context("no capture target")?;
+        let slice = staging_buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).context("failed to read the captured frame back")?;
+
+        let padded_bytes_per_row = self.padded_bytes_per_row() as usize;
+        let bytes_per_row = self.width as usize * 4;
+        let mut pixels = Vec::with_capacity(bytes_per_row * self.height as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row).take(self.height as usize) {
+                pixels.extend_from_slice(&row[..bytes_per_row]);
+            }
+        }
+        staging_buffer.unmap();
+
+        // surfaces are usually BGRA
+        if matches!(self.format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        // the surface alpha isn't meaningful
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+        image::RgbaImage::from_raw(self.width, self.height, pixels).context("captured frame size mismatch")
+    }
+
+    fn capture_path(&self, prefix: &str, extension: &str) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.config.directory)
+            .with_context(|| format!("failed to create {}", self.config.directory.display()))?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        Ok(self.config.directory.join(format!("{}-{}{}", prefix, timestamp, extension)))
+    }
+
+    // encoding is slow, it's done on another thread
+    fn save_screenshot(&self, image: image::RgbaImage) {
+        let path = match self.capture_path("screenshot", ".png") {
+            Ok(path) => path,
+            Err(error) => {
+                eprintln!("{:?}", error);
+                return;
+            }
+        };
+        let copy_to_clipboard = self.config.copy_to_clipboard;
+        std::thread::spawn(move || {
+            if copy_to_clipboard {
+                if let Err(error) = copy_image_to_clipboard(&image) {
+                    eprintln!("{:?}", error);
+                }
+            }
+            match image.save(&path) {
+                Ok(()) => eprintln!("screenshot saved to {}", path.display()),
+                Err(error) => eprintln!("failed to save {}: {}", path.display(), error)
+            }
+        });
+    }
+
+    fn save_clip(&self) {
+        if self.clip_frames.is_empty() {
+            return;
+        }
+        let (extension, as_png_sequence) = if self.config.clip_as_png_sequence { ("", true) } else { (".gif", false) };
+        let path = match self.capture_path("clip", extension) {
+            Ok(path) => path,
+            Err(error) => {
+                eprintln!("{:?}", error);
+                return;
+            }
+        };
+        let frames = self.clip_frames.iter().map(|(_, frame)| frame.clone()).collect::<Vec<_>>();
+        let fps = self.config.clip_fps.max(1.0);
+        std::thread::spawn(move || {
+            let result = if as_png_sequence {
+                save_png_sequence(&path, &frames)
+            } else {
+                save_gif(&path, frames, fps)
+            };
+            match result {
+                Ok(()) => eprintln!("clip saved to {}", path.display()),
+                Err(error) => eprintln!("{:?}", error)
+            }
+        });
+    }
+}
+
+// on the calling thread, e.g. before the window is closed
+fn save_screenshot_to(image: &image::RgbaImage, path: &Path) {
+    if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+        if let Err(error) = std::fs::create_dir_all(directory) {
+            eprintln!("failed to create {}: {}", directory.display(), error);
+            return;
+        }
+    }
+    match image.save(path) {
+        Ok(()) => eprintln!("screenshot saved to {}", path.display()),
+        Err(error) => eprintln!("failed to save {}: {}", path.display(), error)
+    }
+}
+
+// keeps the aspect ratio, smaller images are kept as is
+fn scale_to_width(image: image::RgbaImage, max_width: u32) -> image::RgbaImage {
+    if image.width() > max_width {
+        let height = (image.height() as u64 * max_width as u64 / image.width() as u64).max(1) as u32;
+        image::imageops::thumbnail(&image, max_width.max(1), height)
+    } else {
+        image
+    }
+}
+
+fn copy_image_to_clipboard(image: &image::RgbaImage) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new().context("failed to open the clipboard")?;
+    clipboard
+        .set_image(arboard::ImageData {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            bytes: std::borrow::Cow::Borrowed(image.as_raw())
+        })
+        .context("failed to copy the screenshot to the clipboard")
+}
+
+fn save_gif(path: &Path, frames: Vec<image::RgbaImage>, fps: f32) -> Result<()> {
+    let file = std::fs::File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut encoder = image::codecs::gif::GifEncoder::new(std::io::BufWriter::new(file));
+    encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+    let delay = image::Delay::from_saturating_duration(Duration::from_secs_f32(1.0 / fps));
+    encoder
+        .encode_frames(frames.into_iter().map(|frame| image::Frame::from_parts(frame, 0, 0, delay)))
+        .with_context(|| format!("failed to encode {}", path.display()))
+}
+
+fn save_png_sequence(directory: &Path, frames: &[image::RgbaImage]) -> Result<()> {
+    std::fs::create_dir_all(directory).with_context(|| format!("failed to create {}", directory.display()))?;
+    for (index, frame) in frames.iter().enumerate() {
+        let path = directory.join(format!("{:04}.png", index));
+        frame.save(&path).with_context(|| format!("failed to save {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use legion::{Entity, IntoQuery, Resources, World};
+use nalgebra::Vector3;
+
+use super::dynamic_mesh::DynamicMesh;
+use super::mesh::Vertex;
+use super::time::Time;
+
+// Simple shapes the cloth collides against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClothCollider {
+    Sphere { center: Vector3<f32>, radius: f32 },
+    // the plane `dot(normal, p) == distance`, the cloth stays on the side the normal points to.
+    Plane { normal: Vector3<f32>, distance: f32 }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DistanceConstraint {
+    a: usize,
+    b: usize,
+    rest_length: f32
+}
+
+// Position based dynamics cloth (flags, capes...): a grid of particles linked by distance constraints,
+// pushed by gravity & wind and kept out of simple colliders.
+// ref: https://matthias-research.github.io/pages/publications/posBasedDyn.pdf
+//
+// The particles are laid out row by row, `row * columns + column`,
+// so `positions()`, `tex_coords()` & `indices()` can directly feed a dynamic mesh (see `write_mesh()`).
+//
+// As a component, the scene steps it at the fixed time step (see `Time::fixed_timestep()`) blown by its `wind`,
+// then draws its particles with the `MaterialHandle` of its entity, if any.
+// tips: the particles are in world space, the `Transform` of its entity isn't applied.
+#[derive(Clone, Debug)]
+pub struct Cloth {
+    columns: usize,
+    rows: usize,
+    positions: Vec<Vector3<f32>>,
+    prev_positions: Vec<Vector3<f32>>,
+    inv_masses: Vec<f32>, // 0.0 for pinned particles
+    constraints: Vec<DistanceConstraint>,
+    colliders: Vec<ClothCollider>,
+    pub gravity: Vector3<f32>,
+    // 0.0 ~ 1.0, how strongly the constraints are enforced at each iteration
+    pub stiffness: f32,
+    // 0.0 ~ 1.0, fraction of the velocity lost at each step
+    pub damping: f32,
+    // how much the wind pushes the cloth, relative to the surface facing it
+    pub drag: f32,
+    // constraint solver iterations per step, more is stiffer but slower
+    pub iterations: u32,
+    // extra distance kept from the colliders, avoids the cloth clipping into them
+    pub collision_margin: f32,
+    // wind velocity when stepped by the scene, e.g. `Environment::wind_velocity()`
+    pub wind: Vector3<f32>,
+    // time left to simulate when stepped by the scene, less than a fixed step
+    accumulator: f32
+}
+
+impl Cloth {
+    // A `width` x `height` cloth hanging from `origin` along +x & -y, with `columns` x `rows` particles.
+    pub fn new(origin: Vector3<f32>, width: f32, height: f32, columns: usize, rows: usize) -> Self {
+        let columns = columns.max(2);
+        let rows = rows.max(2);
+
+        let mut positions = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = column as f32 / (columns - 1) as f32 * width;
+                let y = row as f32 / (rows - 1) as f32 * height;
+                positions.push(origin + Vector3::new(x, -y, 0.0));
+            }
+        }
+
+        let mut cloth = Self {
+            columns,
+            rows,
+            prev_positions: positions.clone(),
+            inv_masses: vec![1.0; positions.len()],
+            positions,
+            constraints: Vec::new(),
+            colliders: Vec::new(),
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            stiffness: 1.0,
+            damping: 0.01,
+            drag: 1.0,
+            iterations: 8,
+            collision_margin: 0.01,
+            wind: Vector3::zeros(),
+            accumulator: 0.0
+        };
+
+        // structural (horizontal & vertical) and shear (diagonal) constraints
+        for row in 0..rows {
+            for column in 0..columns {
+                if column + 1 < columns {
+                    cloth.add_constraint(cloth.grid_index(column, row), cloth.grid_index(column + 1, row));
+                }
+                if row + 1 < rows {
+                    cloth.add_constraint(cloth.grid_index(column, row), cloth.grid_index(column, row + 1));
+                }
+                if column + 1 < columns && row + 1 < rows {
+                    cloth.add_constraint(cloth.grid_index(column, row), cloth.grid_index(column + 1, row + 1));
+                    cloth.add_constraint(cloth.grid_index(column + 1, row), cloth.grid_index(column, row + 1));
+                }
+            }
+        }
+
+        cloth
+    }
+
+    // index of the particle at (`column`, `row`), None out of the grid
+    fn index(&self, column: usize, row: usize) -> Option<usize> {
+        (column < self.columns && row < self.rows).then(|| self.grid_index(column, row))
+    }
+
+    // `index()` of coordinates known to be in the grid
+    fn grid_index(&self, column: usize, row: usize) -> usize {
+        row * self.columns + column
+    }
+
+    fn add_constraint(&mut self, a: usize, b: usize) {
+        let rest_length = (self.positions[a] - self.positions[b]).magnitude();
+        self.constraints.push(DistanceConstraint { a, b, rest_length });
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    // Pinned particles don't move, e.g. the top row of a flag attached to its pole.
+    // Return false if there's no particle at (`column`, `row`).
+    pub fn pin(&mut self, column: usize, row: usize) -> bool {
+        match self.index(column, row) {
+            Some(index) => {
+                self.inv_masses[index] = 0.0;
+                true
+            },
+            None => false
+        }
+    }
+
+    // return false if there's no particle at (`column`, `row`)
+    pub fn unpin(&mut self, column: usize, row: usize) -> bool {
+        match self.index(column, row) {
+            Some(index) => {
+                self.inv_masses[index] = 1.0;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn pin_top_row(&mut self) {
+        for column in 0..self.columns {
+            let index = self.grid_index(column, 0);
+            self.inv_masses[index] = 0.0;
+        }
+    }
+
+    // Move a particle, e.g. to drag pinned particles along with the character wearing the cape.
+    // Return false if there's no particle at (`column`, `row`).
+    pub fn set_position(&mut self, column: usize, row: usize, position: Vector3<f32>) -> bool {
+        match self.index(column, row) {
+            Some(index) => {
+                self.positions[index] = position;
+                self.prev_positions[index] = position;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn add_collider(&mut self, collider: ClothCollider) {
+        self.colliders.push(collider);
+    }
+
+    pub fn clear_colliders(&mut self) {
+        self.colliders.clear();
+    }
+
+    // Advance the simulation by `dt` seconds, with the wind velocity `wind`.
+    // tips: keep `dt` small and constant (e.g. 1/60s), PBD gets unstable with big time steps.
+    pub fn step(&mut self, dt: f32, wind: Vector3<f32>) {
+        if dt <= 0.0 {
+            return;
+        }
+
+        let forces = self.wind_forces(wind);
+
+        // predict positions (verlet integration)
+        for (i, force) in forces.iter().enumerate() {
+            if self.inv_masses[i] == 0.0 {
+                continue;
+            }
+            let velocity = (self.positions[i] - self.prev_positions[i]) * (1.0 - self.damping);
+            self.prev_positions[i] = self.positions[i];
+            self.positions[i] += velocity + (self.gravity + force * self.inv_masses[i]) * dt * dt;
+        }
+
+        for _ in 0..self.iterations {
+            self.solve_constraints();
+            self.solve_collisions();
+        }
+    }
+
+    // step by `fixed_timestep` as many times as fit in the time accumulated with `dt`, false if it didn't step
+    fn advance(&mut self, dt: f32, fixed_timestep: f32) -> bool {
+        self.accumulator += dt;
+        let mut stepped = false;
+        while self.accumulator >= fixed_timestep {
+            self.accumulator -= fixed_timestep;
+            self.step(fixed_timestep, self.wind);
+            stepped = true;
+        }
+        stepped
+    }
+
+    // aerodynamic force of every triangle, split between its particles.
+    // only the wind component along the triangle normal pushes it.
+    fn wind_forces(&self, wind: Vector3<f32>) -> Vec<Vector3<f32>> {
+        let mut forces = vec![Vector3::zeros(); self.positions.len()];
+        if wind == Vector3::zeros() || self.drag == 0.0 {
+            return forces;
+        }
+
+        for triangle in self.indices().chunks(3) {
+            let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            // length of the cross product is twice the area of the triangle
+            let normal = (self.positions[b] - self.positions[a]).cross(&(self.positions[c] - self.positions[a])) * 0.5;
+            let area = normal.magnitude();
+            if area == 0.0 {
+                continue;
+            }
+            let normal = normal / area;
+            let force = normal * normal.dot(&wind) * area * self.drag / 3.0;
+            for i in [a, b, c] {
+                forces[i] += force;
+            }
+        }
+
+        forces
+    }
+
+    fn solve_constraints(&mut self) {
+        for constraint in &self.constraints {
+            let (wa, wb) = (self.inv_masses[constraint.a], self.inv_masses[constraint.b]);
+            let weight = wa + wb;
+            if weight == 0.0 {
+                continue;
+            }
+
+            let delta = self.positions[constraint.b] - self.positions[constraint.a];
+            let length = delta.magnitude();
+            if length == 0.0 {
+                continue;
+            }
+
+            let correction = delta * ((length - constraint.rest_length) / (length * weight) * self.stiffness);
+            self.positions[constraint.a] += correction * wa;
+            self.positions[constraint.b] -= correction * wb;
+        }
+    }
+
+    fn solve_collisions(&mut self) {
+        for (position, inv_mass) in self.positions.iter_mut().zip(&self.inv_masses) {
+            if *inv_mass == 0.0 {
+                continue;
+            }
+
+            for collider in &self.colliders {
+                match *collider {
+                    ClothCollider::Sphere { center, radius } => {
+                        let offset = *position - center;
+                        let distance = offset.magnitude();
+                        let min_distance = radius + self.collision_margin;
+                        if distance < min_distance && distance > 0.0 {
+                            *position = center + offset * (min_distance / distance);
+                        }
+                    },
+                    ClothCollider::Plane { normal, distance } => {
+                        let depth = normal.dot(position) - distance - self.collision_margin;
+                        if depth < 0.0 {
+                            *position -= normal * depth;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // move the particles & colliders by `-shift` without changing their velocity, see `WorldOrigin::shifted()`
+    pub fn shift_origin(&mut self, shift: Vector3<f32>) {
+        for position in self.positions.iter_mut().chain(self.prev_positions.iter_mut()) {
+            *position -= shift;
+        }
+        for collider in &mut self.colliders {
+            match collider {
+                ClothCollider::Sphere { center, .. } => *center -= shift,
+                ClothCollider::Plane { normal, distance } => *distance -= normal.dot(&shift)
+            }
+        }
+    }
+
+    pub fn positions(&self) -> &[Vector3<f32>] {
+        &self.positions
+    }
+
+    // replace the geometry of `mesh` with the particles, smoothly shaded
+    pub fn write_mesh(&self, mesh: &mut DynamicMesh) {
+        let vertices = self.positions
+            .iter()
+            .zip(self.tex_coords())
+            .map(|(position, tex_coords)| Vertex::new((*position).into(), tex_coords, [0.0, 0.0, 1.0]))
+            .collect();
+        mesh.set(vertices, self.indices());
+        mesh.smooth_normals();
+    }
+
+    // (0, 0) at the first particle, (1, 1) at the last one
+    pub fn tex_coords(&self) -> Vec<[f32; 2]> {
+        (0..self.rows).flat_map(|row| {
+            (0..self.columns).map(move |column| [
+                column as f32 / (self.columns - 1) as f32,
+                row as f32 / (self.rows - 1) as f32
+            ])
+        }).collect()
+    }
+
+    // counter-clockwise triangles facing +z when the cloth is unfolded
+    pub fn indices(&self) -> Vec<u32> {
+        let mut indices = Vec::with_capacity((self.columns - 1) * (self.rows - 1) * 6);
+        for row in 0..self.rows - 1 {
+            for column in 0..self.columns - 1 {
+                let top_left = self.grid_index(column, row) as u32;
+                let top_right = self.grid_index(column + 1, row) as u32;
+                let bottom_left = self.grid_index(column, row + 1) as u32;
+                let bottom_right = self.grid_index(column + 1, row + 1) as u32;
+                indices.extend_from_slice(&[
+                    top_left, bottom_left, bottom_right,
+                    top_left, bottom_right, top_right,
+                ]);
+            }
+        }
+        indices
+    }
+}
+
+// the meshes of the entities with a `Cloth`, drawn by the scene
+pub(crate) type ClothMeshes = Rc<RefCell<HashMap<Entity, DynamicMesh>>>;
+
+// System stepping every `Cloth` at the fixed time step by the frame's delta time, then writing its particles into its mesh in `meshes`,
+// run by the scene after its schedule. It's thread local, the meshes stay on the main thread.
+pub(crate) fn cloth_system(meshes: ClothMeshes) -> impl FnMut(&mut World, &mut Resources) {
+    move |world, resources| {
+        let time = resources.get::<Time>().map(|time| *time).unwrap_or_default();
+        let mut meshes = meshes.borrow_mut();
+        let mut cloths = HashSet::new();
+        <(Entity, &mut Cloth)>::query().for_each_mut(world, |(entity, cloth)| {
+            let stepped = cloth.advance(time.delta(), time.fixed_timestep());
+            cloths.insert(*entity);
+            // a new cloth is written even before its first step
+            let mut written = true;
+            let mesh = meshes.entry(*entity).or_insert_with(|| {
+                written = false;
+                DynamicMesh::default()
+            });
+            if stepped || !written {
+                cloth.write_mesh(mesh);
+            }
+        });
+        // the entity is gone, or its cloth
+        meshes.retain(|entity, _| cloths.contains(entity));
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+
+use super::mesh::Vertex;
+
+// distance under which a point is on a plane
+const EPSILON: f64 = 1e-5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CsgOperation {
+    Union,
+    Difference,
+    Intersection
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CsgVertex {
+    position: Vector3<f64>,
+    normal: Vector3<f64>,
+    tex_coords: [f32; 2],
+    color: [f32; 4]
+}
+
+impl CsgVertex {
+    fn new(vertex: &Vertex) -> Self {
+        Self {
+            position: Vector3::from(vertex.position).cast(),
+            normal: Vector3::from(vertex.normal).cast(),
+            tex_coords: vertex.tex_coords,
+            color: vertex.color
+        }
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+    }
+
+    // the vertex at `t` between `self` & `other`, on a split edge
+    fn lerp(&self, other: &CsgVertex, t: f64) -> Self {
+        let tf = t as f32;
+        let mix = |a: f32, b: f32| a + (b - a) * tf;
+        Self {
+            position: self.position.lerp(&other.position, t),
+            normal: self.normal.lerp(&other.normal, t).try_normalize(f64::EPSILON).unwrap_or(self.normal),
+            tex_coords: [mix(self.tex_coords[0], other.tex_coords[0]), mix(self.tex_coords[1], other.tex_coords[1])],
+            color: [0, 1, 2, 3].map(|i| mix(self.color[i], other.color[i]))
+        }
+    }
+
+    fn to_vertex(self) -> Vertex {
+        Vertex {
+            position: self.position.cast::<f32>().into(),
+            tex_coords: self.tex_coords,
+            color: self.color,
+            normal: self.normal.cast::<f32>().into()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Plane {
+    normal: Vector3<f64>,
+    // dot(normal, p) == w on the plane
+    w: f64
+}
+
+impl Plane {
+    // None for degenerate triangles
+    fn from_points(a: Vector3<f64>, b: Vector3<f64>, c: Vector3<f64>) -> Option<Self> {
+        let normal = (b - a).cross(&(c - a)).try_normalize(f64::EPSILON)?;
+        Some(Self { normal, w: normal.dot(&a) })
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+        self.w = -self.w;
+    }
+}
+
+// Convex polygon, counter-clockwise seen from the front of its plane.
+#[derive(Clone, Debug)]
+struct Polygon {
+    vertices: Vec<CsgVertex>,
+    plane: Plane
+}
+
+impl Polygon {
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        for vertex in &mut self.vertices {
+            vertex.flip();
+        }
+        self.plane.flip();
+    }
+}
+
+const COPLANAR: u8 = 0;
+const FRONT: u8 = 1;
+const BACK: u8 = 2;
+const SPANNING: u8 = 3;
+
+// Put `polygon` in the right list(s): in front of `plane`, behind it or on it (split in two if it spans it).
+fn split_polygon(
+    plane: &Plane,
+    polygon: &Polygon,
+    coplanar_front: &mut Vec<Polygon>,
+    coplanar_back: &mut Vec<Polygon>,
+    front: &mut Vec<Polygon>,
+    back: &mut Vec<Polygon>
+) {
+    let mut polygon_type = COPLANAR;
+    let types = polygon.vertices
+        .iter()
+        .map(|vertex| {
+            let distance = plane.normal.dot(&vertex.position) - plane.w;
+            let vertex_type = if distance < -EPSILON {
+                BACK
+            } else if distance > EPSILON {
+                FRONT
+            } else {
+                COPLANAR
+            };
+            polygon_type |= vertex_type;
+            vertex_type
+        })
+        .collect::<Vec<_>>();
+
+    match polygon_type {
+        COPLANAR => {
+            if plane.normal.dot(&polygon.plane.normal) > 0.0 {
+                coplanar_front.push(polygon.clone());
+            } else {
+                coplanar_back.push(polygon.clone());
+            }
+        },
+        FRONT => front.push(polygon.clone()),
+        BACK => back.push(polygon.clone()),
+        _ => {
+            let mut front_vertices = Vec::new();
+            let mut back_vertices = Vec::new();
+            let count = polygon.vertices.len();
+            for i in 0..count {
+                let j = (i + 1) % count;
+                let (type_i, type_j) = (types[i], types[j]);
+                let (vertex_i, vertex_j) = (&polygon.vertices[i], &polygon.vertices[j]);
+                if type_i != BACK {
+                    front_vertices.push(*vertex_i);
+                }
+                if type_i != FRONT {
+                    back_vertices.push(*vertex_i);
+                }
+                if (type_i | type_j) == SPANNING {
+                    let t = (plane.w - plane.normal.dot(&vertex_i.position)) / plane.normal.dot(&(vertex_j.position - vertex_i.position));
+                    let vertex = vertex_i.lerp(vertex_j, t);
+                    front_vertices.push(vertex);
+                    back_vertices.push(vertex);
+                }
+            }
+            if front_vertices.len() >= 3 {
+                front.push(Polygon { vertices: front_vertices, plane: polygon.plane });
+            }
+            if back_vertices.len() >= 3 {
+                back.push(Polygon { vertices: back_vertices, plane: polygon.plane });
+            }
+        }
+    }
+}
+
+// BSP tree node: the polygons on its plane, the subtrees in front of & behind it.
+#[derive(Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Vec<Polygon>
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Self {
+        let mut node = Self::default();
+        node.build(polygons);
+        node
+    }
+
+    // swap solid & empty space
+    fn invert(&mut self) {
+        for polygon in &mut self.polygons {
+            polygon.flip();
+        }
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    // the parts of `polygons` outside of this solid
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let plane = match &self.plane {
+            Some(plane) => plane,
+            None => return polygons
+        };
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        for polygon in &polygons {
+            let (mut coplanar_front, mut coplanar_back) = (Vec::new(), Vec::new());
+            split_polygon(plane, polygon, &mut coplanar_front, &mut coplanar_back, &mut front, &mut back);
+            front.append(&mut coplanar_front);
+            back.append(&mut coplanar_back);
+        }
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front
+        };
+        // behind a leaf is inside the solid
+        let mut back = match &self.back {
+            Some(node) => node.clip_polygons(back),
+            None => Vec::new()
+        };
+        front.append(&mut back);
+        front
+    }
+
+    // remove the polygons of this tree inside of `other`
+    fn clip_to(&mut self, other: &Node) {
+        self.polygons = other.clip_polygons(std::mem::take(&mut self.polygons));
+        if let Some(front) = &mut self.front {
+            front.clip_to(other);
+        }
+        if let Some(back) = &mut self.back {
+            back.clip_to(other);
+        }
+    }
+
+    fn all_polygons(&self, polygons: &mut Vec<Polygon>) {
+        polygons.extend(self.polygons.iter().cloned());
+        if let Some(front) = &self.front {
+            front.all_polygons(polygons);
+        }
+        if let Some(back) = &self.back {
+            back.all_polygons(polygons);
+        }
+    }
+
+    // add `polygons` to the tree, split by the planes of its nodes
+    // tips: recursive, the depth grows with the number of polygons of the meshes.
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        for polygon in &polygons {
+            let (mut coplanar_front, mut coplanar_back) = (Vec::new(), Vec::new());
+            split_polygon(&plane, polygon, &mut coplanar_front, &mut coplanar_back, &mut front, &mut back);
+            self.polygons.append(&mut coplanar_front);
+            self.polygons.append(&mut coplanar_back);
+        }
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Box::default).build(front);
+        }
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Box::default).build(back);
+        }
+    }
+}
+
+fn to_polygons(vertices: &[Vertex], indices: &[u32]) -> Vec<Polygon> {
+    indices
+        .chunks_exact(3)
+        .filter_map(|triangle| {
+            let corners = [triangle[0], triangle[1], triangle[2]].map(|index| CsgVertex::new(&vertices[index as usize]));
+            let plane = Plane::from_points(corners[0].position, corners[1].position, corners[2].position)?;
+            Some(Polygon { vertices: corners.to_vec(), plane })
+        })
+        .collect()
+}
+
+// triangle fans of the polygons, with identical vertices merged
+fn to_triangles(polygons: &[Polygon]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut known: HashMap<Vec<u8>, u32> = HashMap::new();
+    for polygon in polygons {
+        let polygon_indices = polygon.vertices
+            .iter()
+            .map(|vertex| {
+                let vertex = vertex.to_vertex();
+                *known.entry(bytemuck::bytes_of(&vertex).to_vec()).or_insert_with(|| {
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 1..polygon_indices.len() - 1 {
+            indices.extend_from_slice(&[polygon_indices[0], polygon_indices[i], polygon_indices[i + 1]]);
+        }
+    }
+    (vertices, indices)
+}
+
+// Boolean operation between two closed triangle meshes, on BSP trees of their triangles.
+// tips: split edges aren't welded to their neighbours (T-junctions), fine to draw but not to run another CSG on a huge mesh.
+// ref: https://github.com/evanw/csg.js
+pub(crate) fn csg(a: (&[Vertex], &[u32]), b: (&[Vertex], &[u32]), operation: CsgOperation) -> (Vec<Vertex>, Vec<u32>) {
+    let mut a = Node::new(to_polygons(a.0, a.1));
+    let mut b = Node::new(to_polygons(b.0, b.1));
+    let mut b_polygons = Vec::new();
+    match operation {
+        CsgOperation::Union => {
+            a.clip_to(&b);
+            b.clip_to(&a);
+            // the coplanar faces of b facing the same way as a's are dropped
+            b.invert();
+            b.clip_to(&a);
+            b.invert();
+            b.all_polygons(&mut b_polygons);
+            a.build(b_polygons);
+        },
+        CsgOperation::Difference => {
+            a.invert();
+            a.clip_to(&b);
+            b.clip_to(&a);
+            b.invert();
+            b.clip_to(&a);
+            b.invert();
+            b.all_polygons(&mut b_polygons);
+            a.build(b_polygons);
+            a.invert();
+        },
+        CsgOperation::Intersection => {
+            a.invert();
+            b.clip_to(&a);
+            b.invert();
+            a.clip_to(&b);
+            b.clip_to(&a);
+            b.all_polygons(&mut b_polygons);
+            a.build(b_polygons);
+            a.invert();
+        }
+    }
+    let mut polygons = Vec::new();
+    a.all_polygons(&mut polygons);
+    to_triangles(&polygons)
+}
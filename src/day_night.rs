@@ -0,0 +1,196 @@
+use nalgebra::Vector3;
+
+use super::environment::Environment;
+
+// Lighting & sky colors at a given hour, the cycle interpolates between these keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkyKey {
+    // hours, 0.0 ~ 24.0
+    pub hour: f32,
+    pub sky_color: [f32; 3],
+    // color of the main directional light: the sun by day, the moon by night
+    pub light_color: [f32; 3],
+    // illuminance, in lux
+    pub light_intensity: f32,
+    pub ambient_color: [f32; 3],
+    // sky luminance, in nits
+    pub ambient_intensity: f32
+}
+
+impl SkyKey {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let lerp_color = |a: [f32; 3], b: [f32; 3]| [lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])];
+
+        Self {
+            hour: lerp(self.hour, other.hour),
+            sky_color: lerp_color(self.sky_color, other.sky_color),
+            light_color: lerp_color(self.light_color, other.light_color),
+            light_intensity: lerp(self.light_intensity, other.light_intensity),
+            ambient_color: lerp_color(self.ambient_color, other.ambient_color),
+            ambient_intensity: lerp(self.ambient_intensity, other.ambient_intensity)
+        }
+    }
+}
+
+// Time of day system: advances the clock, moves the sun (and the moon at night)
+// and animates the sky, light & ambient colors of the `Environment`.
+// Events can be scheduled at given hours (e.g. "dawn" to wake the NPCs up).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DayNightCycle {
+    // hours, 0.0 ~ 24.0
+    pub time_of_day: f32,
+    // real seconds for a whole day
+    pub cycle_length: f32,
+    pub paused: bool,
+    // tilt of the sun path towards +z, 0.0 makes the sun go straight overhead at noon
+    pub sun_tilt: f32,
+    keys: Vec<SkyKey>, // sorted by hour
+    events: Vec<(f32, String)> // (hour, name)
+}
+
+impl DayNightCycle {
+    // starts at `time_of_day` hours, with a full day lasting `cycle_length` seconds.
+    pub fn new(time_of_day: f32, cycle_length: f32) -> Self {
+        Self {
+            time_of_day: time_of_day.rem_euclid(24.0),
+            cycle_length,
+            paused: false,
+            sun_tilt: 0.3,
+            keys: Self::default_keys(),
+            events: Vec::new()
+        }
+    }
+
+    fn default_keys() -> Vec<SkyKey> {
+        vec![
+            // midnight: moonlight, far brighter than a real moon (~0.3 lux) so the night stays readable at a fixed exposure
+            SkyKey {
+                hour: 0.0,
+                sky_color: [0.01, 0.02, 0.05],
+                light_color: [0.6, 0.7, 1.0],
+                light_intensity: 11000.0,
+                ambient_color: [0.3, 0.4, 0.6],
+                ambient_intensity: 2000.0
+            },
+            // dawn
+            SkyKey {
+                hour: 6.0,
+                sky_color: [0.8, 0.45, 0.3],
+                light_color: [1.0, 0.6, 0.4],
+                light_intensity: 44000.0,
+                ambient_color: [0.7, 0.5, 0.5],
+                ambient_intensity: 6000.0
+            },
+            // noon
+            SkyKey {
+                hour: 12.0,
+                sky_color: [0.4, 0.65, 0.95],
+                light_color: [1.0, 0.96, 0.9],
+                light_intensity: 110000.0,
+                ambient_color: [0.6, 0.7, 0.8],
+                ambient_intensity: 12000.0
+            },
+            // dusk
+            SkyKey {
+                hour: 18.0,
+                sky_color: [0.7, 0.35, 0.25],
+                light_color: [1.0, 0.5, 0.3],
+                light_intensity: 44000.0,
+                ambient_color: [0.6, 0.45, 0.5],
+                ambient_intensity: 6000.0
+            },
+        ]
+    }
+
+    // replace the key with the same hour, or add it.
+    pub fn with_key(mut self, key: SkyKey) -> Self {
+        let hour = key.hour.rem_euclid(24.0);
+        self.keys.retain(|other| other.hour != hour);
+        let index = self.keys.partition_point(|other| other.hour < hour);
+        self.keys.insert(index, SkyKey { hour, ..key });
+        self
+    }
+
+    pub fn clear_keys(mut self) -> Self {
+        self.keys.clear();
+        self
+    }
+
+    // fire the event `name` every day at `hour`, see `advance()`.
+    pub fn with_event(mut self, hour: f32, name: &str) -> Self {
+        self.events.push((hour.rem_euclid(24.0), name.to_string()));
+        self
+    }
+
+    // Advance the clock by `dt` real seconds, returns the events passed meanwhile (in order).
+    pub fn advance(&mut self, dt: f32) -> Vec<String> {
+        if self.paused || self.cycle_length <= 0.0 || dt <= 0.0 {
+            return Vec::new();
+        }
+
+        let hours = dt / self.cycle_length * 24.0;
+        let start = self.time_of_day;
+        let end = start + hours;
+        self.time_of_day = end.rem_euclid(24.0);
+
+        // the clock may wrap around midnight, possibly more than once
+        let mut fired = Vec::new();
+        let mut day_start = 0.0;
+        while day_start < end {
+            let mut day_events = self.events
+                .iter()
+                .map(|(hour, name)| (day_start + hour, name))
+                .filter(|(time, _)| *time > start && *time <= end)
+                .collect::<Vec<_>>();
+            day_events.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            fired.extend(day_events.into_iter().map(|(_, name)| name.clone()));
+            day_start += 24.0;
+        }
+        fired
+    }
+
+    // sky key at the current hour, None without keys.
+    pub fn sample(&self) -> Option<SkyKey> {
+        let hour = self.time_of_day;
+        let next = self.keys.partition_point(|key| key.hour <= hour);
+        // the keys wrap around midnight
+        let prev_key = if next == 0 { self.keys.last()? } else { &self.keys[next - 1] };
+        let next_key = if next == self.keys.len() { self.keys.first()? } else { &self.keys[next] };
+
+        let span = (next_key.hour - prev_key.hour).rem_euclid(24.0);
+        let t = if span > 0.0 { (hour - prev_key.hour).rem_euclid(24.0) / span } else { 0.0 };
+        Some(SkyKey { hour, ..prev_key.lerp(next_key, t) })
+    }
+
+    // direction the sunlight travels in: the sun rises in +x at 6h, is the highest at noon and sets in -x at 18h.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = (self.time_of_day - 6.0) / 12.0 * std::f32::consts::PI;
+        let sun_position = Vector3::new(angle.cos(), angle.sin(), self.sun_tilt);
+        -sun_position.normalize()
+    }
+
+    // Write the time of day, light direction & colors into the environment.
+    // At night the main directional light is the moon, opposite to the sun.
+    pub fn apply(&self, environment: &mut Environment) {
+        environment.time_of_day = self.time_of_day;
+
+        let sun_direction = self.sun_direction();
+        environment.sun_direction = if sun_direction.y <= 0.0 { sun_direction } else { -sun_direction };
+
+        if let Some(key) = self.sample() {
+            environment.sky_color = key.sky_color;
+            environment.sun_color = key.light_color;
+            environment.sun_intensity = key.light_intensity;
+            environment.ambient_color = key.ambient_color;
+            environment.ambient_intensity = key.ambient_intensity;
+        }
+    }
+}
+
+impl Default for DayNightCycle {
+    // starts at noon, 10 minutes a day
+    fn default() -> Self {
+        Self::new(12.0, 600.0)
+    }
+}
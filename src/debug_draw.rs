@@ -0,0 +1,244 @@
+use super::polyline::{LineWidth, Polyline};
+
+// Categories of debug shapes, each can be toggled on/off globally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DebugDrawCategory {
+    Aabbs,
+    Spheres,
+    // light epicenters & ranges
+    Lights,
+    // camera frusta
+    Frusta
+}
+
+impl DebugDrawCategory {
+    pub const ALL: [DebugDrawCategory; 4] = [Self::Aabbs, Self::Spheres, Self::Lights, Self::Frusta];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    #[cfg(feature = "egui")]
+    fn name(self) -> &'static str {
+        match self {
+            Self::Aabbs => "bounding boxes",
+            Self::Spheres => "bounding spheres",
+            Self::Lights => "lights",
+            Self::Frusta => "camera frusta"
+        }
+    }
+}
+
+// The debug shape categories drawn, all enabled by default.
+// As a resource of the scene, the global toggles (F1 ~ F4, the "Debug Draw" window with the feature "egui"):
+// the systems & `Application::update_scene()` may change them, they're read back after the systems run.
+// As a component, the bounds of its entity drawn by the scene (see `Scene`), if their category is enabled globally too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugDrawSettings {
+    enabled: [bool; 4]
+}
+
+impl DebugDrawSettings {
+    pub fn new() -> Self {
+        Self {
+            enabled: [true; 4]
+        }
+    }
+
+    // only `categories`, e.g. `&[DebugDrawCategory::Aabbs]` for an entity's bounding box alone
+    pub fn only(categories: &[DebugDrawCategory]) -> Self {
+        let mut settings = Self { enabled: [false; 4] };
+        for category in categories {
+            settings.set_enabled(*category, true);
+        }
+        settings
+    }
+
+    pub fn is_enabled(&self, category: DebugDrawCategory) -> bool {
+        self.enabled[category.index()]
+    }
+
+    pub fn set_enabled(&mut self, category: DebugDrawCategory, enabled: bool) {
+        self.enabled[category.index()] = enabled;
+    }
+
+    pub fn toggle(&mut self, category: DebugDrawCategory) {
+        self.enabled[category.index()] = !self.enabled[category.index()];
+    }
+
+    // A checkbox per category (feature "egui"), e.g. for `Application::ui()`.
+    #[cfg(feature = "egui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        for category in DebugDrawCategory::ALL {
+            ui.checkbox(&mut self.enabled[category.index()], category.name());
+        }
+    }
+}
+
+impl Default for DebugDrawSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Immediate mode debug drawing: shapes are submitted again every frame, and drawn as thin polylines.
+// Shapes of a disabled category are dropped (see `DebugDrawSettings`), whoever submits them.
+#[derive(Clone, Debug)]
+pub struct DebugDraw {
+    settings: DebugDrawSettings,
+    polylines: Vec<Polyline>
+}
+
+impl DebugDraw {
+    // width of the debug lines
+    pub const LINE_WIDTH: LineWidth = LineWidth::Pixels(1.5);
+    // segments used to draw a circle
+    const CIRCLE_SEGMENTS: usize = 32;
+    // length of a frustum with an infinite far plane
+    const INFINITE_FRUSTUM_LENGTH: f32 = 100.0;
+
+    // all categories start enabled.
+    pub fn new() -> Self {
+        Self {
+            settings: DebugDrawSettings::new(),
+            polylines: Vec::new()
+        }
+    }
+
+    pub fn settings(&self) -> DebugDrawSettings {
+        self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut DebugDrawSettings {
+        &mut self.settings
+    }
+
+    pub fn is_enabled(&self, category: DebugDrawCategory) -> bool {
+        self.settings.is_enabled(category)
+    }
+
+    pub fn set_enabled(&mut self, category: DebugDrawCategory, enabled: bool) {
+        self.settings.set_enabled(category, enabled);
+    }
+
+    pub fn toggle(&mut self, category: DebugDrawCategory) {
+        self.settings.toggle(category);
+    }
+
+    // drop the shapes of the previous frame, but keep the toggles.
+    pub(crate) fn clear(&mut self) {
+        self.polylines.clear();
+    }
+
+    pub(crate) fn polylines(&self) -> &[Polyline] {
+        &self.polylines
+    }
+
+    // any line, whatever the toggles
+    pub fn line(&mut self, start: [f32; 3], end: [f32; 3], color: [f32; 4]) {
+        self.polylines.push(Polyline::segment(start, end, color, Self::LINE_WIDTH));
+    }
+
+    // axis-aligned bounding box
+    pub fn aabb(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4]) {
+        if !self.is_enabled(DebugDrawCategory::Aabbs) {
+            return;
+        }
+
+        let corner = |x: bool, y: bool, z: bool| [
+            if x { max[0] } else { min[0] },
+            if y { max[1] } else { min[1] },
+            if z { max[2] } else { min[2] },
+        ];
+        self.box_edges(|i| corner(i & 1 != 0, i & 2 != 0, i & 4 != 0), color);
+    }
+
+    // bounding sphere, drawn as its three axis circles
+    pub fn sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        if !self.is_enabled(DebugDrawCategory::Spheres) {
+            return;
+        }
+
+        self.sphere_circles(center, radius, color);
+    }
+
+    // point light: a cross at its epicenter, and the sphere of its range
+    pub fn point_light(&mut self, position: [f32; 3], range: f32, color: [f32; 4]) {
+        if !self.is_enabled(DebugDrawCategory::Lights) {
+            return;
+        }
+
+        let size = (range * 0.1).min(0.25);
+        for axis in 0..3 {
+            let (mut start, mut end) = (position, position);
+            start[axis] -= size;
+            end[axis] += size;
+            self.line(start, end, color);
+        }
+        self.sphere_circles(position, range, color);
+    }
+
+    // camera frustum, from the view projection matrix of the camera
+    pub fn frustum(&mut self, view_proj: &nalgebra::Matrix4<f32>, color: [f32; 4]) {
+        if !self.is_enabled(DebugDrawCategory::Frusta) {
+            return;
+        }
+
+        let inv_view_proj = match view_proj.try_inverse() {
+            Some(inv_view_proj) => inv_view_proj,
+            None => return
+        };
+        // unproject the corners of the NDC volume (z from 0.0 to 1.0 in wgpu)
+        let unproject = |i: usize| {
+            let ndc = nalgebra::Vector4::new(
+                if i & 1 != 0 { 1.0 } else { -1.0 },
+                if i & 2 != 0 { 1.0 } else { -1.0 },
+                if i & 4 != 0 { 1.0 } else { 0.0 },
+                1.0
+            );
+            inv_view_proj * ndc
+        };
+        let corner = |i: usize| {
+            let position = unproject(i);
+            let opposite = unproject(i ^ 4);
+            // an infinite far plane unprojects to a direction (w ~ 0.0): cut the frustum at a fixed distance from the near plane
+            let position = if position.w.abs() <= opposite.w.abs() * 1e-4 {
+                opposite.xyz() / opposite.w + position.xyz().normalize() * Self::INFINITE_FRUSTUM_LENGTH
+            } else {
+                position.xyz() / position.w
+            };
+            [position.x, position.y, position.z]
+        };
+        self.box_edges(corner, color);
+    }
+
+    // the 12 edges of a box, whose corner `i` has its x/y/z on the max side if bit 0/1/2 of `i` is set.
+    fn box_edges(&mut self, corner: impl Fn(usize) -> [f32; 3], color: [f32; 4]) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    fn sphere_circles(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        for (u, v) in [(0, 1), (1, 2), (0, 2)] {
+            let points = (0..Self::CIRCLE_SEGMENTS).map(|i| {
+                let angle = i as f32 / Self::CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                let mut point = center;
+                point[u] += angle.cos() * radius;
+                point[v] += angle.sin() * radius;
+                point
+            }).collect();
+            self.polylines.push(Polyline::new(points, color, Self::LINE_WIDTH).closed());
+        }
+    }
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self::new()
+    }
+}
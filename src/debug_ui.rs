@@ -0,0 +1,636 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use winit::window::CursorIcon;
+
+use super::debug_draw::DebugDrawSettings;
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+use super::profiler::Profiler;
+
+// points scrolled per wheel notch
+const SCROLL_LINE: f32 = 50.0;
+
+// `egui` vertex layout in the vertex buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugUiVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [u8; 4]
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugUiUniform {
+    screen_size: [f32; 2],
+    srgb_target: f32,
+    _padding: f32
+}
+
+// Immediate mode debug UI (`egui`) drawn over the frame: tweak panels, inspectors, profiler views...
+// It sees the window events first, the ones it uses (e.g. clicks on a panel) don't reach the camera controller.
+pub(crate) struct DebugUi {
+    context: egui::Context,
+    // the events received since the last frame
+    raw_input: egui::RawInput,
+    max_texture_side: usize,
+    start: Instant,
+    pixels_per_point: f32,
+    // in physical pixels
+    size: (u32, u32),
+    pointer_position: egui::Pos2,
+    modifiers: egui::Modifiers,
+    cursor_icon: egui::CursorIcon,
+    // changed since the application last applied it
+    cursor_icon_changed: bool,
+    // this frame's output, drawn by `render()`
+    clipped_meshes: Vec<egui::ClippedMesh>,
+    textures_delta: egui::TexturesDelta,
+    textures: HashMap<egui::TextureId, (wgpu::Texture, wgpu::BindGroup)>,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    // with their size in bytes
+    vertex_buffer: (wgpu::Buffer, u64),
+    index_buffer: (wgpu::Buffer, u64),
+    srgb_target: bool,
+    render_pipeline: Option<wgpu::RenderPipeline>, // None if the pipeline failed to build
+    // the engine's profiler & debug draw windows, over the application's UI (P key)
+    pub(crate) engine_windows_visible: bool
+}
+
+impl DebugUi {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        scale_factor: f64,
+        error_overlay: &mut ErrorOverlay
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug UI Uniform Buffer"),
+            size: std::mem::size_of::<DebugUiUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Debug UI Uniform BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ]
+        });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Debug UI Uniform Bind Group"),
+            layout: &uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Debug UI Texture BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Debug UI Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug UI Pipeline Layout"),
+            bind_group_layouts: &[&uniform_bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let render_pipeline = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Debug UI Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("debug_ui.wgsl", include_str!("res/shaders/debug_ui.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Debug UI Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<DebugUiVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Unorm8x4]
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            // egui's colors have a premultiplied alpha
+                            blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let render_pipeline = render_pipeline
+            .map_err(|error| error_overlay.report("Debug UI Render Pipeline", &error))
+            .ok();
+
+        let vertex_buffer = (Self::create_buffer(device, wgpu::BufferUsages::VERTEX, 1 << 16), 1 << 16);
+        let index_buffer = (Self::create_buffer(device, wgpu::BufferUsages::INDEX, 1 << 16), 1 << 16);
+        Self {
+            context: egui::Context::default(),
+            raw_input: egui::RawInput::default(),
+            max_texture_side: device.limits().max_texture_dimension_2d as usize,
+            start: Instant::now(),
+            pixels_per_point: scale_factor as f32,
+            size: (config.width, config.height),
+            pointer_position: egui::Pos2::ZERO,
+            modifiers: egui::Modifiers::default(),
+            cursor_icon: egui::CursorIcon::Default,
+            cursor_icon_changed: false,
+            clipped_meshes: Vec::new(),
+            textures_delta: egui::TexturesDelta::default(),
+            textures: HashMap::new(),
+            uniform_buffer,
+            uniform_bind_group,
+            texture_bind_group_layout,
+            sampler,
+            vertex_buffer,
+            index_buffer,
+            srgb_target: config.format.describe().srgb,
+            render_pipeline,
+            engine_windows_visible: false
+        }
+    }
+
+    // whether it can be drawn, i.e. its pipeline built
+    pub(crate) fn is_available(&self) -> bool {
+        self.render_pipeline.is_some()
+    }
+
+    fn create_buffer(device: &wgpu::Device, usage: wgpu::BufferUsages, size: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug UI Buffer"),
+            size,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        })
+    }
+
+    pub(crate) fn resize(&mut self, (width, height): (u32, u32)) {
+        self.size = (width, height);
+    }
+
+    // true if the UI uses the event, e.g. a click on a panel or typing in a text field
+    pub(crate) fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.pixels_per_point = *scale_factor as f32;
+                false
+            },
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = Self::modifiers(*state);
+                false
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_position = egui::pos2(position.x as f32 / self.pixels_per_point, position.y as f32 / self.pixels_per_point);
+                self.raw_input.events.push(egui::Event::PointerMoved(self.pointer_position));
+                // hovering a panel doesn't stop the camera
+                false
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.raw_input.events.push(egui::Event::PointerGone);
+                false
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    MouseButton::Other(_) => return false
+                };
+                self.raw_input.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_position,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers
+                });
+                self.context.wants_pointer_input()
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => egui::vec2(*x, *y) * SCROLL_LINE,
+                    MouseScrollDelta::PixelDelta(position) => egui::vec2(position.x as f32, position.y as f32) / self.pixels_per_point
+                };
+                self.raw_input.events.push(egui::Event::Scroll(delta));
+                self.context.wants_pointer_input()
+            },
+            WindowEvent::ReceivedCharacter(c) => {
+                // shortcuts are keys, not text
+                if !c.is_control() && !self.modifiers.ctrl && !self.modifiers.mac_cmd {
+                    self.raw_input.events.push(egui::Event::Text(c.to_string()));
+                }
+                self.context.wants_keyboard_input()
+            },
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                if pressed && self.modifiers.command {
+                    match keycode {
+                        VirtualKeyCode::C => self.raw_input.events.push(egui::Event::Copy),
+                        VirtualKeyCode::X => self.raw_input.events.push(egui::Event::Cut),
+                        VirtualKeyCode::V => {
+                            if let Ok(text) = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+                                self.raw_input.events.push(egui::Event::Paste(text));
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+                if let Some(key) = Self::key(*keycode) {
+                    self.raw_input.events.push(egui::Event::Key {
+                        key,
+                        pressed,
+                        modifiers: self.modifiers
+                    });
+                }
+                self.context.wants_keyboard_input()
+            },
+            _ => false
+        }
+    }
+
+    // the UI has the keyboard focus, e.g. a text field is edited
+    pub(crate) fn wants_keyboard_input(&self) -> bool {
+        self.context.wants_keyboard_input()
+    }
+
+    // Build this frame's UI with the events received since the previous one,
+    // then the engine's windows over it: the unresolved shader errors (label, diagnostics), the profiler & the debug draw toggles.
+    pub(crate) fn run(&mut self, errors: &[(String, String)], debug_draw_settings: &mut DebugDrawSettings, run_ui: impl FnOnce(&egui::Context)) {
+        let raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(self.size.0 as f32, self.size.1 as f32) / self.pixels_per_point
+            )),
+            pixels_per_point: Some(self.pixels_per_point),
+            max_texture_side: Some(self.max_texture_side),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            ..self.raw_input.take()
+        };
+        let engine_windows_visible = &mut self.engine_windows_visible;
+        let output = self.context.run(raw_input, |ctx| {
+            run_ui(ctx);
+            if !errors.is_empty() {
+                // selectable, to copy the diagnostics
+                egui::Window::new("Shader Errors")
+                    .default_pos([16.0, 16.0])
+                    .default_width(640.0)
+                    .show(ctx, |ui| {
+                        egui::ScrollArea::vertical().max_height(480.0).show(ui, |ui| {
+                            for (label, diagnostics) in errors {
+                                ui.colored_label(egui::Color32::from_rgb(255, 110, 110), format!("[{}] creation failed:", label));
+                                ui.add(egui::Label::new(egui::RichText::new(diagnostics).monospace()));
+                            }
+                        });
+                    });
+            }
+            // tips: closing one of them hides both.
+            egui::Window::new("Profiler")
+                .open(engine_windows_visible)
+                .default_width(320.0)
+                .show(ctx, |ui| Profiler::with(|profiler| profiler.ui(ui)));
+            egui::Window::new("Debug Draw")
+                .open(engine_windows_visible)
+                .show(ctx, |ui| debug_draw_settings.ui(ui));
+        });
+
+        let platform_output = output.platform_output;
+        if !platform_output.copied_text.is_empty() {
+            if let Err(error) = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(platform_output.copied_text)) {
+                eprintln!("failed to copy to the clipboard: {}", error);
+            }
+        }
+        if platform_output.cursor_icon != self.cursor_icon {
+            self.cursor_icon = platform_output.cursor_icon;
+            self.cursor_icon_changed = true;
+        }
+        // frames which aren't rendered still get their textures
+        self.textures_delta.append(output.textures_delta);
+        self.clipped_meshes = self.context.tessellate(output.shapes);
+    }
+
+    // the cursor icon to show, if the UI changed it
+    pub(crate) fn take_cursor_icon(&mut self) -> Option<CursorIcon> {
+        if !std::mem::take(&mut self.cursor_icon_changed) {
+            return None;
+        }
+        Some(match self.cursor_icon {
+            egui::CursorIcon::PointingHand => CursorIcon::Hand,
+            egui::CursorIcon::Text => CursorIcon::Text,
+            egui::CursorIcon::Crosshair => CursorIcon::Crosshair,
+            egui::CursorIcon::Move => CursorIcon::Move,
+            egui::CursorIcon::Grab => CursorIcon::Grab,
+            egui::CursorIcon::Grabbing => CursorIcon::Grabbing,
+            egui::CursorIcon::NotAllowed | egui::CursorIcon::NoDrop => CursorIcon::NotAllowed,
+            egui::CursorIcon::ResizeHorizontal => CursorIcon::EwResize,
+            egui::CursorIcon::ResizeVertical => CursorIcon::NsResize,
+            egui::CursorIcon::ResizeNeSw => CursorIcon::NeswResize,
+            egui::CursorIcon::ResizeNwSe => CursorIcon::NwseResize,
+            _ => CursorIcon::Default
+        })
+    }
+
+    // draw this frame's UI over `texture_view` (of the window size)
+    pub(crate) fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_view: &wgpu::TextureView,
+        command_encoder: &mut wgpu::CommandEncoder
+    ) {
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        for (texture_id, image_delta) in textures_delta.set {
+            self.set_texture(device, queue, texture_id, &image_delta);
+        }
+
+        let render_pipeline = match &self.render_pipeline {
+            Some(render_pipeline) if !self.clipped_meshes.is_empty() => render_pipeline,
+            _ => {
+                for texture_id in textures_delta.free {
+                    self.textures.remove(&texture_id);
+                }
+                return;
+            }
+        };
+
+        // every mesh in the same buffers, drawn with its own base vertex
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::with_capacity(self.clipped_meshes.len());
+        for egui::ClippedMesh(clip_rect, mesh) in &self.clipped_meshes {
+            let start = indices.len() as u32;
+            draws.push((*clip_rect, mesh.texture_id, start..start + mesh.indices.len() as u32, vertices.len() as i32));
+            vertices.extend(mesh.vertices.iter().map(|vertex| DebugUiVertex {
+                position: [vertex.pos.x, vertex.pos.y],
+                tex_coords: [vertex.uv.x, vertex.uv.y],
+                color: vertex.color.to_array()
+            }));
+            indices.extend_from_slice(&mesh.indices);
+        }
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&indices);
+        if vertex_bytes.len() as u64 > self.vertex_buffer.1 {
+            let size = (vertex_bytes.len() as u64).next_power_of_two();
+            self.vertex_buffer = (Self::create_buffer(device, wgpu::BufferUsages::VERTEX, size), size);
+        }
+        if index_bytes.len() as u64 > self.index_buffer.1 {
+            let size = (index_bytes.len() as u64).next_power_of_two();
+            self.index_buffer = (Self::create_buffer(device, wgpu::BufferUsages::INDEX, size), size);
+        }
+        queue.write_buffer(&self.vertex_buffer.0, 0, vertex_bytes);
+        queue.write_buffer(&self.index_buffer.0, 0, index_bytes);
+        let uniform = DebugUiUniform {
+            screen_size: [self.size.0 as f32 / self.pixels_per_point, self.size.1 as f32 / self.pixels_per_point],
+            srgb_target: if self.srgb_target { 1.0 } else { 0.0 },
+            _padding: 0.0
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        {
+            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug UI Render Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true
+                    }
+                }],
+                depth_stencil_attachment: None
+            });
+            render_pass.set_pipeline(render_pipeline);
+            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.0.slice(..));
+            render_pass.set_index_buffer(self.index_buffer.0.slice(..), wgpu::IndexFormat::Uint32);
+            for (clip_rect, texture_id, range, base_vertex) in draws {
+                // the clip rectangle in pixels, within the target
+                let min_x = ((clip_rect.min.x * self.pixels_per_point).round().max(0.0) as u32).min(self.size.0);
+                let min_y = ((clip_rect.min.y * self.pixels_per_point).round().max(0.0) as u32).min(self.size.1);
+                let max_x = ((clip_rect.max.x * self.pixels_per_point).round().max(0.0) as u32).min(self.size.0);
+                let max_y = ((clip_rect.max.y * self.pixels_per_point).round().max(0.0) as u32).min(self.size.1);
+                if max_x <= min_x || max_y <= min_y {
+                    continue;
+                }
+                // user textures aren't supported
+                let bind_group = match self.textures.get(&texture_id) {
+                    Some((_, bind_group)) => bind_group,
+                    None => continue
+                };
+                render_pass.set_scissor_rect(min_x, min_y, max_x - min_x, max_y - min_y);
+                render_pass.set_bind_group(1, bind_group, &[]);
+                render_pass.draw_indexed(range, base_vertex, 0..1);
+            }
+        }
+
+        for texture_id in textures_delta.free {
+            self.textures.remove(&texture_id);
+        }
+    }
+
+    // create or update the texture of `texture_id`
+    fn set_texture(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texture_id: egui::TextureId, image_delta: &egui::epaint::ImageDelta) {
+        let (size, pixels) = match &image_delta.image {
+            egui::ImageData::Color(image) => (image.size, image.pixels.iter().flat_map(|color| color.to_array()).collect::<Vec<u8>>()),
+            // the font atlas: white with coverage as alpha
+            egui::ImageData::Alpha(image) => (image.size, image.srgba_pixels(1.0).flat_map(|color| color.to_array()).collect::<Vec<u8>>())
+        };
+        let extent = wgpu::Extent3d {
+            width: size[0] as u32,
+            height: size[1] as u32,
+            depth_or_array_layers: 1
+        };
+
+        let origin = match image_delta.pos {
+            // a region of an existing texture
+            Some([x, y]) => wgpu::Origin3d { x: x as u32, y: y as u32, z: 0 },
+            None => {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Debug UI Texture"),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Debug UI Texture Bind Group"),
+                    layout: &self.texture_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&view)
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler)
+                        }
+                    ]
+                });
+                self.textures.insert(texture_id, (texture, bind_group));
+                wgpu::Origin3d::ZERO
+            }
+        };
+        let texture = match self.textures.get(&texture_id) {
+            Some((texture, _)) => texture,
+            None => return
+        };
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All
+            },
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * extent.width),
+                rows_per_image: std::num::NonZeroU32::new(extent.height)
+            },
+            extent
+        );
+    }
+
+    fn modifiers(state: ModifiersState) -> egui::Modifiers {
+        egui::Modifiers {
+            alt: state.alt(),
+            ctrl: state.ctrl(),
+            shift: state.shift(),
+            mac_cmd: cfg!(target_os = "macos") && state.logo(),
+            command: if cfg!(target_os = "macos") { state.logo() } else { state.ctrl() }
+        }
+    }
+
+    fn key(keycode: VirtualKeyCode) -> Option<egui::Key> {
+        use egui::Key;
+        Some(match keycode {
+            VirtualKeyCode::Down => Key::ArrowDown,
+            VirtualKeyCode::Left => Key::ArrowLeft,
+            VirtualKeyCode::Right => Key::ArrowRight,
+            VirtualKeyCode::Up => Key::ArrowUp,
+            VirtualKeyCode::Escape => Key::Escape,
+            VirtualKeyCode::Tab => Key::Tab,
+            VirtualKeyCode::Back => Key::Backspace,
+            VirtualKeyCode::Return => Key::Enter,
+            VirtualKeyCode::Space => Key::Space,
+            VirtualKeyCode::Insert => Key::Insert,
+            VirtualKeyCode::Delete => Key::Delete,
+            VirtualKeyCode::Home => Key::Home,
+            VirtualKeyCode::End => Key::End,
+            VirtualKeyCode::PageUp => Key::PageUp,
+            VirtualKeyCode::PageDown => Key::PageDown,
+            VirtualKeyCode::Key0 => Key::Num0,
+            VirtualKeyCode::Key1 => Key::Num1,
+            VirtualKeyCode::Key2 => Key::Num2,
+            VirtualKeyCode::Key3 => Key::Num3,
+            VirtualKeyCode::Key4 => Key::Num4,
+            VirtualKeyCode::Key5 => Key::Num5,
+            VirtualKeyCode::Key6 => Key::Num6,
+            VirtualKeyCode::Key7 => Key::Num7,
+            VirtualKeyCode::Key8 => Key::Num8,
+            VirtualKeyCode::Key9 => Key::Num9,
+            VirtualKeyCode::A => Key::A,
+            VirtualKeyCode::B => Key::B,
+            VirtualKeyCode::C => Key::C,
+            VirtualKeyCode::D => Key::D,
+            VirtualKeyCode::E => Key::E,
+            VirtualKeyCode::F => Key::F,
+            VirtualKeyCode::G => Key::G,
+            VirtualKeyCode::H => Key::H,
+            VirtualKeyCode::I => Key::I,
+            VirtualKeyCode::J => Key::J,
+            VirtualKeyCode::K => Key::K,
+            VirtualKeyCode::L => Key::L,
+            VirtualKeyCode::M => Key::M,
+            VirtualKeyCode::N => Key::N,
+            VirtualKeyCode::O => Key::O,
+            VirtualKeyCode::P => Key::P,
+            VirtualKeyCode::Q => Key::Q,
+            VirtualKeyCode::R => Key::R,
+            VirtualKeyCode::S => Key::S,
+            VirtualKeyCode::T => Key::T,
+            VirtualKeyCode::U => Key::U,
+            VirtualKeyCode::V => Key::V,
+            VirtualKeyCode::W => Key::W,
+            VirtualKeyCode::X => Key::X,
+            VirtualKeyCode::Y => Key::Y,
+            VirtualKeyCode::Z => Key::Z,
+            _ => return None
+        })
+    }
+}
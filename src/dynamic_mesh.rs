@@ -0,0 +1,246 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use super::csg::{csg, CsgOperation};
+use super::mesh::{smooth_normals, Mesh, Vertex};
+
+// Editable triangle mesh for tools, destructible props & level blockouts: boolean operations (CSG),
+// face extrusion & normal recomputation, then drawn through `mesh()`.
+// A `Mesh` is uploaded once, so `mesh()` returns a snapshot which is rebuilt (and uploaded again) after the next edit.
+// tips: CSG expects closed meshes (every edge shared by two triangles), e.g. `Mesh::cube()` or `Mesh::sphere()`.
+#[derive(Clone, Default)]
+pub struct DynamicMesh {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    // None after an edit
+    mesh: RefCell<Option<Rc<Mesh>>>
+}
+
+impl DynamicMesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self {
+            vertices,
+            indices,
+            mesh: RefCell::new(None)
+        }
+    }
+
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        Self::new(mesh.vertices().to_vec(), mesh.indices().to_vec())
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // replace the whole geometry, e.g. with the particles of a `Cloth`
+    pub fn set(&mut self, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        self.vertices = vertices;
+        self.indices = indices;
+        self.changed();
+    }
+
+    // edit the vertices in place, e.g. to deform the mesh
+    pub fn vertices_mut(&mut self) -> &mut [Vertex] {
+        self.changed();
+        &mut self.vertices
+    }
+
+    // the mesh to draw, see `MeshDraw::new()`: the same one until the next edit
+    pub fn mesh(&self) -> Rc<Mesh> {
+        self.mesh
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(Mesh::new(self.vertices.clone(), self.indices.clone())))
+            .clone()
+    }
+
+    fn changed(&mut self) {
+        *self.mesh.get_mut() = None;
+    }
+
+    // move the vertices & their normals by `transform`
+    pub fn transform(&mut self, transform: &Matrix4<f32>) {
+        let linear = transform.fixed_slice::<3, 3>(0, 0).into_owned();
+        // normals go through the inverse transpose, in case of non-uniform scale
+        let normal_matrix = linear.try_inverse().map(|inverse| inverse.transpose());
+        for vertex in &mut self.vertices {
+            vertex.position = transform.transform_point(&Point3::from(vertex.position)).into();
+            if let Some(normal_matrix) = &normal_matrix {
+                let normal = normal_matrix * Vector3::from(vertex.normal);
+                vertex.normal = normal.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y).into();
+            }
+        }
+        // a mirroring transform turns the triangles inside out
+        if linear.determinant() < 0.0 {
+            for triangle in self.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+        self.changed();
+    }
+
+    // add the triangles of `other` placed by `transform`, without merging them (e.g. to batch blockout pieces in one mesh)
+    pub fn append(&mut self, other: &DynamicMesh, transform: &Matrix4<f32>) {
+        let mut other = other.clone();
+        other.transform(transform);
+        let first = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices.extend(other.indices.iter().map(|index| first + index));
+        self.changed();
+    }
+
+    // everything in `self` or `other`
+    pub fn union(&self, other: &DynamicMesh) -> DynamicMesh {
+        self.csg(other, CsgOperation::Union)
+    }
+
+    // `self` with `other` carved out of it
+    pub fn difference(&self, other: &DynamicMesh) -> DynamicMesh {
+        self.csg(other, CsgOperation::Difference)
+    }
+
+    // what's in both `self` & `other`
+    pub fn intersection(&self, other: &DynamicMesh) -> DynamicMesh {
+        self.csg(other, CsgOperation::Intersection)
+    }
+
+    fn csg(&self, other: &DynamicMesh, operation: CsgOperation) -> DynamicMesh {
+        let (vertices, indices) = csg((&self.vertices, &self.indices), (&other.vertices, &other.indices), operation);
+        DynamicMesh::new(vertices, indices)
+    }
+
+    // the triangles whose normal is within `max_angle` (radians) of `direction`, e.g. the top of a blockout to extrude
+    pub fn triangles_facing(&self, direction: Vector3<f32>, max_angle: f32) -> Vec<usize> {
+        let direction = match direction.try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => return Vec::new()
+        };
+        let min_cos = max_angle.cos();
+        (0..self.triangle_count())
+            .filter(|triangle| {
+                self.triangle_normal(*triangle)
+                    .is_some_and(|normal| normal.dot(&direction) >= min_cos)
+            })
+            .collect()
+    }
+
+    // None for degenerate triangles
+    fn triangle_normal(&self, triangle: usize) -> Option<Vector3<f32>> {
+        let [a, b, c] = [0, 1, 2].map(|corner| Vector3::from(self.vertices[self.indices[triangle * 3 + corner] as usize].position));
+        (b - a).cross(&(c - a)).try_normalize(f32::EPSILON)
+    }
+
+    // Push the `triangles` (indices of triangles, see `triangles_facing()`) out by `distance` along their average normal,
+    // connected to the rest of the mesh by side walls. A negative `distance` pushes them in.
+    // tips: triangles sharing vertices move together, the walls go around the border of each connected region.
+    pub fn extrude(&mut self, triangles: &[usize], distance: f32) {
+        let triangles = triangles
+            .iter()
+            .copied()
+            .filter(|triangle| *triangle < self.triangle_count())
+            .collect::<Vec<_>>();
+        if triangles.is_empty() {
+            return;
+        }
+
+        // moved copies of the selected vertices, along the average normal of their selected triangles
+        let mut offsets: HashMap<u32, Vector3<f32>> = HashMap::new();
+        for triangle in &triangles {
+            let normal = self.triangle_normal(*triangle).unwrap_or_else(Vector3::zeros);
+            for corner in 0..3 {
+                *offsets.entry(self.indices[triangle * 3 + corner]).or_insert_with(Vector3::zeros) += normal;
+            }
+        }
+        let mut moved: HashMap<u32, u32> = HashMap::new();
+        let mut sorted = offsets.into_iter().collect::<Vec<_>>();
+        sorted.sort_by_key(|(index, _)| *index);
+        for (index, normal) in sorted {
+            let mut vertex = self.vertices[index as usize];
+            let offset = normal.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::zeros) * distance;
+            vertex.position = (Vector3::from(vertex.position) + offset).into();
+            moved.insert(index, self.vertices.len() as u32);
+            self.vertices.push(vertex);
+        }
+
+        // the border: edges of a single selected triangle, in its winding order
+        let mut edges: HashSet<(u32, u32)> = HashSet::new();
+        for triangle in &triangles {
+            for corner in 0..3 {
+                edges.insert((self.indices[triangle * 3 + corner], self.indices[triangle * 3 + (corner + 1) % 3]));
+            }
+        }
+        let mut border = edges
+            .iter()
+            .filter(|(a, b)| !edges.contains(&(*b, *a)))
+            .copied()
+            .collect::<Vec<_>>();
+        border.sort_unstable();
+
+        // the selected triangles now use the moved vertices
+        for triangle in &triangles {
+            for corner in 0..3 {
+                let index = &mut self.indices[triangle * 3 + corner];
+                *index = moved[&*index];
+            }
+        }
+
+        // a flat shaded quad per border edge, from the old edge to the moved one
+        for (a, b) in border {
+            let corners = [a, b, moved[&b], moved[&a]].map(|index| self.vertices[index as usize]);
+            let [pa, pb, pb_moved] = [corners[0], corners[1], corners[2]].map(|vertex| Vector3::from(vertex.position));
+            // pushed in, the walls face the other way
+            let normal = (pb - pa).cross(&(pb_moved - pa)).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y);
+            let length = (pb - pa).norm();
+            let height = distance.abs();
+            let first = self.vertices.len() as u32;
+            for (corner, tex_coords) in corners.iter().zip([[0.0, height], [length, height], [length, 0.0], [0.0, 0.0]]) {
+                self.vertices.push(Vertex {
+                    position: corner.position,
+                    tex_coords,
+                    color: corner.color,
+                    normal: normal.into()
+                });
+            }
+            indices_quad(&mut self.indices, first);
+        }
+        self.changed();
+    }
+
+    // area weighted average of the adjacent triangles at every vertex, see `Mesh::with_smooth_normals()`
+    pub fn smooth_normals(&mut self) {
+        smooth_normals(&mut self.vertices, &self.indices);
+        self.changed();
+    }
+
+    // a normal per triangle, its vertices are split so the edges stay sharp
+    pub fn flat_normals(&mut self) {
+        let mut vertices = Vec::with_capacity(self.indices.len());
+        for triangle in 0..self.triangle_count() {
+            let normal = self.triangle_normal(triangle).unwrap_or_else(Vector3::y);
+            for corner in 0..3 {
+                let mut vertex = self.vertices[self.indices[triangle * 3 + corner] as usize];
+                vertex.normal = normal.into();
+                vertices.push(vertex);
+            }
+        }
+        self.indices = (0..vertices.len() as u32).collect();
+        self.vertices = vertices;
+        self.changed();
+    }
+}
+
+// two counter-clockwise triangles of the quad `first` ~ `first + 3`
+fn indices_quad(indices: &mut Vec<u32>, first: u32) {
+    indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+}
@@ -0,0 +1,114 @@
+use nalgebra::Vector3;
+
+use super::exposure::Exposure;
+
+// Exponential squared distance fog.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub color: [f32; 3],
+    // 0.0 disables the fog, about `1.5 / density` units away everything is fog.
+    pub density: f32
+}
+
+impl Fog {
+    pub fn new(color: [f32; 3], density: f32) -> Self {
+        Self { color, density }
+    }
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self::new([0.5, 0.6, 0.7], 0.0)
+    }
+}
+
+// Global environment settings (sky, sun, ambient, wind, fog & time of day),
+// shared by everything that should stay visually consistent: lighting, skybox, foliage, cloth, particles...
+// The renderer reads it from `Application::environment()` every frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Environment {
+    // background color without a skybox (see `Application::skybox()`)
+    pub sky_color: [f32; 3],
+    // multiplies the colors of the skybox, e.g. to dim it at night
+    pub sky_intensity: f32,
+    // direction the sunlight travels in (from the sun towards the ground)
+    pub sun_direction: Vector3<f32>,
+    pub sun_color: [f32; 3],
+    // illuminance on the ground, in lux (~110000 at noon on a clear day)
+    pub sun_intensity: f32,
+    pub ambient_color: [f32; 3],
+    // luminance of the sky, in nits (cd/m²)
+    pub ambient_intensity: f32,
+    // camera exposure the lights are rendered with
+    pub exposure: Exposure,
+    // direction the wind blows towards
+    pub wind_direction: Vector3<f32>,
+    // wind speed, in units per second
+    pub wind_strength: f32,
+    pub fog: Fog,
+    // hours, 0.0 ~ 24.0
+    pub time_of_day: f32
+}
+
+impl Environment {
+    // a clear noon: sun high in the sky, light breeze, no fog
+    pub fn new() -> Self {
+        Self {
+            sky_color: [0.1, 0.2, 0.3],
+            sky_intensity: 1.0,
+            sun_direction: Vector3::new(-0.3, -1.0, -0.2),
+            sun_color: [1.0, 0.96, 0.9],
+            sun_intensity: 110000.0,
+            ambient_color: [0.6, 0.7, 0.8],
+            ambient_intensity: 12000.0,
+            exposure: Exposure::sunny_16(),
+            wind_direction: Vector3::new(1.0, 0.0, 0.0),
+            wind_strength: 1.0,
+            fog: Fog::default(),
+            time_of_day: 12.0
+        }
+    }
+
+    // wind velocity, e.g. for `Cloth::step()`
+    pub fn wind_velocity(&self) -> Vector3<f32> {
+        match self.wind_direction.try_normalize(f32::EPSILON) {
+            Some(direction) => direction * self.wind_strength,
+            None => Vector3::zeros()
+        }
+    }
+
+    pub(crate) fn to_uniform(self) -> EnvironmentUniform {
+        let sun_direction = self.sun_direction.try_normalize(f32::EPSILON).unwrap_or_else(|| -Vector3::y());
+        // pre-exposed, so the shaders work with pixel values
+        let exposure = self.exposure.multiplier();
+        let scale = |color: [f32; 3], intensity: f32| {
+            let intensity = intensity * exposure;
+            [color[0] * intensity, color[1] * intensity, color[2] * intensity, 1.0]
+        };
+
+        EnvironmentUniform {
+            sun_direction: [sun_direction.x, sun_direction.y, sun_direction.z, 0.0],
+            sun_color: scale(self.sun_color, self.sun_intensity),
+            ambient_color: scale(self.ambient_color, self.ambient_intensity),
+            fog_color: self.fog.color,
+            fog_density: self.fog.density
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// `Environment` layout in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct EnvironmentUniform {
+    sun_direction: [f32; 4],
+    sun_color: [f32; 4], // premultiplied by the intensity & exposure
+    ambient_color: [f32; 4], // premultiplied by the intensity & exposure
+    fog_color: [f32; 3],
+    fog_density: f32
+}
@@ -0,0 +1,350 @@
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use wgpu::util::DeviceExt; // for `create_buffer_init`
+
+use super::bitmap_font::{draw_text, CELL_HEIGHT, CELL_WIDTH};
+use super::texture::Texture;
+
+// held from the push to the pop of an error scope, see `catch_validation_error()`
+static ERROR_SCOPE: Mutex<()> = Mutex::new(());
+
+// Run `create` (shader module / pipeline creation) inside a wgpu validation error scope.
+// By default wgpu panics on any validation error, which means a typo in a shader kills the whole application.
+// Catching the error here lets the caller skip the broken pipeline and keep rendering the rest of the scene.
+// tips: error scopes are a stack shared by the whole device, so scopes opened from several threads (e.g. the scene pipelines thread)
+// would catch each other's errors: only one scope is open at a time.
+pub(crate) fn catch_validation_error<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> Result<T> {
+    // a panic while a scope was open doesn't stop the others
+    let _scope = ERROR_SCOPE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+    // tips: on native backends the error scope is resolved immediately, so blocking here is cheap.
+    match pollster::block_on(device.pop_error_scope()) {
+        // only the first error is kept by the scope, which is the compiler diagnostic if the shader is broken.
+        Some(error) => Err(anyhow!("{}", error)),
+        None => Ok(created)
+    }
+}
+
+// characters per line of the text panel, longer lines are wrapped
+const TEXT_COLUMNS: usize = 120;
+// lines of the text panel, the rest is cut
+const TEXT_LINES: usize = 60;
+// pixels around the text & between the panel and the frame edges, before scaling
+const TEXT_PADDING: u32 = 4;
+
+// The panel with the diagnostics, uploaded when the errors change.
+struct ErrorText {
+    bind_group: wgpu::BindGroup,
+    // in pixels
+    size: (u32, u32),
+    // kept alive with the bind group
+    _texture: Texture
+}
+
+// Draw a striped red overlay on top of the frame while there are unresolved shader/pipeline errors,
+// with their compiler diagnostics in a text panel (or in a debug UI window with the feature "egui").
+// They're printed to stderr as well.
+pub(crate) struct ErrorOverlay {
+    render_pipeline: wgpu::RenderPipeline,
+    text_render_pipeline: wgpu::RenderPipeline,
+    text_bind_group_layout: wgpu::BindGroupLayout,
+    // panel corners in clip space
+    text_rect_buffer: wgpu::Buffer,
+    text_sampler: wgpu::Sampler,
+    // None without errors, or until the changed errors are uploaded
+    text: Option<ErrorText>,
+    errors: Vec<(String, String)> // (label, diagnostics)
+}
+
+impl ErrorOverlay {
+    pub(crate) fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        // tips: this shader is embedded and known-good, so it is not created inside an error scope.
+        let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Error Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("res/shaders/error_overlay.wgsl").into())
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Error Overlay Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[]
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Error Overlay Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                // the full-screen triangle is generated from `vertex_index`, no vertex buffer needed.
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[
+                    wgpu::ColorTargetState {
+                        format: config.format,
+                        // blend the overlay over the scene, so the scene stays visible below it.
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL
+                    }
+                ]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None
+        });
+
+        // the text panel: a quad textured with the diagnostics, written on the CPU with the bitmap font
+        let text_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Error Text Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+        let text_render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Error Text Pipeline Layout"),
+            bind_group_layouts: &[&text_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let text_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Error Text Render Pipeline"),
+            layout: Some(&text_render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_text",
+                // the quad is generated from `vertex_index` too
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_text",
+                targets: &[
+                    wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL
+                    }
+                ]
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None
+        });
+        let text_rect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Error Text Rect Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        });
+        // crisp glyphs when the panel is scaled up
+        let text_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Error Text Sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            render_pipeline,
+            text_render_pipeline,
+            text_bind_group_layout,
+            text_rect_buffer,
+            text_sampler,
+            text: None,
+            errors: Vec::new()
+        }
+    }
+
+    // record a failure and print its diagnostics.
+    pub(crate) fn report(&mut self, label: &str, error: &anyhow::Error) {
+        eprintln!("[{}] creation failed: {}", label, error);
+        // replace the previous error of the same label, so a failure reported again doesn't pile up.
+        self.errors.retain(|(error_label, _)| error_label != label);
+        self.errors.push((label.to_string(), error.to_string()));
+        self.text = None;
+    }
+
+    // forget the error of `label`, e.g. before building it again
+    pub(crate) fn resolve(&mut self, label: &str) {
+        let count = self.errors.len();
+        self.errors.retain(|(error_label, _)| error_label != label);
+        if self.errors.len() != count {
+            self.text = None;
+        }
+    }
+
+    // (label, diagnostics) of the unresolved errors, e.g. for the debug UI
+    #[cfg(feature = "egui")]
+    pub(crate) fn errors(&self) -> &[(String, String)] {
+        &self.errors
+    }
+
+    // the lines of the text panel: the label of each error, then its diagnostics
+    fn text_lines(&self) -> Vec<(String, bool)> {
+        let mut lines = Vec::new();
+        for (label, diagnostics) in &self.errors {
+            lines.push((format!("[{}] creation failed:", label), true));
+            for line in diagnostics.lines() {
+                let line = line.replace('\t', "    ").chars().collect::<Vec<_>>();
+                // empty lines are kept
+                let mut chunks = line.chunks(TEXT_COLUMNS).peekable();
+                if chunks.peek().is_none() {
+                    lines.push((String::new(), false));
+                }
+                lines.extend(chunks.map(|chunk| (chunk.iter().collect(), false)));
+            }
+            lines.push((String::new(), false));
+        }
+        if lines.len() > TEXT_LINES {
+            lines.truncate(TEXT_LINES - 1);
+            lines.push(("...".to_string(), false));
+        }
+        lines
+    }
+
+    // write the diagnostics into a texture
+    fn upload_text(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<ErrorText> {
+        let lines = self.text_lines();
+        let columns = lines.iter().map(|(line, _)| line.chars().count()).max().unwrap_or(0) as u32;
+        let size = (
+            TEXT_PADDING * 2 + columns.max(1) * CELL_WIDTH,
+            TEXT_PADDING * 2 + lines.len().max(1) as u32 * CELL_HEIGHT
+        );
+        let mut image = image::RgbaImage::from_pixel(size.0, size.1, image::Rgba([16, 16, 16, 224]));
+        for (index, (line, is_label)) in lines.iter().enumerate() {
+            let color = if *is_label { image::Rgba([255, 110, 110, 255]) } else { image::Rgba([235, 235, 235, 255]) };
+            draw_text(&mut image, TEXT_PADDING, TEXT_PADDING + index as u32 * CELL_HEIGHT, line, color);
+        }
+        let texture = Texture::from_image(device, queue, &image::DynamicImage::ImageRgba8(image), Some("Error Text"))?;
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Error Text Bind Group"),
+            layout: &self.text_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.text_rect_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.text_sampler)
+                }
+            ]
+        });
+        Ok(ErrorText {
+            bind_group,
+            size,
+            _texture: texture
+        })
+    }
+
+    pub(crate) fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    // `frame_size` in pixels, the text panel is left to the debug UI if `draw_text` is false
+    pub(crate) fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame_size: (u32, u32),
+        draw_text: bool,
+        texture_view: &wgpu::TextureView,
+        command_encoder: &mut wgpu::CommandEncoder
+    ) {
+        if !self.has_errors() {
+            return;
+        }
+        if draw_text && self.text.is_none() {
+            match self.upload_text(device, queue) {
+                Ok(text) => self.text = Some(text),
+                // the stripes are still drawn
+                Err(error) => eprintln!("[Error Overlay] failed to upload the diagnostics: {:?}", error)
+            }
+        }
+        let text = self.text.as_ref().filter(|_| draw_text);
+        if let Some(text) = text {
+            // top left, twice as big if it fits
+            let (frame_width, frame_height) = (frame_size.0.max(1) as f32, frame_size.1.max(1) as f32);
+            let fits = TEXT_PADDING * 2 + text.size.0 * 2 <= frame_size.0 && TEXT_PADDING * 2 + text.size.1 * 2 <= frame_size.1;
+            let scale = if fits { 2.0 } else { 1.0 };
+            let (left, top) = (TEXT_PADDING as f32 * scale, TEXT_PADDING as f32 * scale);
+            let (right, bottom) = (left + text.size.0 as f32 * scale, top + text.size.1 as f32 * scale);
+            let rect = [
+                left / frame_width * 2.0 - 1.0,
+                1.0 - top / frame_height * 2.0,
+                right / frame_width * 2.0 - 1.0,
+                1.0 - bottom / frame_height * 2.0
+            ];
+            queue.write_buffer(&self.text_rect_buffer, 0, bytemuck::cast_slice(&rect));
+        }
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Error Overlay Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // keep the scene rendered so far.
+                    load: wgpu::LoadOp::Load,
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.draw(0..3, 0..1);
+
+        if let Some(text) = text {
+            render_pass.set_pipeline(&self.text_render_pipeline);
+            render_pass.set_bind_group(0, &text.bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
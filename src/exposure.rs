@@ -0,0 +1,55 @@
+// Physical camera exposure, turning physical light units (lux, nits, candela) into pixel values.
+// ref: https://google.github.io/filament/Filament.md.html#imagingpipeline/physicallybasedcamera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exposure {
+    Camera {
+        // f-number, e.g. 16.0 for f/16
+        aperture: f32,
+        // in seconds, e.g. 1.0 / 125.0
+        shutter_speed: f32,
+        // sensor sensitivity, e.g. 100.0
+        iso: f32
+    },
+    // exposure value at ISO 100, e.g. from a light meter or a photo's metadata
+    Ev100(f32)
+}
+
+impl Exposure {
+    // "sunny 16" rule: f/16, 1/125s, ISO 100 (about EV100 15), for bright daylight
+    pub fn sunny_16() -> Self {
+        Self::Camera {
+            aperture: 16.0,
+            shutter_speed: 1.0 / 125.0,
+            iso: 100.0
+        }
+    }
+
+    pub fn ev100(&self) -> f32 {
+        match *self {
+            Self::Camera { aperture, shutter_speed, iso } => (aperture * aperture / shutter_speed * 100.0 / iso).log2(),
+            Self::Ev100(ev100) => ev100
+        }
+    }
+
+    // Scale from luminance (nits) to pixel values, 1.0 for the brightest luminance the camera captures without saturating.
+    pub fn multiplier(&self) -> f32 {
+        1.0 / (1.2 * 2.0f32.powf(self.ev100()))
+    }
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self::sunny_16()
+    }
+}
+
+// Luminous intensity (candela) of a point light emitting `lumens` in every direction, e.g. 800 lm for a 60W bulb.
+// tips: glTF KHR_lights_punctual intensities are already in candela for point & spot lights (and in lux for directional lights).
+pub fn point_light_candela(lumens: f32) -> f32 {
+    lumens / (4.0 * std::f32::consts::PI)
+}
+
+// Luminous intensity (candela) of a spot light emitting `lumens` inside its cone of `outer_angle` (half angle, in radians).
+pub fn spot_light_candela(lumens: f32, outer_angle: f32) -> f32 {
+    lumens / (2.0 * std::f32::consts::PI * (1.0 - outer_angle.cos()))
+}
@@ -0,0 +1,115 @@
+use std::borrow::Cow;
+
+use anyhow::{bail, Context, Result};
+
+// first word of a header line
+const HEADER_PREFIX: &str = "#!eyengine";
+
+// upgrades the body of a version to the next one
+pub type Migration = fn(&str) -> Result<String>;
+
+// Versioned text file format written by the engine or its tools, with its migrations.
+// Files start with the header line `#!eyengine <name> <version>`: a newer engine upgrades old files step by step
+// through the migration hooks, an older one refuses newer files with a clear error rather than misreading them.
+// tips: the header is a `#` comment line, so formats with `#` comments stay readable by hand.
+#[derive(Clone, Debug)]
+pub struct FileFormat {
+    name: &'static str,
+    version: u32,
+    // version assumed for files without a header, None if the header is required
+    unversioned: Option<u32>,
+    // by the version they upgrade from
+    migrations: Vec<(u32, Migration)>
+}
+
+impl FileFormat {
+    // `version` is the one written, starting at 1
+    pub fn new(name: &'static str, version: u32) -> Self {
+        Self {
+            name,
+            version: version.max(1),
+            unversioned: None,
+            migrations: Vec::new()
+        }
+    }
+
+    // read files without a header (written before the format was versioned) as `version`
+    pub fn with_unversioned(mut self, version: u32) -> Self {
+        self.unversioned = Some(version);
+        self
+    }
+
+    // hook upgrading the body of a file from version `from` to `from + 1`
+    pub fn with_migration(mut self, from: u32, migration: Migration) -> Self {
+        self.migrations.retain(|(version, _)| *version != from);
+        self.migrations.push((from, migration));
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn header(&self) -> String {
+        format!("{} {} {}", HEADER_PREFIX, self.name, self.version)
+    }
+
+    // `body` of the current version, with its header line
+    pub fn write(&self, body: &str) -> String {
+        format!("{}\n{}", self.header(), body)
+    }
+
+    // the body of `source` (without its header line), migrated to the current version
+    pub fn read<'a>(&self, source: &'a str) -> Result<Cow<'a, str>> {
+        let (version, body) = match source.lines().next() {
+            Some(line) if line.starts_with(HEADER_PREFIX) => {
+                let version = self.parse_header(line)?;
+                let body = source.split_once('\n').map_or("", |(_, body)| body);
+                (version, body)
+            },
+            _ => match self.unversioned {
+                Some(version) => (version, source),
+                None => bail!("not a `{}` file: missing its `{} {} <version>` header", self.name, HEADER_PREFIX, self.name)
+            }
+        };
+        if version > self.version {
+            bail!(
+                "`{}` file of version {} was written by a newer engine, this one reads up to version {}",
+                self.name,
+                version,
+                self.version
+            );
+        }
+
+        let mut body = Cow::Borrowed(body);
+        for from in version..self.version {
+            let migration = match self.migrations.iter().find(|(version, _)| *version == from) {
+                Some((_, migration)) => migration,
+                None => bail!("`{}` file of version {} is no longer supported: no migration to version {}", self.name, from, from + 1)
+            };
+            body = Cow::Owned(
+                migration(&body).with_context(|| format!("failed to migrate a `{}` file from version {} to {}", self.name, from, from + 1))?
+            );
+        }
+        Ok(body)
+    }
+
+    fn parse_header(&self, line: &str) -> Result<u32> {
+        let mut words = line.split_whitespace().skip(1);
+        let (name, version) = match (words.next(), words.next(), words.next()) {
+            (Some(name), Some(version), None) => (name, version),
+            _ => bail!("malformed header `{}`, expected `{} <name> <version>`", line, HEADER_PREFIX)
+        };
+        if name != self.name {
+            bail!("expected a `{}` file, got a `{}` file", self.name, name);
+        }
+        match version.parse::<u32>() {
+            Ok(version) if version > 0 => Ok(version),
+            _ => bail!("`{}` file: invalid version `{}`", self.name, version)
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+
+use super::transform::Transform;
+
+// Floating origin for large worlds: f32 positions lose precision far from zero (about a millimeter at 10 km), so the camera
+// & the scene are shifted back toward zero once the camera is further than `threshold`, see `Scene::with_floating_origin()`.
+// The positions of the scene stay relative to `offset()`, in the scene's resources for its systems.
+// tips: the shifts are whole units, so the ground grid & the tiled textures don't jump.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldOrigin {
+    // in world units from the origin
+    pub threshold: f32,
+    // absolute position of the origin
+    offset: Vector3<f64>,
+    // the shift of this frame
+    shifted: Option<Vector3<f32>>
+}
+
+impl WorldOrigin {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            offset: Vector3::zeros(),
+            shifted: None
+        }
+    }
+
+    // absolute position of the scene's origin, in double precision
+    pub fn offset(&self) -> Vector3<f64> {
+        self.offset
+    }
+
+    // The shift subtracted from every position this frame, None most frames.
+    // tips: what the application keeps outside of the scene (a `Cloth` it steps itself, debug shapes...) has to be moved by it too.
+    pub fn shifted(&self) -> Option<Vector3<f32>> {
+        self.shifted
+    }
+
+    // absolute position of a point of the scene, e.g. to save it
+    pub fn to_world(&self, position: &Point3<f32>) -> Point3<f64> {
+        Point3::from(position.coords.cast::<f64>() + self.offset)
+    }
+
+    // position in the scene of an absolute point
+    pub fn to_local(&self, position: &Point3<f64>) -> Point3<f32> {
+        Point3::from((position.coords - self.offset).cast::<f32>())
+    }
+
+    // move the origin under `eye` if it's too far, returning the shift
+    pub(crate) fn recenter(&mut self, eye: &Point3<f32>) -> Option<Vector3<f32>> {
+        self.shifted = None;
+        if eye.coords.norm() <= self.threshold {
+            return None;
+        }
+        let shift = eye.coords.map(f32::round);
+        // under half a unit from the origin, e.g. with a zero threshold
+        if shift == Vector3::zeros() {
+            return None;
+        }
+        self.offset += shift.cast::<f64>();
+        self.shifted = Some(shift);
+        self.shifted
+    }
+}
+
+// Component placing an entity in double precision, for space-scale scenes (planets, orbits...): the scene converts it
+// to the entity's `Transform` every frame after its systems, relative to the `WorldOrigin`.
+// With `Scene::with_floating_origin()` the origin stays close to the camera, so what's drawn is precise wherever it is.
+// A low threshold keeps the origin on the camera: the scene is then drawn camera-relative.
+// tips: only for the entities without `Parent`, their children are placed by their `Transform` as usual.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PreciseTransform {
+    pub translation: Vector3<f64>,
+    pub rotation: UnitQuaternion<f64>,
+    pub scale: Vector3<f64>
+}
+
+impl PreciseTransform {
+    pub fn new(translation: Vector3<f64>) -> Self {
+        Self {
+            translation,
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::repeat(1.0)
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: UnitQuaternion<f64>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector3<f64>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    // the part relative to `origin` (absolute) fits in single precision
+    pub(crate) fn to_transform(self, origin: &Vector3<f64>, transform: &mut Transform) {
+        transform.translation = (self.translation - origin).cast();
+        transform.rotation = self.rotation.cast();
+        transform.scale = self.scale.cast();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recenters_past_threshold() {
+        let mut origin = WorldOrigin::new(100.0);
+        assert_eq!(origin.recenter(&Point3::new(60.0, 0.0, -80.0)), None);
+        assert_eq!(origin.recenter(&Point3::new(0.0, 99.9, 0.0)), None);
+        assert_eq!(origin.offset(), Vector3::zeros());
+
+        // whole units only
+        let shift = origin.recenter(&Point3::new(100.4, 0.0, -0.6));
+        assert_eq!(shift, Some(Vector3::new(100.0, 0.0, -1.0)));
+        assert_eq!(origin.shifted(), shift);
+        assert_eq!(origin.offset(), Vector3::new(100.0, 0.0, -1.0));
+
+        // only for the frame it happened
+        assert_eq!(origin.recenter(&Point3::new(0.4, 0.0, 0.4)), None);
+        assert_eq!(origin.shifted(), None);
+
+        // nothing to shift under half a unit, even with a zero threshold
+        let mut origin = WorldOrigin::new(0.0);
+        assert_eq!(origin.recenter(&Point3::new(0.3, -0.2, 0.4)), None);
+        assert_eq!(origin.recenter(&Point3::new(0.0, 0.0, 0.6)), Some(Vector3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn accumulates_offset_in_double_precision() {
+        let mut origin = WorldOrigin::new(1000.0);
+        // the camera flies 10 million units along x, recentering every 1001 units
+        let mut eye = Point3::new(0.0f32, 0.0, 0.0);
+        let mut travelled = 0.0f64;
+        while travelled < 1.0e7 {
+            eye.x += 1001.0;
+            travelled += 1001.0;
+            if let Some(shift) = origin.recenter(&eye) {
+                eye -= shift;
+            }
+        }
+        assert_eq!(origin.offset().x + eye.x as f64, travelled);
+        assert!(eye.coords.norm() <= origin.threshold);
+
+        // a quarter unit is lost in f32 at 10 million units, not relative to the origin
+        let position = Point3::new(eye.x + 0.25, 0.0, 0.0);
+        assert_eq!(origin.to_world(&position).x, travelled + 0.25);
+        assert_eq!(origin.to_local(&origin.to_world(&position)), position);
+    }
+}
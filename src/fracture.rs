@@ -0,0 +1,369 @@
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+
+use super::csg::{csg, CsgOperation};
+use super::mesh::{Mesh, Vertex};
+use super::scene::MeshHandle;
+use super::transform::Transform;
+
+// distance under which a point is on a cutting plane
+const EPSILON: f32 = 1e-5;
+
+// One convex piece of a `FracturedMesh`, centered on the origin.
+#[derive(Clone)]
+pub struct FracturePiece {
+    pub mesh: Rc<Mesh>,
+    // where the piece was in the original mesh
+    pub center: Vector3<f32>
+}
+
+// A mesh split ahead of time into the pieces it breaks into, so nothing is cut on the frame it's destroyed.
+// The pieces are the Voronoi cells of random points in its bounds, intersected with the mesh (see `DynamicMesh::intersection()`).
+// tips: the mesh should be closed, the faces inside of it are white & flat shaded, textured by a planar mapping.
+#[derive(Clone)]
+pub struct FracturedMesh {
+    pieces: Vec<FracturePiece>
+}
+
+impl FracturedMesh {
+    // Split `mesh` into about `pieces` pieces, always the same ones for the same `seed`.
+    // tips: each piece is a CSG operation on the whole mesh, keep the meshes low poly & split them while loading.
+    pub fn new(mesh: &Mesh, pieces: usize, seed: u32) -> Self {
+        let (min, max) = match bounds(mesh.vertices()) {
+            Some(bounds) => bounds,
+            None => return Self { pieces: Vec::new() }
+        };
+        // the cells start as a box slightly bigger than the mesh, so its faces are kept whole
+        let margin = (max - min).norm() * 0.01 + EPSILON;
+        let (min, max) = (min - Vector3::repeat(margin), max + Vector3::repeat(margin));
+
+        let mut random = Random::new(seed);
+        let sites = (0..pieces.max(1))
+            .map(|_| min + (max - min).component_mul(&Vector3::new(random.unit(), random.unit(), random.unit())))
+            .collect::<Vec<_>>();
+
+        let pieces = sites
+            .iter()
+            .enumerate()
+            .filter_map(|(index, site)| {
+                // the cell of a site: the space closer to it than to any other site
+                let mut cell = box_faces(min, max);
+                for (other_index, other) in sites.iter().enumerate() {
+                    if other_index == index {
+                        continue;
+                    }
+                    let normal = match (other - site).try_normalize(f32::EPSILON) {
+                        Some(normal) => normal,
+                        None => continue
+                    };
+                    cell = clip_convex(cell, normal, normal.dot(&((site + other) * 0.5)));
+                }
+                let (cell_vertices, cell_indices) = triangulate(&cell);
+                let (mut vertices, indices) = csg(
+                    (mesh.vertices(), mesh.indices()),
+                    (&cell_vertices, &cell_indices),
+                    CsgOperation::Intersection
+                );
+                // a site outside of the mesh
+                if indices.is_empty() {
+                    return None;
+                }
+                let (piece_min, piece_max) = bounds(&vertices)?;
+                let center = (piece_min + piece_max) * 0.5;
+                for vertex in &mut vertices {
+                    vertex.position = (Vector3::from(vertex.position) - center).into();
+                }
+                Some(FracturePiece { mesh: Rc::new(Mesh::new(vertices, indices)), center })
+            })
+            .collect();
+        Self { pieces }
+    }
+
+    pub fn pieces(&self) -> &[FracturePiece] {
+        &self.pieces
+    }
+}
+
+// How the debris of a `Destructible` move & disappear.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebrisSettings {
+    // seconds before the pieces are removed from the scene
+    pub lifetime: f32,
+    // seconds at the end of the lifetime during which the pieces shrink to nothing
+    // tips: the materials are shared by the scene, so the pieces can't fade to transparent one by one.
+    pub fade_time: f32,
+    pub gravity: Vector3<f32>,
+    // height of the ground plane the pieces bounce on, None to let them fall forever
+    pub ground_height: Option<f32>,
+    // 0.0 ~ 1.0, fraction of the vertical speed kept when bouncing
+    pub restitution: f32,
+    // 0.0 ~ 1.0, fraction of the horizontal & angular speed lost when touching the ground
+    pub friction: f32,
+    // spin of the pieces, in radians per second for a unit `force` in `Scene::destroy()`
+    pub spin: f32
+}
+
+impl Default for DebrisSettings {
+    fn default() -> Self {
+        Self {
+            lifetime: 5.0,
+            fade_time: 1.0,
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            ground_height: Some(0.0),
+            restitution: 0.3,
+            friction: 0.3,
+            spin: 1.0
+        }
+    }
+}
+
+impl DebrisSettings {
+    // the ground follows the shifts of the origin, see `WorldOrigin`
+    pub(crate) fn shift_origin(&mut self, shift: &Vector3<f32>) {
+        if let Some(ground_height) = &mut self.ground_height {
+            *ground_height -= shift.y;
+        }
+    }
+}
+
+// Component of an entity which breaks into the pieces of a `FracturedMesh` with `Scene::destroy()`,
+// made with `Scene::add_destructible()`. The pieces keep the entity's transform & material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Destructible {
+    pub(crate) pieces: Vec<(MeshHandle, Vector3<f32>)>,
+    pub debris: DebrisSettings
+}
+
+impl Destructible {
+    pub fn with_debris(mut self, debris: DebrisSettings) -> Self {
+        self.debris = debris;
+        self
+    }
+}
+
+// Component of a piece of a destroyed entity, moved by the scene every frame until it's removed at the end of its lifetime.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Debris {
+    pub position: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub velocity: Vector3<f32>,
+    // axis scaled by the speed in radians per second
+    pub angular_velocity: Vector3<f32>,
+    pub age: f32,
+    pub settings: DebrisSettings,
+    // the piece placed at the origin with the scale & orientation of the destroyed entity
+    shape: Matrix4<f32>,
+    // of the bounding sphere, in world units
+    radius: f32
+}
+
+impl Debris {
+    // The piece of the entity placed by `transform`, thrown away from `impact`.
+    pub(crate) fn new(piece_mesh: &Mesh, center: Vector3<f32>, transform: &Matrix4<f32>, impact: &Point3<f32>, force: f32, settings: DebrisSettings, random: &mut Random) -> Self {
+        let position = transform.transform_point(&Point3::from(center)).coords;
+        let shape = Matrix4::new_translation(&-position) * transform * Matrix4::new_translation(&center);
+        let radius = piece_mesh
+            .vertices()
+            .iter()
+            .map(|vertex| shape.transform_vector(&Vector3::from(vertex.position)).norm())
+            .fold(0.0, f32::max);
+
+        // faster close to the impact
+        let offset = position - impact.coords;
+        let direction = offset.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y);
+        let velocity = direction * force / (1.0 + offset.norm());
+        let axis = Vector3::new(random.signed(), random.signed(), random.signed())
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::x);
+
+        Self {
+            position,
+            rotation: UnitQuaternion::identity(),
+            velocity,
+            angular_velocity: axis * settings.spin * force * random.unit(),
+            age: 0.0,
+            settings,
+            shape,
+            radius
+        }
+    }
+
+    // false once the lifetime is over
+    pub fn is_alive(&self) -> bool {
+        self.age < self.settings.lifetime
+    }
+
+    // 1.0 ~ 0.0 over the fade time
+    pub fn fade(&self) -> f32 {
+        let remaining = self.settings.lifetime - self.age;
+        if self.settings.fade_time <= 0.0 {
+            return if remaining > 0.0 { 1.0 } else { 0.0 };
+        }
+        (remaining / self.settings.fade_time).clamp(0.0, 1.0)
+    }
+
+    // integrate gravity & bounce on the ground for `dt` seconds
+    pub(crate) fn step(&mut self, dt: f32) {
+        self.age += dt;
+        self.velocity += self.settings.gravity * dt;
+        self.position += self.velocity * dt;
+        self.rotation = UnitQuaternion::from_scaled_axis(self.angular_velocity * dt) * self.rotation;
+
+        // the bounding sphere against the ground
+        if let Some(ground_height) = self.settings.ground_height {
+            let bottom = self.position.y - self.radius * self.fade();
+            if bottom < ground_height {
+                self.position.y += ground_height - bottom;
+                if self.velocity.y < 0.0 {
+                    self.velocity.y = -self.velocity.y * self.settings.restitution;
+                }
+                let keep = 1.0 - self.settings.friction.clamp(0.0, 1.0);
+                self.velocity.x *= keep;
+                self.velocity.z *= keep;
+                self.angular_velocity *= keep;
+            }
+        }
+    }
+
+    pub(crate) fn shift_origin(&mut self, shift: &Vector3<f32>) {
+        self.position -= shift;
+        self.settings.shift_origin(shift);
+    }
+
+    // transform of the piece, shrinking as it fades
+    pub(crate) fn transform(&self) -> Transform {
+        Transform::from_translation(self.position)
+            .with_rotation(self.rotation)
+            .with_scale(Vector3::repeat(self.fade()))
+    }
+
+    // drawn with the mesh of the piece, after the scale & orientation of the destroyed entity
+    pub(crate) fn shape(&self) -> &Matrix4<f32> {
+        &self.shape
+    }
+}
+
+// xorshift32, same sequence for the same seed
+pub(crate) struct Random(u32);
+
+impl Random {
+    pub(crate) fn new(seed: u32) -> Self {
+        Self(seed.max(1)) // xorshift never leaves 0
+    }
+
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    // 0.0 ~ 1.0
+    fn unit(&mut self) -> f32 {
+        self.next() as f32 / u32::MAX as f32
+    }
+
+    // -1.0 ~ 1.0
+    fn signed(&mut self) -> f32 {
+        self.unit() * 2.0 - 1.0
+    }
+}
+
+fn bounds(vertices: &[Vertex]) -> Option<(Vector3<f32>, Vector3<f32>)> {
+    let first = Vector3::from(vertices.first()?.position);
+    Some(vertices.iter().fold((first, first), |(min, max), vertex| {
+        let position = Vector3::from(vertex.position);
+        (min.inf(&position), max.sup(&position))
+    }))
+}
+
+// the faces of a box, counter-clockwise seen from outside
+fn box_faces(min: Vector3<f32>, max: Vector3<f32>) -> Vec<Vec<Vector3<f32>>> {
+    let corner = |x: usize, y: usize, z: usize| Vector3::new([min.x, max.x][x], [min.y, max.y][y], [min.z, max.z][z]);
+    vec![
+        vec![corner(1, 0, 0), corner(1, 1, 0), corner(1, 1, 1), corner(1, 0, 1)],
+        vec![corner(0, 0, 0), corner(0, 0, 1), corner(0, 1, 1), corner(0, 1, 0)],
+        vec![corner(0, 1, 0), corner(0, 1, 1), corner(1, 1, 1), corner(1, 1, 0)],
+        vec![corner(0, 0, 0), corner(1, 0, 0), corner(1, 0, 1), corner(0, 0, 1)],
+        vec![corner(0, 0, 1), corner(1, 0, 1), corner(1, 1, 1), corner(0, 1, 1)],
+        vec![corner(0, 0, 0), corner(0, 1, 0), corner(1, 1, 0), corner(1, 0, 0)]
+    ]
+}
+
+// Cut the convex polyhedron `faces` by the plane `dot(normal, p) == w`, keeping what's behind it & closing the cut with a new face.
+fn clip_convex(faces: Vec<Vec<Vector3<f32>>>, normal: Vector3<f32>, w: f32) -> Vec<Vec<Vector3<f32>>> {
+    let mut clipped = Vec::with_capacity(faces.len() + 1);
+    let mut cut = Vec::new();
+    for face in faces {
+        let distances = face.iter().map(|point| normal.dot(point) - w).collect::<Vec<_>>();
+        let mut kept = Vec::with_capacity(face.len() + 1);
+        for i in 0..face.len() {
+            let j = (i + 1) % face.len();
+            if distances[i] <= EPSILON {
+                kept.push(face[i]);
+            }
+            if distances[i].abs() <= EPSILON {
+                cut.push(face[i]);
+            }
+            if (distances[i] < -EPSILON && distances[j] > EPSILON) || (distances[i] > EPSILON && distances[j] < -EPSILON) {
+                let point = face[i].lerp(&face[j], distances[i] / (distances[i] - distances[j]));
+                kept.push(point);
+                cut.push(point);
+            }
+        }
+        if kept.len() >= 3 {
+            clipped.push(kept);
+        }
+    }
+
+    // the cut points form a convex polygon on the plane, sorted around its center to face `normal`
+    cut.dedup_by(|a, b| (*a - *b).norm() <= EPSILON);
+    let mut cap: Vec<Vector3<f32>> = Vec::with_capacity(cut.len());
+    for point in cut {
+        if cap.iter().all(|other| (other - point).norm() > EPSILON) {
+            cap.push(point);
+        }
+    }
+    if cap.len() >= 3 {
+        let center = cap.iter().sum::<Vector3<f32>>() / cap.len() as f32;
+        let u = (cap[0] - center).try_normalize(f32::EPSILON).unwrap_or_else(|| normal.cross(&Vector3::x()).normalize());
+        let v = normal.cross(&u);
+        cap.sort_by(|a, b| {
+            let angle = |point: &Vector3<f32>| (point - center).dot(&v).atan2((point - center).dot(&u));
+            angle(a).total_cmp(&angle(b))
+        });
+        clipped.push(cap);
+    }
+    clipped
+}
+
+// flat shaded triangle fans of the faces
+fn triangulate(faces: &[Vec<Vector3<f32>>]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for face in faces {
+        // Newell's method, robust to nearly collinear first points
+        let mut normal = Vector3::zeros();
+        for i in 0..face.len() {
+            let (a, b) = (face[i], face[(i + 1) % face.len()]);
+            normal += Vector3::new((a.y - b.y) * (a.z + b.z), (a.z - b.z) * (a.x + b.x), (a.x - b.x) * (a.y + b.y));
+        }
+        let normal = match normal.try_normalize(f32::EPSILON) {
+            Some(normal) => normal,
+            None => continue
+        };
+        // planar mapping along the main axis of the face
+        let axis = normal.iamax();
+        let (u_axis, v_axis) = [(1, 2), (0, 2), (0, 1)][axis];
+
+        let first = vertices.len() as u32;
+        for point in face {
+            vertices.push(Vertex::new((*point).into(), [point[u_axis], point[v_axis]], normal.into()));
+        }
+        for i in 1..face.len() as u32 - 1 {
+            indices.extend_from_slice(&[first, first + i, first + i + 1]);
+        }
+    }
+    (vertices, indices)
+}
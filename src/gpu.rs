@@ -1,88 +1,103 @@
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
 use wgpu::util::DeviceExt; // for `create_buffer_init`
+use super::app_config::{AppConfig, Backend, DepthMode};
+use super::camera::{Camera, CameraRig};
+use super::capture::{CaptureConfig, FrameCapture, FrameStream};
+use super::debug_draw::{DebugDraw, DebugDrawCategory, DebugDrawSettings};
+#[cfg(feature = "egui")]
+use super::debug_ui::DebugUi;
+use super::environment::Environment;
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::grid::GridPass;
+use super::hot_reload::{enable_shader_hot_reload, load_shader, ShaderWatcher};
+use super::input::Input;
+use super::lens_flare::{LensFlare, LensFlarePass};
+use super::light::{Light, LightsUniform, OccludersUniform};
+use super::mesh::{MeshBuffers, MeshDraw, Vertex};
+use super::msaa::MsaaTargets;
+use super::pipeline_cache::{ScenePipelineKey, ScenePipelines};
+use super::material::{Material, MaterialBinding, ShaderMaterialBinding};
+use super::material_params::MaterialParams;
+use super::paint::PaintCanvas;
+use super::parallax::{ParallaxLayer, ParallaxPass};
+use super::pixel_perfect::PixelPerfectTargets;
+use super::polyline::{Polyline, PolylineRenderer};
+use super::post_process::{PostProcessPass, PostProcessStack};
+use super::profiler::{profile_scope, GpuPass, GpuTimer};
+use super::render_state::RenderState;
+use super::skybox::SkyboxPass;
+use super::sprite::{Sprite, SpriteBatch};
+use super::texture::Cubemap;
+use super::time::Time;
+use super::transition::{Transition, TransitionPass};
 use winit::{
-    event::{WindowEvent, KeyboardInput, VirtualKeyCode, ElementState},
+    event::{DeviceEvent, WindowEvent, VirtualKeyCode},
     window::Window
 };
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2] // color space depends on `surface.get_preferred_format()`, mostly sRGB
-}
-
-impl Vertex {
-    // get Vertex Layout
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            // stride defines how wide a vertex is.
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            // step mode tells the pipeline how often it should move to the next vertex.
-            step_mode: wgpu::VertexStepMode::Vertex,  // specify wgpu::VertexStepMode::Instance if we only want to change vertices when we start drawing a new instance.
-            // vertex attributes describe the individual parts of the vertex.
-            attributes: &[
-                // attribute: position
-                wgpu::VertexAttribute {
-                    // define the offset in bytes of this attribute startpoint.
-                    offset: 0, 
-                    // tell the shader what location store this attribute at.
-                    shader_location: 0, 
-                    // tell the shader the shape of the attribute.
-                    format: wgpu::VertexFormat::Float32x3
-                },
-                // attribute: texture coordinates
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x2
-                }
-            ]
-            // attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3]
-        }
-    }
-}
-
 // vertex attribute data for Vertex Buffer
 // tips: sRGB 0.2176 == RGB 0.5 (srgb_color = (rgb_color / 255) ^ 2.2)
+// tips: the pentagon is wound clockwise seen from +z, so it faces -z.
 const VERTICES: &[Vertex] = &[
     Vertex { 
         position: [-0.0868241, -0.49240386, 0.0],
-        tex_coords: [1.0 - 0.4131759, 1.0 - 0.00759614], 
+        tex_coords: [1.0 - 0.4131759, 1.0 - 0.00759614],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, -1.0]
     }, // A
     Vertex { 
         position: [-0.49513406, -0.06958647, 0.0],
-        tex_coords: [1.0 - 0.0048659444, 1.0 - 0.43041354]
+        tex_coords: [1.0 - 0.0048659444, 1.0 - 0.43041354],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, -1.0]
     }, // B
     Vertex { 
         position: [-0.21918549, 0.44939706, 0.0],
-        tex_coords: [1.0 - 0.28081453, 1.0 - 0.949397] 
+        tex_coords: [1.0 - 0.28081453, 1.0 - 0.949397],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, -1.0]
     }, // C
     Vertex { 
         position: [0.35966998, 0.3473291, 0.0],
-        tex_coords: [1.0 - 0.85967, 1.0 - 0.84732914] 
+        tex_coords: [1.0 - 0.85967, 1.0 - 0.84732914],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, -1.0]
     }, // D
     Vertex { 
         position: [0.44147372, -0.2347359, 0.0],
-        tex_coords: [1.0 - 0.9414737, 1.0 - 0.2652641] 
+        tex_coords: [1.0 - 0.9414737, 1.0 - 0.2652641],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, -1.0]
     }, // E
 ];
 
 const DEPTH_VERTICES: &[Vertex] = &[
     Vertex { 
         position: [0.0, 0.0, 0.0], 
-        tex_coords: [0.0, 1.0], 
+        tex_coords: [0.0, 1.0],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, 1.0]
     }, // A
     Vertex {
         position: [1.0, 0.0, 0.0], 
-        tex_coords: [1.0, 1.0] 
+        tex_coords: [1.0, 1.0],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, 1.0]
     }, // B
     Vertex { 
         position: [1.0, 1.0, 0.0], 
-        tex_coords: [1.0, 0.0] 
+        tex_coords: [1.0, 0.0],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, 1.0]
     }, // C
     Vertex { 
         position: [0.0, 1.0, 0.0], 
-        tex_coords: [0.0, 0.0] 
+        tex_coords: [0.0, 0.0],
+        color: Vertex::WHITE,
+        normal: [0.0, 0.0, 1.0]
     }, // D
 ];
 
@@ -101,166 +116,30 @@ const DEPTH_INDICES: &[u16] = &[
     0, 2, 3,
 ];
 
-// The coordinate system in Wgpu is based on DirectX, and Metal's coordinate systems.
-// So that in normalized device coordinates the z axis is 0.0 to +1.0.
-// But nalgebra crate are built for OpenGL's coordinate system whose z axis is -1.0 to +1.0.
-// Thus, we need to translate matrix from OpenGL to Wgpu coord system.
-// ref: https://github.com/gfx-rs/gfx/tree/master/src/backend/dx12
-// ref: https://github.com/gfx-rs/gfx/tree/master/src/backend/gl
-#[rustfmt::skip]
-pub const OPENGL_TO_WGPU_MATRIX: nalgebra::Matrix4<f32> = nalgebra::Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.0,
-    0.0, 0.0, 0.5, 1.0,
-);
 
 // We need this for Rust to store our data correctly for the shaders
 #[repr(C)]
 // This is so we can store this in a buffer
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct CameraUniform {
+pub(crate) struct CameraUniform {
     // We can't use nalgebra Matrix4 with bytemuck directly so we'll have
     // to convert the Matrix4 into a 4x4 f32 array
     view_proj_matrix: [[f32; 4]; 4],
+    // camera position, e.g. for distance fog (w unused)
+    view_position: [f32; 4]
 }
 
 impl CameraUniform {
-    fn new() -> Self {
-        Self {
-            view_proj_matrix: nalgebra::Matrix4::identity().into()
-        }
-    }
-
-    fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj_matrix = camera.build_view_projection_matrix().into()
-    }
-}
-
-struct CameraController {
-    speed: f32,
-    is_up_pressed: bool,
-    is_down_pressed: bool,
-    is_forward_pressed: bool,
-    is_backward_pressed: bool,
-    is_left_pressed: bool,
-    is_right_pressed: bool,
-}
-
-impl CameraController {
-    fn new(speed: f32) -> Self {
+    pub(crate) fn new() -> Self {
         Self {
-            speed,
-            is_up_pressed: false,
-            is_down_pressed: false,
-            is_forward_pressed: false,
-            is_backward_pressed: false,
-            is_left_pressed: false,
-            is_right_pressed: false,
+            view_proj_matrix: nalgebra::Matrix4::identity().into(),
+            view_position: [0.0; 4]
         }
     }
 
-    fn process_events(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput {
-                input: KeyboardInput {
-                    state,
-                    virtual_keycode: Some(keycode),
-                    ..
-                },
-                ..
-            } => {
-                let is_pressed = *state == ElementState::Pressed;
-                match keycode {
-                    VirtualKeyCode::J => {
-                        self.is_up_pressed = is_pressed;
-                        true
-                    },
-                    VirtualKeyCode::K => {
-                        self.is_down_pressed = is_pressed;
-                        true
-                    },
-                    VirtualKeyCode::W | VirtualKeyCode::Up => {
-                        self.is_forward_pressed = is_pressed;
-                        true
-                    },
-                    VirtualKeyCode::S | VirtualKeyCode::Down => {
-                        self.is_backward_pressed = is_pressed;
-                        true
-                    },
-                    VirtualKeyCode::A | VirtualKeyCode::Left => {
-                        self.is_left_pressed = is_pressed;
-                        true
-                    },
-                    VirtualKeyCode::D | VirtualKeyCode::Right => {
-                        self.is_right_pressed = is_pressed;
-                        true
-                    },
-                    _ => false,
-                }
-            },
-            _ => false,
-        }
-    }
-
-    fn update_camera(&self, camera: &mut Camera) {
-        let forward = camera.target - camera.eye;
-        let forward_norm = forward.normalize();
-        let forward_mag = forward.magnitude();
-
-        if self.is_forward_pressed && forward_mag > self.speed {
-            camera.eye += forward_norm * self.speed;
-        }
-        if self.is_backward_pressed {
-            camera.eye -= forward_norm * self.speed;
-        }
-
-        // new forward direction
-        let forward = camera.target - camera.eye;
-        let forward_mag = forward.magnitude();
-
-        let left = forward_norm.cross(&camera.up);
-        let right = -left;
-
-        // right/left move is rotation around the "target"
-        if self.is_right_pressed {
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
-        }
-        if self.is_left_pressed {
-            camera.eye = camera.target - (forward +  left * self.speed).normalize() * forward_mag;
-        }
-
-        // up/down move is translation toward/backward "up"
-        if self.is_up_pressed {
-            camera.eye += camera.up * self.speed;
-        }
-        if self.is_down_pressed {
-            camera.eye -= camera.up * self.speed;
-        }
-    }
-}
-
-struct Camera {
-    eye: nalgebra::Point3<f32>,
-    target: nalgebra::Point3<f32>,
-    up: nalgebra::Vector3<f32>,
-    aspect: f32,
-    fovy: f32,
-    znear: f32,
-    zfar: f32
-}
-
-impl Camera {
-    // ref: https://nalgebra.org/docs/user_guide/cg_recipes/#build-a-mvp-matrix
-    fn build_view_projection_matrix(&self) -> nalgebra::Matrix4<f32> {
-        // view tranform matrix (right-handed)
-        // right-handed: camera always look at -z after transform
-        // left-handed:  camera always look at +z after transform
-        let view = nalgebra::Matrix4::look_at_rh(&self.eye, &self.target, &self.up);
-        // projection tranform matrix
-        let proj = nalgebra::Perspective3::new(self.aspect, self.fovy, self.znear, self.zfar);
-
-        return OPENGL_TO_WGPU_MATRIX * proj.as_matrix() * view;
+    pub(crate) fn update_view_proj(&mut self, camera: &Camera) {
+        self.view_proj_matrix = camera.view_projection_matrix().into();
+        self.view_position = camera.eye.to_homogeneous().into();
     }
 }
 
@@ -288,12 +167,12 @@ impl Instance {
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct InstanceRaw {
-    model: [[f32; 4]; 4]
+pub(crate) struct InstanceRaw {
+    pub(crate) model: [[f32; 4]; 4]
 }
 
 impl InstanceRaw {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    pub(crate) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
 
         wgpu::VertexBufferLayout {
@@ -333,19 +212,28 @@ impl InstanceRaw {
 
 struct DepthPass {
     texture: super::texture::Texture,
+    depth_mode: DepthMode,
+    // depth of the far plane, the reference of the depth comparison
+    uniform_buffer: wgpu::Buffer,
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     indices_num: u32,
-    render_pipeline: wgpu::RenderPipeline
+    render_pipeline: Option<wgpu::RenderPipeline> // None if the pipeline failed to build
 }
 
 // Render Depth Buffer to Screen
 impl DepthPass {
-    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, depth_mode: DepthMode, error_overlay: &mut ErrorOverlay) -> Self {
         // Create Depth Texture
-        let depth_texture = super::texture::Texture::create_depth_texture(device, config, "Depth Texture");
+        let depth_texture = super::texture::Texture::create_depth_texture(device, config, depth_mode, "Depth Texture");
+        // uniform buffers are laid out in 16 bytes blocks
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Depth Pass Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[depth_mode.far_depth(), 0.0, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
 
         // Bind Group
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -366,6 +254,16 @@ impl DepthPass {
                     count: None,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
                     visibility: wgpu::ShaderStages::FRAGMENT
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT
                 }
             ]
         });
@@ -380,6 +278,10 @@ impl DepthPass {
                 wgpu::BindGroupEntry {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&depth_texture.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding()
                 }
             ]
         });
@@ -404,60 +306,68 @@ impl DepthPass {
             push_constant_ranges: &[]
         });
 
-        let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Depth Buffer Shadow Display Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("res/shaders/depth_buffer.wgsl").into())
-        });
-        let vertex_shader_ref = &shader_module;
-        let fragment_shader_ref = &shader_module;
-        let vertex_entry = "vs_main";
-        let fragment_entry = "fs_main";
-        // let vertex_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/depth_buffer.vert.spv"));
-        // let fragment_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/depth_buffer.frag.spv"));
-        // let vertex_shader_ref = &vertex_shader_module;
-        // let fragment_shader_ref = &fragment_shader_module;
-        // let vertex_entry = "main";
-        // let fragment_entry = "main";
+        let render_pipeline = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Depth Buffer Shadow Display Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("depth_buffer.wgsl", include_str!("res/shaders/depth_buffer.wgsl")))
+            });
+            let vertex_shader_ref = &shader_module;
+            let fragment_shader_ref = &shader_module;
+            let vertex_entry = "vs_main";
+            let fragment_entry = "fs_main";
+            // let vertex_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/depth_buffer.vert.spv"));
+            // let fragment_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/depth_buffer.frag.spv"));
+            // let vertex_shader_ref = &vertex_shader_module;
+            // let fragment_shader_ref = &fragment_shader_module;
+            // let vertex_entry = "main";
+            // let fragment_entry = "main";
         
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Depth Pass Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: vertex_shader_ref,
-                entry_point: vertex_entry,
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: fragment_shader_ref,
-                entry_point: fragment_entry,
-                targets: &[
-                    wgpu::ColorTargetState { 
-                        format: config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL
-                    }
-                ]
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None, 
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None 
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Depth Pass Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: vertex_shader_ref,
+                    entry_point: vertex_entry,
+                    buffers: &[Vertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: fragment_shader_ref,
+                    entry_point: fragment_entry,
+                    targets: &[
+                        wgpu::ColorTargetState { 
+                            format: config.format,
+                            blend: Some(wgpu::BlendState::REPLACE),
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None, 
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None 
+            })
         });
+        // keep going without the Depth Pass if its shader is broken
+        let render_pipeline = render_pipeline
+            .map_err(|error| error_overlay.report("Depth Pass Render Pipeline", &error))
+            .ok();
 
         Self {
             texture: depth_texture,
+            depth_mode,
+            uniform_buffer,
             bind_group_layout,
             bind_group,
             vertex_buffer,
@@ -470,7 +380,7 @@ impl DepthPass {
     fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
         // recreate Depth Texture
         // If you don't, your program will crash as the depth_texture will be a different size than the surface texture.
-        self.texture = super::texture::Texture::create_depth_texture(&device, &config, "Depth Texture");
+        self.texture = super::texture::Texture::create_depth_texture(&device, &config, self.depth_mode, "Depth Texture");
 
         // rebind Depth Texture
         self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -484,12 +394,21 @@ impl DepthPass {
                 wgpu::BindGroupEntry {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&self.texture.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding()
                 }
             ]
         });
     }
 
     fn render(&self, texture_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) {
+        let render_pipeline = match &self.render_pipeline {
+            Some(render_pipeline) => render_pipeline,
+            None => return
+        };
+
         let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[wgpu::RenderPassColorAttachment {
@@ -503,7 +422,7 @@ impl DepthPass {
             depth_stencil_attachment: None,
         });
 
-        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_pipeline(render_pipeline);
         render_pass.set_bind_group(0, &self.bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
@@ -511,41 +430,224 @@ impl DepthPass {
     }
 }
 
+// the bind group a mesh is drawn with this frame
+enum PreparedMaterial {
+    // without a material: the picked one
+    Picked,
+    Standard(Rc<MaterialBinding>),
+    // custom material, with the key of its shader & the dynamic offset of the instance's parameters
+    Shader(u64, Rc<ShaderMaterialBinding>, Option<u32>),
+    // failed to upload, not drawn
+    Unavailable
+}
+
+// a mesh drawn this frame: its buffers, material & render state (None for the picked one)
+struct PreparedMeshDraw {
+    mesh_buffers: Rc<MeshBuffers>,
+    material: PreparedMaterial,
+    render_state: Option<RenderState>
+}
+
 pub(crate) struct GPUState {
     surface: wgpu::Surface,
-    device: wgpu::Device,
+    device: Arc<wgpu::Device>,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     pub(crate) size: winit::dpi::PhysicalSize<u32>,
     clear_color: wgpu::Color,
-    render_pipeline: wgpu::RenderPipeline,
+    scene_pipelines: ScenePipelines,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     indices_num: u32,
-    camera: Camera,
-    camera_controller: CameraController,
+    camera_rig: CameraRig,
     camera_uniform: CameraUniform,
     camera_uniform_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
-    #[allow(dead_code)]
-    diffuse_texture: super::texture::Texture,
-    diffuse_bind_group: wgpu::BindGroup,
-    #[allow(dead_code)]
-    cartoon_texture: super::texture::Texture,
-    cartoon_bind_group: wgpu::BindGroup,
+    environment_uniform_buffer: wgpu::Buffer,
+    lights_uniform_buffer: wgpu::Buffer,
+    occluders_uniform_buffer: wgpu::Buffer,
+    environment_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout, // shared by the bind groups of every material
+    diffuse_material: Rc<Material>,
+    diffuse_canvas: PaintCanvas, // CPU side copy of the diffuse material's texture, painted by the application
+    cartoon_material: Rc<Material>,
     depth_pass: DepthPass,
+    msaa: Option<MsaaTargets>, // None without MSAA, the scene is rendered into the HDR target & the depth texture directly
+    sample_count: u32, // of the scene's color & depth targets
+    pixel_perfect_targets: Option<PixelPerfectTargets>, // the scene is rendered into these in pixel-perfect mode
+    grid_pass: GridPass,
+    polyline_renderer: PolylineRenderer,
+    lens_flare_pass: LensFlarePass,
+    post_process_pass: PostProcessPass, // the scene is rendered into its HDR target
+    transition_pass: TransitionPass,
+    parallax_pass: ParallaxPass,
+    skybox_pass: SkyboxPass,
+    sprite_batch: SpriteBatch,
+    frame_capture: FrameCapture,
+    // None if the adapter can't time the frames
+    gpu_timer: Option<GpuTimer>,
+    // window frame acquired ahead of the update with late latching, see `AppConfig::late_latch`
+    acquired_frame: Option<wgpu::SurfaceTexture>,
+    #[cfg(feature = "egui")]
+    debug_ui: DebugUi,
+    debug_draw: DebugDraw,
+    error_overlay: ErrorOverlay,
+    shader_watcher: Option<ShaderWatcher>, // None without shader hot-reloading
     instances: Vec<Instance>,
     instance_buffer: wgpu::Buffer,
-    is_space_pressed: bool,
-    is_enter_pressed: bool
+    mesh_draws: Vec<PreparedMeshDraw>, // meshes of this frame, their transforms are in `mesh_instance_buffer` in the same order
+    mesh_instance_buffer: wgpu::Buffer,
+    mesh_instance_capacity: usize,
+    cartoon_material_picked: bool, // while Space is held
+    elapsed: f32, // seconds since the first frame, drives UV scrolling
+    depth_view_visible: bool // while Enter is held
+}
+
+// the window's configuration with the format of the HDR target the scene is rendered into
+fn scene_config(config: &wgpu::SurfaceConfiguration) -> wgpu::SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        format: PostProcessPass::HDR_FORMAT,
+        ..config.clone()
+    }
+}
+
+// bind group layout of the materials (group 0 of the scene pipelines), see `MaterialBinding`
+pub(crate) fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture bind group layout"),
+            entries: &[
+                // entry for a sampled texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    // shader visibility
+                    visibility: wgpu::ShaderStages::FRAGMENT, // visible only to the fragment shader
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // entry for a sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // entry for the UV transform uniform
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX, // texture coordinates are transformed in the vertex shader
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // entry for the alpha mode uniform (alpha cutoff of masked materials)
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // entry for the normal map, sampled with the albedo's sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                }
+            ]
+        }
+    )
+}
+
+// bind group layout of the camera uniform (group 1 of the scene pipelines)
+pub(crate) fn create_camera_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+            label: Some("camera bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT, // the camera position is used by the fog
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        // whether this buffer will change size or not,
+                        // This is useful if we want to store an array of things in our uniforms.
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ]
+        }
+    )
+}
+
+// bind group layout of the environment, lights & occluders uniforms (group 2 of the scene pipelines)
+pub(crate) fn create_environment_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+            label: Some("environment bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ]
+        }
+    )
 }
 
 // ref: https://sotrh.github.io/learn-wgpu/beginner/tutorial2-surface/
 impl GPUState {
     // Init, move Window Controlling power
     // tips: Creating some of the wgpu types requires async code
-    pub(crate) async fn new(window: &Window) -> Self {
+    pub(crate) async fn new(window: &Window, app_config: &AppConfig) -> Self {
         /* Chore States */
+        // the shaders are read from disk from now on
+        if app_config.shader_hot_reload {
+            enable_shader_hot_reload();
+        }
         let size = window.inner_size(); // Get the size of the Window (excluding the title bar and borders)
         let clear_color = wgpu::Color { // default clear color
             r: 0.1,
@@ -556,33 +658,47 @@ impl GPUState {
 
         /* Instace */
         // Create wgpu Instace, whose is a handle to our GPU to create Adapter(s) and Surface(s)
-        let instance = wgpu::Instance::new(wgpu::Backends::all()); // Backens:all => Vulkan + Metal + DX12 + Browser WebGPU
+        let mut instance = wgpu::Instance::new(app_config.backend.to_wgpu()); // Backend::Auto => Vulkan + Metal + DX12 + Browser WebGPU
 
         /* Surface */
         // Create wgpu Surface by winit Window, which is the part of the window that we can draw to.
-        let surface = unsafe { instance.create_surface(&window) };
+        let mut surface = unsafe { instance.create_surface(&window) };
 
         /* Adapter */
         // Create wgpu Adapter, which is a handle to our actual grahics card.
         // You can use this to get information about the graphics card
-        let adapter = instance.request_adapter(
-            &wgpu::RequestAdapterOptions {
+        fn adapter_options(surface: &wgpu::Surface) -> wgpu::RequestAdapterOptions<'_> {
+            wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(), // LowPower or HighPerformance
-                compatible_surface: Some(&surface), // tells wgpu to find an adapter that can present to the supplied surface.
+                compatible_surface: Some(surface), // tells wgpu to find an adapter that can present to the supplied surface.
                 force_fallback_adapter: false, // whether forces wgpu to pick an adapter that will work on all hardware.
             }
-        ).await.expect("No backends support current surface!");
+        }
+        let mut adapter = instance.request_adapter(&adapter_options(&surface)).await;
+        // the preferred backend is only a preference
+        if adapter.is_none() && app_config.backend != Backend::Auto {
+            eprintln!("no {:?} adapter can present to the window, trying the other backends", app_config.backend);
+            instance = wgpu::Instance::new(wgpu::Backends::all());
+            surface = unsafe { instance.create_surface(&window) };
+            adapter = instance.request_adapter(&adapter_options(&surface)).await;
+        }
+        let adapter = adapter.expect("No backends support current surface!");
         
         /* Device & Queue */
         // Create Device & (GPU's Render) Queue by Adapter
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::SPIRV_SHADER_PASSTHROUGH, // allows us to specify extra features. https://docs.rs/wgpu/0.12.0/wgpu/struct.Features.html
+                // allows us to specify extra features. https://docs.rs/wgpu/0.12.0/wgpu/struct.Features.html
+                // timestamp queries time the frames on the GPU, where the adapter supports them
+                features: wgpu::Features::SPIRV_SHADER_PASSTHROUGH | (adapter.features() & wgpu::Features::TIMESTAMP_QUERY),
                 limits: wgpu::Limits::default(), // describes the limit of certain types of resources that we can create. https://docs.rs/wgpu/0.12.0/wgpu/struct.Limits.html
                 label: None
             }, 
             None    
         ).await.unwrap();
+        // shared with the thread compiling the scene pipelines
+        let device = Arc::new(device);
+        let gpu_timer = GpuTimer::new(&device, &queue);
         
         /* Surface Configure */
         // This will define how the surface creates its underlying SurfaceTextures.
@@ -601,101 +717,40 @@ impl GPUState {
             // * Fifo
             // * VSync
             // https://docs.rs/wgpu/0.12.0/wgpu/enum.PresentMode.html
-            present_mode: wgpu::PresentMode::Fifo
+            present_mode: app_config.present_mode.to_wgpu()
         };
         surface.configure(&device, &config);
 
         /* Texture */
         let diffuse_bytes = include_bytes!("res/textures/happy-tree.png");
-        let diffuse_texture = super::texture::Texture::from_bytes(&device, &queue, diffuse_bytes, Some("happy tree texture")).unwrap();
+        let diffuse_image = image::load_from_memory(diffuse_bytes).unwrap().to_rgba8();
+        let diffuse_canvas = PaintCanvas::from_image(&diffuse_image);
 
         // Create "BindGroup Layout": the layout of "BindGroup"
-        let texture_bind_group_layout = device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
-                label: Some("texture bind group layout"),
-                entries: &[
-                    // entry for a sampled texture
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        // shader visibility
-                        visibility: wgpu::ShaderStages::FRAGMENT, // visible only to the fragment shader
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    // entry for a sampler
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    }
-                ]
-            }
-        );
-        // Create "BindGroup" to bind texture: describes a set of resources and how they can be accessed by a shader
-        // each texutre and sampler we create will need to be added to a "BindGroup"
-        // BindGroup is a more specific declaration of the BindGroupLayout. 
-        // The reason they're separate is that it allows us to swap out BindGroups on the fly, so long as they all share the same BindGroupLayout. 
-        let diffuse_bind_group = device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                label: Some("diffuse texture bind group"),
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                    }
-                ]
-            }
-        );
+        let texture_bind_group_layout = create_texture_bind_group_layout(&device);
 
+        /* Materials */
+        // the diffuse material: default back-face culling
+        let diffuse_material = Rc::new(Material::new("happy tree").with_albedo(diffuse_image));
         let cartoon_bytes = include_bytes!("res/textures/happy-tree-cartoon.png");
-        let cartoon_texture = super::texture::Texture::from_bytes(&device, &queue, cartoon_bytes, Some("happy tree cartoon texture")).unwrap();
-
-        // Create "BindGroup" to bind texture: describes a set of resources and how they can be accessed by a shader
-        // each texutre and sampler we create will need to be added to a "BindGroup"
-        // BindGroup is a more specific declaration of the BindGroupLayout. 
-        // The reason they're separate is that it allows us to swap out BindGroups on the fly, so long as they all share the same BindGroupLayout. 
-        let cartoon_bind_group = device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                label: Some("cartoon texture bind group"),
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&cartoon_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&cartoon_texture.sampler),
-                    }
-                ]
-            }
+        let cartoon_image = image::load_from_memory(cartoon_bytes).unwrap().to_rgba8();
+        // the cartoon tree is a flat card, so keep it visible from behind while the instances rotate.
+        let cartoon_material = Rc::new(
+            Material::new("happy tree cartoon")
+                .with_albedo(cartoon_image)
+                .with_render_state(RenderState::two_sided())
         );
+        // upload them now rather than on the first frame
+        diffuse_material.binding(&device, &queue, &texture_bind_group_layout);
+        cartoon_material.binding(&device, &queue, &texture_bind_group_layout);
 
         /* Camera */
-        let camera = Camera {
-            eye: [0.0, 0.0, 2.0].into(), // position of the camera
-            target: [0.0, 0.0, 0.0].into(), // look at which point
-            up: nalgebra::Vector3::y(), // direction of "up"
-            aspect: config.width as f32 / config.height as f32,
-            fovy: 45.0,
-            znear: 0.1,
-            zfar: 100.0,
-        };
-        let camera_controller = CameraController::new(0.1);
+        // looking at the origin, moved by the orbit controller until the application replaces it
+        let camera_rig = CameraRig::new(config.width, config.height, app_config.depth_mode);
 
         /* Uniform Buffer */
         let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_proj(&camera);
+        camera_uniform.update_view_proj(camera_rig.camera());
 
         // Create Uniform Buffer for camera
         let camera_uniform_buffer = device.create_buffer_init(
@@ -706,33 +761,62 @@ impl GPUState {
             }
         );
         // Create BindGroup of Uniform Buffer
-        let camera_bind_group_layout = device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
-                label: Some("camera bind group layout"),
+        let camera_bind_group_layout = create_camera_bind_group_layout(&device);
+        let camera_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("camera bind group"),
+                layout: &camera_bind_group_layout,
                 entries: &[
-                    wgpu::BindGroupLayoutEntry {
+                    wgpu::BindGroupEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            // whether this buffer will change size or not,
-                            // This is useful if we want to store an array of things in our uniforms.
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
+                        resource: camera_uniform_buffer.as_entire_binding(),
                     },
                 ]
             }
         );
-        let camera_bind_group = device.create_bind_group(
+
+        /* Environment */
+        // sun, ambient & fog settings, updated every frame from `Application::environment()`
+        let environment_uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Environment Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[Environment::default().to_uniform()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+        // the lights of the scene, updated every frame from the entities with a `Light`
+        let lights_uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Lights Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[LightsUniform::new(&[], 1.0)]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+        // the edges of the scene's `LightOccluder`s, shadowing the lights in 2D scenes
+        let occluders_uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Occluders Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[OccludersUniform::new(&[])]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+        let environment_bind_group_layout = create_environment_bind_group_layout(&device);
+        let environment_bind_group = device.create_bind_group(
             &wgpu::BindGroupDescriptor {
-                label: Some("camera bind group"),
-                layout: &camera_bind_group_layout,
+                label: Some("environment bind group"),
+                layout: &environment_bind_group_layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: camera_uniform_buffer.as_entire_binding(),
+                        resource: environment_uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: lights_uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: occluders_uniform_buffer.as_entire_binding(),
                     },
                 ]
             }
@@ -764,6 +848,10 @@ impl GPUState {
             }
         );
 
+        // transforms of the application meshes, grown when needed
+        let mesh_instance_capacity = 64;
+        let mesh_instance_buffer = Self::create_mesh_instance_buffer(&device, mesh_instance_capacity);
+
         /* Pipeline */ 
         // Create "Pipeline Layout"
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -772,98 +860,27 @@ impl GPUState {
             bind_group_layouts: &[
                 &texture_bind_group_layout,
                 &camera_bind_group_layout,
+                &environment_bind_group_layout,
             ],
             push_constant_ranges: &[]
         });
 
-        // Load "Shaders" (WGSL)
-        let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("res/shaders/shader.wgsl").into())
-        });
-        let vertex_shader_ref = &shader_module;
-        let fragment_shader_ref = &shader_module;
-        let vertex_entry = "vs_main";
-        let fragment_entry = "fs_main";
-        // Load "Shaders" (GLSL/HLSL)
-        // let vertex_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/shader.vert.spv"));
-        // let fragment_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/shader.frag.spv"));
-        // let vertex_shader_ref = &vertex_shader_module;
-        // let fragment_shader_ref = &fragment_shader_module;
-        // let vertex_entry = "main";
-        // let fragment_entry = "main";
-        
-        // Create "Render Pipeline"
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            // setup Pipeline Layout
-            layout: Some(&render_pipeline_layout),
-            // setup Vertex Shader
-            vertex: wgpu::VertexState {
-                // specify the shader module
-                module: vertex_shader_ref,
-                // specify the entry point of vertex shader in shader file
-                entry_point: vertex_entry,
-                // layout of the vertices which we want to pass to the vertex shader
-                buffers: &[ 
-                    Vertex::desc(),
-                    InstanceRaw::desc(),
-                ],
-            },
-            // setup Fragment Shader
-            // this is technically optional, so you have to wrap it in Some(). 
-            // We need it if we want to store color data to the surface.
-            fragment: Some(wgpu::FragmentState {
-                module: fragment_shader_ref,
-                // specify the entry point of fragment shader in shader file
-                entry_point: fragment_entry,
-                // tells wgpu what color outputs it should set up.
-                // Currently, we only need one for the "Surface"
-                targets: &[
-                    wgpu::ColorTargetState { 
-                        format: config.format,
-                        blend: Some(wgpu::BlendState::REPLACE), // REPLACE : replace old pixel data with new data
-                        write_mask: wgpu::ColorWrites::ALL // ALL: write all color channels (R G B A)
-                    }
-                ]
-            }),
-            // describes how to interpret our vertices when converting them into triangles.
-            // == OpenGL Vertex Buffer Layout
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // TriangleList: each three vertices will correspond to one triangle.
-                strip_index_format: None,
-                // `front_face` & `cull_mode`: how to determine whether a given triangle is facing forward or not.
-                front_face: wgpu::FrontFace::Ccw, // Ccw: triangle is facing forward if the vertices are arranged in a counter-clockwise direction.
-                cull_mode: Some(wgpu::Face::Back), // Back: triangles that are not facing forward are culled (not included in the render)
-                // tips: Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // tips: Enable requires Features::DEPTH_CLAMPING
-                unclipped_depth: false,
-                // tips: Enable requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            // using a depth/stencil buffer
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: super::texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                // how to compare depth values in the depth test.
-                depth_compare: wgpu::CompareFunction::Less, // pixels will be drawn front to back.
-                // here's another type of buffer called a stencil buffer. 
-                // It's common practice to store the stencil buffer and depth buffer in the same texture.
-                stencil: wgpu::StencilState::default(), // we aren't using stencil buffer, so set default value.
-                bias: wgpu::DepthBiasState::default()
-            }), 
-            multisample: wgpu::MultisampleState {
-                // how many samples the pipeline will use
-                count: 1,
-                // which samples should be active
-                mask: !0, // !0 means using all of them
-                alpha_to_coverage_enabled: false,
-            },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None
-        });
+        /* Error Overlay */
+        // shown on top of the frame when a shader or pipeline fails to build
+        let mut error_overlay = ErrorOverlay::new(&device, &config);
+
+        /* Post Processing */
+        // the scene (meshes, grid & polylines) is rendered into an HDR target, see `Application::post_process()`
+        let post_process_pass = PostProcessPass::new(&device, &config, &mut error_overlay);
+        let scene_config = scene_config(&config);
+
+        // how many samples per pixel the scene is rendered with
+        let sample_count = app_config.sample_count(&adapter);
+        let msaa = if sample_count > 1 {
+            Some(MsaaTargets::new(&device, &scene_config, sample_count, &mut error_overlay))
+        } else {
+            None
+        };
 
         /* Vertex Buffer */
         let vertex_buffer = device.create_buffer_init(
@@ -885,7 +902,47 @@ impl GPUState {
         let indices_num = INDICES.len() as u32;
 
         /* Depth Buffer Rendering Pass */
-        let depth_pass = DepthPass::new(&device, &config);
+        let depth_pass = DepthPass::new(&device, &config, app_config.depth_mode, &mut error_overlay);
+
+        /* Ground Grid */
+        // toggled with the G key
+        let grid_pass = GridPass::new(&device, &scene_config, sample_count, app_config.depth_mode, &mut error_overlay);
+
+        /* Polylines */
+        // thick lines drawn over the scene, see `Application::draw_polylines()`
+        let polyline_renderer = PolylineRenderer::new(&device, &scene_config, sample_count, app_config.depth_mode, &mut error_overlay);
+
+        /* Lens Flare */
+        // see `Application::lens_flare()`
+        let lens_flare_pass = LensFlarePass::new(&device, &config, &depth_pass.texture.view, app_config.depth_mode, &mut error_overlay);
+
+        /* Transition */
+        // see `Application::transition()`
+        let transition_pass = TransitionPass::new(&device, &config, &mut error_overlay);
+        let parallax_pass = ParallaxPass::new(&device, &scene_config, sample_count, &texture_bind_group_layout, &mut error_overlay);
+
+        /* Skybox */
+        // see `Application::skybox()`, drawn instead of clearing the scene
+        let skybox_pass = SkyboxPass::new(&device, &scene_config, sample_count, app_config.depth_mode, &mut error_overlay);
+
+        /* Sprites */
+        // the scene's `Sprite` entities, batched by material & drawn in the scene pass
+        let sprite_batch = SpriteBatch::new(&device);
+
+        /* Frame Capture */
+        // F12: screenshot, F9: clip recording on/off, F10: save the last seconds as a clip
+        let frame_capture = FrameCapture::new(&device, &config, &mut error_overlay);
+
+        /* Debug UI */
+        // see `Application::ui()`
+        #[cfg(feature = "egui")]
+        let debug_ui = DebugUi::new(&device, &config, window.scale_factor(), &mut error_overlay);
+
+        /* Scene Render Pipelines */
+        // compiled in the background, started last: see `ScenePipelines`
+        let mut scene_pipelines = ScenePipelines::new(device.clone(), render_pipeline_layout, scene_config.format, sample_count, app_config.depth_mode);
+        scene_pipelines.request(diffuse_material.render_state());
+        scene_pipelines.request(cartoon_material.render_state());
 
         Self {
             surface,
@@ -894,27 +951,62 @@ impl GPUState {
             config,
             size,
             clear_color,
-            render_pipeline,
+            scene_pipelines,
             vertex_buffer,
             index_buffer,
             indices_num,
-            camera,
-            camera_controller,
+            camera_rig,
             camera_uniform,
             camera_uniform_buffer,
             camera_bind_group,
-            diffuse_texture,
-            diffuse_bind_group,
-            cartoon_texture,
-            cartoon_bind_group,
+            environment_uniform_buffer,
+            lights_uniform_buffer,
+            occluders_uniform_buffer,
+            environment_bind_group,
+            texture_bind_group_layout,
+            diffuse_material,
+            diffuse_canvas,
+            cartoon_material,
             depth_pass,
+            msaa,
+            sample_count,
+            pixel_perfect_targets: None,
+            grid_pass,
+            polyline_renderer,
+            lens_flare_pass,
+            post_process_pass,
+            transition_pass,
+            parallax_pass,
+            skybox_pass,
+            sprite_batch,
+            frame_capture,
+            gpu_timer,
+            acquired_frame: None,
+            #[cfg(feature = "egui")]
+            debug_ui,
+            debug_draw: DebugDraw::new(),
+            error_overlay,
+            shader_watcher: app_config.shader_hot_reload.then(ShaderWatcher::new),
             instances,
             instance_buffer,
-            is_space_pressed: false,
-            is_enter_pressed: false
+            mesh_draws: Vec::new(),
+            mesh_instance_buffer,
+            mesh_instance_capacity,
+            cartoon_material_picked: false,
+            elapsed: 0.0,
+            depth_view_visible: false,
         }
     }
 
+    fn create_mesh_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Mesh Instance (Vertex) Buffer"),
+            size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        })
+    }
+
     // resize Window
     pub(crate) fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         // size 0 will cause your app to crash!
@@ -923,62 +1015,206 @@ impl GPUState {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
 
+            // a frame of the previous size can't be presented
+            self.acquired_frame = None;
             self.surface.configure(&self.device, &self.config);
             
             // resize Depth Pass
             self.depth_pass.resize(&self.device, &self.config);
+            if let Some(msaa) = &mut self.msaa {
+                msaa.resize(&self.device, &scene_config(&self.config));
+            }
+            self.post_process_pass.resize(&self.device, &self.config);
+            self.lens_flare_pass.resize(&self.device, &self.depth_pass.texture.view);
+            self.frame_capture.resize(new_size.width, new_size.height);
+            self.camera_rig.resize(new_size.width, new_size.height);
+            #[cfg(feature = "egui")]
+            self.debug_ui.resize((new_size.width, new_size.height));
         }
     }
 
+    // raw device input (mouse motion...), returns true if it's used by the camera controller
+    pub(crate) fn device_input(&mut self, event: &DeviceEvent) -> bool {
+        self.camera_rig.process_device_event(event)
+    }
+
     // indicate whether an event has been fully processed.
     // If the method returns true, the main loop won't process the event any further.
-    // So the main idea of this function is catching some specific events and handle them in it.
+    // tips: the engine's hotkeys are read from the `Input` of the frame, see `handle_hotkeys()`.
     pub(crate) fn input(&mut self, event: &WindowEvent) -> bool {
-        if self.camera_controller.process_events(&event) {
+        // the debug UI is on top of the scene
+        #[cfg(feature = "egui")]
+        if self.debug_ui.process_event(event) {
             return true;
         }
+        self.camera_rig.process_event(event)
+    }
 
-        match event {
-            WindowEvent::CursorMoved { position, .. } => {
-                self.clear_color = wgpu::Color {
-                    r: position.x as f64 / self.size.width as f64,
-                    g: position.y as f64 / self.size.height as f64,
-                    b: 1.0,
-                    a: 1.0
-                };
-                true
-            },
-            WindowEvent::KeyboardInput {
-                input: KeyboardInput {
-                    state,
-                    virtual_keycode: Some(VirtualKeyCode::Space),
-                    ..
-                },
-                ..
-            } => {
-                self.is_space_pressed = *state == ElementState::Pressed;
-                true
-            },
-            WindowEvent::KeyboardInput {
-                input: KeyboardInput {
-                    state,
-                    virtual_keycode: Some(VirtualKeyCode::Return),
-                    ..
-                },
-                ..
-            } => {
-                self.is_enter_pressed = *state == ElementState::Pressed;
-                true
-            },
-            _ => false
+    #[cfg(feature = "egui")]
+    pub(crate) fn debug_ui(&mut self) -> &mut DebugUi {
+        &mut self.debug_ui
+    }
+
+    // build the debug UI of the frame, with the engine's windows (shader errors, profiler)
+    #[cfg(feature = "egui")]
+    pub(crate) fn run_debug_ui(&mut self, run_ui: impl FnOnce(&egui::Context)) {
+        self.debug_ui.run(self.error_overlay.errors(), self.debug_draw.settings_mut(), run_ui);
+    }
+
+    // Space: cartoon material, Enter: depth view, G: grid, F1 ~ F4: debug draw categories, P: profiler & debug draw windows (feature "egui"),
+    // F9: clip recording, F10: save the clip, F12: screenshot
+    fn handle_hotkeys(&mut self, input: &Input) {
+        self.cartoon_material_picked = input.pressed(VirtualKeyCode::Space);
+        self.depth_view_visible = input.pressed(VirtualKeyCode::Return);
+
+        // toggle once per press, key repeats aren't presses (see `Input::just_pressed()`)
+        if input.just_pressed(VirtualKeyCode::G) {
+            self.grid_pass.visible = !self.grid_pass.visible;
+        }
+        let categories = [
+            (VirtualKeyCode::F1, DebugDrawCategory::Aabbs),
+            (VirtualKeyCode::F2, DebugDrawCategory::Spheres),
+            (VirtualKeyCode::F3, DebugDrawCategory::Lights),
+            (VirtualKeyCode::F4, DebugDrawCategory::Frusta)
+        ];
+        for (key, category) in categories {
+            if input.just_pressed(key) {
+                self.debug_draw.toggle(category);
+            }
         }
+        #[cfg(feature = "egui")]
+        if input.just_pressed(VirtualKeyCode::P) {
+            self.debug_ui.engine_windows_visible = !self.debug_ui.engine_windows_visible;
+        }
+        if input.just_pressed(VirtualKeyCode::F9) {
+            self.frame_capture.toggle_recording();
+        }
+        if input.just_pressed(VirtualKeyCode::F10) {
+            self.frame_capture.request_clip();
+        }
+        if input.just_pressed(VirtualKeyCode::F12) {
+            self.frame_capture.request_screenshot();
+        }
+    }
+
+    // Compile the pipelines of these render states in the background now, rather than when a mesh first uses them.
+    pub(crate) fn warm_up(&mut self, render_states: &[RenderState]) {
+        for render_state in render_states {
+            self.scene_pipelines.request(*render_state);
+        }
+    }
+
+    // shaders & render states whose scene pipeline failed to build
+    pub(crate) fn failed_pipelines(&self) -> &[ScenePipelineKey] {
+        self.scene_pipelines.failed()
+    }
+
+    // the application moves or replaces the camera before `update()`
+    pub(crate) fn camera_rig(&mut self) -> &mut CameraRig {
+        &mut self.camera_rig
+    }
+
+    pub(crate) fn set_capture_config(&mut self, config: CaptureConfig) {
+        self.frame_capture.set_config(config);
+    }
+
+    pub(crate) fn set_frame_stream(&mut self, stream: Option<FrameStream>) {
+        self.frame_capture.set_stream(stream);
     }
 
-    pub(crate) fn update(&mut self) {
+    // the next rendered frame is saved to `path`, see `AppConfig::final_screenshot`
+    pub(crate) fn request_screenshot_to(&mut self, path: PathBuf) {
+        self.frame_capture.request_screenshot_to(path);
+    }
+
+    pub(crate) fn set_environment(&mut self, environment: &Environment) {
+        // the sky is the background until there's a skybox
+        self.clear_color = wgpu::Color {
+            r: environment.sky_color[0] as f64,
+            g: environment.sky_color[1] as f64,
+            b: environment.sky_color[2] as f64,
+            a: 1.0
+        };
+        self.queue.write_buffer(&self.environment_uniform_buffer, 0, bytemuck::cast_slice(&[environment.to_uniform()]));
+
+        // the flare dims with the exposed sunlight, e.g. at night
+        let brightness = (environment.sun_intensity * environment.exposure.multiplier()).min(1.0);
+        self.lens_flare_pass.set_light(-environment.sun_direction.normalize(), environment.sun_color.map(|c| c * brightness));
+    }
+
+    // lights with their world position, pre-exposed with the environment's camera exposure,
+    // & the world space occluder edges shadowing them
+    pub(crate) fn set_lights(&mut self, lights: &[(Light, nalgebra::Point3<f32>)], occluder_segments: &[[f32; 4]], environment: &Environment) {
+        let lights_uniform = LightsUniform::new(lights, environment.exposure.multiplier());
+        self.queue.write_buffer(&self.lights_uniform_buffer, 0, bytemuck::cast_slice(&[lights_uniform]));
+        let occluders_uniform = OccludersUniform::new(occluder_segments);
+        self.queue.write_buffer(&self.occluders_uniform_buffer, 0, bytemuck::cast_slice(&[occluders_uniform]));
+    }
+
+    // the sky drawn behind the scene, cleared to the environment's sky color without one
+    pub(crate) fn set_skybox(&mut self, cubemap: Option<Rc<Cubemap>>, environment: &Environment) {
+        self.skybox_pass.set_skybox(&self.device, &self.queue, cubemap, environment.sky_intensity);
+    }
+
+    pub(crate) fn set_lens_flare(&mut self, lens_flare: Option<LensFlare>) {
+        self.lens_flare_pass.set_lens_flare(lens_flare);
+    }
+
+    // the effects are applied in order on the way to the window
+    pub(crate) fn set_post_process(&mut self, stack: PostProcessStack) {
+        self.post_process_pass.set_stack(stack);
+    }
+
+    pub(crate) fn set_transition(&mut self, transition: Option<Transition>) {
+        self.transition_pass.update(&self.queue, transition, self.config.width, self.config.height);
+    }
+
+    pub(crate) fn update(&mut self, time: &Time, input: &Input) {
+        // no hotkeys while typing in the debug UI
+        #[cfg(feature = "egui")]
+        let hotkeys = !self.debug_ui.wants_keyboard_input();
+        #[cfg(not(feature = "egui"))]
+        let hotkeys = true;
+        if hotkeys {
+            self.handle_hotkeys(input);
+        }
+
+        // rebuild what uses the shaders saved since the last check
+        let changed_shaders = self.shader_watcher
+            .as_mut()
+            .map(ShaderWatcher::poll)
+            .unwrap_or_default();
+        for file in changed_shaders {
+            self.reload_shader(&file);
+        }
+        // pick up the pipelines compiled since the last frame
+        self.scene_pipelines.poll(&mut self.error_overlay);
+
         // update camera data
-        self.camera_controller.update_camera(&mut self.camera);
-        self.camera_uniform.update_view_proj(&self.camera);
+        self.camera_rig.update(time.delta());
+        self.update_pixel_perfect_targets();
+        let camera = self.camera_rig.render_camera();
+        self.camera_uniform.update_view_proj(&camera);
         self.queue.write_buffer(&self.camera_uniform_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        self.grid_pass.update(&self.queue, camera.view_projection_matrix(), camera.eye);
+        self.skybox_pass.update(&self.queue, camera.view_projection_matrix());
+        self.lens_flare_pass.update(&self.queue, camera.view_projection_matrix(), self.config.width, self.config.height, time.delta());
+
+        // update UV transform data, the elapsed time drives UV scrolling
+        self.elapsed = time.elapsed();
+        for material in [&self.diffuse_material, &self.cartoon_material] {
+            if let Some(material_binding) = material.binding(&self.device, &self.queue, &self.texture_bind_group_layout) {
+                material_binding.update(&self.queue, material.uv_transform(), self.elapsed);
+            }
+        }
+
+        // upload the pixels painted on the diffuse texture since the last frame
+        if let Some((region, pixels)) = self.diffuse_canvas.take_dirty() {
+            let material_binding = self.diffuse_material.binding(&self.device, &self.queue, &self.texture_bind_group_layout);
+            if let Some(Err(error)) = material_binding.map(|material_binding| material_binding.texture.write_region(&self.queue, region, &pixels)) {
+                eprintln!("{:?}", error);
+            }
+        }
 
         // update instance buffer data
         for instance in &mut self.instances {
@@ -997,20 +1233,318 @@ impl GPUState {
         );
     }
 
+    // Rebuild the pipelines using the shader `file` (shader hot-reloading), forgetting their previous errors.
+    // tips: the passes are built again, their state set every frame comes back on the next one.
+    fn reload_shader(&mut self, file: &str) {
+        eprintln!("reloading {}", file);
+        let depth_mode = self.depth_pass.depth_mode;
+        match file {
+            "shader.wgsl" => {
+                self.error_overlay.resolve("Render Pipeline");
+                self.scene_pipelines.reload();
+            }
+            "depth_buffer.wgsl" => {
+                self.error_overlay.resolve("Depth Pass Render Pipeline");
+                self.depth_pass = DepthPass::new(&self.device, &self.config, depth_mode, &mut self.error_overlay);
+                // the occlusion test reads the new depth texture
+                self.lens_flare_pass.resize(&self.device, &self.depth_pass.texture.view);
+            }
+            "grid.wgsl" => {
+                self.error_overlay.resolve("Grid Render Pipeline");
+                let visible = self.grid_pass.visible;
+                self.grid_pass = GridPass::new(&self.device, &scene_config(&self.config), self.sample_count, depth_mode, &mut self.error_overlay);
+                self.grid_pass.visible = visible;
+            }
+            "polyline.wgsl" => {
+                self.error_overlay.resolve("Polyline Render Pipeline");
+                self.polyline_renderer = PolylineRenderer::new(&self.device, &scene_config(&self.config), self.sample_count, depth_mode, &mut self.error_overlay);
+            }
+            "lens_flare.wgsl" => {
+                self.error_overlay.resolve("Lens Flare Pipelines");
+                self.lens_flare_pass = LensFlarePass::new(&self.device, &self.config, &self.depth_pass.texture.view, depth_mode, &mut self.error_overlay);
+            }
+            "post_process.wgsl" => {
+                self.error_overlay.resolve("Post Process Render Pipeline");
+                self.post_process_pass = PostProcessPass::new(&self.device, &self.config, &mut self.error_overlay);
+            }
+            "transition.wgsl" => {
+                self.error_overlay.resolve("Transition Render Pipeline");
+                self.transition_pass = TransitionPass::new(&self.device, &self.config, &mut self.error_overlay);
+            }
+            "parallax.wgsl" => {
+                self.error_overlay.resolve("Parallax Render Pipeline");
+                self.parallax_pass = ParallaxPass::new(&self.device, &scene_config(&self.config), self.sample_count, &self.texture_bind_group_layout, &mut self.error_overlay);
+            }
+            "skybox.wgsl" => {
+                self.error_overlay.resolve("Skybox Render Pipeline");
+                self.skybox_pass = SkyboxPass::new(&self.device, &scene_config(&self.config), self.sample_count, depth_mode, &mut self.error_overlay);
+            }
+            // built once at startup (MSAA resolve, frame capture, debug UI, error overlay) or by the application (blur)
+            _ => eprintln!("{} is only read at startup, restart the application to apply it", file)
+        }
+    }
+
+    // upload the parallax layers of this frame & their materials, placed for the current camera
+    pub(crate) fn prepare_parallax_layers(&mut self, layers: &[(ParallaxLayer, Rc<Material>)]) {
+        let mut layers = layers
+            .iter()
+            .filter_map(|(layer, material)| {
+                let material_binding = material.binding(&self.device, &self.queue, &self.texture_bind_group_layout)?;
+                Some((*layer, material_binding))
+            })
+            .collect::<Vec<_>>();
+        // back to front
+        layers.sort_by(|(a, _), (b, _)| a.scroll_factor[0].total_cmp(&b.scroll_factor[0]));
+        self.parallax_pass.update(&self.queue, &self.camera_rig.render_camera(), layers);
+    }
+
+    // upload the meshes & material textures drawn this frame (the first time they're drawn) & their transforms.
+    pub(crate) fn prepare_meshes(&mut self, mesh_draws: &[MeshDraw]) {
+        if mesh_draws.len() > self.mesh_instance_capacity {
+            self.mesh_instance_capacity = mesh_draws.len().next_power_of_two();
+            self.mesh_instance_buffer = Self::create_mesh_instance_buffer(&self.device, self.mesh_instance_capacity);
+        }
+        let instance_data = mesh_draws
+            .iter()
+            .map(|mesh_draw| InstanceRaw { model: mesh_draw.transform.into() })
+            .collect::<Vec<_>>();
+        self.queue.write_buffer(&self.mesh_instance_buffer, 0, bytemuck::cast_slice(&instance_data));
+
+        // Custom materials: their own parameters in the first block of their buffer, then a block per mesh overriding them.
+        // the materials with the parameters of their overriding meshes
+        let mut shader_materials: Vec<(&Rc<Material>, Vec<MaterialParams>)> = Vec::new();
+        // the material & block of each mesh
+        let mut shader_blocks = Vec::with_capacity(mesh_draws.len());
+        for mesh_draw in mesh_draws {
+            let material = match &mesh_draw.material {
+                Some(material) if material.shader().is_some() => material,
+                _ => {
+                    shader_blocks.push(None);
+                    continue;
+                }
+            };
+            let index = match shader_materials.iter().position(|(shader_material, _)| Rc::ptr_eq(shader_material, material)) {
+                Some(index) => index,
+                None => {
+                    shader_materials.push((material, Vec::new()));
+                    shader_materials.len() - 1
+                }
+            };
+            let overridden_params = mesh_draw.param_overrides
+                .as_ref()
+                .and_then(|param_overrides| material.overridden_params(param_overrides));
+            let block = match overridden_params {
+                Some(params) => {
+                    shader_materials[index].1.push(params);
+                    shader_materials[index].1.len()
+                }
+                None => 0
+            };
+            shader_blocks.push(Some((index, block)));
+        }
+        let shader_bindings = shader_materials
+            .iter()
+            .map(|(material, overridden_params)| {
+                let shader_binding = material.shader_binding(&self.device, &self.queue, 1 + overridden_params.len())?;
+                for (block, params) in overridden_params.iter().enumerate() {
+                    shader_binding.write_block(&self.queue, 1 + block, params);
+                }
+                Some(shader_binding)
+            })
+            .collect::<Vec<_>>();
+
+        self.mesh_draws = mesh_draws
+            .iter()
+            .zip(shader_blocks)
+            .map(|(mesh_draw, shader_block)| {
+                let material = mesh_draw.material.as_ref();
+                // the mesh's own render state, or its material's
+                let render_state = mesh_draw.render_state.or_else(|| material.map(|material| material.render_state()));
+                let material = match material {
+                    None => PreparedMaterial::Picked,
+                    Some(material) => match material.shader() {
+                        Some(shader) => {
+                            // a shader & render state seen for the first time are compiled in the background
+                            self.scene_pipelines.request_material_shader(shader, render_state.unwrap_or_else(|| material.render_state()));
+                            let shader_binding = shader_block.and_then(|(index, block)| Some((shader_bindings[index].clone()?, block)));
+                            match shader_binding {
+                                Some((shader_binding, block)) => {
+                                    let dynamic_offset = shader_binding.dynamic_offset(block);
+                                    PreparedMaterial::Shader(shader.key(), shader_binding, dynamic_offset)
+                                }
+                                None => PreparedMaterial::Unavailable
+                            }
+                        }
+                        // an engine material which failed to upload keeps the picked one
+                        None => self.material_binding(material).map_or(PreparedMaterial::Picked, PreparedMaterial::Standard)
+                    }
+                };
+                PreparedMeshDraw {
+                    mesh_buffers: mesh_draw.mesh.buffers(&self.device),
+                    material,
+                    render_state
+                }
+            })
+            .collect();
+        // a render state seen for the first time is compiled in the background
+        for mesh_draw in &self.mesh_draws {
+            if let (PreparedMaterial::Picked | PreparedMaterial::Standard(_), Some(render_state)) = (&mesh_draw.material, mesh_draw.render_state) {
+                self.scene_pipelines.request(render_state);
+            }
+        }
+    }
+
+    // the sprites of this frame with their world transform, batched by material
+    pub(crate) fn prepare_sprites(&mut self, sprites: &[(Sprite, nalgebra::Matrix4<f32>, Rc<Material>)]) {
+        let sprites = sprites
+            .iter()
+            .filter_map(|(sprite, transform, material)| {
+                let material_binding = self.material_binding(material)?;
+                Some((*sprite, *transform, material_binding, material.render_state()))
+            })
+            .collect();
+        self.sprite_batch.prepare(&self.device, &self.queue, sprites);
+        // a render state seen for the first time is compiled in the background
+        for render_state in self.sprite_batch.render_states() {
+            self.scene_pipelines.request(render_state);
+        }
+    }
+
+    // the bind group of `material`, uploaded the first time & with its UVs up to date
+    fn material_binding(&self, material: &Material) -> Option<Rc<MaterialBinding>> {
+        let material_binding = material.binding(&self.device, &self.queue, &self.texture_bind_group_layout)?;
+        // static UVs were written with the bind group
+        let uv_transform_changed = material.take_uv_transform_changed();
+        if uv_transform_changed || material.uv_transform().scroll != [0.0, 0.0] {
+            material_binding.update(&self.queue, material.uv_transform(), self.elapsed);
+        }
+        Some(material_binding)
+    }
+
+    // the diffuse texture, to paint on before `update()` uploads the changes.
+    pub(crate) fn diffuse_canvas(&mut self) -> &mut PaintCanvas {
+        &mut self.diffuse_canvas
+    }
+
+    // the global debug draw toggles, e.g. for the scene's resources
+    pub(crate) fn debug_draw_settings(&self) -> DebugDrawSettings {
+        self.debug_draw.settings()
+    }
+
+    pub(crate) fn set_debug_draw_settings(&mut self, settings: DebugDrawSettings) {
+        *self.debug_draw.settings_mut() = settings;
+    }
+
+    // clear the debug shapes of the previous frame, the toggles are kept.
+    pub(crate) fn begin_debug_draw(&mut self) -> &mut DebugDraw {
+        self.debug_draw.clear();
+        &mut self.debug_draw
+    }
+
+    // upload the polylines & debug shapes of this frame, call it after `update()` so they follow the camera.
+    pub(crate) fn prepare_polylines(&mut self, polylines: &[Polyline]) {
+        let polylines = [polylines, self.debug_draw.polylines()].concat();
+        // pixel widths are in texels in pixel-perfect mode
+        let camera = self.camera_rig.render_camera();
+        let viewport = match &self.pixel_perfect_targets {
+            Some(pixel_perfect_targets) => pixel_perfect_targets.size().into(),
+            None => self.size
+        };
+        self.polyline_renderer.prepare(
+            &self.device,
+            &self.queue,
+            &polylines,
+            camera.view_projection_matrix(),
+            camera.eye,
+            viewport
+        );
+    }
+
+    // follow the camera rig's pixel-perfect mode & the window size
+    fn update_pixel_perfect_targets(&mut self) {
+        match (self.camera_rig.pixel_target(), &mut self.pixel_perfect_targets) {
+            (None, _) => self.pixel_perfect_targets = None,
+            (Some((size, zoom)), Some(pixel_perfect_targets)) => pixel_perfect_targets.resize(&self.device, size, zoom),
+            (Some((size, zoom)), None) => {
+                self.pixel_perfect_targets = Some(PixelPerfectTargets::new(
+                    &self.device,
+                    &scene_config(&self.config),
+                    self.sample_count,
+                    self.depth_pass.depth_mode,
+                    size,
+                    zoom,
+                    &mut self.error_overlay
+                ));
+            }
+        }
+    }
+
+    // Wait for the next window frame before the input of the frame is read (late latching), `render()` draws into it.
+    // tips: the errors are left to `render()`, which acquires the frame again.
+    pub(crate) fn acquire_frame(&mut self) {
+        if self.acquired_frame.is_none() {
+            self.acquired_frame = self.surface.get_current_texture().ok();
+        }
+    }
+
     pub(crate) fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         // get a frame(桢) to render to.
-        // wait Surface to provide a new SurfaceTexture that we will render to
-        let output_texture = self.surface.get_current_texture()?;
+        // wait Surface to provide a new SurfaceTexture that we will render to, unless it's acquired already
+        let output_texture = match self.acquired_frame.take() {
+            Some(output_texture) => output_texture,
+            None => {
+                let _scope = profile_scope("acquire surface texture");
+                self.surface.get_current_texture()?
+            }
+        };
         // Create "TextureView" with default settings,
         // so that we can control how the render code interacts with the texture.
-        let texture_view = output_texture.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let surface_view = output_texture.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // captured frames are rendered offscreen, then copied to the surface
+        let texture_view = self.frame_capture.begin_frame(&self.device).unwrap_or(&surface_view);
         // Create "CommandEncoder" to create the actual commands to send to the gpu and builds a command buffer to store them.
         // Most modern graphics frameworks expect commands to be stored in a command buffer before being sent to the gpu.
         let mut command_encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder")
         });
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin_frame(&mut command_encoder);
+            gpu_timer.begin_pass(GpuPass::Scene, &mut command_encoder);
+        }
+        // the scene is rendered into the HDR target, post-processed into the frame after the polylines
+        let hdr_view = self.post_process_pass.scene_view();
+        // in pixel-perfect mode the scene is rendered into low resolution targets, scaled up after the polylines
+        let (frame_view, frame_depth_view, msaa) = match &self.pixel_perfect_targets {
+            Some(pixel_perfect_targets) => (pixel_perfect_targets.color_view(), pixel_perfect_targets.depth_view(), pixel_perfect_targets.msaa()),
+            None => (hdr_view, &self.depth_pass.texture.view, self.msaa.as_ref())
+        };
+        // with MSAA the scene is rendered into multisampled targets, resolved after the polylines
+        let (scene_view, scene_depth_view) = match msaa {
+            Some(msaa) => (msaa.color_view(), msaa.depth_view()),
+            None => (frame_view, frame_depth_view)
+        };
         
+        // Skybox set commands, behind everything
+        let skybox_drawn = self.skybox_pass.render(scene_view, &mut command_encoder);
+
+        // Parallax Layers set commands, behind the scene but over the sky
+        let background_load = if skybox_drawn {
+            wgpu::LoadOp::Load
+        } else {
+            wgpu::LoadOp::Clear(self.clear_color)
+        };
+        let background_drawn = self.parallax_pass.render(scene_view, background_load, &mut command_encoder) || skybox_drawn;
+
         {
+            // pick the material: bind group & render state
+            let picked_material = if self.cartoon_material_picked {
+                &self.cartoon_material
+            } else {
+                &self.diffuse_material
+            };
+            let render_state = picked_material.render_state();
+            let picked_binding = picked_material.binding(&self.device, &self.queue, &self.texture_bind_group_layout);
+
             // Create a "RenderPass" by CommandEncoder.
             // It has all the methods for the actual drawing.
             let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -1020,7 +1554,7 @@ impl GPUState {
                 color_attachments: &[wgpu::RenderPassColorAttachment {
                     // `view` field informs wgpu what texture to save the colors to.
                     // here we use the TextureView to make sure that we render to the screen.
-                    view: &texture_view,
+                    view: scene_view,
                     // it's the texture that will receive the resolved output.
                     // this will be the same as `view` field texture unless multisampling is enabled,
                     // so we don't need to store this texture currently.
@@ -1028,7 +1562,12 @@ impl GPUState {
                     // This tells wgpu what to do with the colors on the screen (specified by frame.view)
                     ops: wgpu::Operations {
                         // tells wgpu how to handle colors stored from the previous frame.
-                        load: wgpu::LoadOp::Clear(self.clear_color),
+                        // the sky & parallax layers were drawn over the cleared frame already
+                        load: if background_drawn {
+                            wgpu::LoadOp::Load
+                        } else {
+                            wgpu::LoadOp::Clear(self.clear_color)
+                        },
                         // tells wgpu whether we want to store the rendered results to the Texture behind our TextureView
                         // in this case, it's the SurfaceTexture.
                         store: true
@@ -1036,44 +1575,157 @@ impl GPUState {
                 }],
                 // using Depth Texture
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_pass.texture.view,
+                    view: scene_depth_view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        // the far plane: 1.0, or 0.0 with reversed-Z
+                        load: wgpu::LoadOp::Clear(self.depth_pass.depth_mode.far_depth()),
                         store: true,
                     }),
                     stencil_ops: None,
                 })
             });
 
-            // specify Render Pipeline to current RenderPass
-            render_pass.set_pipeline(&self.render_pipeline);
             // specify bind group
-            let texture_bind_group = if self.is_space_pressed {
-                &self.cartoon_bind_group
-            } else {
-                &self.diffuse_bind_group
-            };
-            render_pass.set_bind_group(0, texture_bind_group, &[]);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            // send Vertex Buffer data to current RenderPass
-            // tips: we could set multiple vertex buffer to a render pass
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..)); // send vertex_buffer to buffer slot 0
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..)); // send instance_buffer to buffer slot 1
-            // send Index Buffer to current RenderPass
-            // tips: we only could set one index buffer to a render pass
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            // Draw Call: send vertex index & instance id to wgpu
-            render_pass.draw_indexed(0..self.indices_num, 0, 0..self.instances.len() as _);
+            render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
+
+            // specify Render Pipeline to current RenderPass
+            // tips: while the pipeline is compiling or if it failed to build, we still clear the frame and draw the error overlay below.
+            if let (Some(render_pipeline), Some(picked_binding)) = (self.scene_pipelines.get(&render_state.into()), &picked_binding) {
+                render_pass.set_pipeline(render_pipeline);
+                render_pass.set_bind_group(0, &picked_binding.bind_group, &[]);
+                // send Vertex Buffer data to current RenderPass
+                // tips: we could set multiple vertex buffer to a render pass
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..)); // send vertex_buffer to buffer slot 0
+                render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..)); // send instance_buffer to buffer slot 1
+                // send Index Buffer to current RenderPass
+                // tips: we only could set one index buffer to a render pass
+                render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                // Draw Call: send vertex index & instance id to wgpu
+                render_pass.draw_indexed(0..self.indices_num, 0, 0..self.instances.len() as _);
+            }
+
+            // the application meshes, one instance each
+            render_pass.set_vertex_buffer(1, self.mesh_instance_buffer.slice(..));
+            for (instance, mesh_draw) in self.mesh_draws.iter().enumerate() {
+                let instance = instance as u32;
+                // meshes without a render state keep the picked one
+                let mesh_render_state = mesh_draw.render_state.unwrap_or(render_state);
+                // meshes without a material keep the picked one, custom materials are drawn with their shader
+                let (pipeline_key, bind_group, dynamic_offset) = match &mesh_draw.material {
+                    PreparedMaterial::Picked => match &picked_binding {
+                        Some(picked_binding) => (ScenePipelineKey::from(mesh_render_state), &picked_binding.bind_group, None),
+                        None => continue
+                    },
+                    PreparedMaterial::Standard(material_binding) => (mesh_render_state.into(), &material_binding.bind_group, None),
+                    PreparedMaterial::Shader(shader, shader_binding, dynamic_offset) => {
+                        let pipeline_key = ScenePipelineKey { shader: Some(*shader), render_state: mesh_render_state };
+                        (pipeline_key, &shader_binding.bind_group, *dynamic_offset)
+                    }
+                    PreparedMaterial::Unavailable => continue
+                };
+                // a mesh is skipped until its pipeline is compiled
+                let render_pipeline = match self.scene_pipelines.get(&pipeline_key) {
+                    Some(render_pipeline) => render_pipeline,
+                    None => continue
+                };
+                render_pass.set_pipeline(render_pipeline);
+                render_pass.set_bind_group(0, bind_group, dynamic_offset.as_slice());
+                let mesh_buffers = &mesh_draw.mesh_buffers;
+                render_pass.set_vertex_buffer(0, mesh_buffers.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh_buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..mesh_buffers.indices_num, 0, instance..instance + 1);
+            }
+
+            // the sprites, a draw call per batch
+            self.sprite_batch.render(&self.scene_pipelines, &mut render_pass);
         }
 
-        // Depth Pass set commands
-        if self.is_enter_pressed {
-            self.depth_pass.render(&texture_view, &mut command_encoder);
+        // Ground Grid set commands, over the scene but hidden behind it
+        self.grid_pass.render(scene_view, scene_depth_view, &mut command_encoder);
+
+        // Polylines set commands, depth tested against the scene
+        self.polyline_renderer.render(scene_view, scene_depth_view, &mut command_encoder);
+
+        // MSAA resolve set commands, the passes below read the resolved color & depth
+        if let Some(msaa) = msaa {
+            msaa.resolve(frame_view, frame_depth_view, &mut command_encoder);
         }
 
+        // Pixel Perfect upscale set commands, into the HDR target
+        if let Some(pixel_perfect_targets) = &self.pixel_perfect_targets {
+            pixel_perfect_targets.upscale(hdr_view, (self.config.width, self.config.height), &mut command_encoder);
+        }
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end_pass(GpuPass::Scene, &mut command_encoder);
+            gpu_timer.begin_pass(GpuPass::PostProcess, &mut command_encoder);
+        }
+
+        // Post Processing set commands, from the HDR target into the frame
+        self.post_process_pass.render(&self.device, texture_view, &mut command_encoder);
+
+        // the passes reading the window sized depth are skipped in pixel-perfect mode
+        if self.pixel_perfect_targets.is_none() {
+            // Lens Flare set commands, over the finished scene
+            self.lens_flare_pass.render(texture_view, &mut command_encoder);
+
+            // Depth Pass set commands
+            if self.depth_view_visible {
+                self.depth_pass.render(texture_view, &mut command_encoder);
+            }
+        }
+
+        // Transition set commands, over the scene & its debug views
+        self.transition_pass.render(texture_view, &mut command_encoder);
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end_pass(GpuPass::PostProcess, &mut command_encoder);
+        }
+
+        // Debug UI set commands, over the frame but below the shader errors
+        #[cfg(feature = "egui")]
+        {
+            if let Some(gpu_timer) = &mut self.gpu_timer {
+                gpu_timer.begin_pass(GpuPass::DebugUi, &mut command_encoder);
+            }
+            self.debug_ui.render(&self.device, &self.queue, texture_view, &mut command_encoder);
+            if let Some(gpu_timer) = &mut self.gpu_timer {
+                gpu_timer.end_pass(GpuPass::DebugUi, &mut command_encoder);
+            }
+        }
+
+        // Error Overlay set commands, drawn last to stay on top of everything
+        // tips: the diagnostics are shown in the debug UI, unless it failed to build.
+        #[cfg(feature = "egui")]
+        let draw_text = !self.debug_ui.is_available();
+        #[cfg(not(feature = "egui"))]
+        let draw_text = true;
+        let frame_size = (self.config.width, self.config.height);
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin_pass(GpuPass::ErrorOverlay, &mut command_encoder);
+        }
+        self.error_overlay.render(&self.device, &self.queue, frame_size, draw_text, texture_view, &mut command_encoder);
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end_pass(GpuPass::ErrorOverlay, &mut command_encoder);
+        }
+
+        // Frame Capture set commands, if this frame is captured
+        self.frame_capture.end_frame(&surface_view, &mut command_encoder);
+
         // finish the command buffer, and to submit it to the GPU's render queue
+        let _scope = profile_scope("submit & present");
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end_frame(&mut command_encoder);
+        }
         self.queue.submit(std::iter::once(command_encoder.finish()));
         output_texture.present();
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.read_back(&self.device);
+        }
+
+        // read the captured frame back, once it's rendered
+        self.frame_capture.read_frame(&self.device);
 
         Ok(())
     }
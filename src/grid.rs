@@ -0,0 +1,193 @@
+use super::app_config::DepthMode;
+use super::hot_reload::load_shader;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct GridUniform {
+    view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+    minor_spacing: f32,
+    major_every: f32,
+    fade_distance: f32,
+    near_depth: f32 // NDC depth of the near plane, see `DepthMode`
+}
+
+// Editor-style infinite ground grid on the y = 0 plane, so empty scenes still have a spatial reference.
+// It's drawn over the scene with a full-screen shader, depth tested against it.
+pub(crate) struct GridPass {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    render_pipeline: Option<wgpu::RenderPipeline>, // None if the pipeline failed to build
+    pub(crate) visible: bool,
+    minor_spacing: f32,
+    major_every: f32,
+    fade_distance: f32,
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+    depth_mode: DepthMode
+}
+
+impl GridPass {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32, // of the scene's color & depth targets
+        depth_mode: DepthMode,
+        error_overlay: &mut super::error_overlay::ErrorOverlay
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Uniform Buffer"),
+            size: std::mem::size_of::<GridUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grid BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ]
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grid Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let render_pipeline = super::error_overlay::catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Grid Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("grid.wgsl", include_str!("res/shaders/grid.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Grid Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the full-screen triangle is generated from the vertex index
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING), // faded lines blend over the scene
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // the fragment shader writes the depth of the ground plane,
+                // but the grid is translucent so it doesn't hide what's drawn after it.
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: super::texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: depth_mode.compare(),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default()
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let render_pipeline = render_pipeline
+            .map_err(|error| error_overlay.report("Grid Render Pipeline", &error))
+            .ok();
+
+        Self {
+            uniform_buffer,
+            bind_group,
+            render_pipeline,
+            visible: true,
+            minor_spacing: 1.0,
+            major_every: 10.0,
+            fade_distance: 50.0,
+            minor_color: [0.5, 0.5, 0.5, 0.4],
+            major_color: [0.8, 0.8, 0.8, 0.7],
+            depth_mode
+        }
+    }
+
+    // upload the camera data of this frame
+    pub(crate) fn update(&self, queue: &wgpu::Queue, view_proj: nalgebra::Matrix4<f32>, eye: nalgebra::Point3<f32>) {
+        let uniform = GridUniform {
+            view_proj: view_proj.into(),
+            inv_view_proj: view_proj.try_inverse().unwrap_or_else(nalgebra::Matrix4::identity).into(),
+            eye: [eye.x, eye.y, eye.z, 1.0],
+            minor_color: self.minor_color,
+            major_color: self.major_color,
+            minor_spacing: self.minor_spacing,
+            major_every: self.major_every,
+            fade_distance: self.fade_distance,
+            near_depth: self.depth_mode.near_depth()
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub(crate) fn render(&self, texture_view: &wgpu::TextureView, depth_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) {
+        let render_pipeline = match &self.render_pipeline {
+            Some(render_pipeline) if self.visible => render_pipeline,
+            _ => return
+        };
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Grid Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            })
+        });
+
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,88 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+// the engine's shaders in its source tree, where they're edited
+const SHADER_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/res/shaders");
+
+// how often the shader files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// set once at startup from `AppConfig::shader_hot_reload`
+static HOT_RELOAD: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn enable_shader_hot_reload() {
+    HOT_RELOAD.store(true, Ordering::Relaxed);
+}
+
+// Source of the WGSL shader `file`: the embedded one, or the one on disk with shader hot-reloading.
+// tips: falls back to the embedded source if the file can't be read.
+pub(crate) fn load_shader(file: &str, embedded: &'static str) -> Cow<'static, str> {
+    if !HOT_RELOAD.load(Ordering::Relaxed) {
+        return Cow::Borrowed(embedded);
+    }
+    let path = Path::new(SHADER_DIRECTORY).join(file);
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Cow::Owned(source),
+        Err(error) => {
+            eprintln!("failed to read shader {}: {}, using the embedded one", path.display(), error);
+            Cow::Borrowed(embedded)
+        }
+    }
+}
+
+// Watches the WGSL files of the shader directory by polling their modification times,
+// which needs no file system notification backend & is cheap for a few dozen files.
+pub(crate) struct ShaderWatcher {
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant
+}
+
+impl ShaderWatcher {
+    pub(crate) fn new() -> Self {
+        Self {
+            modified: Self::scan(),
+            last_poll: Instant::now()
+        }
+    }
+
+    // names of the shader files changed (or added) since the last poll
+    pub(crate) fn poll(&mut self) -> Vec<String> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let modified = Self::scan();
+        let mut changed = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .filter_map(|(path, _)| path.file_name()?.to_str().map(str::to_string))
+            .collect::<Vec<_>>();
+        changed.sort();
+        self.modified = modified;
+        changed
+    }
+
+    fn scan() -> HashMap<PathBuf, SystemTime> {
+        let entries = match std::fs::read_dir(SHADER_DIRECTORY) {
+            Ok(entries) => entries,
+            Err(error) => {
+                eprintln!("failed to watch the shaders in {}: {}", SHADER_DIRECTORY, error);
+                return HashMap::new();
+            }
+        };
+        entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "wgsl" {
+                    return None;
+                }
+                let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+                Some((path, modified))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
+use winit::event::{DeviceEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+// What an `InputEvent` is: presses & releases (key repeats aren't), cursor & raw mouse motion, wheel notches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEventKind {
+    KeyPressed(VirtualKeyCode),
+    KeyReleased(VirtualKeyCode),
+    MousePressed(MouseButton),
+    MouseReleased(MouseButton),
+    // in pixels from the top left of the window
+    CursorMoved(f32, f32),
+    // raw motion, see `Input::mouse_delta()`
+    MouseMotion(f32, f32),
+    Scroll(f32)
+}
+
+// An input event of the frame & when the engine received it, for what needs better than the frame time:
+// rhythm games judging a hit, the speed of a flick or a gesture, measuring the input latency...
+// tips: the platforms don't give the event times, they're stamped as the events come out of the window's queue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputEvent {
+    pub kind: InputEventKind,
+    pub time: Instant
+}
+
+impl InputEvent {
+    // seconds between the event & `instant`, e.g. the start of the frame
+    pub fn seconds_before(&self, instant: Instant) -> f32 {
+        instant.saturating_duration_since(self.time).as_secs_f32()
+    }
+}
+
+// Keyboard & mouse state of the frame, passed to `Application::update()` & inserted in the scene's resources every frame.
+// Presses & releases between two frames are all seen: a key tapped within a single frame is just pressed & just released.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    keys: HashSet<VirtualKeyCode>,
+    just_pressed_keys: HashSet<VirtualKeyCode>,
+    just_released_keys: HashSet<VirtualKeyCode>,
+    buttons: HashSet<MouseButton>,
+    just_pressed_buttons: HashSet<MouseButton>,
+    just_released_buttons: HashSet<MouseButton>,
+    // None while the cursor is outside of the window
+    cursor_position: Option<(f32, f32)>,
+    mouse_delta: (f32, f32),
+    scroll_delta: f32,
+    // since the previous frame, in the order received
+    events: Vec<InputEvent>
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // held down now
+    pub fn pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    // pressed since the previous frame, key repeats aren't presses
+    pub fn just_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.just_pressed_keys.contains(&key)
+    }
+
+    // released since the previous frame
+    pub fn just_released(&self, key: VirtualKeyCode) -> bool {
+        self.just_released_keys.contains(&key)
+    }
+
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    pub fn mouse_just_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed_buttons.contains(&button)
+    }
+
+    pub fn mouse_just_released(&self, button: MouseButton) -> bool {
+        self.just_released_buttons.contains(&button)
+    }
+
+    // in pixels from the top left of the window, None while the cursor is outside of it
+    pub fn cursor_position(&self) -> Option<(f32, f32)> {
+        self.cursor_position
+    }
+
+    // raw mouse motion since the previous frame (about pixels), it keeps coming while the pointer is locked
+    pub fn mouse_delta(&self) -> (f32, f32) {
+        self.mouse_delta
+    }
+
+    // wheel notches since the previous frame, positive away from the user
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll_delta
+    }
+
+    // the events since the previous frame, in the order they were received
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    // when the last event of the frame was received, None without any
+    pub fn last_event_time(&self) -> Option<Instant> {
+        self.events.last().map(|event| event.time)
+    }
+
+    pub(crate) fn process_event(&mut self, event: &WindowEvent) {
+        let time = Instant::now();
+        let kind = match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+                ..
+            } => Self::track(*keycode, *state, &mut self.keys, &mut self.just_pressed_keys, &mut self.just_released_keys)
+                .map(|pressed| if pressed { InputEventKind::KeyPressed(*keycode) } else { InputEventKind::KeyReleased(*keycode) }),
+            WindowEvent::MouseInput { state, button, .. } => {
+                Self::track(*button, *state, &mut self.buttons, &mut self.just_pressed_buttons, &mut self.just_released_buttons)
+                    .map(|pressed| if pressed { InputEventKind::MousePressed(*button) } else { InputEventKind::MouseReleased(*button) })
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = (position.x as f32, position.y as f32);
+                self.cursor_position = Some(position);
+                Some(InputEventKind::CursorMoved(position.0, position.1))
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+                None
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                // pixel deltas (touchpads) are converted, like the camera controllers do
+                let notches = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0
+                };
+                self.scroll_delta += notches;
+                Some(InputEventKind::Scroll(notches))
+            },
+            // the releases are missed while the window isn't focused, so nothing stays held down
+            WindowEvent::Focused(false) => {
+                self.events.extend(self.keys.iter().map(|key| InputEvent { kind: InputEventKind::KeyReleased(*key), time }));
+                self.events.extend(self.buttons.iter().map(|button| InputEvent { kind: InputEventKind::MouseReleased(*button), time }));
+                self.just_released_keys.extend(self.keys.drain());
+                self.just_released_buttons.extend(self.buttons.drain());
+                None
+            },
+            _ => None
+        };
+        if let Some(kind) = kind {
+            self.events.push(InputEvent { kind, time });
+        }
+    }
+
+    pub(crate) fn process_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.mouse_delta.0 += delta.0 as f32;
+            self.mouse_delta.1 += delta.1 as f32;
+            self.events.push(InputEvent {
+                kind: InputEventKind::MouseMotion(delta.0 as f32, delta.1 as f32),
+                time: Instant::now()
+            });
+        }
+    }
+
+    // once the frame is over, the next one only sees the new presses & motion
+    pub(crate) fn end_frame(&mut self) {
+        self.just_pressed_keys.clear();
+        self.just_released_keys.clear();
+        self.just_pressed_buttons.clear();
+        self.just_released_buttons.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
+        self.events.clear();
+    }
+
+    // Some(true) for a press, Some(false) for a release, None if nothing changed (key repeat...)
+    fn track<T: Copy + Eq + std::hash::Hash>(
+        input: T,
+        state: ElementState,
+        held: &mut HashSet<T>,
+        just_pressed: &mut HashSet<T>,
+        just_released: &mut HashSet<T>
+    ) -> Option<bool> {
+        match state {
+            // `insert()` is false for key repeats
+            ElementState::Pressed => held.insert(input).then(|| {
+                just_pressed.insert(input);
+                true
+            }),
+            ElementState::Released => held.remove(&input).then(|| {
+                just_released.insert(input);
+                false
+            })
+        }
+    }
+}
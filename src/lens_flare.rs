@@ -0,0 +1,429 @@
+use nalgebra::{Matrix4, Vector3};
+
+use super::app_config::DepthMode;
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlareShape {
+    // soft glow, e.g. around the light itself
+    Disc,
+    // thin circle, e.g. a halo
+    Ring,
+    // ghost of the lens aperture
+    Hexagon
+}
+
+// One sprite of a lens flare chain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlareElement {
+    pub shape: FlareShape,
+    // along the axis from the light through the screen center: 0.0 on the light, 1.0 on the center, 2.0 mirrored
+    pub position: f32,
+    // fraction of the screen height
+    pub radius: f32,
+    // tinted by the light color & added to the scene, alpha scales the intensity
+    pub color: [f32; 4]
+}
+
+impl FlareElement {
+    pub fn new(shape: FlareShape, position: f32, radius: f32, color: [f32; 4]) -> Self {
+        Self {
+            shape,
+            position,
+            radius,
+            color
+        }
+    }
+}
+
+// Lens flare of the sun, returned by `Application::lens_flare()`.
+// The sun is tested against the depth buffer every frame, the flare fades out while it's hidden or off screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LensFlare {
+    // up to `MAX_ELEMENTS`, the extra ones aren't drawn
+    pub elements: Vec<FlareElement>,
+    // radius of the occlusion test around the sun, in pixels
+    pub occlusion_radius: f32,
+    // how fast the flare fades in & out, higher is faster (per second)
+    pub fade_speed: f32
+}
+
+impl LensFlare {
+    pub const MAX_ELEMENTS: usize = 16;
+
+    // no elements, add them with `with_element()`
+    pub fn new() -> Self {
+        Self {
+            elements: Vec::new(),
+            occlusion_radius: 8.0,
+            fade_speed: 8.0
+        }
+    }
+
+    // a glow on the sun, a halo & a chain of aperture ghosts across the screen
+    pub fn sun() -> Self {
+        Self::new()
+            .with_element(FlareElement::new(FlareShape::Disc, 0.0, 0.3, [1.0, 0.9, 0.7, 0.5]))
+            .with_element(FlareElement::new(FlareShape::Ring, 0.0, 0.2, [1.0, 0.8, 0.6, 0.15]))
+            .with_element(FlareElement::new(FlareShape::Hexagon, 0.5, 0.04, [0.6, 0.8, 1.0, 0.2]))
+            .with_element(FlareElement::new(FlareShape::Hexagon, 0.8, 0.07, [0.5, 1.0, 0.6, 0.15]))
+            .with_element(FlareElement::new(FlareShape::Disc, 1.2, 0.05, [1.0, 0.6, 0.3, 0.3]))
+            .with_element(FlareElement::new(FlareShape::Hexagon, 1.5, 0.1, [0.7, 0.5, 1.0, 0.12]))
+            .with_element(FlareElement::new(FlareShape::Ring, 2.0, 0.15, [0.6, 0.9, 1.0, 0.1]))
+    }
+
+    pub fn with_element(mut self, element: FlareElement) -> Self {
+        self.elements.push(element);
+        self
+    }
+}
+
+impl Default for LensFlare {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// `FlareElement` layout in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct FlareElementUniform {
+    color: [f32; 4],
+    params: [f32; 4] // position, radius, shape, unused
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct FlareUniform {
+    light: [f32; 4], // NDC position (xy), in front of the camera (z), depth of the far plane (w)
+    light_color: [f32; 4],
+    screen: [f32; 4], // width, height, occlusion radius, fade amount of this frame
+    elements: [FlareElementUniform; LensFlare::MAX_ELEMENTS]
+}
+
+// Occlusion test (compute) & additive flare sprites (render), drawn over the scene.
+// The smoothed visibility stays on the GPU, so there's no readback.
+pub(crate) struct LensFlarePass {
+    uniform_buffer: wgpu::Buffer,
+    visibility_buffer: wgpu::Buffer,
+    occlusion_bind_group_layout: wgpu::BindGroupLayout,
+    occlusion_bind_group: wgpu::BindGroup, // recreated with the depth texture
+    render_bind_group: wgpu::BindGroup,
+    occlusion_pipeline: Option<wgpu::ComputePipeline>, // None if the pipeline failed to build
+    render_pipeline: Option<wgpu::RenderPipeline>, // None if the pipeline failed to build
+    lens_flare: Option<LensFlare>,
+    light_direction: Vector3<f32>, // towards the light
+    light_color: [f32; 3],
+    depth_mode: DepthMode
+}
+
+impl LensFlarePass {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        depth_mode: DepthMode,
+        error_overlay: &mut ErrorOverlay
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lens Flare Uniform Buffer"),
+            size: std::mem::size_of::<FlareUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        // a single f32, zero initialized: the flare fades in on the first frames
+        let visibility_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lens Flare Visibility Buffer"),
+            size: std::mem::size_of::<f32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false
+        });
+
+        let uniform_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let visibility_entry = |visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // the compute pass writes the visibility, the render pass only reads it
+        let occlusion_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lens Flare Occlusion BindGroup Layout"),
+            entries: &[
+                uniform_entry(wgpu::ShaderStages::COMPUTE),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2
+                    },
+                    count: None,
+                },
+                visibility_entry(wgpu::ShaderStages::COMPUTE, false)
+            ]
+        });
+        let render_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lens Flare Render BindGroup Layout"),
+            entries: &[
+                uniform_entry(wgpu::ShaderStages::VERTEX),
+                visibility_entry(wgpu::ShaderStages::FRAGMENT, true)
+            ]
+        });
+        let occlusion_bind_group = Self::create_occlusion_bind_group(
+            device,
+            &occlusion_bind_group_layout,
+            &uniform_buffer,
+            depth_view,
+            &visibility_buffer
+        );
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lens Flare Render Bind Group"),
+            layout: &render_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: visibility_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        let pipelines = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Lens Flare Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("lens_flare.wgsl", include_str!("res/shaders/lens_flare.wgsl")))
+            });
+
+            let occlusion_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Lens Flare Occlusion Pipeline Layout"),
+                bind_group_layouts: &[&occlusion_bind_group_layout],
+                push_constant_ranges: &[]
+            });
+            let occlusion_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Lens Flare Occlusion Pipeline"),
+                layout: Some(&occlusion_pipeline_layout),
+                module: &shader_module,
+                entry_point: "occlusion_main"
+            });
+
+            let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Lens Flare Render Pipeline Layout"),
+                bind_group_layouts: &[&render_bind_group_layout],
+                push_constant_ranges: &[]
+            });
+            // additive: the flare only brightens the scene
+            let additive = wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add
+            };
+            let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Lens Flare Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the quads are generated from the vertex & instance indices
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: Some(wgpu::BlendState {
+                                color: additive,
+                                alpha: additive
+                            }),
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // screen space, over everything
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            });
+            (occlusion_pipeline, render_pipeline)
+        });
+        let (occlusion_pipeline, render_pipeline) = match pipelines {
+            Ok((occlusion_pipeline, render_pipeline)) => (Some(occlusion_pipeline), Some(render_pipeline)),
+            Err(error) => {
+                error_overlay.report("Lens Flare Pipelines", &error);
+                (None, None)
+            }
+        };
+
+        Self {
+            uniform_buffer,
+            visibility_buffer,
+            occlusion_bind_group_layout,
+            occlusion_bind_group,
+            render_bind_group,
+            occlusion_pipeline,
+            render_pipeline,
+            lens_flare: None,
+            light_direction: Vector3::y(),
+            light_color: [1.0; 3],
+            depth_mode
+        }
+    }
+
+    fn create_occlusion_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        visibility_buffer: &wgpu::Buffer
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lens Flare Occlusion Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: visibility_buffer.as_entire_binding()
+                }
+            ]
+        })
+    }
+
+    // the depth texture is recreated on resize
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView) {
+        self.occlusion_bind_group = Self::create_occlusion_bind_group(
+            device,
+            &self.occlusion_bind_group_layout,
+            &self.uniform_buffer,
+            depth_view,
+            &self.visibility_buffer
+        );
+    }
+
+    pub(crate) fn set_lens_flare(&mut self, lens_flare: Option<LensFlare>) {
+        self.lens_flare = lens_flare;
+    }
+
+    // `direction` points towards the light, `color` is its exposed color (1.0 for a light at the exposure's white point)
+    pub(crate) fn set_light(&mut self, direction: Vector3<f32>, color: [f32; 3]) {
+        self.light_direction = direction;
+        self.light_color = color;
+    }
+
+    // upload the light position & the flare elements of this frame
+    pub(crate) fn update(&mut self, queue: &wgpu::Queue, view_proj: Matrix4<f32>, width: u32, height: u32, delta_time: f32) {
+        let lens_flare = match &self.lens_flare {
+            Some(lens_flare) => lens_flare,
+            None => return
+        };
+
+        // the light is infinitely far away: project its direction (w = 0.0)
+        let clip = view_proj * self.light_direction.to_homogeneous();
+        let in_front = clip.w > 0.0;
+        let light = if in_front {
+            [clip.x / clip.w, clip.y / clip.w, 1.0, self.depth_mode.far_depth()]
+        } else {
+            [0.0; 4]
+        };
+
+        let mut elements = [FlareElementUniform { color: [0.0; 4], params: [0.0; 4] }; LensFlare::MAX_ELEMENTS];
+        for (uniform, element) in elements.iter_mut().zip(&lens_flare.elements) {
+            let shape = match element.shape {
+                FlareShape::Disc => 0.0,
+                FlareShape::Ring => 1.0,
+                FlareShape::Hexagon => 2.0
+            };
+            *uniform = FlareElementUniform {
+                color: element.color,
+                params: [element.position, element.radius, shape, 0.0]
+            };
+        }
+        // frame rate independent exponential smoothing
+        let fade = 1.0 - (-lens_flare.fade_speed * delta_time).exp();
+        let [r, g, b] = self.light_color;
+        let uniform = FlareUniform {
+            light,
+            light_color: [r, g, b, 0.0],
+            screen: [width as f32, height as f32, lens_flare.occlusion_radius, fade],
+            elements
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // run after the scene is drawn, the occlusion test reads the depth buffer.
+    pub(crate) fn render(&self, texture_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) {
+        let (occlusion_pipeline, render_pipeline) = match (&self.occlusion_pipeline, &self.render_pipeline) {
+            (Some(occlusion_pipeline), Some(render_pipeline)) => (occlusion_pipeline, render_pipeline),
+            _ => return
+        };
+        let element_count = match &self.lens_flare {
+            Some(lens_flare) if !lens_flare.elements.is_empty() => lens_flare.elements.len().min(LensFlare::MAX_ELEMENTS) as u32,
+            _ => return
+        };
+
+        {
+            let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Lens Flare Occlusion Pass")
+            });
+            compute_pass.set_pipeline(occlusion_pipeline);
+            compute_pass.set_bind_group(0, &self.occlusion_bind_group, &[]);
+            compute_pass.dispatch(1, 1, 1);
+        }
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lens Flare Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: None
+        });
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
+        // 2 triangles per element
+        render_pass.draw(0..6, 0..element_count);
+    }
+}
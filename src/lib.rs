@@ -1,7 +1,108 @@
+mod app_config;
 mod application;
+mod asset_loader;
+mod assets;
+mod bitmap_font;
+mod blur;
+mod camera;
+mod capture;
+mod cloth;
+mod csg;
+mod day_night;
+mod debug_draw;
+#[cfg(feature = "egui")]
+mod debug_ui;
+mod dynamic_mesh;
+mod environment;
+mod error_overlay;
+mod exposure;
+mod file_format;
+mod floating_origin;
+mod fracture;
 mod gpu;
+mod grid;
+mod hot_reload;
+mod input;
+mod lens_flare;
+mod light;
+mod localization;
+mod material;
+mod material_params;
+mod mesh;
+mod model;
+mod msaa;
+mod paint;
+mod parallax;
+mod pathfinding;
+mod pipeline_cache;
+mod pixel_perfect;
+mod polyline;
+mod post_process;
+mod profiler;
+mod render_state;
+mod render_target;
+mod scene;
+mod scene_loader;
+mod shader;
+mod simplify;
+mod skybox;
+mod sprite;
+mod sprite_animation;
+mod steering;
+mod tags;
 mod texture;
+mod thumbnail;
+mod time;
 mod transform;
+mod transition;
+mod validation;
 
+pub use app_config::{AppConfig, Backend, DepthMode, PresentMode, WindowMode};
 pub use application::Application;
-pub use transform::Transform;
\ No newline at end of file
+pub use asset_loader::{AssetHandle, AssetLoader, LoadState};
+pub use assets::AssetRoot;
+pub use blur::{BlurKernel, BlurPasses, MipChain};
+pub use camera::{Camera, CameraBounds, CameraController, CameraRig, FlyController, OrbitController, OrthographicCamera, PanZoomController, PerspectiveCamera, PixelPerfect, Projection};
+pub use capture::{CaptureConfig, FrameStream, StreamedFrame};
+pub use cloth::{Cloth, ClothCollider};
+pub use day_night::{DayNightCycle, SkyKey};
+pub use debug_draw::{DebugDraw, DebugDrawCategory, DebugDrawSettings};
+pub use dynamic_mesh::DynamicMesh;
+pub use environment::{Environment, Fog};
+pub use exposure::{point_light_candela, spot_light_candela, Exposure};
+pub use file_format::{FileFormat, Migration};
+pub use floating_origin::{PreciseTransform, WorldOrigin};
+pub use fracture::{Debris, DebrisSettings, Destructible, FracturePiece, FracturedMesh};
+pub use input::{Input, InputEvent, InputEventKind};
+pub use lens_flare::{FlareElement, FlareShape, LensFlare};
+pub use light::{Light, LightKind, LightOccluder};
+pub use localization::{Localization, StringTable};
+pub use material::{Material, SamplerSettings, TextureFilter, TextureWrap};
+pub use material_params::{material_param_animation_system, MaterialParamAnimation, MaterialParamOverrides, MaterialParamTrack, MaterialParamValue};
+pub use mesh::{Mesh, MeshDraw, Vertex};
+pub use model::{Model, ModelMesh};
+pub use paint::{Brush, PaintCanvas, PixelRegion};
+pub use parallax::{ParallaxLayer, ParallaxRepeat};
+pub use pathfinding::{PathAlgorithm, PathGrid};
+pub use polyline::{LineWidth, Polyline};
+pub use post_process::{PostEffect, PostEffectKind, PostProcessStack, TonemapOperator};
+pub use profiler::{profile_scope, FrameProfile, ProfileScope, ProfileSpan, Profiler};
+pub use render_state::{AlphaMode, CullMode, DepthBias, RenderState};
+pub use render_target::PingPongTargets;
+pub use scene::{MaterialHandle, MeshHandle, Scene};
+pub use scene_loader::{LoadProgress, LoadedScene, SceneLoad, SceneLoader};
+pub use shader::{MaterialParam, MaterialParamType, MaterialShader, MaterialShaderLayout};
+pub use simplify::MeshSimplifier;
+pub use sprite::Sprite;
+pub use sprite_animation::{SpriteAnimator, SpriteClip, SpriteCondition, SpriteSheet, SpriteTransition};
+pub use steering::{steering_system, Flocking, SteeringAgent, Wander};
+pub use tags::{GameplayTag, Tags};
+pub use texture::{Cubemap, Texture, UvTransform};
+pub use thumbnail::{ThumbnailRenderer, ThumbnailSubject};
+pub use time::Time;
+pub use transform::{transform_propagation_system, Children, Parent, Transform};
+pub use transition::{Transition, TransitionEffect};
+pub use validation::{SceneIssue, SceneReport, SceneStats};
+pub use winit::event::{MouseButton, VirtualKeyCode};
+#[cfg(feature = "egui")]
+pub use egui;
\ No newline at end of file
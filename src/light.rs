@@ -0,0 +1,190 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+// lights shaded per frame, the others are ignored
+pub(crate) const MAX_LIGHTS: usize = 16;
+// occluder edges casting shadows per frame, the others are ignored
+pub(crate) const MAX_OCCLUDER_SEGMENTS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    // infinitely far away (moon, second sun...), `direction` is the direction the light travels in
+    Directional { direction: Vector3<f32> },
+    // shines in every direction from the entity's `Transform`, fading out to nothing at `range`
+    Point { range: f32 },
+    // a point light shining along `direction` (world space) only, fully inside `inner_angle` & fading out to `outer_angle`
+    // tips: the angles are half angles of the cone, in radians.
+    Spot { direction: Vector3<f32>, range: f32, inner_angle: f32, outer_angle: f32 }
+}
+
+// Light source component of a `Scene` entity, shaded (Blinn-Phong) on top of the sun & ambient of the `Environment`.
+// tips: only the first 16 lights of the scene are shaded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    // illuminance in lux for directional lights, luminous intensity in candela for point & spot lights
+    // (see `point_light_candela()` & `spot_light_candela()`)
+    pub intensity: f32,
+    // radius of the light source casting shadows from the `LightOccluder`s, None for no shadows
+    pub occluder_shadows: Option<f32>
+}
+
+impl Light {
+    pub fn directional(direction: Vector3<f32>, color: [f32; 3], illuminance: f32) -> Self {
+        Self {
+            kind: LightKind::Directional { direction },
+            color,
+            intensity: illuminance,
+            occluder_shadows: None
+        }
+    }
+
+    pub fn point(color: [f32; 3], intensity: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Point { range },
+            color,
+            intensity,
+            occluder_shadows: None
+        }
+    }
+
+    // e.g. a torch or a street lamp seen from the side in a 2D scene
+    pub fn spot(direction: Vector3<f32>, color: [f32; 3], intensity: f32, range: f32, inner_angle: f32, outer_angle: f32) -> Self {
+        Self {
+            kind: LightKind::Spot { direction, range, inner_angle, outer_angle },
+            color,
+            intensity,
+            occluder_shadows: None
+        }
+    }
+
+    // Shadows of the `LightOccluder`s of the scene, cast in the xy plane (2D scenes) by point & spot lights.
+    // `source_radius` is the radius of the light source in world units: 0.0 casts hard shadows, larger ones softer penumbrae.
+    pub fn with_occluder_shadows(mut self, source_radius: f32) -> Self {
+        self.occluder_shadows = Some(source_radius.max(0.0));
+        self
+    }
+
+    // `position` is ignored by directional lights
+    fn to_raw(self, position: Point3<f32>, exposure: f32) -> LightRaw {
+        let normalize = |direction: Vector3<f32>| direction.try_normalize(f32::EPSILON).unwrap_or_else(|| -Vector3::y());
+        let (position, range, direction, cone) = match self.kind {
+            LightKind::Directional { direction } => {
+                let direction = normalize(direction);
+                ([direction.x, direction.y, direction.z, 0.0], 0.0, Vector3::zeros(), [0.0; 4])
+            }
+            LightKind::Point { range } => ([position.x, position.y, position.z, 1.0], range.max(f32::EPSILON), Vector3::zeros(), [0.0; 4]),
+            LightKind::Spot { direction, range, inner_angle, outer_angle } => {
+                let outer_angle = outer_angle.clamp(0.0, std::f32::consts::FRAC_PI_2);
+                let inner_angle = inner_angle.clamp(0.0, outer_angle);
+                let cone = [outer_angle.cos(), inner_angle.cos(), 0.0, 0.0];
+                ([position.x, position.y, position.z, 2.0], range.max(f32::EPSILON), normalize(direction), cone)
+            }
+        };
+        // directional lights don't cast occluder shadows
+        let shadow_radius = match self.kind {
+            LightKind::Directional { .. } => -1.0,
+            _ => self.occluder_shadows.unwrap_or(-1.0)
+        };
+        // pre-exposed, like the environment
+        let intensity = self.intensity * exposure;
+        LightRaw {
+            position,
+            color: [self.color[0] * intensity, self.color[1] * intensity, self.color[2] * intensity, range],
+            direction: [direction.x, direction.y, direction.z, shadow_radius],
+            cone
+        }
+    }
+}
+
+// 2D shadow caster component of a `Scene` entity: a closed polygon (or a wall, with two points) in the entity's xy plane, placed by its `Transform`.
+// It blocks the light of the lights casting occluder shadows (see `Light::with_occluder_shadows()`) in the xy plane,
+// e.g. the walls & crates of a 2D level, lit from the side by torches.
+// tips: the surfaces inside a polygon are shadowed by its far edges, give the occluders the size of the sprite's base
+// (or leave a gap) to keep the sprite itself lit; only the first 64 edges of the scene cast shadows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightOccluder {
+    pub points: Vec<[f32; 2]>
+}
+
+impl LightOccluder {
+    pub fn new(points: Vec<[f32; 2]>) -> Self {
+        Self {
+            points
+        }
+    }
+
+    // axis-aligned box of `size` centered on the entity
+    pub fn rectangle(width: f32, height: f32) -> Self {
+        let (x, y) = (width * 0.5, height * 0.5);
+        Self::new(vec![[-x, -y], [x, -y], [x, y], [-x, y]])
+    }
+
+    // the polygon's edges in world space (x0, y0, x1, y1), a single point has none
+    pub(crate) fn segments(&self, transform: &Matrix4<f32>, segments: &mut Vec<[f32; 4]>) {
+        if self.points.len() < 2 {
+            return;
+        }
+        let world = self.points
+            .iter()
+            .map(|point| transform.transform_point(&Point3::new(point[0], point[1], 0.0)))
+            .collect::<Vec<_>>();
+        // two points make a single wall, not a polygon
+        let edges = if world.len() == 2 { 1 } else { world.len() };
+        for i in 0..edges {
+            let (start, end) = (world[i], world[(i + 1) % world.len()]);
+            segments.push([start.x, start.y, end.x, end.y]);
+        }
+    }
+}
+
+// `Light` layout in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightRaw {
+    position: [f32; 4], // w: 2.0 for spot lights, 1.0 for point lights, 0.0 for directional lights (xyz is then their direction)
+    color: [f32; 4], // premultiplied by the intensity & exposure, w: range of point & spot lights
+    direction: [f32; 4], // of spot lights, w: radius of the source casting occluder shadows, negative for none
+    cone: [f32; 4] // of spot lights: cosines of the outer & inner angles
+}
+
+// the lights of a frame in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LightsUniform {
+    count: u32,
+    _padding: [u32; 3], // the light array starts on a 16 bytes block
+    lights: [LightRaw; MAX_LIGHTS]
+}
+
+impl LightsUniform {
+    // lights with their world position, e.g. from `Scene::lights()`
+    pub(crate) fn new(lights: &[(Light, Point3<f32>)], exposure: f32) -> Self {
+        let mut uniform: Self = bytemuck::Zeroable::zeroed();
+        for (raw, (light, position)) in uniform.lights.iter_mut().zip(lights) {
+            *raw = light.to_raw(*position, exposure);
+        }
+        uniform.count = lights.len().min(MAX_LIGHTS) as u32;
+        uniform
+    }
+}
+
+// the occluder edges of a frame in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct OccludersUniform {
+    count: u32,
+    _padding: [u32; 3],
+    segments: [[f32; 4]; MAX_OCCLUDER_SEGMENTS]
+}
+
+impl OccludersUniform {
+    // world space edges, e.g. from `Scene::occluder_segments()`
+    pub(crate) fn new(segments: &[[f32; 4]]) -> Self {
+        let mut uniform: Self = bytemuck::Zeroable::zeroed();
+        let count = segments.len().min(MAX_OCCLUDER_SEGMENTS);
+        uniform.segments[..count].copy_from_slice(&segments[..count]);
+        uniform.count = count as u32;
+        uniform
+    }
+}
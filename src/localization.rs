@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use super::file_format::FileFormat;
+
+// Translated strings of a language, by key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StringTable {
+    entries: HashMap<String, String>
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the versioned `strings` file format, tables written by hand before it have no header
+    pub fn file_format() -> FileFormat {
+        FileFormat::new("strings", 1).with_unversioned(1)
+    }
+
+    // Parse a `key = value` table, one entry per line.
+    // Lines starting with `#` are comments, `\n` & `\\` are escapes in values.
+    pub fn parse(source: &str) -> Result<Self> {
+        let source = Self::file_format().read(source)?;
+        let mut table = Self::new();
+        for (line_index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => bail!("line {}: expected `key = value`, got `{}`", line_index + 1, line)
+            };
+            if key.is_empty() {
+                bail!("line {}: empty key", line_index + 1);
+            }
+            table.insert(key, &Self::unescape(value));
+        }
+        Ok(table)
+    }
+
+    fn unescape(value: &str) -> String {
+        let mut unescaped = String::with_capacity(value.len());
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => unescaped.push('\n'),
+                    Some(other) => unescaped.push(other),
+                    None => unescaped.push('\\')
+                }
+            } else {
+                unescaped.push(c);
+            }
+        }
+        unescaped
+    }
+
+    fn escape(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('\n', "\\n")
+    }
+
+    // the table in the format read by `parse()`, with its header & sorted by key
+    pub fn to_source(&self) -> String {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort();
+        let body = entries
+            .into_iter()
+            .map(|(key, value)| format!("{} = {}\n", key, Self::escape(value)))
+            .collect::<String>();
+        Self::file_format().write(&body)
+    }
+
+    pub fn insert(&mut self, key: &str, value: &str) {
+        self.entries.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+}
+
+// String tables per language, with runtime language switching.
+// Missing keys fall back along the language chain: "pt-BR" -> "pt" -> fallback language.
+#[derive(Clone, Debug)]
+pub struct Localization {
+    tables: HashMap<String, StringTable>,
+    fonts: HashMap<String, Vec<String>>, // font families by language, in preference order
+    language: String,
+    fallback_language: String
+}
+
+impl Localization {
+    // starts in `fallback_language`, which should have every key.
+    pub fn new(fallback_language: &str) -> Self {
+        Self {
+            tables: HashMap::new(),
+            fonts: HashMap::new(),
+            language: fallback_language.to_string(),
+            fallback_language: fallback_language.to_string()
+        }
+    }
+
+    pub fn add_language(&mut self, language: &str, table: StringTable) {
+        self.tables.insert(language.to_string(), table);
+    }
+
+    // parse & add the table of `language`, see `StringTable::parse()`.
+    pub fn load_language(&mut self, language: &str, source: &str) -> Result<()> {
+        let table = StringTable::parse(source)?;
+        self.add_language(language, table);
+        Ok(())
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.tables.keys().map(String::as_str)
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    // Switch the current language, fails if neither it nor a parent language ("pt" for "pt-BR") has a table.
+    pub fn set_language(&mut self, language: &str) -> Result<()> {
+        let language_chain = Self::parent_languages(language);
+        if !language_chain.iter().any(|language| self.tables.contains_key(*language)) {
+            bail!("no string table for language `{}`", language);
+        }
+        self.language = language.to_string();
+        Ok(())
+    }
+
+    // "zh-Hant-TW" -> ["zh-Hant-TW", "zh-Hant", "zh"]
+    fn parent_languages(language: &str) -> Vec<&str> {
+        let mut chain = vec![language];
+        let mut language = language;
+        while let Some((parent, _)) = language.rsplit_once('-') {
+            chain.push(parent);
+            language = parent;
+        }
+        chain
+    }
+
+    // current language, its parents, then the fallback language
+    fn language_chain(&self) -> Vec<&str> {
+        let mut chain = Self::parent_languages(&self.language);
+        if !chain.contains(&self.fallback_language.as_str()) {
+            chain.push(&self.fallback_language);
+        }
+        chain
+    }
+
+    // None if no language of the chain has `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.language_chain()
+            .into_iter()
+            .filter_map(|language| self.tables.get(language))
+            .find_map(|table| table.get(key))
+    }
+
+    // Translate `key`, the key itself is shown when it's missing, so missing strings are easy to spot.
+    pub fn tr(&self, key: &str) -> String {
+        self.get(key).unwrap_or(key).to_string()
+    }
+
+    // Translate `key` and replace its `{name}` placeholders.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let mut text = self.tr(key);
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    // Font families able to display `language`, in preference order (e.g. a CJK font for "ja").
+    pub fn set_fonts(&mut self, language: &str, fonts: &[&str]) {
+        self.fonts.insert(language.to_string(), fonts.iter().map(|font| font.to_string()).collect());
+    }
+
+    // Fonts to try for the current language: its own, its parents' & the fallback language's ones.
+    pub fn fonts(&self) -> Vec<&str> {
+        let mut fonts: Vec<&str> = Vec::new();
+        for language in self.language_chain() {
+            for font in self.fonts.get(language).into_iter().flatten() {
+                if !fonts.contains(&font.as_str()) {
+                    fonts.push(font);
+                }
+            }
+        }
+        fonts
+    }
+}
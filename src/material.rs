@@ -0,0 +1,627 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use wgpu::util::DeviceExt; // for `create_buffer_init`
+
+use super::material_params::{MaterialParamOverrides, MaterialParamValue, MaterialParams};
+use super::pipeline_cache::ScenePipelineKey;
+use super::render_state::RenderState;
+use super::shader::MaterialShader;
+use super::texture::{Texture, UvTransform};
+
+// How the texels of a texture are blended when it's magnified or minified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    // blend the neighbouring texels, smooth
+    Linear,
+    // the closest texel, crisp (pixel art, voxels...)
+    Nearest
+}
+
+impl TextureFilter {
+    fn to_wgpu(self) -> wgpu::FilterMode {
+        match self {
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest
+        }
+    }
+}
+
+// What's sampled outside of the 0.0 ~ 1.0 texture coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureWrap {
+    // tile the texture, so materials can scale their UVs (see `UvTransform`)
+    Repeat,
+    // tile the texture, mirrored every other tile
+    MirrorRepeat,
+    // stretch the edge texels
+    Clamp
+}
+
+impl TextureWrap {
+    fn to_wgpu(self) -> wgpu::AddressMode {
+        match self {
+            TextureWrap::Repeat => wgpu::AddressMode::Repeat,
+            TextureWrap::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+            TextureWrap::Clamp => wgpu::AddressMode::ClampToEdge
+        }
+    }
+}
+
+// Sampler of a material's albedo texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplerSettings {
+    pub mag_filter: TextureFilter,
+    pub min_filter: TextureFilter,
+    pub wrap: TextureWrap
+}
+
+impl SamplerSettings {
+    // nearest filtering both ways, e.g. for pixel art
+    pub fn pixelated() -> Self {
+        Self {
+            mag_filter: TextureFilter::Nearest,
+            min_filter: TextureFilter::Nearest,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for SamplerSettings {
+    // smooth up close, repeated
+    fn default() -> Self {
+        Self {
+            mag_filter: TextureFilter::Linear,
+            min_filter: TextureFilter::Nearest,
+            wrap: TextureWrap::Repeat
+        }
+    }
+}
+
+// `Material` data in the shader uniform buffer
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    tint: [f32; 4],
+    alpha_cutoff: f32, // 0.0 never discards anything
+    specular: f32,
+    shininess: f32,
+    normal_map: f32 // 1.0 if the normal map is sampled, uniform buffers are laid out in 16 bytes blocks anyway
+}
+
+// GPU copy of a `Material`, its bind group follows the engine's texture bind group layout.
+pub(crate) struct MaterialBinding {
+    pub(crate) bind_group: wgpu::BindGroup,
+    // painted by `PaintCanvas` for the engine's materials
+    pub(crate) texture: Texture,
+    uv_buffer: wgpu::Buffer,
+    // kept alive with the bind group
+    _normal_texture: Texture,
+    _sampler: wgpu::Sampler,
+    _material_buffer: wgpu::Buffer
+}
+
+impl MaterialBinding {
+    // tips: not cached, see `Material::binding()`
+    pub(crate) fn new(
+        material: &Material,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_bind_group_layout: &wgpu::BindGroupLayout
+    ) -> Result<Self> {
+        let label = format!("{} material", material.name);
+        // materials without an albedo texture sample plain white, i.e. their tint
+        let albedo = material.albedo
+            .clone()
+            .unwrap_or_else(|| image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+        let texture = Texture::from_image(device, queue, &image::DynamicImage::ImageRgba8(albedo), Some(&label))?;
+        // flat (+z in tangent space) without a normal map
+        let normal_map = material.normal_map
+            .clone()
+            .unwrap_or_else(|| image::RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 255, 255])));
+        let normal_texture = Texture::from_linear_image(device, queue, &image::DynamicImage::ImageRgba8(normal_map), Some(&label))?;
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&label),
+            address_mode_u: material.sampler.wrap.to_wgpu(),
+            address_mode_v: material.sampler.wrap.to_wgpu(),
+            address_mode_w: material.sampler.wrap.to_wgpu(),
+            mag_filter: material.sampler.mag_filter.to_wgpu(),
+            min_filter: material.sampler.min_filter.to_wgpu(),
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let uv_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material UV Transform Buffer"),
+            contents: bytemuck::cast_slice(&[material.uv_transform().to_uniform(0.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        });
+        let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[MaterialUniform {
+                tint: material.tint,
+                alpha_cutoff: material.render_state.alpha_mode.cutoff(),
+                specular: material.specular,
+                shininess: material.shininess,
+                normal_map: if material.normal_map.is_some() { 1.0 } else { 0.0 }
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&label),
+            layout: texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uv_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: material_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&normal_texture.view)
+                }
+            ]
+        });
+        Ok(Self {
+            bind_group,
+            texture,
+            uv_buffer,
+            _normal_texture: normal_texture,
+            _sampler: sampler,
+            _material_buffer: material_buffer
+        })
+    }
+
+    // the elapsed time (in seconds) drives UV scrolling
+    pub(crate) fn update(&self, queue: &wgpu::Queue, uv_transform: UvTransform, elapsed: f32) {
+        queue.write_buffer(&self.uv_buffer, 0, bytemuck::cast_slice(&[uv_transform.to_uniform(elapsed)]));
+    }
+}
+
+// GPU copy of a custom `Material`, its bind group follows the layout reflected from its `MaterialShader`.
+// The parameters are laid out in blocks bound at a dynamic offset: the material's own values first,
+// then a block per instance overriding them this frame (see `MaterialParamOverrides`).
+pub(crate) struct ShaderMaterialBinding {
+    pub(crate) bind_group: wgpu::BindGroup,
+    // None without parameters
+    params_buffer: Option<wgpu::Buffer>,
+    // bytes from a block to the next, aligned for dynamic offsets
+    block_stride: u64,
+    block_capacity: usize,
+    // shared with the binding replacing this one when it grows
+    textures: Rc<Vec<Texture>>,
+    sampler: Rc<wgpu::Sampler>
+}
+
+impl ShaderMaterialBinding {
+    // tips: not cached, see `Material::shader_binding()`
+    pub(crate) fn new(material: &Material, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Self> {
+        let inputs = material.shader_inputs()?;
+        let label = format!("{} material", material.name);
+        // texture slots without an image sample plain white
+        let textures = inputs.textures
+            .iter()
+            .map(|image| {
+                let image = image
+                    .clone()
+                    .unwrap_or_else(|| image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+                Texture::from_image(device, queue, &image::DynamicImage::ImageRgba8(image), Some(&label))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // every slot is sampled with the material's sampler settings
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&label),
+            address_mode_u: material.sampler.wrap.to_wgpu(),
+            address_mode_v: material.sampler.wrap.to_wgpu(),
+            address_mode_w: material.sampler.wrap.to_wgpu(),
+            mag_filter: material.sampler.mag_filter.to_wgpu(),
+            min_filter: material.sampler.min_filter.to_wgpu(),
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Ok(Self::with_blocks(material, inputs, device, queue, Rc::new(textures), Rc::new(sampler), 1))
+    }
+
+    fn with_blocks(
+        material: &Material,
+        inputs: &ShaderInputs,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        textures: Rc<Vec<Texture>>,
+        sampler: Rc<wgpu::Sampler>,
+        block_capacity: usize
+    ) -> Self {
+        let label = format!("{} material", material.name);
+        let params = inputs.params.borrow();
+        let block_size = params.as_bytes().len() as u64;
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let block_stride = block_size.div_ceil(alignment) * alignment;
+        let params_buffer = (block_size > 0).then(|| {
+            let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Material Params Buffer"),
+                size: block_stride * block_capacity as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false
+            });
+            // the material's own block, the instances' ones are written every frame
+            queue.write_buffer(&params_buffer, 0, params.as_bytes());
+            params_buffer
+        });
+
+        let mut entries = Vec::new();
+        if let Some(params_buffer) = &params_buffer {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: params_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(block_size)
+                })
+            });
+        }
+        for (slot, texture) in textures.iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: MaterialShader::texture_binding(slot),
+                resource: wgpu::BindingResource::TextureView(&texture.view)
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: MaterialShader::sampler_binding(slot),
+                resource: wgpu::BindingResource::Sampler(&sampler)
+            });
+        }
+        // tips: wgpu shares the layouts with equal entries, this one is compatible with the pipelines' one
+        let bind_group_layout = inputs.shader.create_bind_group_layout(device);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&label),
+            layout: &bind_group_layout,
+            entries: &entries
+        });
+        Self {
+            bind_group,
+            params_buffer,
+            block_stride,
+            block_capacity,
+            textures,
+            sampler
+        }
+    }
+
+    // write the parameters of a block, 0 being the material's own values
+    pub(crate) fn write_block(&self, queue: &wgpu::Queue, block: usize, params: &MaterialParams) {
+        if let Some(params_buffer) = &self.params_buffer {
+            queue.write_buffer(params_buffer, block as u64 * self.block_stride, params.as_bytes());
+        }
+    }
+
+    // what `set_bind_group()` draws a block with, None without parameters
+    pub(crate) fn dynamic_offset(&self, block: usize) -> Option<u32> {
+        self.params_buffer.as_ref().map(|_| (block as u64 * self.block_stride) as u32)
+    }
+}
+
+// What a custom `Material` feeds its `MaterialShader` with.
+struct ShaderInputs {
+    shader: MaterialShader,
+    params: RefCell<MaterialParams>,
+    // set since the parameters were last uploaded
+    params_changed: Cell<bool>,
+    // an image per texture slot of the shader
+    textures: Vec<Option<image::RgbaImage>>,
+    binding: RefCell<Option<Rc<ShaderMaterialBinding>>>
+}
+
+// Surface of a mesh: albedo & normal textures, sampler settings, color tint, specular highlights & render state.
+// The render state also picks the shader variant, e.g. the alpha tested one of `AlphaMode::Mask`.
+// Its bind group is created the first time it's drawn, then reused: share a material between meshes with `Rc<Material>`.
+// A custom material is drawn with its own shader instead, see `Material::custom()`.
+pub struct Material {
+    name: String,
+    albedo: Option<image::RgbaImage>,
+    normal_map: Option<image::RgbaImage>,
+    tint: [f32; 4],
+    specular: f32,
+    shininess: f32,
+    sampler: SamplerSettings,
+    uv_transform: Cell<UvTransform>,
+    // set since the UVs were last uploaded
+    uv_transform_changed: Cell<bool>,
+    render_state: RenderState,
+    binding: RefCell<Option<Rc<MaterialBinding>>>,
+    // None for the engine's shader
+    shader: Option<ShaderInputs>
+}
+
+impl Material {
+    // white, untextured & opaque, with soft highlights
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            albedo: None,
+            normal_map: None,
+            tint: [1.0; 4],
+            specular: 0.25,
+            shininess: 32.0,
+            sampler: SamplerSettings::default(),
+            uv_transform: Cell::new(UvTransform::new()),
+            uv_transform_changed: Cell::new(false),
+            render_state: RenderState::new(),
+            binding: RefCell::new(None),
+            shader: None
+        }
+    }
+
+    // Drawn with a user material shader: its parameters start zeroed & its texture slots white,
+    // the albedo, normal map, tint & specular settings are left to the shader.
+    // tips: the alpha mode of the render state blends, but doesn't discard: that's up to the shader.
+    // Fails if the shader doesn't match its declarations, see `MaterialShader::reflect()`.
+    pub fn custom(name: &str, shader: &MaterialShader) -> Result<Self> {
+        let layout = shader.reflect()?;
+        Ok(Self {
+            shader: Some(ShaderInputs {
+                shader: shader.clone(),
+                params: RefCell::new(MaterialParams::new(shader, &layout)),
+                params_changed: Cell::new(false),
+                textures: vec![None; shader.textures().len()],
+                binding: RefCell::new(None)
+            }),
+            ..Self::new(name)
+        })
+    }
+
+    // the image of a texture slot of the custom material's shader
+    pub fn with_texture(mut self, slot: &str, image: image::RgbaImage) -> Result<Self> {
+        let inputs = self.shader
+            .as_mut()
+            .ok_or_else(|| anyhow!("material `{}` has no shader with texture slots", self.name))?;
+        let index = inputs.shader
+            .textures()
+            .iter()
+            .position(|name| name == slot)
+            .ok_or_else(|| anyhow!("material `{}`: unknown texture slot `{}`", self.name, slot))?;
+        inputs.textures[index] = Some(image);
+        Ok(self)
+    }
+
+    // the initial value of a parameter of the custom material's shader
+    pub fn with_param(self, name: &str, value: MaterialParamValue) -> Result<Self> {
+        self.set_param(name, value)?;
+        Ok(self)
+    }
+
+    // e.g. from `AssetRoot::load_image()`, it's uploaded the first time the material is drawn
+    pub fn with_albedo(mut self, albedo: image::RgbaImage) -> Self {
+        self.albedo = Some(albedo);
+        self
+    }
+
+    // Tangent space normals (OpenGL convention: green is up in the image), sampled like the albedo,
+    // e.g. to light a flat sprite as if it had relief.
+    // tips: the tangents come from the UVs, so the mesh needs non-degenerate texture coordinates.
+    pub fn with_normal_map(mut self, normal_map: image::RgbaImage) -> Self {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    // multiplied with the albedo (& the vertex colors)
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    // Blinn-Phong highlights: `specular` scales them (0.0 for none), `shininess` tightens them (~1 ~ 1000).
+    pub fn with_specular(mut self, specular: f32, shininess: f32) -> Self {
+        self.specular = specular.max(0.0);
+        self.shininess = shininess.max(1.0);
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerSettings) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn with_uv_transform(self, uv_transform: UvTransform) -> Self {
+        self.uv_transform.set(uv_transform);
+        self
+    }
+
+    pub fn with_render_state(mut self, render_state: RenderState) -> Self {
+        self.render_state = render_state;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn albedo(&self) -> Option<&image::RgbaImage> {
+        self.albedo.as_ref()
+    }
+
+    pub fn normal_map(&self) -> Option<&image::RgbaImage> {
+        self.normal_map.as_ref()
+    }
+
+    pub fn tint(&self) -> [f32; 4] {
+        self.tint
+    }
+
+    pub fn specular(&self) -> f32 {
+        self.specular
+    }
+
+    pub fn shininess(&self) -> f32 {
+        self.shininess
+    }
+
+    pub fn sampler(&self) -> SamplerSettings {
+        self.sampler
+    }
+
+    pub fn uv_transform(&self) -> UvTransform {
+        self.uv_transform.get()
+    }
+
+    // change the UVs while the material is in use, e.g. to the frame of a `SpriteAnimator`
+    pub fn set_uv_transform(&self, uv_transform: UvTransform) {
+        self.uv_transform.set(uv_transform);
+        self.uv_transform_changed.set(true);
+    }
+
+    // whether `set_uv_transform()` was called since the last time, the UVs are uploaded again then
+    pub(crate) fn take_uv_transform_changed(&self) -> bool {
+        self.uv_transform_changed.replace(false)
+    }
+
+    pub fn render_state(&self) -> RenderState {
+        self.render_state
+    }
+
+    // None for the engine's shader
+    pub fn shader(&self) -> Option<&MaterialShader> {
+        self.shader.as_ref().map(|inputs| &inputs.shader)
+    }
+
+    // change a parameter of the custom material's shader, it's uploaded before the next frame
+    pub fn set_param(&self, name: &str, value: MaterialParamValue) -> Result<()> {
+        let inputs = self.shader_inputs()?;
+        inputs.params
+            .borrow_mut()
+            .set(name, value)
+            .map_err(|error| error.context(format!("material `{}`", self.name)))?;
+        inputs.params_changed.set(true);
+        Ok(())
+    }
+
+    // None for the engine's shader, if the parameter doesn't exist or has never been set
+    pub fn param(&self, name: &str) -> Option<MaterialParamValue> {
+        self.shader.as_ref().and_then(|inputs| inputs.params.borrow().get(name))
+    }
+
+    fn shader_inputs(&self) -> Result<&ShaderInputs> {
+        self.shader
+            .as_ref()
+            .ok_or_else(|| anyhow!("material `{}` has no shader parameters", self.name))
+    }
+
+    // The parameters of an instance of the custom material, with its overrides applied.
+    // tips: the overrides which aren't parameters of the shader are left out, see `Scene::validate()`.
+    pub(crate) fn overridden_params(&self, overrides: &MaterialParamOverrides) -> Option<MaterialParams> {
+        let inputs = self.shader.as_ref()?;
+        Some(inputs.params.borrow().with_overrides(overrides))
+    }
+
+    // the pipeline the material is drawn with, in `render_state`
+    pub(crate) fn pipeline_key(&self, render_state: RenderState) -> ScenePipelineKey {
+        ScenePipelineKey {
+            shader: self.shader().map(|shader| shader.key()),
+            render_state
+        }
+    }
+
+    // Sliders of the custom material's parameters (feature "egui"), for `Application::ui()`.
+    #[cfg(feature = "egui")]
+    pub fn inspector(&self, ui: &mut egui::Ui) {
+        let inputs = match &self.shader {
+            Some(inputs) => inputs,
+            None => {
+                ui.label(format!("{}: engine shader", self.name));
+                return;
+            }
+        };
+        ui.label(format!("{} ({})", self.name, inputs.shader.label()));
+        for param in inputs.shader.params() {
+            let mut value = self.param(&param.name).unwrap_or_else(|| MaterialParamValue::zeroed(param.ty));
+            let changed = ui.horizontal(|ui| {
+                ui.label(&param.name);
+                let components: &mut [f32] = match &mut value {
+                    MaterialParamValue::Float(value) => std::slice::from_mut(value),
+                    MaterialParamValue::Vec2(value) => value,
+                    MaterialParamValue::Vec3(value) => value,
+                    MaterialParamValue::Vec4(value) => value,
+                    MaterialParamValue::Mat4(value) => value.as_flattened_mut()
+                };
+                // every component gets its slider, even after a changed one
+                let mut changed = false;
+                for component in components {
+                    changed |= ui.add(egui::DragValue::new(component).speed(0.01)).changed();
+                }
+                changed
+            })
+            .inner;
+            if changed {
+                // the type is the declared one
+                let _ = self.set_param(&param.name, value);
+            }
+        }
+    }
+
+    // upload the material the first time, then reuse its bind group, None for custom materials
+    pub(crate) fn binding(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_bind_group_layout: &wgpu::BindGroupLayout
+    ) -> Option<Rc<MaterialBinding>> {
+        if self.shader.is_some() {
+            return None;
+        }
+        let mut binding = self.binding.borrow_mut();
+        if binding.is_none() {
+            match MaterialBinding::new(self, device, queue, texture_bind_group_layout) {
+                Ok(material_binding) => *binding = Some(Rc::new(material_binding)),
+                Err(error) => {
+                    eprintln!("{:?}", error);
+                    return None;
+                }
+            }
+        }
+        binding.clone()
+    }
+
+    // Upload the custom material the first time, then reuse its bind group with its parameters up to date.
+    // It's replaced by a larger one when `blocks` don't fit, the instances' blocks are left to the caller.
+    pub(crate) fn shader_binding(&self, device: &wgpu::Device, queue: &wgpu::Queue, blocks: usize) -> Option<Rc<ShaderMaterialBinding>> {
+        let inputs = self.shader.as_ref()?;
+        let mut binding = inputs.binding.borrow_mut();
+        match binding.as_ref() {
+            Some(shader_binding) if shader_binding.block_capacity < blocks => {
+                let textures = shader_binding.textures.clone();
+                let sampler = shader_binding.sampler.clone();
+                let block_capacity = blocks.next_power_of_two();
+                *binding = Some(Rc::new(ShaderMaterialBinding::with_blocks(self, inputs, device, queue, textures, sampler, block_capacity)));
+                inputs.params_changed.set(false);
+            }
+            Some(shader_binding) => {
+                if inputs.params_changed.replace(false) {
+                    shader_binding.write_block(queue, 0, &inputs.params.borrow());
+                }
+            }
+            None => match ShaderMaterialBinding::new(self, device, queue) {
+                Ok(shader_binding) => {
+                    inputs.params_changed.set(false);
+                    *binding = Some(Rc::new(shader_binding));
+                    // grown right away if some instances override the parameters
+                    drop(binding);
+                    return self.shader_binding(device, queue, blocks);
+                }
+                Err(error) => {
+                    eprintln!("{:?}", error);
+                    return None;
+                }
+            }
+        }
+        binding.clone()
+    }
+}
@@ -0,0 +1,333 @@
+use anyhow::{anyhow, bail, Result};
+use legion::systems::{CommandBuffer, Runnable};
+use legion::world::SubWorld;
+use legion::{Entity, IntoQuery, SystemBuilder};
+
+use super::material::Material;
+use super::shader::{MaterialParam, MaterialParamType, MaterialShader, MaterialShaderLayout};
+use super::time::Time;
+
+// Value of a material parameter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaterialParamValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4([[f32; 4]; 4])
+}
+
+impl MaterialParamValue {
+    // the value parameters of `ty` start with
+    pub fn zeroed(ty: MaterialParamType) -> Self {
+        match ty {
+            MaterialParamType::Float => Self::Float(0.0),
+            MaterialParamType::Vec2 => Self::Vec2([0.0; 2]),
+            MaterialParamType::Vec3 => Self::Vec3([0.0; 3]),
+            MaterialParamType::Vec4 => Self::Vec4([0.0; 4]),
+            MaterialParamType::Mat4 => Self::Mat4([[0.0; 4]; 4])
+        }
+    }
+
+    pub fn ty(&self) -> MaterialParamType {
+        match self {
+            Self::Float(_) => MaterialParamType::Float,
+            Self::Vec2(_) => MaterialParamType::Vec2,
+            Self::Vec3(_) => MaterialParamType::Vec3,
+            Self::Vec4(_) => MaterialParamType::Vec4,
+            Self::Mat4(_) => MaterialParamType::Mat4
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Float(value) => bytemuck::bytes_of(value),
+            Self::Vec2(value) => bytemuck::bytes_of(value),
+            Self::Vec3(value) => bytemuck::bytes_of(value),
+            Self::Vec4(value) => bytemuck::bytes_of(value),
+            Self::Mat4(value) => bytemuck::bytes_of(value)
+        }
+    }
+
+    // component-wise linear interpolation, None if the two values have different types.
+    pub fn lerp(&self, other: &Self, t: f32) -> Option<Self> {
+        fn lerp_array<const N: usize>(a: &[f32; N], b: &[f32; N], t: f32) -> [f32; N] {
+            let mut out = [0.0; N];
+            for i in 0..N {
+                out[i] = a[i] + (b[i] - a[i]) * t;
+            }
+            out
+        }
+
+        match (self, other) {
+            (Self::Float(a), Self::Float(b)) => Some(Self::Float(a + (b - a) * t)),
+            (Self::Vec2(a), Self::Vec2(b)) => Some(Self::Vec2(lerp_array(a, b, t))),
+            (Self::Vec3(a), Self::Vec3(b)) => Some(Self::Vec3(lerp_array(a, b, t))),
+            (Self::Vec4(a), Self::Vec4(b)) => Some(Self::Vec4(lerp_array(a, b, t))),
+            (Self::Mat4(a), Self::Mat4(b)) => Some(Self::Mat4([
+                lerp_array(&a[0], &b[0], t),
+                lerp_array(&a[1], &b[1], t),
+                lerp_array(&a[2], &b[2], t),
+                lerp_array(&a[3], &b[3], t),
+            ])),
+            _ => None
+        }
+    }
+}
+
+// CPU side copy of a material uniform block, laid out as reflected from the material shader.
+// Kept by the custom materials (see `Material::custom()`), uploaded into their uniform buffer after changing values.
+#[derive(Clone, Debug)]
+pub(crate) struct MaterialParams {
+    params: Vec<MaterialParam>,
+    offsets: Vec<u32>,
+    values: Vec<Option<MaterialParamValue>>,
+    data: Vec<u8>
+}
+
+impl MaterialParams {
+    // all parameters start zeroed.
+    pub(crate) fn new(shader: &MaterialShader, layout: &MaterialShaderLayout) -> Self {
+        Self {
+            params: shader.params().to_vec(),
+            offsets: layout.param_offsets.clone(),
+            values: vec![None; shader.params().len()],
+            data: vec![0; layout.uniform_size.unwrap_or(0) as usize]
+        }
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.params.iter().position(|param| param.name == name)
+    }
+
+    pub(crate) fn set(&mut self, name: &str, value: MaterialParamValue) -> Result<()> {
+        let index = self.index_of(name).ok_or_else(|| anyhow!("unknown material parameter `{}`", name))?;
+        if self.params[index].ty != value.ty() {
+            bail!("material parameter `{}` is a {:?}, got a {:?}", name, self.params[index].ty, value.ty());
+        }
+
+        let offset = self.offsets[index] as usize;
+        let bytes = value.as_bytes();
+        self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.values[index] = Some(value);
+
+        Ok(())
+    }
+
+    // None if the parameter doesn't exist or has never been set.
+    pub(crate) fn get(&self, name: &str) -> Option<MaterialParamValue> {
+        self.index_of(name).and_then(|index| self.values[index])
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Copy of these parameters with per-instance overrides applied,
+    // so one material can be reused with varying values.
+    // tips: the unknown parameters & the values of another type are skipped.
+    pub(crate) fn with_overrides(&self, overrides: &MaterialParamOverrides) -> Self {
+        let mut params = self.clone();
+        for (name, value) in overrides.iter() {
+            let _ = params.set(name, value);
+        }
+        params
+    }
+}
+
+// Component overriding parameters of the entity's custom material (see `Material::custom()`),
+// so the entities sharing a material can each have their own values, e.g. a dissolve threshold.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaterialParamOverrides {
+    values: Vec<(String, MaterialParamValue)>
+}
+
+impl MaterialParamOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: &str, value: MaterialParamValue) {
+        match self.values.iter_mut().find(|(param_name, _)| param_name == name) {
+            Some((_, old_value)) => *old_value = value,
+            None => self.values.push((name.to_string(), value))
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.values.retain(|(param_name, _)| param_name != name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<MaterialParamValue> {
+        self.values.iter().find(|(param_name, _)| param_name == name).map(|(_, value)| *value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, MaterialParamValue)> {
+        self.values.iter().map(|(name, value)| (name.as_str(), *value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+// Keyframed animation of a single material parameter (e.g. emissive intensity, UV scroll, dissolve threshold).
+// Values between two keys are linearly interpolated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialParamTrack {
+    param: String,
+    keys: Vec<(f32, MaterialParamValue)>, // (time in seconds, value), sorted by time
+    looping: bool
+}
+
+impl MaterialParamTrack {
+    pub fn new(param: &str) -> Self {
+        Self {
+            param: param.to_string(),
+            keys: Vec::new(),
+            looping: false
+        }
+    }
+
+    pub fn with_key(mut self, time: f32, value: MaterialParamValue) -> Self {
+        let index = self.keys.partition_point(|(key_time, _)| *key_time <= time);
+        self.keys.insert(index, (time, value));
+        self
+    }
+
+    // restart from the first key after the last one, instead of holding the last value.
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn param(&self) -> &str {
+        &self.param
+    }
+
+    // time of the last key
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map(|(time, _)| *time).unwrap_or(0.0)
+    }
+
+    // value of the track at `time`, None if the track has no keys or mixes value types.
+    pub fn sample(&self, time: f32) -> Option<MaterialParamValue> {
+        let (first_time, first_value) = *self.keys.first()?;
+        let duration = self.duration();
+        let time = if self.looping && duration > first_time {
+            first_time + (time - first_time).rem_euclid(duration - first_time)
+        } else {
+            time
+        };
+
+        if time <= first_time {
+            return Some(first_value);
+        }
+
+        let next = self.keys.partition_point(|(key_time, _)| *key_time <= time);
+        if next == self.keys.len() {
+            return self.keys.last().map(|(_, value)| *value);
+        }
+        let (prev_time, prev_value) = self.keys[next - 1];
+        let (next_time, next_value) = self.keys[next];
+        prev_value.lerp(&next_value, (time - prev_time) / (next_time - prev_time))
+    }
+
+    // write the value of the track at `time` into the parameters of a custom material, shared by all its instances.
+    pub fn apply(&self, time: f32, material: &Material) -> Result<()> {
+        match self.sample(time) {
+            Some(value) => material.set_param(&self.param, value),
+            None => Ok(())
+        }
+    }
+
+    // write the value of the track at `time` into per-instance overrides.
+    pub fn apply_override(&self, time: f32, overrides: &mut MaterialParamOverrides) {
+        if let Some(value) = self.sample(time) {
+            overrides.set(&self.param, value);
+        }
+    }
+}
+
+// Component playing material parameter tracks on the entity, through its `MaterialParamOverrides` (added if missing),
+// see `material_param_animation_system()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialParamAnimation {
+    tracks: Vec<MaterialParamTrack>,
+    // seconds played
+    time: f32,
+    speed: f32
+}
+
+impl MaterialParamAnimation {
+    pub fn new() -> Self {
+        Self {
+            tracks: Vec::new(),
+            time: 0.0,
+            speed: 1.0
+        }
+    }
+
+    pub fn with_track(mut self, track: MaterialParamTrack) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    // 0.0 pauses, 2.0 plays twice as fast
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn tracks(&self) -> &[MaterialParamTrack] {
+        &self.tracks
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    // jump to `time`, e.g. 0.0 to replay a one-shot effect
+    pub fn seek(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    fn advance(&mut self, dt: f32, overrides: &mut MaterialParamOverrides) {
+        self.time += dt * self.speed;
+        for track in &self.tracks {
+            track.apply_override(self.time, overrides);
+        }
+    }
+}
+
+impl Default for MaterialParamAnimation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// System playing the `MaterialParamAnimation` of every entity by the frame's delta time, run by the scene after its schedule.
+pub fn material_param_animation_system() -> impl Runnable {
+    SystemBuilder::new("material_param_animation")
+        .read_resource::<Time>()
+        .with_query(<(Entity, &mut MaterialParamAnimation, Option<&mut MaterialParamOverrides>)>::query())
+        .build(|commands, world, time, animations| {
+            animate_material_params(commands, world, time.delta(), animations);
+        })
+}
+
+type AnimationQuery = legion::Query<(Entity, &'static mut MaterialParamAnimation, Option<&'static mut MaterialParamOverrides>)>;
+
+fn animate_material_params(commands: &mut CommandBuffer, world: &mut SubWorld, dt: f32, animations: &mut AnimationQuery) {
+    for (entity, animation, overrides) in animations.iter_mut(world) {
+        match overrides {
+            Some(overrides) => animation.advance(dt, overrides),
+            None => {
+                // added once the schedule is done, before the meshes are drawn
+                let mut overrides = MaterialParamOverrides::new();
+                animation.advance(dt, &mut overrides);
+                commands.add_component(*entity, overrides);
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Vector3};
+use wgpu::util::DeviceExt; // for `create_buffer_init`
+
+use super::material::Material;
+use super::material_params::MaterialParamOverrides;
+use super::render_state::RenderState;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2], // color space depends on `surface.get_preferred_format()`, mostly sRGB
+    pub color: [f32; 4], // multiplied with the albedo, use `Vertex::WHITE` for no tint
+    pub normal: [f32; 3] // unit length, in model space
+}
+
+impl Vertex {
+    // vertex color which leaves the albedo unchanged
+    pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    pub fn new(position: [f32; 3], tex_coords: [f32; 2], normal: [f32; 3]) -> Self {
+        Self {
+            position,
+            tex_coords,
+            color: Self::WHITE,
+            normal
+        }
+    }
+
+    // get Vertex Layout
+    pub(crate) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            // stride defines how wide a vertex is.
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            // step mode tells the pipeline how often it should move to the next vertex.
+            step_mode: wgpu::VertexStepMode::Vertex,  // specify wgpu::VertexStepMode::Instance if we only want to change vertices when we start drawing a new instance.
+            // vertex attributes describe the individual parts of the vertex.
+            attributes: &[
+                // attribute: position
+                wgpu::VertexAttribute {
+                    // define the offset in bytes of this attribute startpoint.
+                    offset: 0,
+                    // tell the shader what location store this attribute at.
+                    shader_location: 0,
+                    // tell the shader the shape of the attribute.
+                    format: wgpu::VertexFormat::Float32x3
+                },
+                // attribute: texture coordinates
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                // attribute: vertex color
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4
+                },
+                // attribute: normal
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x3
+                }
+            ]
+            // attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3]
+        }
+    }
+}
+
+// GPU copy of a `Mesh`
+pub(crate) struct MeshBuffers {
+    pub(crate) vertex_buffer: wgpu::Buffer,
+    pub(crate) index_buffer: wgpu::Buffer,
+    pub(crate) indices_num: u32
+}
+
+// Indexed triangle list (counter-clockwise front faces), drawn with `Application::draw_meshes()`.
+// Its vertex & index buffers are uploaded the first time it's drawn, and freed with the mesh.
+pub struct Mesh {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    buffers: RefCell<Option<Rc<MeshBuffers>>>
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self {
+            vertices,
+            indices,
+            buffers: RefCell::new(None)
+        }
+    }
+
+    // `width` x `height` rectangle in the xy plane, centered on the origin & facing +z.
+    pub fn quad(width: f32, height: f32) -> Self {
+        let mut vertices = Vec::with_capacity(4);
+        let mut indices = Vec::with_capacity(6);
+        Self::push_face(&mut vertices, &mut indices, Vector3::zeros(), Vector3::x() * width * 0.5, Vector3::y() * height * 0.5);
+        Self::new(vertices, indices)
+    }
+
+    // cube of `size` centered on the origin, the texture is mapped on each face.
+    pub fn cube(size: f32) -> Self {
+        let half = size * 0.5;
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        // (normal, u axis, v axis) with u x v == normal, so the faces are counter-clockwise seen from outside
+        let faces = [
+            (Vector3::x(), -Vector3::z(), Vector3::y()),
+            (-Vector3::x(), Vector3::z(), Vector3::y()),
+            (Vector3::y(), Vector3::x(), -Vector3::z()),
+            (-Vector3::y(), Vector3::x(), Vector3::z()),
+            (Vector3::z(), Vector3::x(), Vector3::y()),
+            (-Vector3::z(), -Vector3::x(), Vector3::y())
+        ];
+        for (normal, u, v) in faces {
+            Self::push_face(&mut vertices, &mut indices, normal * half, u * half, v * half);
+        }
+        Self::new(vertices, indices)
+    }
+
+    // UV sphere centered on the origin, `segments` around the y axis & `rings` from pole to pole.
+    pub fn sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let segments = segments.max(3);
+        let rings = rings.max(2);
+        let mut vertices = Vec::with_capacity(((segments + 1) * (rings + 1)) as usize);
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            let theta = v * std::f32::consts::PI; // from the north pole
+            for segment in 0..=segments {
+                // the first & last columns overlap, with different texture coordinates
+                let u = segment as f32 / segments as f32;
+                let phi = u * std::f32::consts::TAU;
+                let direction = [theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin()];
+                vertices.push(Vertex::new(direction.map(|x| x * radius), [u, v], direction));
+            }
+        }
+
+        let mut indices = Vec::with_capacity((segments * rings * 6) as usize);
+        let columns = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let top_left = ring * columns + segment;
+                let bottom_left = top_left + columns;
+                // the pole rows are single triangles
+                if ring != 0 {
+                    indices.extend_from_slice(&[top_left, bottom_left, top_left + 1]);
+                }
+                if ring != rings - 1 {
+                    indices.extend_from_slice(&[top_left + 1, bottom_left, bottom_left + 1]);
+                }
+            }
+        }
+        Self::new(vertices, indices)
+    }
+
+    // a rectangle from `center - u - v` to `center + u + v`, facing `u x v`
+    fn push_face(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, center: Vector3<f32>, u: Vector3<f32>, v: Vector3<f32>) {
+        let first = vertices.len() as u32;
+        let normal = u.cross(&v).normalize();
+        for (corner, tex_coords) in [(-u - v, [0.0, 1.0]), (u - v, [1.0, 1.0]), (u + v, [1.0, 0.0]), (v - u, [0.0, 0.0])] {
+            vertices.push(Vertex::new((center + corner).into(), tex_coords, normal.into()));
+        }
+        indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    // replace the normals by the area weighted average of the adjacent faces, e.g. for meshes built without normals
+    // tips: vertices duplicated along UV seams aren't merged, so seams stay visible.
+    pub fn with_smooth_normals(mut self) -> Self {
+        smooth_normals(&mut self.vertices, &self.indices);
+        self
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    // upload the mesh the first time, then reuse its buffers
+    pub(crate) fn buffers(&self, device: &wgpu::Device) -> Rc<MeshBuffers> {
+        self.buffers
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(self.upload(device)))
+            .clone()
+    }
+
+    // new buffers every time, e.g. for another device than the window's
+    pub(crate) fn upload(&self, device: &wgpu::Device) -> MeshBuffers {
+        MeshBuffers {
+            vertex_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Mesh Vertex Buffer"),
+                contents: bytemuck::cast_slice(&self.vertices),
+                usage: wgpu::BufferUsages::VERTEX
+            }),
+            index_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Mesh Index Buffer"),
+                contents: bytemuck::cast_slice(&self.indices),
+                usage: wgpu::BufferUsages::INDEX
+            }),
+            indices_num: self.indices.len() as u32
+        }
+    }
+}
+
+// area weighted vertex normals of an indexed triangle list, `Vector3::y()` for vertices of degenerate triangles only
+pub(crate) fn smooth_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vector3::zeros(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| Vector3::from(vertices[index as usize].position));
+        // the cross product length is twice the triangle area
+        let normal = (b - a).cross(&(c - a));
+        for index in triangle {
+            normals[*index as usize] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y).into();
+    }
+}
+
+// A mesh to draw this frame, placed in the world by `transform`.
+#[derive(Clone)]
+pub struct MeshDraw {
+    pub mesh: Rc<Mesh>,
+    pub transform: Matrix4<f32>,
+    // draws the mesh instead of the engine's picked material
+    pub material: Option<Rc<Material>>,
+    // cull mode, depth bias & alpha mode instead of the material's (or the engine's picked one)
+    // tips: a new render state is compiled in the background, the mesh isn't drawn until it's ready, see `Application::render_states()`.
+    pub render_state: Option<RenderState>,
+    // values of its custom material's parameters for this mesh only
+    pub param_overrides: Option<MaterialParamOverrides>
+}
+
+impl MeshDraw {
+    pub fn new(mesh: &Rc<Mesh>, transform: Matrix4<f32>) -> Self {
+        Self {
+            mesh: mesh.clone(),
+            transform,
+            material: None,
+            render_state: None,
+            param_overrides: None
+        }
+    }
+
+    pub fn with_material(mut self, material: &Rc<Material>) -> Self {
+        self.material = Some(material.clone());
+        self
+    }
+
+    pub fn with_render_state(mut self, render_state: RenderState) -> Self {
+        self.render_state = Some(render_state);
+        self
+    }
+
+    pub fn with_param_overrides(mut self, param_overrides: MaterialParamOverrides) -> Self {
+        self.param_overrides = Some(param_overrides);
+        self
+    }
+}
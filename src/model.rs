@@ -0,0 +1,174 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+use nalgebra::Matrix4;
+
+use super::material::Material;
+use super::mesh::{smooth_normals, Mesh, MeshDraw, Vertex};
+
+// One object of a model, with the material it's drawn with.
+#[derive(Clone)]
+pub struct ModelMesh {
+    pub name: String,
+    pub mesh: Rc<Mesh>,
+    pub material: Option<Rc<Material>>
+}
+
+// Meshes & materials loaded from a Wavefront OBJ file, drawn with `Application::draw_meshes()`.
+pub struct Model {
+    meshes: Vec<ModelMesh>,
+    materials: Vec<Rc<Material>>
+}
+
+impl Model {
+    // Load an OBJ file, its MTL files & their diffuse textures (relative to the OBJ file).
+    // tips: a missing MTL file isn't an error, the meshes are drawn with the engine's material.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::from_data(ModelData::load(path.as_ref())?))
+    }
+
+    pub(crate) fn from_data(data: ModelData) -> Self {
+        let materials = data.materials
+            .into_iter()
+            .map(|data| {
+                // the MTL diffuse color tints the diffuse texture
+                let material = Material::new(&data.name).with_tint(data.diffuse_color);
+                Rc::new(match data.diffuse_image {
+                    Some(diffuse_image) => material.with_albedo(diffuse_image),
+                    None => material
+                })
+            })
+            .collect::<Vec<_>>();
+        let meshes = data.meshes
+            .into_iter()
+            .map(|mesh| ModelMesh {
+                name: mesh.name,
+                mesh: Rc::new(Mesh::new(mesh.vertices, mesh.indices)),
+                material: mesh.material.and_then(|id| materials.get(id)).cloned()
+            })
+            .collect();
+
+        Self {
+            meshes,
+            materials
+        }
+    }
+
+    pub fn meshes(&self) -> &[ModelMesh] {
+        &self.meshes
+    }
+
+    pub fn materials(&self) -> &[Rc<Material>] {
+        &self.materials
+    }
+
+    // draw every mesh of the model, placed in the world by `transform`.
+    pub fn draw(&self, transform: Matrix4<f32>, mesh_draws: &mut Vec<MeshDraw>) {
+        for model_mesh in &self.meshes {
+            let mut mesh_draw = MeshDraw::new(&model_mesh.mesh, transform);
+            mesh_draw.material = model_mesh.material.clone();
+            mesh_draws.push(mesh_draw);
+        }
+    }
+}
+
+struct MeshData {
+    name: String,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    material: Option<usize>
+}
+
+struct MaterialData {
+    name: String,
+    diffuse_color: [f32; 4],
+    diffuse_image: Option<image::RgbaImage>
+}
+
+// `Model` content before it's shared with `Rc`, so it can be loaded on another thread.
+pub(crate) struct ModelData {
+    meshes: Vec<MeshData>,
+    materials: Vec<MaterialData>
+}
+
+impl ModelData {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let (obj_models, obj_materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)
+            .with_context(|| format!("failed to load model {}", path.display()))?;
+        let obj_materials = obj_materials.unwrap_or_else(|error| {
+            eprintln!("failed to load the materials of model {}: {}", path.display(), error);
+            Vec::new()
+        });
+
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let materials = obj_materials
+            .iter()
+            .map(|obj_material| {
+                let diffuse_image = if obj_material.diffuse_texture.is_empty() {
+                    None
+                } else {
+                    let texture_path = directory.join(&obj_material.diffuse_texture);
+                    let image = image::open(&texture_path)
+                        .with_context(|| format!("failed to load texture {}", texture_path.display()))?;
+                    Some(image.to_rgba8())
+                };
+                let [r, g, b] = obj_material.diffuse;
+                Ok(MaterialData {
+                    name: obj_material.name.clone(),
+                    diffuse_color: [r, g, b, obj_material.dissolve],
+                    diffuse_image
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let meshes = obj_models
+            .into_iter()
+            .map(|obj_model| {
+                let obj_mesh = obj_model.mesh;
+                let mut vertices: Vec<Vertex> = (0..obj_mesh.positions.len() / 3)
+                    .map(|i| {
+                        // OBJ texture coordinates start at the bottom left, wgpu's at the top left
+                        let tex_coords = if obj_mesh.texcoords.is_empty() {
+                            [0.0, 0.0]
+                        } else {
+                            [obj_mesh.texcoords[i * 2], 1.0 - obj_mesh.texcoords[i * 2 + 1]]
+                        };
+                        // computed below when the file has none
+                        let normal = if obj_mesh.normals.is_empty() {
+                            [0.0, 1.0, 0.0]
+                        } else {
+                            [obj_mesh.normals[i * 3], obj_mesh.normals[i * 3 + 1], obj_mesh.normals[i * 3 + 2]]
+                        };
+                        // the `v x y z r g b` extension, white when the file has none
+                        let color = if obj_mesh.vertex_color.is_empty() {
+                            Vertex::WHITE
+                        } else {
+                            [obj_mesh.vertex_color[i * 3], obj_mesh.vertex_color[i * 3 + 1], obj_mesh.vertex_color[i * 3 + 2], 1.0]
+                        };
+                        Vertex {
+                            position: [obj_mesh.positions[i * 3], obj_mesh.positions[i * 3 + 1], obj_mesh.positions[i * 3 + 2]],
+                            tex_coords,
+                            color,
+                            normal
+                        }
+                    })
+                    .collect();
+                if obj_mesh.normals.is_empty() {
+                    smooth_normals(&mut vertices, &obj_mesh.indices);
+                }
+                MeshData {
+                    name: obj_model.name,
+                    vertices,
+                    indices: obj_mesh.indices,
+                    material: obj_mesh.material_id
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            meshes,
+            materials
+        })
+    }
+}
@@ -0,0 +1,199 @@
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+use super::texture::Texture;
+
+// Multisampled color & depth targets the scene is rendered into when MSAA is on, see `AppConfig::msaa_samples`.
+// `resolve()` averages the color samples into the frame & copies the first depth sample into the depth texture,
+// so the passes after the scene (lens flare, depth view...) read single-sampled textures as without MSAA.
+pub(crate) struct MsaaTargets {
+    sample_count: u32,
+    color_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    depth_resolve_pipeline: Option<wgpu::RenderPipeline> // None if the pipeline failed to build
+}
+
+impl MsaaTargets {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        error_overlay: &mut ErrorOverlay
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Resolve BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: true,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                }
+            ]
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Resolve Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let depth_resolve_pipeline = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Depth Resolve Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("depth_resolve.wgsl", include_str!("res/shaders/depth_resolve.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Depth Resolve Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the full-screen triangle is generated from the vertex index
+                },
+                // only writes the depth
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let depth_resolve_pipeline = depth_resolve_pipeline
+            .map_err(|error| error_overlay.report("Depth Resolve Render Pipeline", &error))
+            .ok();
+
+        let (color_view, depth_view, bind_group) = Self::create_targets(device, config, sample_count, &bind_group_layout);
+        Self {
+            sample_count,
+            color_view,
+            depth_view,
+            bind_group_layout,
+            bind_group,
+            depth_resolve_pipeline
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        bind_group_layout: &wgpu::BindGroupLayout
+    ) -> (wgpu::TextureView, wgpu::TextureView, wgpu::BindGroup) {
+        let create_texture = |label: &str, format: wgpu::TextureFormat, usage: wgpu::TextureUsages| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: config.width.max(1),
+                    height: config.height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+            })
+        };
+        let color_texture = create_texture("MSAA Color Target", config.format, wgpu::TextureUsages::empty());
+        let depth_texture = create_texture("MSAA Depth Target", Texture::DEPTH_FORMAT, wgpu::TextureUsages::TEXTURE_BINDING);
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Resolve Bind Group"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_view)
+                }
+            ]
+        });
+        (color_view, depth_view, bind_group)
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let (color_view, depth_view, bind_group) = Self::create_targets(device, config, self.sample_count, &self.bind_group_layout);
+        self.color_view = color_view;
+        self.depth_view = depth_view;
+        self.bind_group = bind_group;
+    }
+
+    pub(crate) fn color_view(&self) -> &wgpu::TextureView {
+        &self.color_view
+    }
+
+    pub(crate) fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_view
+    }
+
+    // after the scene passes: resolve the color into `texture_view` & the depth into `depth_view`
+    pub(crate) fn resolve(
+        &self,
+        texture_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        command_encoder: &mut wgpu::CommandEncoder
+    ) {
+        // an empty pass resolves its color attachment when it ends
+        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("MSAA Color Resolve Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &self.color_view,
+                resolve_target: Some(texture_view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    // the samples aren't needed once resolved
+                    store: false
+                }
+            }],
+            depth_stencil_attachment: None
+        });
+
+        let depth_resolve_pipeline = match &self.depth_resolve_pipeline {
+            Some(depth_resolve_pipeline) => depth_resolve_pipeline,
+            None => return
+        };
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("MSAA Depth Resolve Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            })
+        });
+        render_pass.set_pipeline(depth_resolve_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
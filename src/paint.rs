@@ -0,0 +1,204 @@
+// Round brush for `PaintCanvas`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Brush {
+    // in pixels
+    pub radius: f32,
+    // RGBA 0.0 ~ 1.0, alpha is the opacity of a single stamp
+    pub color: [f32; 4],
+    // 0.0 ~ 1.0, 1.0 paints a sharp disk, lower values fade out from the center sooner
+    pub hardness: f32
+}
+
+impl Brush {
+    pub fn new(radius: f32, color: [f32; 4]) -> Self {
+        Self {
+            radius,
+            color,
+            hardness: 1.0
+        }
+    }
+
+    // coverage of a pixel `distance` pixels away from the brush center
+    fn coverage(&self, distance: f32) -> f32 {
+        if distance >= self.radius {
+            return 0.0;
+        }
+        let hard_radius = self.radius * self.hardness.clamp(0.0, 1.0);
+        if distance <= hard_radius {
+            1.0
+        } else {
+            (self.radius - distance) / (self.radius - hard_radius)
+        }
+    }
+}
+
+// Pixel region, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+// CPU side RGBA8 copy of a texture modified at runtime: fog of war maps, terrain splat maps, decals painted on surfaces...
+// It keeps track of the changed pixels, so only the changed region has to be uploaded with `take_dirty()`.
+#[derive(Clone, Debug)]
+pub struct PaintCanvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    dirty: Option<PixelRegion>
+}
+
+impl PaintCanvas {
+    // the whole canvas starts dirty, so the first upload fills the texture.
+    pub fn new(width: u32, height: u32, fill: [u8; 4]) -> Self {
+        Self {
+            width,
+            height,
+            pixels: fill.repeat((width * height) as usize),
+            dirty: Some(PixelRegion { x: 0, y: 0, width, height })
+        }
+    }
+
+    // a copy of the image a texture was loaded from, it starts clean as the texture already has these pixels.
+    pub fn from_image(image: &image::RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.as_raw().clone(),
+            dirty: None
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // all the pixels, row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = ((y * self.width + x) * 4) as usize;
+        Some([self.pixels[index], self.pixels[index + 1], self.pixels[index + 2], self.pixels[index + 3]])
+    }
+
+    // pixels outside of the canvas are ignored.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = ((y * self.width + x) * 4) as usize;
+        self.pixels[index..index + 4].copy_from_slice(&color);
+        self.mark_dirty(PixelRegion { x, y, width: 1, height: 1 });
+    }
+
+    // overwrite a rectangle, clipped to the canvas
+    pub fn fill_rect(&mut self, region: PixelRegion, color: [u8; 4]) {
+        let region = match self.clip(region) {
+            Some(region) => region,
+            None => return
+        };
+        for y in region.y..region.y + region.height {
+            let start = ((y * self.width + region.x) * 4) as usize;
+            let end = start + region.width as usize * 4;
+            for pixel in self.pixels[start..end].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+        self.mark_dirty(region);
+    }
+
+    // Blend a single brush stamp centered on `center` (in pixels, 0.0 is the left/top edge of the first pixel).
+    pub fn stamp(&mut self, center: [f32; 2], brush: &Brush) {
+        let min_x = (center[0] - brush.radius).floor().max(0.0) as u32;
+        let min_y = (center[1] - brush.radius).floor().max(0.0) as u32;
+        let max_x = ((center[0] + brush.radius).ceil().max(0.0) as u32).min(self.width);
+        let max_y = ((center[1] + brush.radius).ceil().max(0.0) as u32).min(self.height);
+        if min_x >= max_x || min_y >= max_y {
+            return;
+        }
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let dx = x as f32 + 0.5 - center[0];
+                let dy = y as f32 + 0.5 - center[1];
+                let alpha = brush.coverage((dx * dx + dy * dy).sqrt()) * brush.color[3];
+                if alpha <= 0.0 {
+                    continue;
+                }
+                let index = ((y * self.width + x) * 4) as usize;
+                for (channel, value) in self.pixels[index..index + 4].iter_mut().enumerate() {
+                    // "over" blending, alpha accumulates
+                    let source = if channel == 3 { 1.0 } else { brush.color[channel] };
+                    let blended = *value as f32 / 255.0 * (1.0 - alpha) + source * alpha;
+                    *value = (blended.clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+            }
+        }
+        self.mark_dirty(PixelRegion { x: min_x, y: min_y, width: max_x - min_x, height: max_y - min_y });
+    }
+
+    // Paint a line from `from` to `to`, with stamps a quarter of the brush radius apart.
+    pub fn stroke(&mut self, from: [f32; 2], to: [f32; 2], brush: &Brush) {
+        let length = ((to[0] - from[0]).powi(2) + (to[1] - from[1]).powi(2)).sqrt();
+        let spacing = (brush.radius * 0.25).max(0.5);
+        let steps = (length / spacing).ceil().max(1.0) as u32;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            self.stamp([from[0] + (to[0] - from[0]) * t, from[1] + (to[1] - from[1]) * t], brush);
+        }
+    }
+
+    // changed region since the last `take_dirty()`
+    pub fn dirty_region(&self) -> Option<PixelRegion> {
+        self.dirty
+    }
+
+    // Take the changed region & its pixels (row by row) to upload, e.g. with `Texture::write_region()`.
+    pub fn take_dirty(&mut self) -> Option<(PixelRegion, Vec<u8>)> {
+        let region = self.dirty.take()?;
+        let mut pixels = Vec::with_capacity((region.width * region.height * 4) as usize);
+        for y in region.y..region.y + region.height {
+            let start = ((y * self.width + region.x) * 4) as usize;
+            pixels.extend_from_slice(&self.pixels[start..start + region.width as usize * 4]);
+        }
+        Some((region, pixels))
+    }
+
+    fn clip(&self, region: PixelRegion) -> Option<PixelRegion> {
+        let max_x = region.x.saturating_add(region.width).min(self.width);
+        let max_y = region.y.saturating_add(region.height).min(self.height);
+        if region.x >= max_x || region.y >= max_y {
+            return None;
+        }
+        Some(PixelRegion { x: region.x, y: region.y, width: max_x - region.x, height: max_y - region.y })
+    }
+
+    // grow the dirty region to also cover `region`
+    fn mark_dirty(&mut self, region: PixelRegion) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => {
+                let x = dirty.x.min(region.x);
+                let y = dirty.y.min(region.y);
+                PixelRegion {
+                    x,
+                    y,
+                    width: (dirty.x + dirty.width).max(region.x + region.width) - x,
+                    height: (dirty.y + dirty.height).max(region.y + region.height) - y
+                }
+            },
+            None => region
+        });
+    }
+}
@@ -0,0 +1,260 @@
+use std::rc::Rc;
+
+use super::camera::Camera;
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+use super::material::MaterialBinding;
+
+// layers drawn per frame, the others are ignored
+pub(crate) const MAX_PARALLAX_LAYERS: usize = 8;
+
+// Which way the texture of a parallax layer repeats, the layer is transparent beyond its tiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParallaxRepeat {
+    // sky, clouds, a starfield...
+    Both,
+    // a row of hills, a city skyline...
+    Horizontal,
+    // a shaft, a waterfall...
+    Vertical,
+    // a single picture, e.g. the moon
+    None
+}
+
+impl ParallaxRepeat {
+    fn to_uniform(self) -> [f32; 2] {
+        match self {
+            ParallaxRepeat::Both => [1.0, 1.0],
+            ParallaxRepeat::Horizontal => [1.0, 0.0],
+            ParallaxRepeat::Vertical => [0.0, 1.0],
+            ParallaxRepeat::None => [0.0, 0.0]
+        }
+    }
+}
+
+// Background layer component of a 2D `Scene` entity, drawn over the whole screen behind the scene.
+// Its texture is the albedo of the entity's material (`MaterialHandle`, tint & sampler wrap included), in tiles of `size` world units.
+// The layer moves with the camera scaled by `scroll_factor`: 0.0 stays still on screen (sky), 1.0 moves with the world,
+// the far layers are in between.
+// tips: layers are drawn from the lowest horizontal scroll factor (the farthest) to the highest, 8 at most.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParallaxLayer {
+    pub size: [f32; 2],
+    // bottom left corner of a tile, when the camera is at the origin
+    pub offset: [f32; 2],
+    pub scroll_factor: [f32; 2],
+    pub repeat: ParallaxRepeat
+}
+
+impl ParallaxLayer {
+    // tiles of `size` world units repeated in both directions, the same `scroll_factor` along x & y
+    pub fn new(size: [f32; 2], scroll_factor: f32) -> Self {
+        Self {
+            size,
+            offset: [0.0, 0.0],
+            scroll_factor: [scroll_factor, scroll_factor],
+            repeat: ParallaxRepeat::Both
+        }
+    }
+
+    pub fn with_offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = [x, y];
+        self
+    }
+
+    // e.g. a layer which follows the camera vertically (y = 0.0) while scrolling sideways
+    pub fn with_scroll_factor(mut self, x: f32, y: f32) -> Self {
+        self.scroll_factor = [x, y];
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: ParallaxRepeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    // UVs of the screen seen by `camera`, in the camera's plane (xy for a 2D camera looking along -z)
+    fn to_raw(self, camera: &Camera) -> ParallaxLayerRaw {
+        let right = camera.right();
+        let up = right.cross(&camera.forward());
+        let height = camera.world_units_per_pixel() * camera.viewport().1 as f32;
+        let half_size = [height * 0.5 * camera.aspect(), height * 0.5];
+        let position = [camera.target.coords.dot(&right), camera.target.coords.dot(&up)];
+
+        let size = [self.size[0].max(f32::EPSILON), self.size[1].max(f32::EPSILON)];
+        let center = [position[0] * self.scroll_factor[0] - self.offset[0], position[1] * self.scroll_factor[1] - self.offset[1]];
+        // textures start at the top left
+        ParallaxLayerRaw {
+            uv_center: [center[0] / size[0], 1.0 - center[1] / size[1]],
+            uv_scale: [half_size[0] / size[0], -half_size[1] / size[1]],
+            repeat: self.repeat.to_uniform(),
+            _padding: [0.0; 2]
+        }
+    }
+}
+
+// `ParallaxLayer` layout in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParallaxLayerRaw {
+    uv_center: [f32; 2], // at the screen center
+    uv_scale: [f32; 2], // per NDC unit
+    repeat: [f32; 2], // 1.0 along the repeated axes
+    _padding: [f32; 2]
+}
+
+// Full-screen pass drawing the parallax layers of the frame, before the scene.
+pub(crate) struct ParallaxPass {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    render_pipeline: Option<wgpu::RenderPipeline>, // None if the pipeline failed to build
+    // this frame's layer materials, back to front
+    layers: Vec<Rc<MaterialBinding>>
+}
+
+impl ParallaxPass {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32, // of the scene's color target
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        error_overlay: &mut ErrorOverlay
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Parallax Uniform Buffer"),
+            size: (std::mem::size_of::<ParallaxLayerRaw>() * MAX_PARALLAX_LAYERS) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Parallax BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ]
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Parallax Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        // the layers sample their material like the scene does
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Parallax Pipeline Layout"),
+            bind_group_layouts: &[texture_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let render_pipeline = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Parallax Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("parallax.wgsl", include_str!("res/shaders/parallax.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Parallax Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the full-screen triangle is generated from the vertex index
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // behind everything, the scene clears the depth afterwards
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let render_pipeline = render_pipeline
+            .map_err(|error| error_overlay.report("Parallax Render Pipeline", &error))
+            .ok();
+
+        Self {
+            uniform_buffer,
+            bind_group,
+            render_pipeline,
+            layers: Vec::new()
+        }
+    }
+
+    // upload the layers of this frame, back to front
+    pub(crate) fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, layers: Vec<(ParallaxLayer, Rc<MaterialBinding>)>) {
+        let layers_raw = layers
+            .iter()
+            .take(MAX_PARALLAX_LAYERS)
+            .map(|(layer, _)| layer.to_raw(camera))
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&layers_raw));
+        self.layers = layers
+            .into_iter()
+            .take(MAX_PARALLAX_LAYERS)
+            .map(|(_, material_binding)| material_binding)
+            .collect();
+    }
+
+    // clear `texture_view` (or keep the skybox, see `load`) & draw the layers over it, false if there's nothing to draw (the scene clears the frame then)
+    pub(crate) fn render(&self, texture_view: &wgpu::TextureView, load: wgpu::LoadOp<wgpu::Color>, command_encoder: &mut wgpu::CommandEncoder) -> bool {
+        let render_pipeline = match &self.render_pipeline {
+            Some(render_pipeline) if !self.layers.is_empty() => render_pipeline,
+            _ => return false
+        };
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Parallax Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: None
+        });
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        // the instance index picks the layer
+        for (layer, material_binding) in self.layers.iter().enumerate() {
+            let layer = layer as u32;
+            render_pass.set_bind_group(0, &material_binding.bind_group, &[]);
+            render_pass.draw(0..3, layer..layer + 1);
+        }
+        true
+    }
+}
@@ -0,0 +1,342 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// Pathfinding algorithms on a `PathGrid`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathAlgorithm {
+    AStar,
+    // Jump point search: A* which skips over the symmetric paths of uniform cost grids, much faster on open areas.
+    // It needs diagonal moves, so it falls back to A* on 4-connected grids.
+    // ref: https://harablog.wordpress.com/2011/09/07/jump-point-search/
+    JumpPoint
+}
+
+// Walkable/blocked cells of a 2D grid (tile based games), for pathfinding.
+// Cells can be blocked & freed at any time (doors, moving obstacles...), paths are found against the current state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathGrid {
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+    // 8-connected if true, 4-connected otherwise.
+    // tips: diagonal moves never cut corners, both cells next to the diagonal must be walkable.
+    pub allow_diagonal: bool
+}
+
+// open list entry, ordered by lowest estimated total cost first
+#[derive(Clone, Copy, Debug)]
+struct OpenNode {
+    f: f32,
+    cell: (i64, i64)
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, `BinaryHeap` is a max-heap
+        other.f.total_cmp(&self.f)
+    }
+}
+
+impl PathGrid {
+    // all cells start walkable.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            blocked: vec![false; width * height],
+            allow_diagonal: true
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set_blocked(&mut self, x: usize, y: usize, blocked: bool) {
+        if x < self.width && y < self.height {
+            self.blocked[y * self.width + x] = blocked;
+        }
+    }
+
+    // cells outside of the grid are never walkable.
+    pub fn is_walkable(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && !self.blocked[y * self.width + x]
+    }
+
+    fn walkable(&self, (x, y): (i64, i64)) -> bool {
+        x >= 0 && y >= 0 && self.is_walkable(x as usize, y as usize)
+    }
+
+    fn index(&self, (x, y): (i64, i64)) -> usize {
+        y as usize * self.width + x as usize
+    }
+
+    // whether a path is still walkable, e.g. to find a new one after obstacles moved.
+    pub fn is_path_valid(&self, path: &[(usize, usize)]) -> bool {
+        path.iter().all(|&(x, y)| self.is_walkable(x, y))
+    }
+
+    // Shortest path from `start` to `goal`, both included. None if there's no path.
+    pub fn find_path(&self, start: (usize, usize), goal: (usize, usize), algorithm: PathAlgorithm) -> Option<Vec<(usize, usize)>> {
+        if !self.is_walkable(start.0, start.1) || !self.is_walkable(goal.0, goal.1) {
+            return None;
+        }
+
+        let start = (start.0 as i64, start.1 as i64);
+        let goal = (goal.0 as i64, goal.1 as i64);
+        let jump_point = algorithm == PathAlgorithm::JumpPoint && self.allow_diagonal;
+
+        let mut g_costs = vec![f32::INFINITY; self.width * self.height];
+        let mut parents: Vec<Option<(i64, i64)>> = vec![None; self.width * self.height];
+        let mut closed = vec![false; self.width * self.height];
+        let mut open = BinaryHeap::new();
+
+        g_costs[self.index(start)] = 0.0;
+        open.push(OpenNode { f: self.heuristic(start, goal), cell: start });
+
+        while let Some(OpenNode { cell, .. }) = open.pop() {
+            if cell == goal {
+                return Some(self.build_path(&parents, goal));
+            }
+            if closed[self.index(cell)] {
+                continue;
+            }
+            closed[self.index(cell)] = true;
+
+            let successors = if jump_point {
+                self.jump_successors(cell, parents[self.index(cell)], goal)
+            } else {
+                self.neighbors(cell)
+            };
+            for successor in successors {
+                if closed[self.index(successor)] {
+                    continue;
+                }
+                let g = g_costs[self.index(cell)] + self.heuristic(cell, successor);
+                if g < g_costs[self.index(successor)] {
+                    g_costs[self.index(successor)] = g;
+                    parents[self.index(successor)] = Some(cell);
+                    open.push(OpenNode { f: g + self.heuristic(successor, goal), cell: successor });
+                }
+            }
+        }
+
+        None
+    }
+
+    // distance without obstacles: octile with diagonal moves, manhattan otherwise.
+    fn heuristic(&self, a: (i64, i64), b: (i64, i64)) -> f32 {
+        let dx = (a.0 - b.0).abs() as f32;
+        let dy = (a.1 - b.1).abs() as f32;
+        if self.allow_diagonal {
+            dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+        } else {
+            dx + dy
+        }
+    }
+
+    fn neighbors(&self, (x, y): (i64, i64)) -> Vec<(i64, i64)> {
+        let mut neighbors = Vec::with_capacity(8);
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            if self.walkable((x + dx, y + dy)) {
+                neighbors.push((x + dx, y + dy));
+            }
+        }
+        if self.allow_diagonal {
+            for (dx, dy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                // no corner cutting
+                if self.walkable((x + dx, y + dy)) && self.walkable((x + dx, y)) && self.walkable((x, y + dy)) {
+                    neighbors.push((x + dx, y + dy));
+                }
+            }
+        }
+        neighbors
+    }
+
+    // jump points reachable from `cell`, pruning the directions already covered by its parent.
+    fn jump_successors(&self, cell: (i64, i64), parent: Option<(i64, i64)>, goal: (i64, i64)) -> Vec<(i64, i64)> {
+        let (x, y) = cell;
+        let directions = match parent {
+            None => self.neighbors(cell),
+            Some((px, py)) => {
+                let dx = (x - px).signum();
+                let dy = (y - py).signum();
+                let mut directions = Vec::with_capacity(5);
+                if dx != 0 && dy != 0 {
+                    // diagonal: keep going diagonally, or along its two components
+                    if self.walkable((x, y + dy)) {
+                        directions.push((x, y + dy));
+                    }
+                    if self.walkable((x + dx, y)) {
+                        directions.push((x + dx, y));
+                    }
+                    if self.walkable((x, y + dy)) && self.walkable((x + dx, y)) {
+                        directions.push((x + dx, y + dy));
+                    }
+                } else {
+                    // straight: keep going, or turn to the sides (forced neighbors around obstacles)
+                    let (side_a, side_b) = if dx != 0 { ((0, 1), (0, -1)) } else { ((1, 0), (-1, 0)) };
+                    let next_walkable = self.walkable((x + dx, y + dy));
+                    for (sx, sy) in [side_a, side_b] {
+                        let side_walkable = self.walkable((x + sx, y + sy));
+                        if next_walkable && side_walkable {
+                            directions.push((x + dx + sx, y + dy + sy));
+                        }
+                        if side_walkable {
+                            directions.push((x + sx, y + sy));
+                        }
+                    }
+                    if next_walkable {
+                        directions.push((x + dx, y + dy));
+                    }
+                }
+                directions
+            }
+        };
+
+        directions
+            .into_iter()
+            .filter_map(|(nx, ny)| self.jump((nx, ny), (nx - x, ny - y), goal))
+            .collect()
+    }
+
+    // walk from `cell` in `direction` until a jump point (the goal, or a cell with forced neighbors) or an obstacle.
+    fn jump(&self, mut cell: (i64, i64), (dx, dy): (i64, i64), goal: (i64, i64)) -> Option<(i64, i64)> {
+        loop {
+            let (x, y) = cell;
+            if !self.walkable(cell) {
+                return None;
+            }
+            if cell == goal {
+                return Some(cell);
+            }
+
+            if dx != 0 && dy != 0 {
+                // diagonal: a jump point if one of its straight components finds one
+                if self.jump((x + dx, y), (dx, 0), goal).is_some() || self.jump((x, y + dy), (0, dy), goal).is_some() {
+                    return Some(cell);
+                }
+            } else if dx != 0 {
+                if (self.walkable((x, y - 1)) && !self.walkable((x - dx, y - 1)))
+                    || (self.walkable((x, y + 1)) && !self.walkable((x - dx, y + 1))) {
+                    return Some(cell);
+                }
+            } else if (self.walkable((x - 1, y)) && !self.walkable((x - 1, y - dy)))
+                || (self.walkable((x + 1, y)) && !self.walkable((x + 1, y - dy))) {
+                return Some(cell);
+            }
+
+            // no corner cutting (for straight moves this only checks the next cell)
+            if self.walkable((x + dx, y)) && self.walkable((x, y + dy)) {
+                cell = (x + dx, y + dy);
+            } else {
+                return None;
+            }
+        }
+    }
+
+    // follow the parents back from the goal, filling in the cells between jump points.
+    fn build_path(&self, parents: &[Option<(i64, i64)>], goal: (i64, i64)) -> Vec<(usize, usize)> {
+        let mut path = vec![(goal.0 as usize, goal.1 as usize)];
+        let mut cell = goal;
+        while let Some(parent) = parents[self.index(cell)] {
+            let dx = (parent.0 - cell.0).signum();
+            let dy = (parent.1 - cell.1).signum();
+            while cell != parent {
+                cell = (cell.0 + dx, cell.1 + dy);
+                path.push((cell.0 as usize, cell.1 as usize));
+            }
+        }
+        path.reverse();
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // length of a path of adjacent cells, checking it doesn't go through walls or cut corners
+    fn path_cost(grid: &PathGrid, path: &[(usize, usize)]) -> f32 {
+        assert!(grid.is_path_valid(path));
+        path.windows(2)
+            .map(|step| {
+                let (ax, ay) = (step[0].0 as i64, step[0].1 as i64);
+                let (bx, by) = (step[1].0 as i64, step[1].1 as i64);
+                let (dx, dy) = (bx - ax, by - ay);
+                assert!(dx.abs() <= 1 && dy.abs() <= 1 && (dx, dy) != (0, 0), "{:?} isn't a step", step);
+                if dx != 0 && dy != 0 {
+                    assert!(grid.walkable((bx, ay)) && grid.walkable((ax, by)), "{:?} cuts a corner", step);
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                }
+            })
+            .sum()
+    }
+
+    // a 16x16 grid with two walls to go around
+    fn walled_grid() -> PathGrid {
+        let mut grid = PathGrid::new(16, 16);
+        for y in 0..12 {
+            grid.set_blocked(5, y, true);
+        }
+        for y in 4..16 {
+            grid.set_blocked(10, y, true);
+        }
+        for x in 11..15 {
+            grid.set_blocked(x, 8, true);
+        }
+        grid
+    }
+
+    #[test]
+    fn jump_point_matches_a_star_cost() {
+        let grid = walled_grid();
+        for (start, goal) in [((0, 0), (15, 15)), ((0, 15), (15, 0)), ((2, 3), (12, 14)), ((15, 9), (0, 0))] {
+            let a_star = grid.find_path(start, goal, PathAlgorithm::AStar).unwrap();
+            let jump_point = grid.find_path(start, goal, PathAlgorithm::JumpPoint).unwrap();
+            assert_eq!((a_star[0], *a_star.last().unwrap()), (start, goal));
+            assert_eq!((jump_point[0], *jump_point.last().unwrap()), (start, goal));
+
+            let a_star_cost = path_cost(&grid, &a_star);
+            let jump_point_cost = path_cost(&grid, &jump_point);
+            assert!((a_star_cost - jump_point_cost).abs() < 1e-3, "{} != {} from {:?} to {:?}", a_star_cost, jump_point_cost, start, goal);
+        }
+    }
+
+    #[test]
+    fn walled_off_goal_has_no_path() {
+        let mut grid = walled_grid();
+        for (x, y) in [(13, 13), (14, 13), (15, 13), (13, 14), (13, 15)] {
+            grid.set_blocked(x, y, true);
+        }
+        for algorithm in [PathAlgorithm::AStar, PathAlgorithm::JumpPoint] {
+            assert_eq!(grid.find_path((0, 0), (15, 15), algorithm), None);
+        }
+
+        // freed again
+        grid.set_blocked(13, 15, false);
+        for algorithm in [PathAlgorithm::AStar, PathAlgorithm::JumpPoint] {
+            assert!(grid.find_path((0, 0), (15, 15), algorithm).is_some());
+        }
+    }
+}
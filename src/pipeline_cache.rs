@@ -0,0 +1,395 @@
+use std::sync::{mpsc, Arc};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::app_config::DepthMode;
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::gpu::{create_camera_bind_group_layout, create_environment_bind_group_layout, InstanceRaw};
+use super::hot_reload::load_shader;
+use super::mesh::Vertex;
+use super::render_state::RenderState;
+use super::shader::MaterialShader;
+
+// Work of the thread compiling the scene pipelines.
+enum ScenePipelineJob {
+    Compile(RenderState),
+    // with a user material shader
+    CompileMaterialShader(MaterialShader, RenderState),
+    // load the shader again, see `ScenePipelines::reload()`
+    ReloadShader
+}
+
+// What a scene pipeline is compiled from: a shader & a render state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ScenePipelineKey {
+    // `MaterialShader::key()` of a user material shader, None for the engine's shader
+    pub(crate) shader: Option<u64>,
+    pub(crate) render_state: RenderState
+}
+
+impl From<RenderState> for ScenePipelineKey {
+    // with the engine's shader
+    fn from(render_state: RenderState) -> Self {
+        Self {
+            shader: None,
+            render_state
+        }
+    }
+}
+
+// pipeline layout & module of a user material shader, the error message if it's broken
+type CompiledMaterialShader = Result<(wgpu::PipelineLayout, wgpu::ShaderModule), String>;
+
+// What a scene pipeline is built for, besides its render state.
+#[derive(Clone, Copy, Debug)]
+struct ScenePipelineTarget {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_mode: DepthMode
+}
+
+// Render pipelines of the scene, one per shader & "Render State", compiled on a background thread.
+// Every material can have its own cull mode, depth bias & alpha mode, which are baked into the render pipeline,
+// & its own shader (see `MaterialShader`): the pipelines are requested ahead of time (warm-up), or on first use,
+// and what's drawn with them is skipped until they're ready, instead of stalling the frame while the driver compiles them.
+// tips: wgpu's error scopes are shared by the whole device, the error scope of each pipeline is taken in turn with the ones of the main thread
+// (see `catch_validation_error()`), so an error is never reported by the wrong scope.
+pub(crate) struct ScenePipelines {
+    ready: Vec<(ScenePipelineKey, wgpu::RenderPipeline)>,
+    // requested & not ready yet
+    pending: Vec<ScenePipelineKey>,
+    // failed to build, not requested again
+    failed: Vec<ScenePipelineKey>,
+    // of the user material shaders requested so far, to report their errors
+    shader_labels: Vec<(u64, String)>,
+    jobs: mpsc::Sender<ScenePipelineJob>,
+    results: mpsc::Receiver<(ScenePipelineKey, Result<wgpu::RenderPipeline>)>
+}
+
+impl ScenePipelines {
+    pub(crate) fn new(
+        device: Arc<wgpu::Device>,
+        render_pipeline_layout: wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_mode: DepthMode
+    ) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<ScenePipelineJob>();
+        let (result_sender, results) = mpsc::channel();
+        let target = ScenePipelineTarget { format, sample_count, depth_mode };
+
+        std::thread::spawn(move || {
+            // Load "Shaders" (WGSL)
+            let load_shader_module = || catch_validation_error(&device, || {
+                device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                    label: Some("Shader"),
+                    source: wgpu::ShaderSource::Wgsl(load_shader("shader.wgsl", include_str!("res/shaders/shader.wgsl")))
+                })
+            });
+            let mut shader_module = load_shader_module();
+            // Load "Shaders" (GLSL/HLSL)
+            // let vertex_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/shader.vert.spv"));
+            // let fragment_shader_module = device.create_shader_module(&wgpu::include_spirv!("res/shaders/shader.frag.spv"));
+
+            // the engine's bind groups of the user material shaders (camera, environment)
+            let engine_bind_group_layouts = (create_camera_bind_group_layout(&device), create_environment_bind_group_layout(&device));
+            // every user material shader seen so far
+            let mut material_shaders: Vec<(u64, CompiledMaterialShader)> = Vec::new();
+
+            // stops once the engine is dropped
+            for job in job_receiver {
+                let (key, render_pipeline) = match job {
+                    ScenePipelineJob::Compile(render_state) => {
+                        // Create "Render Pipeline" inside an error scope, a broken shader shouldn't crash the whole application.
+                        let render_pipeline = match &shader_module {
+                            Ok(shader_module) => catch_validation_error(&device, || {
+                                let fragment_entry = render_state.fragment_entry(target.sample_count);
+                                create_scene_pipeline(&device, &render_pipeline_layout, shader_module, fragment_entry, target, &render_state)
+                            }),
+                            Err(error) => Err(anyhow!("{}", error))
+                        };
+                        (render_state.into(), render_pipeline)
+                    }
+                    ScenePipelineJob::CompileMaterialShader(shader, render_state) => {
+                        if !material_shaders.iter().any(|(key, _)| *key == shader.key()) {
+                            let compiled = compile_material_shader(&device, &shader, &engine_bind_group_layouts)
+                                .map_err(|error| format!("{:?}", error));
+                            material_shaders.push((shader.key(), compiled));
+                        }
+                        let compiled = material_shaders
+                            .iter()
+                            .find(|(key, _)| *key == shader.key())
+                            .map(|(_, compiled)| compiled);
+                        let render_pipeline = match compiled {
+                            Some(Ok((layout, shader_module))) => catch_validation_error(&device, || {
+                                create_scene_pipeline(&device, layout, shader_module, MaterialShader::FRAGMENT_ENTRY, target, &render_state)
+                            }),
+                            Some(Err(error)) => Err(anyhow!("{}", error)),
+                            None => continue
+                        };
+                        (ScenePipelineKey { shader: Some(shader.key()), render_state }, render_pipeline)
+                    }
+                    ScenePipelineJob::ReloadShader => {
+                        shader_module = load_shader_module();
+                        continue;
+                    }
+                };
+                if result_sender.send((key, render_pipeline)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            ready: Vec::new(),
+            pending: Vec::new(),
+            failed: Vec::new(),
+            shader_labels: Vec::new(),
+            jobs,
+            results
+        }
+    }
+
+    fn is_known(&self, key: &ScenePipelineKey) -> bool {
+        self.pending.contains(key) || self.failed.contains(key) || self.ready.iter().any(|(ready_key, _)| ready_key == key)
+    }
+
+    // compile the pipeline of `render_state` with the engine's shader in the background, if it isn't already
+    pub(crate) fn request(&mut self, render_state: RenderState) {
+        let key = render_state.into();
+        if !self.is_known(&key) && self.jobs.send(ScenePipelineJob::Compile(render_state)).is_ok() {
+            self.pending.push(key);
+        }
+    }
+
+    // compile the pipeline of `render_state` with a user material shader in the background, if it isn't already
+    pub(crate) fn request_material_shader(&mut self, shader: &MaterialShader, render_state: RenderState) {
+        let key = ScenePipelineKey { shader: Some(shader.key()), render_state };
+        if self.is_known(&key) {
+            return;
+        }
+        if !self.shader_labels.iter().any(|(shader_key, _)| *shader_key == shader.key()) {
+            self.shader_labels.push((shader.key(), shader.label().to_string()));
+        }
+        if self.jobs.send(ScenePipelineJob::CompileMaterialShader(shader.clone(), render_state)).is_ok() {
+            self.pending.push(key);
+        }
+    }
+
+    // Compile every pipeline of the engine's shader again with the shader on disk (shader hot-reloading),
+    // the current ones are drawn with until they're replaced.
+    pub(crate) fn reload(&mut self) {
+        if self.jobs.send(ScenePipelineJob::ReloadShader).is_err() {
+            return;
+        }
+        let failed = std::mem::take(&mut self.failed);
+        let (engine_failed, material_failed) = failed.into_iter().partition::<Vec<_>, _>(|key| key.shader.is_none());
+        self.failed = material_failed;
+        let render_states = self.ready
+            .iter()
+            .map(|(key, _)| *key)
+            .chain(engine_failed)
+            .filter(|key| key.shader.is_none())
+            .map(|key| key.render_state)
+            .collect::<Vec<_>>();
+        for render_state in render_states {
+            let key = render_state.into();
+            if !self.pending.contains(&key) && self.jobs.send(ScenePipelineJob::Compile(render_state)).is_ok() {
+                self.pending.push(key);
+            }
+        }
+    }
+
+    // the label of the errors of `key`'s pipeline
+    fn error_label(&self, key: &ScenePipelineKey) -> String {
+        let shader_label = key.shader.and_then(|shader| {
+            self.shader_labels
+                .iter()
+                .find(|(shader_key, _)| *shader_key == shader)
+                .map(|(_, label)| label.as_str())
+        });
+        match shader_label {
+            Some(shader_label) => format!("{} Render Pipeline", shader_label),
+            None => "Render Pipeline".to_string()
+        }
+    }
+
+    // a pipeline compiled in the background, replacing the previous one once reloaded
+    fn finish(&mut self, key: ScenePipelineKey, render_pipeline: wgpu::RenderPipeline) {
+        self.pending.retain(|pending_key| *pending_key != key);
+        self.ready.retain(|(ready_key, _)| *ready_key != key);
+        self.ready.push((key, render_pipeline));
+    }
+
+    // collect the pipelines compiled since the last frame
+    pub(crate) fn poll(&mut self, error_overlay: &mut ErrorOverlay) {
+        while let Ok((key, render_pipeline)) = self.results.try_recv() {
+            match render_pipeline {
+                Ok(render_pipeline) => self.finish(key, render_pipeline),
+                Err(error) => {
+                    // a pipeline which fails to reload keeps drawing with the previous shader
+                    self.pending.retain(|pending_key| *pending_key != key);
+                    error_overlay.report(&self.error_label(&key), &error);
+                    self.failed.push(key);
+                }
+            }
+        }
+    }
+
+    // block until the pipeline of `render_state` with `shader` (the engine's one if None) is compiled,
+    // e.g. for offscreen rendering without frames to poll on
+    pub(crate) fn wait(&mut self, shader: Option<&MaterialShader>, render_state: RenderState) -> Result<&wgpu::RenderPipeline> {
+        let key = match shader {
+            Some(shader) => {
+                self.request_material_shader(shader, render_state);
+                ScenePipelineKey { shader: Some(shader.key()), render_state }
+            }
+            None => {
+                self.request(render_state);
+                render_state.into()
+            }
+        };
+        while self.pending.contains(&key) {
+            let (done_key, render_pipeline) = self.results.recv().context("the scene pipelines thread stopped")?;
+            match render_pipeline {
+                Ok(render_pipeline) => self.finish(done_key, render_pipeline),
+                Err(error) => {
+                    self.pending.retain(|pending_key| *pending_key != done_key);
+                    self.failed.push(done_key);
+                    if done_key == key {
+                        return Err(error.context(self.error_label(&key)));
+                    }
+                }
+            }
+        }
+        self.get(&key).with_context(|| format!("the pipeline of {:?} failed to build", render_state))
+    }
+
+    // pipelines which failed to build, until the shader is reloaded
+    pub(crate) fn failed(&self) -> &[ScenePipelineKey] {
+        &self.failed
+    }
+
+    // None until it's compiled, or if it failed to build
+    pub(crate) fn get(&self, key: &ScenePipelineKey) -> Option<&wgpu::RenderPipeline> {
+        self.ready
+            .iter()
+            .find(|(ready_key, _)| ready_key == key)
+            .map(|(_, render_pipeline)| render_pipeline)
+    }
+}
+
+// Check a user material shader against its declarations, then build its module & pipeline layout:
+// its material bind group, then the engine's ones.
+fn compile_material_shader(
+    device: &wgpu::Device,
+    shader: &MaterialShader,
+    (camera_bind_group_layout, environment_bind_group_layout): &(wgpu::BindGroupLayout, wgpu::BindGroupLayout)
+) -> Result<(wgpu::PipelineLayout, wgpu::ShaderModule)> {
+    shader.reflect()?;
+    catch_validation_error(device, || {
+        let material_bind_group_layout = shader.create_bind_group_layout(device);
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} Pipeline Layout", shader.label())),
+            bind_group_layouts: &[&material_bind_group_layout, camera_bind_group_layout, environment_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(shader.label()),
+            source: wgpu::ShaderSource::Wgsl(shader.source().into())
+        });
+        (render_pipeline_layout, shader_module)
+    })
+}
+
+// `fragment_entry` of the engine's shader depends on the alpha mode: "fs_main" or "fs_masked"
+fn create_scene_pipeline(
+    device: &wgpu::Device,
+    render_pipeline_layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    fragment_entry: &str,
+    target: ScenePipelineTarget,
+    render_state: &RenderState
+) -> wgpu::RenderPipeline {
+    let vertex_shader_ref = shader_module;
+    let fragment_shader_ref = shader_module;
+    let vertex_entry = "vs_main";
+    // let vertex_shader_ref = &vertex_shader_module;
+    // let fragment_shader_ref = &fragment_shader_module;
+    // let vertex_entry = "main";
+    // let fragment_entry = "main"; // tips: masked materials aren't supported by the GLSL shaders
+
+    // Create "Render Pipeline"
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        // setup Pipeline Layout
+        layout: Some(render_pipeline_layout),
+        // setup Vertex Shader
+        vertex: wgpu::VertexState {
+            // specify the shader module
+            module: vertex_shader_ref,
+            // specify the entry point of vertex shader in shader file
+            entry_point: vertex_entry,
+            // layout of the vertices which we want to pass to the vertex shader
+            buffers: &[
+                Vertex::desc(),
+                InstanceRaw::desc(),
+            ],
+        },
+        // setup Fragment Shader
+        // this is technically optional, so you have to wrap it in Some().
+        // We need it if we want to store color data to the surface.
+        fragment: Some(wgpu::FragmentState {
+            module: fragment_shader_ref,
+            // specify the entry point of fragment shader in shader file
+            entry_point: fragment_entry,
+            // tells wgpu what color outputs it should set up.
+            // Currently, we only need one for the "Surface"
+            targets: &[
+                wgpu::ColorTargetState {
+                    format: target.format,
+                    blend: Some(wgpu::BlendState::REPLACE), // REPLACE : replace old pixel data with new data
+                    write_mask: wgpu::ColorWrites::ALL // ALL: write all color channels (R G B A)
+                }
+            ]
+        }),
+        // describes how to interpret our vertices when converting them into triangles.
+        // == OpenGL Vertex Buffer Layout
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList, // TriangleList: each three vertices will correspond to one triangle.
+            strip_index_format: None,
+            // `front_face` & `cull_mode`: how to determine whether a given triangle is facing forward or not.
+            front_face: wgpu::FrontFace::Ccw, // Ccw: triangle is facing forward if the vertices are arranged in a counter-clockwise direction.
+            // Back: triangles that are not facing forward are culled (not included in the render)
+            cull_mode: render_state.cull_mode.into(),
+            // tips: Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // tips: Enable requires Features::DEPTH_CLAMPING
+            unclipped_depth: false,
+            // tips: Enable requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        // using a depth/stencil buffer
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: super::texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            // how to compare depth values in the depth test.
+            depth_compare: target.depth_mode.compare(), // pixels will be drawn front to back.
+            // here's another type of buffer called a stencil buffer.
+            // It's common practice to store the stencil buffer and depth buffer in the same texture.
+            stencil: wgpu::StencilState::default(), // we aren't using stencil buffer, so set default value.
+            bias: target.depth_mode.depth_bias(render_state.depth_bias.into())
+        }),
+        multisample: wgpu::MultisampleState {
+            // how many samples the pipeline will use
+            count: target.sample_count,
+            // which samples should be active
+            mask: !0, // !0 means using all of them
+            // use the fragment alpha as sample coverage (for masked materials)
+            alpha_to_coverage_enabled: render_state.uses_alpha_to_coverage(target.sample_count),
+        },
+        // If the pipeline will be used with a multiview render pass, this
+        // indicates how many array layers the attachments will have.
+        multiview: None
+    })
+}
@@ -0,0 +1,234 @@
+use super::app_config::DepthMode;
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+use super::msaa::MsaaTargets;
+use super::texture::Texture;
+
+// Low resolution color & depth targets the scene is rendered into in pixel-perfect mode (see `PixelPerfect`),
+// scaled up into the frame by a whole factor with nearest filtering.
+pub(crate) struct PixelPerfectTargets {
+    // of the low resolution targets
+    config: wgpu::SurfaceConfiguration,
+    zoom: u32,
+    color: Texture,
+    depth: Texture,
+    depth_mode: DepthMode,
+    // multisampled targets of the low resolution, with MSAA
+    msaa: Option<MsaaTargets>,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    upscale_pipeline: Option<wgpu::RenderPipeline> // None if the pipeline failed to build
+}
+
+impl PixelPerfectTargets {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration, // of the window
+        sample_count: u32,
+        depth_mode: DepthMode,
+        (width, height): (u32, u32),
+        zoom: u32,
+        error_overlay: &mut ErrorOverlay
+    ) -> Self {
+        let config = wgpu::SurfaceConfiguration {
+            width,
+            height,
+            ..config.clone()
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Pixel Perfect BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+        // crisp texels
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Pixel Perfect Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        // the upscale is a plain blit, the viewport does the scaling
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pixel Perfect Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let upscale_pipeline = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Pixel Perfect Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("blit.wgsl", include_str!("res/shaders/blit.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Pixel Perfect Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the full-screen triangle is generated from the vertex index
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let upscale_pipeline = upscale_pipeline
+            .map_err(|error| error_overlay.report("Pixel Perfect Render Pipeline", &error))
+            .ok();
+
+        let msaa = if sample_count > 1 {
+            Some(MsaaTargets::new(device, &config, sample_count, error_overlay))
+        } else {
+            None
+        };
+        let (color, depth, bind_group) = Self::create_targets(device, &config, depth_mode, &sampler, &bind_group_layout);
+        Self {
+            config,
+            zoom,
+            color,
+            depth,
+            depth_mode,
+            msaa,
+            sampler,
+            bind_group_layout,
+            bind_group,
+            upscale_pipeline
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_mode: DepthMode,
+        sampler: &wgpu::Sampler,
+        bind_group_layout: &wgpu::BindGroupLayout
+    ) -> (Texture, Texture, wgpu::BindGroup) {
+        let color = Texture::create_render_target(device, config.width, config.height, config.format, wgpu::TextureUsages::empty(), "Pixel Perfect Color Target");
+        let depth = Texture::create_depth_texture(device, config, depth_mode, "Pixel Perfect Depth Target");
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Pixel Perfect Bind Group"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler)
+                }
+            ]
+        });
+        (color, depth, bind_group)
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    // new low resolution & zoom, e.g. when the window is resized
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, (width, height): (u32, u32), zoom: u32) {
+        self.zoom = zoom;
+        if (width, height) == self.size() {
+            return;
+        }
+        self.config.width = width;
+        self.config.height = height;
+        let (color, depth, bind_group) = Self::create_targets(device, &self.config, self.depth_mode, &self.sampler, &self.bind_group_layout);
+        self.color = color;
+        self.depth = depth;
+        self.bind_group = bind_group;
+        if let Some(msaa) = &mut self.msaa {
+            msaa.resize(device, &self.config);
+        }
+    }
+
+    // the single-sampled targets the scene ends up in
+    pub(crate) fn color_view(&self) -> &wgpu::TextureView {
+        &self.color.view
+    }
+
+    pub(crate) fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth.view
+    }
+
+    pub(crate) fn msaa(&self) -> Option<&MsaaTargets> {
+        self.msaa.as_ref()
+    }
+
+    // scale the low resolution frame up into `texture_view` (of the window size), centered on black borders
+    pub(crate) fn upscale(&self, texture_view: &wgpu::TextureView, (width, height): (u32, u32), command_encoder: &mut wgpu::CommandEncoder) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Pixel Perfect Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: None
+        });
+        let upscale_pipeline = match &self.upscale_pipeline {
+            Some(upscale_pipeline) => upscale_pipeline,
+            None => return
+        };
+        // the low resolution times the zoom is never larger than the window
+        let scaled = ((self.config.width * self.zoom).min(width), (self.config.height * self.zoom).min(height));
+        if scaled.0 == 0 || scaled.1 == 0 {
+            return;
+        }
+        let offset = ((width - scaled.0) / 2, (height - scaled.1) / 2);
+        render_pass.set_viewport(offset.0 as f32, offset.1 as f32, scaled.0 as f32, scaled.1 as f32, 0.0, 1.0);
+        render_pass.set_pipeline(upscale_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
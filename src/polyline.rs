@@ -0,0 +1,293 @@
+use super::app_config::DepthMode;
+use super::hot_reload::load_shader;
+
+// Width of a polyline. Native lines are always 1px wide, so polylines are expanded into quads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineWidth {
+    // constant width on screen, whatever the distance to the camera.
+    Pixels(f32),
+    // width in world units, the line gets thinner with distance.
+    World(f32)
+}
+
+// A 3D line strip: trajectories, graphs, editor guides...
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<[f32; 3]>,
+    pub color: [f32; 4],
+    pub width: LineWidth
+}
+
+impl Polyline {
+    pub fn new(points: Vec<[f32; 3]>, color: [f32; 4], width: LineWidth) -> Self {
+        Self { points, color, width }
+    }
+
+    // a single segment
+    pub fn segment(start: [f32; 3], end: [f32; 3], color: [f32; 4], width: LineWidth) -> Self {
+        Self::new(vec![start, end], color, width)
+    }
+
+    // connect the last point back to the first one.
+    pub fn closed(mut self) -> Self {
+        if let Some(first) = self.points.first().copied() {
+            self.points.push(first);
+        }
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct LineVertex {
+    position: [f32; 3],
+    direction: [f32; 3],
+    side: f32,
+    width: f32,
+    world_space: f32,
+    color: [f32; 4]
+}
+
+impl LineVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+            0 => Float32x3, // position
+            1 => Float32x3, // direction
+            2 => Float32, // side
+            3 => Float32, // width
+            4 => Float32, // world_space
+            5 => Float32x4 // color
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct LineUniform {
+    view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    viewport: [f32; 4]
+}
+
+// Render polylines as camera-facing quads, on top of the scene but depth tested against it.
+pub(crate) struct PolylineRenderer {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    vertices_num: u32,
+    render_pipeline: Option<wgpu::RenderPipeline> // None if the pipeline failed to build
+}
+
+impl PolylineRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32, // of the scene's color & depth targets
+        depth_mode: DepthMode,
+        error_overlay: &mut super::error_overlay::ErrorOverlay
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Polyline Uniform Buffer"),
+            size: std::mem::size_of::<LineUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Polyline BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ]
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Polyline Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        // the vertex buffer grows on demand, see `prepare()`
+        let vertex_capacity = 1024;
+        let vertex_buffer = Self::create_vertex_buffer(device, vertex_capacity);
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Polyline Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let render_pipeline = super::error_overlay::catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Polyline Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("polyline.wgsl", include_str!("res/shaders/polyline.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Polyline Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[LineVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING), // lines may be translucent
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    // the quads may face either way depending on the line direction
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // lines are hidden behind the scene geometry, but don't hide it themselves
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: super::texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: depth_mode.compare(),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default()
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let render_pipeline = render_pipeline
+            .map_err(|error| error_overlay.report("Polyline Render Pipeline", &error))
+            .ok();
+
+        Self {
+            uniform_buffer,
+            bind_group,
+            vertex_buffer,
+            vertex_capacity,
+            vertices_num: 0,
+            render_pipeline
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, vertex_capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Polyline Vertex Buffer"),
+            size: (vertex_capacity * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        })
+    }
+
+    // Expand the polylines into quads and upload them, with the camera data.
+    pub(crate) fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        polylines: &[Polyline],
+        view_proj: nalgebra::Matrix4<f32>,
+        eye: nalgebra::Point3<f32>,
+        viewport: winit::dpi::PhysicalSize<u32>
+    ) {
+        let mut vertices = Vec::new();
+        for polyline in polylines {
+            let (width, world_space) = match polyline.width {
+                LineWidth::Pixels(width) => (width, 0.0),
+                LineWidth::World(width) => (width, 1.0)
+            };
+            for segment in polyline.points.windows(2) {
+                let (start, end) = (segment[0], segment[1]);
+                let direction = [end[0] - start[0], end[1] - start[1], end[2] - start[2]];
+                let vertex = |position: [f32; 3], side: f32| LineVertex {
+                    position,
+                    direction,
+                    side,
+                    width,
+                    world_space,
+                    color: polyline.color
+                };
+                // two triangles per segment
+                vertices.extend_from_slice(&[
+                    vertex(start, -1.0), vertex(start, 1.0), vertex(end, 1.0),
+                    vertex(start, -1.0), vertex(end, 1.0), vertex(end, -1.0),
+                ]);
+            }
+        }
+
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.vertices_num = vertices.len() as u32;
+
+        let uniform = LineUniform {
+            view_proj: view_proj.into(),
+            eye: [eye.x, eye.y, eye.z, 1.0],
+            viewport: [viewport.width as f32, viewport.height as f32, 0.0, 0.0]
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub(crate) fn render(&self, texture_view: &wgpu::TextureView, depth_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) {
+        let render_pipeline = match &self.render_pipeline {
+            Some(render_pipeline) if self.vertices_num > 0 => render_pipeline,
+            _ => return
+        };
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Polyline Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true
+                }
+            }],
+            // depth test against the scene rendered before
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            })
+        });
+
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertices_num, 0..1);
+    }
+}
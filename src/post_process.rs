@@ -0,0 +1,502 @@
+use wgpu::util::DeviceExt; // for `create_buffer_init`
+
+use super::blur::{BlurKernel, BlurPasses};
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+use super::render_target::PingPongTargets;
+use super::texture::Texture;
+
+// widest gaussian a single blur pass covers (3 sigmas <= 32 pixels), see `BlurKernel`
+const MAX_BLUR_SIGMA: f32 = 10.0;
+// the bloom is blurred at a quarter of the window size
+const BLOOM_DOWNSCALE: u32 = 4;
+
+// Curve mapping the HDR scene to the displayable range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TonemapOperator {
+    // cut at 1.0, what the scene looked like without post-processing
+    Clamp,
+    // c / (1 + luminance), soft & desaturates little
+    Reinhard,
+    // filmic curve fitted to ACES (Narkowicz), punchy contrast
+    Aces
+}
+
+impl TonemapOperator {
+    fn to_uniform(self) -> f32 {
+        match self {
+            TonemapOperator::Clamp => 0.0,
+            TonemapOperator::Reinhard => 1.0,
+            TonemapOperator::Aces => 2.0
+        }
+    }
+}
+
+// Which effect a `PostEffect` is, a `PostProcessStack` holds one of each at most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PostEffectKind {
+    Bloom,
+    Tonemap,
+    Vignette
+}
+
+// Full-screen effect of a `PostProcessStack`, applied to the HDR scene.
+// tips: the values are pre-exposed (see `Environment::exposure`), 1.0 is white on screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PostEffect {
+    // glow around the parts brighter than `threshold`, blurred over `radius` pixels (of the quarter size image) & added back
+    Bloom { threshold: f32, intensity: f32, radius: f32 },
+    // HDR to the displayable range, then `gamma` on top of the display encoding (1.0 leaves it as is)
+    Tonemap { operator: TonemapOperator, gamma: f32 },
+    // darkens (or tints with `color`) the corners: from `radius` (0.0 center, 1.0 corners) over `smoothness`
+    Vignette { intensity: f32, radius: f32, smoothness: f32, color: [f32; 3] }
+}
+
+impl PostEffect {
+    // bright highlights only
+    pub fn bloom() -> Self {
+        PostEffect::Bloom {
+            threshold: 1.0,
+            intensity: 0.3,
+            radius: 12.0
+        }
+    }
+
+    pub fn tonemap(operator: TonemapOperator) -> Self {
+        PostEffect::Tonemap { operator, gamma: 1.0 }
+    }
+
+    // subtle black corners
+    pub fn vignette() -> Self {
+        PostEffect::Vignette {
+            intensity: 0.4,
+            radius: 0.5,
+            smoothness: 0.6,
+            color: [0.0; 3]
+        }
+    }
+
+    pub fn kind(&self) -> PostEffectKind {
+        match self {
+            PostEffect::Bloom { .. } => PostEffectKind::Bloom,
+            PostEffect::Tonemap { .. } => PostEffectKind::Tonemap,
+            PostEffect::Vignette { .. } => PostEffectKind::Vignette
+        }
+    }
+}
+
+// Ordered chain of post-processing effects, returned by `Application::post_process()` every frame.
+// The scene (with its grid & polylines) is rendered into an HDR target, every enabled effect is applied in order,
+// then the result is written to the window; the lens flare, debug views, transitions & UI are drawn over it.
+// tips: effects usually go bloom (HDR), tonemap, then the LDR ones (vignette).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostProcessStack {
+    // in order, with whether they're enabled
+    effects: Vec<(PostEffect, bool)>
+}
+
+impl PostProcessStack {
+    // no effects: the scene is clamped to the displayable range
+    pub fn new() -> Self {
+        Self { effects: Vec::new() }
+    }
+
+    // Add `effect` at the end, enabled. An effect of the same kind is replaced in place instead.
+    pub fn with(mut self, effect: PostEffect) -> Self {
+        self.push(effect);
+        self
+    }
+
+    // see `with()`
+    pub fn push(&mut self, effect: PostEffect) {
+        match self.effects.iter_mut().find(|(own, _)| own.kind() == effect.kind()) {
+            Some((own, enabled)) => {
+                *own = effect;
+                *enabled = true;
+            },
+            None => self.effects.push((effect, true))
+        }
+    }
+
+    pub fn remove(&mut self, kind: PostEffectKind) -> Option<PostEffect> {
+        let index = self.index(kind)?;
+        Some(self.effects.remove(index).0)
+    }
+
+    // disabled effects keep their settings & place in the chain, false if there's no such effect
+    pub fn set_enabled(&mut self, kind: PostEffectKind, enabled: bool) -> bool {
+        match self.index(kind) {
+            Some(index) => {
+                self.effects[index].1 = enabled;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn is_enabled(&self, kind: PostEffectKind) -> bool {
+        self.index(kind).is_some_and(|index| self.effects[index].1)
+    }
+
+    pub fn get(&self, kind: PostEffectKind) -> Option<&PostEffect> {
+        self.index(kind).map(|index| &self.effects[index].0)
+    }
+
+    pub fn get_mut(&mut self, kind: PostEffectKind) -> Option<&mut PostEffect> {
+        let index = self.index(kind)?;
+        Some(&mut self.effects[index].0)
+    }
+
+    // Move the effect to `index` in the chain (clamped to the end), false if there's no such effect.
+    pub fn move_to(&mut self, kind: PostEffectKind, index: usize) -> bool {
+        match self.index(kind) {
+            Some(from) => {
+                let effect = self.effects.remove(from);
+                self.effects.insert(index.min(self.effects.len()), effect);
+                true
+            },
+            None => false
+        }
+    }
+
+    // in order, with whether they're enabled
+    pub fn iter(&self) -> impl Iterator<Item = (&PostEffect, bool)> {
+        self.effects.iter().map(|(effect, enabled)| (effect, *enabled))
+    }
+
+    fn index(&self, kind: PostEffectKind) -> Option<usize> {
+        self.effects.iter().position(|(effect, _)| effect.kind() == kind)
+    }
+}
+
+impl Default for PostProcessStack {
+    // ACES tonemapping, with bloom & vignette set up but disabled
+    fn default() -> Self {
+        let mut stack = Self::new()
+            .with(PostEffect::bloom())
+            .with(PostEffect::tonemap(TonemapOperator::Aces))
+            .with(PostEffect::vignette());
+        stack.set_enabled(PostEffectKind::Bloom, false);
+        stack.set_enabled(PostEffectKind::Vignette, false);
+        stack
+    }
+}
+
+// `PostEffect` settings in the shader uniform buffer, their meaning depends on the effect
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostProcessUniform {
+    params: [f32; 4],
+    color: [f32; 4]
+}
+
+// Pipelines of the post-processing effects, one per fragment entry of post_process.wgsl.
+struct PostProcessPipelines {
+    bloom_prefilter: wgpu::RenderPipeline,
+    bloom_composite: wgpu::RenderPipeline,
+    tonemap: wgpu::RenderPipeline,
+    vignette: wgpu::RenderPipeline,
+    // writes the window's format
+    output: wgpu::RenderPipeline
+}
+
+// HDR scene target & the passes applying a `PostProcessStack` to it, on the way to the window.
+pub(crate) struct PostProcessPass {
+    stack: PostProcessStack,
+    // the scene is rendered into it
+    scene: Texture,
+    // results of the effects
+    targets: PingPongTargets,
+    // quarter size, blurred by `blur_passes`
+    bloom_targets: PingPongTargets,
+    blur_passes: Option<BlurPasses>, // None if the blur pipelines failed to build
+    srgb_target: bool,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipelines: Option<PostProcessPipelines> // None if the pipelines failed to build
+}
+
+impl PostProcessPass {
+    // float color, so the lighting can go over 1.0 until it's tonemapped
+    pub(crate) const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    pub(crate) fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, error_overlay: &mut ErrorOverlay) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Process BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                }
+            ]
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Process Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipelines = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Post Process Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("post_process.wgsl", include_str!("res/shaders/post_process.wgsl")))
+            });
+            let create_pipeline = |entry_point: &str, format: wgpu::TextureFormat| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Post Process Render Pipeline"),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader_module,
+                        entry_point: "vs_main",
+                        buffers: &[], // the full-screen triangle is generated from the vertex index
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader_module,
+                        entry_point,
+                        targets: &[
+                            wgpu::ColorTargetState {
+                                format,
+                                blend: None,
+                                write_mask: wgpu::ColorWrites::ALL
+                            }
+                        ]
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: None,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState {
+                        count: 1,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None
+                })
+            };
+            PostProcessPipelines {
+                bloom_prefilter: create_pipeline("fs_bloom_prefilter", Self::HDR_FORMAT),
+                bloom_composite: create_pipeline("fs_bloom_composite", Self::HDR_FORMAT),
+                tonemap: create_pipeline("fs_tonemap", Self::HDR_FORMAT),
+                vignette: create_pipeline("fs_vignette", Self::HDR_FORMAT),
+                output: create_pipeline("fs_output", config.format)
+            }
+        });
+        let pipelines = pipelines
+            .map_err(|error| error_overlay.report("Post Process Render Pipeline", &error))
+            .ok();
+        // the bloom is blurred by compute passes
+        let blur_passes = BlurPasses::new(device, Self::HDR_FORMAT)
+            .map_err(|error| error_overlay.report("Post Process Bloom", &error))
+            .ok();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let (scene, targets, bloom_targets) = Self::create_targets(device, (config.width, config.height));
+
+        Self {
+            stack: PostProcessStack::default(),
+            scene,
+            targets,
+            bloom_targets,
+            blur_passes,
+            srgb_target: config.format.describe().srgb,
+            sampler,
+            bind_group_layout,
+            pipelines
+        }
+    }
+
+    fn create_targets(device: &wgpu::Device, (width, height): (u32, u32)) -> (Texture, PingPongTargets, PingPongTargets) {
+        let scene = Texture::create_render_target(device, width, height, Self::HDR_FORMAT, wgpu::TextureUsages::empty(), "HDR Scene Target");
+        let targets = PingPongTargets::new(device, width, height, Self::HDR_FORMAT, wgpu::TextureUsages::empty(), "Post Process Target");
+        let bloom_targets = PingPongTargets::new(
+            device,
+            (width / BLOOM_DOWNSCALE).max(1),
+            (height / BLOOM_DOWNSCALE).max(1),
+            Self::HDR_FORMAT,
+            wgpu::TextureUsages::STORAGE_BINDING,
+            "Bloom Target"
+        );
+        (scene, targets, bloom_targets)
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let (scene, targets, bloom_targets) = Self::create_targets(device, (config.width, config.height));
+        self.scene = scene;
+        self.targets = targets;
+        self.bloom_targets = bloom_targets;
+    }
+
+    pub(crate) fn set_stack(&mut self, stack: PostProcessStack) {
+        self.stack = stack;
+    }
+
+    // the HDR target the scene is rendered into, of the window size
+    pub(crate) fn scene_view(&self) -> &wgpu::TextureView {
+        &self.scene.view
+    }
+
+    fn bind_group(&self, device: &wgpu::Device, source: &wgpu::TextureView, bloom: &wgpu::TextureView, uniform: PostProcessUniform) -> wgpu::BindGroup {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Process Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post Process Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(bloom)
+                }
+            ]
+        })
+    }
+
+    fn draw(command_encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::RenderPipeline, target: &wgpu::TextureView, bind_group: &wgpu::BindGroup) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Process Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: None
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // apply the enabled effects to the scene, in order, & write the result into `texture_view` (of the window)
+    pub(crate) fn render(&mut self, device: &wgpu::Device, texture_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) {
+        let pipelines = match &self.pipelines {
+            Some(pipelines) => pipelines,
+            None => return
+        };
+        // the first effect reads the scene, the next ones the result of the previous one
+        let mut from_scene = true;
+        for (effect, enabled) in self.stack.iter() {
+            if !enabled {
+                continue;
+            }
+            let source = if from_scene { &self.scene.view } else { self.targets.read_view() };
+            match *effect {
+                PostEffect::Bloom { threshold, intensity, radius } => {
+                    let blur_passes = match &self.blur_passes {
+                        Some(blur_passes) => blur_passes,
+                        None => continue
+                    };
+                    // bright parts, at a quarter size
+                    let uniform = PostProcessUniform {
+                        params: [threshold, threshold * 0.5, 0.0, 0.0],
+                        color: [0.0; 4]
+                    };
+                    let bind_group = self.bind_group(device, source, source, uniform);
+                    Self::draw(command_encoder, &pipelines.bloom_prefilter, self.bloom_targets.write_view(), &bind_group);
+                    self.bloom_targets.swap();
+                    // several small gaussians add up to a wide one: sigma = sqrt(passes) * sigma of a pass
+                    let passes = ((radius / MAX_BLUR_SIGMA).powi(2).ceil() as u32).clamp(1, 8);
+                    let sigma = radius.max(0.5) / (passes as f32).sqrt();
+                    for _ in 0..passes {
+                        blur_passes.blur(device, command_encoder, &mut self.bloom_targets, BlurKernel::Gaussian { sigma });
+                    }
+                    let uniform = PostProcessUniform {
+                        params: [intensity, 0.0, 0.0, 0.0],
+                        color: [0.0; 4]
+                    };
+                    let bind_group = self.bind_group(device, source, self.bloom_targets.read_view(), uniform);
+                    Self::draw(command_encoder, &pipelines.bloom_composite, self.targets.write_view(), &bind_group);
+                },
+                PostEffect::Tonemap { operator, gamma } => {
+                    let uniform = PostProcessUniform {
+                        params: [operator.to_uniform(), gamma, 0.0, 0.0],
+                        color: [0.0; 4]
+                    };
+                    let bind_group = self.bind_group(device, source, source, uniform);
+                    Self::draw(command_encoder, &pipelines.tonemap, self.targets.write_view(), &bind_group);
+                },
+                PostEffect::Vignette { intensity, radius, smoothness, color } => {
+                    let uniform = PostProcessUniform {
+                        params: [intensity, radius, smoothness, 0.0],
+                        color: [color[0], color[1], color[2], 1.0]
+                    };
+                    let bind_group = self.bind_group(device, source, source, uniform);
+                    Self::draw(command_encoder, &pipelines.vignette, self.targets.write_view(), &bind_group);
+                }
+            }
+            self.targets.swap();
+            from_scene = false;
+        }
+
+        // clamped & encoded for the window
+        let source = if from_scene { &self.scene.view } else { self.targets.read_view() };
+        let uniform = PostProcessUniform {
+            params: [if self.srgb_target { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0],
+            color: [0.0; 4]
+        };
+        let bind_group = self.bind_group(device, source, source, uniform);
+        Self::draw(command_encoder, &pipelines.output, texture_view, &bind_group);
+    }
+}
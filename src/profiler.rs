@@ -0,0 +1,444 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::time::{Duration, Instant};
+
+// A timed span of a frame, spans nest inside each other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileSpan {
+    pub name: &'static str,
+    // nesting level, 0 for the outermost spans
+    pub depth: usize,
+    // since the beginning of the frame
+    pub start: Duration,
+    pub duration: Duration
+}
+
+// All the spans of a frame, in the order they began.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameProfile {
+    pub index: u64,
+    pub duration: Duration,
+    // time the GPU spent on the frame's commands, None until it's read back or if the adapter can't measure it
+    pub gpu_duration: Option<Duration>,
+    pub spans: Vec<ProfileSpan>,
+    // the GPU time of the engine's passes (scene, post process...), read back with `gpu_duration`
+    pub gpu_spans: Vec<ProfileSpan>
+}
+
+impl FrameProfile {
+    // flame-style breakdown: one span per line, indented by depth.
+    pub fn report(&self) -> String {
+        let mut report = format!("frame #{}: {:.3}ms", self.index, self.duration.as_secs_f64() * 1000.0);
+        if let Some(gpu_duration) = self.gpu_duration {
+            report += &format!(" (gpu {:.3}ms)", gpu_duration.as_secs_f64() * 1000.0);
+        }
+        report += "\n";
+        for span in &self.spans {
+            report += &format!(
+                "{}{} {:.3}ms\n",
+                "  ".repeat(span.depth + 1),
+                span.name,
+                span.duration.as_secs_f64() * 1000.0
+            );
+        }
+        if let Some(gpu_duration) = self.gpu_duration {
+            report += &format!("  gpu {:.3}ms\n", gpu_duration.as_secs_f64() * 1000.0);
+            for span in &self.gpu_spans {
+                report += &format!(
+                    "{}{} {:.3}ms\n",
+                    "  ".repeat(span.depth + 2),
+                    span.name,
+                    span.duration.as_secs_f64() * 1000.0
+                );
+            }
+        }
+        report
+    }
+}
+
+// Hierarchical CPU profiler, keeping the last frames to find spikes, with the GPU time of the frames where the adapter can measure it.
+// Use the thread-local one through `profile_scope()` & `Profiler::with()`, the engine shows it in a debug UI window (feature "egui", P key).
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    pub enabled: bool,
+    frame_start: Option<Instant>,
+    frame_index: u64,
+    current: Vec<ProfileSpan>,
+    open: Vec<(usize, Instant)>, // (index in `current`, start) of the spans not ended yet
+    history: VecDeque<FrameProfile>,
+    history_len: usize,
+    worst: Option<FrameProfile>
+}
+
+thread_local! {
+    static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::new(300));
+}
+
+impl Profiler {
+    // keeps the last `history_len` frames.
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            enabled: true,
+            frame_start: None,
+            frame_index: 0,
+            current: Vec::new(),
+            open: Vec::new(),
+            history: VecDeque::with_capacity(history_len),
+            history_len,
+            worst: None
+        }
+    }
+
+    // access the profiler of the current thread
+    pub fn with<T>(f: impl FnOnce(&mut Profiler) -> T) -> T {
+        PROFILER.with(|profiler| f(&mut profiler.borrow_mut()))
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.current.clear();
+        self.open.clear();
+        self.frame_start = if self.enabled { Some(Instant::now()) } else { None };
+    }
+
+    pub fn end_frame(&mut self) {
+        let frame_start = match self.frame_start.take() {
+            Some(frame_start) => frame_start,
+            None => return
+        };
+        // close the spans left open
+        while !self.open.is_empty() {
+            self.end_span();
+        }
+
+        let frame = FrameProfile {
+            index: self.frame_index,
+            duration: frame_start.elapsed(),
+            gpu_duration: None,
+            spans: std::mem::take(&mut self.current),
+            gpu_spans: Vec::new()
+        };
+        self.frame_index += 1;
+
+        let is_worst = match &self.worst {
+            Some(worst) => frame.duration > worst.duration,
+            None => true
+        };
+        if is_worst {
+            self.worst = Some(frame.clone());
+        }
+        if self.history.len() == self.history_len {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame);
+    }
+
+    pub fn begin_span(&mut self, name: &'static str) {
+        let frame_start = match self.frame_start {
+            Some(frame_start) => frame_start,
+            None => return
+        };
+        let now = Instant::now();
+        self.current.push(ProfileSpan {
+            name,
+            depth: self.open.len(),
+            start: now - frame_start,
+            duration: Duration::ZERO
+        });
+        self.open.push((self.current.len() - 1, now));
+    }
+
+    pub fn end_span(&mut self) {
+        if let Some((index, start)) = self.open.pop() {
+            self.current[index].duration = start.elapsed();
+        }
+    }
+
+    // index of the frame being profiled
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    // the GPU time of a frame & of its passes, measured a few frames later (see `GpuTimer`)
+    pub fn record_gpu_duration(&mut self, frame_index: u64, gpu_duration: Duration, gpu_spans: &[ProfileSpan]) {
+        let frames = self.history.iter_mut().chain(self.worst.as_mut());
+        for frame in frames.filter(|frame| frame.index == frame_index) {
+            frame.gpu_duration = Some(gpu_duration);
+            frame.gpu_spans = gpu_spans.to_vec();
+        }
+    }
+
+    // the last frames, oldest first (e.g. for a frame time graph)
+    pub fn history(&self) -> impl Iterator<Item = &FrameProfile> {
+        self.history.iter()
+    }
+
+    pub fn last_frame(&self) -> Option<&FrameProfile> {
+        self.history.back()
+    }
+
+    // slowest frame since the start or the last `reset_worst_frame()`
+    pub fn worst_frame(&self) -> Option<&FrameProfile> {
+        self.worst.as_ref()
+    }
+
+    pub fn reset_worst_frame(&mut self) {
+        self.worst = None;
+    }
+
+    // Frame time graph of the history (CPU & GPU), the last frame & the breakdown of the worst one (feature "egui").
+    #[cfg(feature = "egui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        use egui::plot::{Legend, Line, Plot, Value, Values};
+
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        ui.checkbox(&mut self.enabled, "enabled");
+        if let Some(last_frame) = self.last_frame() {
+            let gpu = last_frame.gpu_duration.map_or_else(|| "-".to_string(), |gpu_duration| format!("{:.2}ms", milliseconds(gpu_duration)));
+            ui.label(format!("frame #{}: cpu {:.2}ms, gpu {}", last_frame.index, milliseconds(last_frame.duration), gpu));
+            if !last_frame.gpu_spans.is_empty() {
+                let passes = last_frame.gpu_spans
+                    .iter()
+                    .map(|span| format!("{} {:.2}ms", span.name, milliseconds(span.duration)))
+                    .collect::<Vec<_>>();
+                ui.label(format!("gpu passes: {}", passes.join(", ")));
+            }
+        }
+
+        let cpu_line = Line::new(Values::from_values_iter(
+            self.history.iter().map(|frame| Value::new(frame.index as f64, milliseconds(frame.duration)))
+        ))
+        .name("cpu (ms)");
+        let gpu_line = Line::new(Values::from_values_iter(self.history.iter().filter_map(|frame| {
+            frame.gpu_duration.map(|gpu_duration| Value::new(frame.index as f64, milliseconds(gpu_duration)))
+        })))
+        .name("gpu (ms)");
+        Plot::new("profiler history")
+            .height(120.0)
+            .include_y(0.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .legend(Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui.line(cpu_line);
+                plot_ui.line(gpu_line);
+            });
+
+        ui.horizontal(|ui| {
+            ui.label("worst frame");
+            // look for the next spike
+            if ui.button("reset").clicked() {
+                self.reset_worst_frame();
+            }
+        });
+        if let Some(worst_frame) = &self.worst {
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                ui.monospace(worst_frame.report());
+            });
+        }
+    }
+}
+
+// pending readback of a frame's timestamps
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+// The passes timed on the GPU, in the order they're recorded: each one is between two timestamps.
+// tips: a pass groups the render passes in between, e.g. the post process, lens flare & transition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum GpuPass {
+    Scene,
+    PostProcess,
+    #[cfg(feature = "egui")]
+    DebugUi,
+    ErrorOverlay
+}
+
+const GPU_PASSES: &[GpuPass] = &[
+    GpuPass::Scene,
+    GpuPass::PostProcess,
+    #[cfg(feature = "egui")]
+    GpuPass::DebugUi,
+    GpuPass::ErrorOverlay
+];
+
+impl GpuPass {
+    fn name(self) -> &'static str {
+        match self {
+            GpuPass::Scene => "scene",
+            GpuPass::PostProcess => "post process",
+            #[cfg(feature = "egui")]
+            GpuPass::DebugUi => "debug ui",
+            GpuPass::ErrorOverlay => "error overlay"
+        }
+    }
+
+    // its first timestamp, after the ones of the frame
+    fn query(self) -> u32 {
+        2 + self as u32 * 2
+    }
+}
+
+// timestamps at the beginning & the end of a frame, then of every pass
+const GPU_TIMESTAMPS: u32 = 2 + GPU_PASSES.len() as u32 * 2;
+// frames read back at once, a frame isn't timed while they're all pending
+const GPU_TIMER_READBACKS: usize = 4;
+
+// GPU time of the frames & of their passes (see `GpuPass`), measured with timestamp queries where the adapter supports them
+// (`Features::TIMESTAMP_QUERY`), & recorded into the thread-local `Profiler`.
+// tips: every pass has to be timed in every frame, the timestamps are resolved all at once.
+// tips: the timestamps are read back a few frames later, the frame doesn't wait for the GPU.
+pub(crate) struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    // resolved into, with the frame index & the mapping of the pending ones
+    readbacks: Vec<(wgpu::Buffer, Option<(u64, MapFuture)>)>,
+    // readback of the frame being recorded, None if it isn't timed
+    recording: Option<usize>,
+    next_readback: usize,
+    // nanoseconds per tick
+    timestamp_period: f32
+}
+
+impl GpuTimer {
+    // None if the device can't write timestamps
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let size = GPU_TIMESTAMPS as u64 * std::mem::size_of::<u64>() as u64;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Timer Queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count: GPU_TIMESTAMPS
+        });
+        let readbacks = (0..GPU_TIMER_READBACKS)
+            .map(|_| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("GPU Timer Readback Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false
+                });
+                (buffer, None)
+            })
+            .collect();
+        Some(Self {
+            query_set,
+            readbacks,
+            recording: None,
+            next_readback: 0,
+            timestamp_period: queue.get_timestamp_period()
+        })
+    }
+
+    // before the frame's first command
+    pub(crate) fn begin_frame(&mut self, command_encoder: &mut wgpu::CommandEncoder) {
+        let free = self.readbacks[self.next_readback].1.is_none();
+        self.recording = free.then_some(self.next_readback);
+        if self.recording.is_some() {
+            command_encoder.write_timestamp(&self.query_set, 0);
+        }
+    }
+
+    // before the first command of `pass`
+    pub(crate) fn begin_pass(&mut self, pass: GpuPass, command_encoder: &mut wgpu::CommandEncoder) {
+        if self.recording.is_some() {
+            command_encoder.write_timestamp(&self.query_set, pass.query());
+        }
+    }
+
+    // after the last command of `pass`
+    pub(crate) fn end_pass(&mut self, pass: GpuPass, command_encoder: &mut wgpu::CommandEncoder) {
+        if self.recording.is_some() {
+            command_encoder.write_timestamp(&self.query_set, pass.query() + 1);
+        }
+    }
+
+    // after the frame's last command
+    pub(crate) fn end_frame(&mut self, command_encoder: &mut wgpu::CommandEncoder) {
+        if let Some(readback) = self.recording {
+            command_encoder.write_timestamp(&self.query_set, 1);
+            command_encoder.resolve_query_set(&self.query_set, 0..GPU_TIMESTAMPS, &self.readbacks[readback].0, 0);
+        }
+    }
+
+    // once the frame is submitted: read its timestamps back, & record the ones read back since the last frame
+    pub(crate) fn read_back(&mut self, device: &wgpu::Device) {
+        if let Some(readback) = self.recording.take() {
+            let frame_index = Profiler::with(|profiler| profiler.frame_index());
+            let (buffer, pending) = &mut self.readbacks[readback];
+            *pending = Some((frame_index, Box::pin(buffer.slice(..).map_async(wgpu::MapMode::Read))));
+            self.next_readback = (readback + 1) % self.readbacks.len();
+        }
+
+        device.poll(wgpu::Maintain::Poll);
+        let waker = noop_waker();
+        let mut context = Context::from_waker(&waker);
+        for (buffer, pending) in &mut self.readbacks {
+            let mapped = match pending {
+                Some((_, mapping)) => match mapping.as_mut().poll(&mut context) {
+                    Poll::Ready(mapped) => mapped,
+                    Poll::Pending => continue
+                },
+                None => continue
+            };
+            let frame_index = match pending.take() {
+                Some((frame_index, _)) => frame_index,
+                None => continue
+            };
+            if mapped.is_err() {
+                continue;
+            }
+            let timestamps = {
+                let data = buffer.slice(..).get_mapped_range();
+                bytemuck::cast_slice::<u8, u64>(&data).to_vec()
+            };
+            buffer.unmap();
+            let duration = |begin: u64, end: u64| Duration::from_nanos((end.saturating_sub(begin) as f64 * self.timestamp_period as f64) as u64);
+            let gpu_duration = duration(timestamps[0], timestamps[1]);
+            let gpu_spans = GPU_PASSES
+                .iter()
+                .map(|pass| {
+                    let query = pass.query() as usize;
+                    ProfileSpan {
+                        name: pass.name(),
+                        depth: 0,
+                        start: duration(timestamps[0], timestamps[query]),
+                        duration: duration(timestamps[query], timestamps[query + 1])
+                    }
+                })
+                .collect::<Vec<_>>();
+            Profiler::with(|profiler| profiler.record_gpu_duration(frame_index, gpu_duration, &gpu_spans));
+        }
+    }
+}
+
+// a waker doing nothing, the readbacks are polled every frame anyway
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    // safety: the functions of the vtable don't use the data pointer
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+}
+
+// Ends its span when dropped, see `profile_scope()`.
+pub struct ProfileScope {
+    _private: ()
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        Profiler::with(|profiler| profiler.end_span());
+    }
+}
+
+// Time the rest of the enclosing scope on the thread-local profiler:
+// `let _scope = profile_scope("physics");`
+pub fn profile_scope(name: &'static str) -> ProfileScope {
+    Profiler::with(|profiler| profiler.begin_span(name));
+    ProfileScope { _private: () }
+}
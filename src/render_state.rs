@@ -0,0 +1,124 @@
+// Which faces of a material are culled (not included in the render).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CullMode {
+    // cull triangles that are not facing forward, the usual case for closed meshes.
+    Back,
+    // cull triangles that are facing forward, e.g. for rendering the inside of a skybox.
+    Front,
+    // two-sided rendering, e.g. foliage cards, cloth and decals.
+    None
+}
+
+impl From<CullMode> for Option<wgpu::Face> {
+    fn from(cull_mode: CullMode) -> Self {
+        match cull_mode {
+            CullMode::Back => Some(wgpu::Face::Back),
+            CullMode::Front => Some(wgpu::Face::Front),
+            CullMode::None => None
+        }
+    }
+}
+
+// Offset applied to the depth of a material's fragments, e.g. to keep decals from z-fighting with the surface below.
+// Given for the standard depth range (negative towards the camera), it's flipped with `DepthMode::ReversedZ`.
+// ref: https://docs.rs/wgpu/0.12.0/wgpu/struct.DepthBiasState.html
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepthBias {
+    // constant depth biasing factor, in basic units of the depth format.
+    pub constant: i32,
+    // slope depth biasing factor.
+    pub slope_scale: f32,
+    // depth bias clamp value (absolute).
+    pub clamp: f32
+}
+
+impl From<DepthBias> for wgpu::DepthBiasState {
+    fn from(depth_bias: DepthBias) -> Self {
+        wgpu::DepthBiasState {
+            constant: depth_bias.constant,
+            slope_scale: depth_bias.slope_scale,
+            clamp: depth_bias.clamp
+        }
+    }
+}
+
+// How the alpha channel of a material is used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    // alpha is ignored, the material is fully opaque.
+    Opaque,
+    // fragments with an alpha below `cutoff` are discarded (fences, leaves, hair...),
+    // so the material can still be drawn with the opaque geometry instead of the sorted transparent path.
+    Mask { cutoff: f32 }
+}
+
+impl AlphaMode {
+    // alpha cutoff written in the material uniform, 0.0 never discards anything.
+    pub(crate) fn cutoff(&self) -> f32 {
+        match self {
+            AlphaMode::Opaque => 0.0,
+            AlphaMode::Mask { cutoff } => *cutoff
+        }
+    }
+}
+
+// Per-material rasterization settings.
+// The renderer builds one render pipeline for each distinct `RenderState` in use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderState {
+    pub cull_mode: CullMode,
+    pub depth_bias: DepthBias,
+    pub alpha_mode: AlphaMode,
+    // With MSAA, derive the sample coverage from the alpha of masked materials instead of discarding fragments,
+    // which smooths their cutout edges. It has no effect without MSAA.
+    pub alpha_to_coverage: bool
+}
+
+impl RenderState {
+    pub fn new() -> Self {
+        Self {
+            cull_mode: CullMode::Back,
+            depth_bias: DepthBias::default(),
+            alpha_mode: AlphaMode::Opaque,
+            alpha_to_coverage: false
+        }
+    }
+
+    pub fn two_sided() -> Self {
+        Self {
+            cull_mode: CullMode::None,
+            ..Self::new()
+        }
+    }
+
+    // two-sided alpha cutout, the usual setup of foliage cards.
+    pub fn masked(cutoff: f32) -> Self {
+        Self {
+            cull_mode: CullMode::None,
+            alpha_mode: AlphaMode::Mask { cutoff },
+            alpha_to_coverage: true,
+            ..Self::new()
+        }
+    }
+
+    // whether alpha to coverage is actually used with `sample_count` samples per pixel.
+    pub(crate) fn uses_alpha_to_coverage(&self, sample_count: u32) -> bool {
+        // tips: wgpu only allows alpha to coverage on multisampled pipelines.
+        self.alpha_to_coverage && sample_count > 1 && matches!(self.alpha_mode, AlphaMode::Mask { .. })
+    }
+
+    // fragment shader entry point of the material shaders for this render state.
+    pub(crate) fn fragment_entry(&self, sample_count: u32) -> &'static str {
+        match self.alpha_mode {
+            // with alpha to coverage, the coverage mask replaces the discard.
+            AlphaMode::Mask { .. } if !self.uses_alpha_to_coverage(sample_count) => "fs_masked",
+            _ => "fs_main"
+        }
+    }
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,111 @@
+use super::texture::Texture;
+
+// Two render targets of the same size & format for iterative effects (blur chains, fluid & cellular automata simulations, trails...):
+// each step samples `read_view()`, renders into `write_view()`, then calls `swap()` so its result is read by the next step.
+pub struct PingPongTargets {
+    label: String,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+    targets: [Texture; 2],
+    read_index: usize,
+    generation: u32
+}
+
+impl PingPongTargets {
+    // `usage` adds to RENDER_ATTACHMENT | TEXTURE_BINDING, e.g. STORAGE_BINDING to write them from compute shaders.
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        label: &str
+    ) -> Self {
+        Self {
+            label: label.to_string(),
+            width,
+            height,
+            format,
+            usage,
+            targets: Self::create_targets(device, width, height, format, usage, label),
+            read_index: 0,
+            generation: 0
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        label: &str
+    ) -> [Texture; 2] {
+        [
+            Texture::create_render_target(device, width, height, format, usage, &format!("{} A", label)),
+            Texture::create_render_target(device, width, height, format, usage, &format!("{} B", label))
+        ]
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    // the last result
+    pub fn read_view(&self) -> &wgpu::TextureView {
+        &self.targets[self.read_index].view
+    }
+
+    // the target of the current step
+    pub fn write_view(&self) -> &wgpu::TextureView {
+        &self.targets[1 - self.read_index].view
+    }
+
+    pub fn read_texture(&self) -> &wgpu::Texture {
+        &self.targets[self.read_index].texture
+    }
+
+    pub fn write_texture(&self) -> &wgpu::Texture {
+        &self.targets[1 - self.read_index].texture
+    }
+
+    // linear filtering, clamped to the edges
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.targets[self.read_index].sampler
+    }
+
+    // the written target becomes the one to read.
+    pub fn swap(&mut self) {
+        self.read_index = 1 - self.read_index;
+    }
+
+    // Recreate both targets at the new size (e.g. on window resize), their content is lost.
+    // Returns false if the size didn't change.
+    // tips: bind groups of the old views must be recreated, compare `generation()` to know when.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> bool {
+        if width == self.width && height == self.height {
+            return false;
+        }
+        self.width = width;
+        self.height = height;
+        self.targets = Self::create_targets(device, width, height, self.format, self.usage, &self.label);
+        self.read_index = 0;
+        self.generation += 1;
+        true
+    }
+
+    // changes every time the targets are recreated
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
@@ -0,0 +1,98 @@
+/// Compute Shaders
+// tips: `rgba8unorm` is replaced by the format of the destination textures when the pipelines are created.
+
+struct BlurUniform {
+    direction: vec2<i32>; // (1, 0) horizontal or (0, 1) vertical
+    radius: i32; // taps on each side of the center, up to 32
+    _padding: i32;
+    weights: array<vec4<f32>, 9>; // weight of the taps 0 ~ 32 away from the center, 4 per vec4
+};
+
+[[group(0), binding(0)]]
+var source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var destination: texture_storage_2d<rgba8unorm, write>;
+[[group(0), binding(2)]]
+var<uniform> blur: BlurUniform;
+[[group(0), binding(3)]]
+var source_sampler: sampler;
+
+fn weight(offset: i32) -> f32 {
+    let weights = blur.weights[offset / 4];
+    let component = offset % 4;
+    if (component == 0) {
+        return weights.x;
+    }
+    if (component == 1) {
+        return weights.y;
+    }
+    if (component == 2) {
+        return weights.z;
+    }
+    return weights.w;
+}
+
+// one direction of a separable convolution, edges are clamped.
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn blur_main(
+    [[builtin(global_invocation_id)]] id: vec3<u32>
+) {
+    let size = textureDimensions(source);
+    let pixel = vec2<i32>(id.xy);
+    if (pixel.x >= size.x || pixel.y >= size.y) {
+        return;
+    }
+
+    var color = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var offset: i32 = -blur.radius; offset <= blur.radius; offset = offset + 1) {
+        let tap = clamp(pixel + blur.direction * offset, vec2<i32>(0, 0), size - vec2<i32>(1, 1));
+        color = color + textureLoad(source, tap, 0) * weight(abs(offset));
+    }
+    textureStore(destination, pixel, color);
+}
+
+// Half resolution: the center and 4 diagonal bilinear taps, which stays stable on thin bright details.
+// ref: https://community.arm.com/cfs-file/__key/communityserver-blogs-components-weblogfiles/00-00-00-20-66/siggraph2015_2D00_mmg_2D00_marius_2D00_notes.pdf
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn downsample_main(
+    [[builtin(global_invocation_id)]] id: vec3<u32>
+) {
+    let size = textureDimensions(destination);
+    let pixel = vec2<i32>(id.xy);
+    if (pixel.x >= size.x || pixel.y >= size.y) {
+        return;
+    }
+
+    let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(size);
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var color = textureSampleLevel(source, source_sampler, uv, 0.0) * 4.0;
+    color = color + textureSampleLevel(source, source_sampler, uv + vec2<f32>(-texel.x, -texel.y), 0.0);
+    color = color + textureSampleLevel(source, source_sampler, uv + vec2<f32>(texel.x, -texel.y), 0.0);
+    color = color + textureSampleLevel(source, source_sampler, uv + vec2<f32>(-texel.x, texel.y), 0.0);
+    color = color + textureSampleLevel(source, source_sampler, uv + vec2<f32>(texel.x, texel.y), 0.0);
+    textureStore(destination, pixel, color / 8.0);
+}
+
+// Double resolution: 3x3 tent filter, smooth enough to hide the blockiness of the lower resolution.
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn upsample_main(
+    [[builtin(global_invocation_id)]] id: vec3<u32>
+) {
+    let size = textureDimensions(destination);
+    let pixel = vec2<i32>(id.xy);
+    if (pixel.x >= size.x || pixel.y >= size.y) {
+        return;
+    }
+
+    let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(size);
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var color = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var y: i32 = -1; y <= 1; y = y + 1) {
+        for (var x: i32 = -1; x <= 1; x = x + 1) {
+            // 1 2 1 / 2 4 2 / 1 2 1
+            let tent = f32((2 - abs(x)) * (2 - abs(y)));
+            color = color + textureSampleLevel(source, source_sampler, uv + vec2<f32>(f32(x), f32(y)) * texel, 0.0) * tent;
+        }
+    }
+    textureStore(destination, pixel, color / 16.0);
+}
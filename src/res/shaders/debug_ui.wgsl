@@ -0,0 +1,71 @@
+/// Vertex Shader
+
+struct DebugUiUniform {
+    screen_size: vec2<f32>; // in points
+    srgb_target: f32; // 1.0 if the target encodes sRGB itself
+    _padding: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> debug_ui: DebugUiUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>; // in points from the top left
+    [[location(1)]] tex_coords: vec2<f32>;
+    [[location(2)]] color: vec4<f32>; // sRGB, premultiplied alpha
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] color: vec4<f32>; // linear, premultiplied alpha
+};
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+[[stage(vertex)]]
+fn vs_main(
+    in: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        2.0 * in.position.x / debug_ui.screen_size.x - 1.0,
+        1.0 - 2.0 * in.position.y / debug_ui.screen_size.y,
+        0.0,
+        1.0
+    );
+    out.tex_coords = in.tex_coords;
+    out.color = vec4<f32>(linear_from_srgb(in.color.rgb), in.color.a);
+    return out;
+}
+
+/// Fragment Shader
+
+[[group(1), binding(0)]]
+var t_ui: texture_2d<f32>;
+[[group(1), binding(1)]]
+var s_ui: sampler;
+
+fn srgb_from_linear(linear: vec3<f32>) -> vec3<f32> {
+    let cutoff = linear < vec3<f32>(0.0031308);
+    let lower = linear * vec3<f32>(12.92);
+    let higher = vec3<f32>(1.055) * pow(linear, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(higher, lower, cutoff);
+}
+
+// the textures are sRGB too, sampled as linear
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    let color = in.color * textureSample(t_ui, s_ui, in.tex_coords);
+    if (debug_ui.srgb_target > 0.5) {
+        return color;
+    }
+    // a linear target stores the sRGB values as they are, like egui expects
+    return vec4<f32>(srgb_from_linear(color.rgb), color.a);
+}
@@ -27,13 +27,19 @@ var t_shadow: texture_depth_2d;
 [[group(0), binding(1)]]
 var s_shadow: sampler_comparison;
 
+struct DepthRange {
+    far_depth: f32; // 1.0, or 0.0 with reversed-Z
+};
+[[group(0), binding(2)]]
+var<uniform> depth_range: DepthRange;
+
 [[stage(fragment)]]
 fn fs_main(
     in: VertexOutput
 )-> [[location(0)]] vec4<f32> {
     let near = 0.1;
     let far = 100.0;
-    let depth = textureSampleCompare(t_shadow, s_shadow, in.tex_coords, in.clip_position.w);
+    let depth = textureSampleCompare(t_shadow, s_shadow, in.tex_coords, depth_range.far_depth);
     let r = (2.0 * near) / (far + near - depth * (far - near));
 
     return vec4<f32>(vec3<f32>(r), 1.0);
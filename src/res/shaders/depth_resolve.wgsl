@@ -0,0 +1,30 @@
+/// Vertex Shader
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+// full-screen triangle
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+/// Fragment Shader
+
+[[group(0), binding(0)]]
+var t_depth: texture_depth_multisampled_2d;
+
+// depth can't be averaged: keep the first sample of each pixel
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> [[builtin(frag_depth)]] f32 {
+    return textureLoad(t_depth, vec2<i32>(in.clip_position.xy), 0);
+}
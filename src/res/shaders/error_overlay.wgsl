@@ -0,0 +1,83 @@
+/// Vertex Shader
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+// draw one triangle covering the whole screen, its vertices are generated from the vertex index:
+// (-1, -1), (3, -1), (-1, 3)
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+    let x = f32(i32(vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+
+    return out;
+}
+
+/// Fragment Shader
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    // `clip_position` is in framebuffer coordinates (pixels) in the fragment stage.
+    let pos = in.clip_position.xy;
+
+    // diagonal warning stripes, 16px wide.
+    let stripe = step(0.5, fract((pos.x + pos.y) / 32.0));
+    let alpha = 0.15 + 0.15 * stripe;
+
+    return vec4<f32>(0.8, 0.05, 0.05, alpha);
+}
+
+/// Text Panel
+
+// corners of the panel in clip space: left, top, right, bottom
+struct TextRect {
+    rect: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> text_rect: TextRect;
+[[group(0), binding(1)]]
+var t_text: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_text: sampler;
+
+struct TextVertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// two triangles covering the panel, from the vertex index
+[[stage(vertex)]]
+fn vs_text(
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> TextVertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0)
+    );
+    let corner = corners[vertex_index];
+
+    var out: TextVertexOutput;
+    out.clip_position = vec4<f32>(mix(text_rect.rect.xy, text_rect.rect.zw, corner), 0.0, 1.0);
+    out.tex_coords = corner;
+
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_text(
+    in: TextVertexOutput
+) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_text, s_text, in.tex_coords);
+}
@@ -0,0 +1,96 @@
+/// Vertex Shader
+
+struct GridUniform {
+    view_proj: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    eye: vec4<f32>; // camera position (w unused)
+    minor_color: vec4<f32>;
+    major_color: vec4<f32>;
+    minor_spacing: f32; // distance between two minor lines, in world units
+    major_every: f32; // a major line every N minor lines
+    fade_distance: f32; // the grid vanishes at this distance from the camera
+    near_depth: f32; // NDC depth of the near plane: 0.0, or 1.0 with reversed-Z
+};
+[[group(0), binding(0)]]
+var<uniform> grid: GridUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+// full-screen triangle, every pixel casts a ray against the ground plane.
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+/// Fragment Shader
+
+struct FragmentOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[builtin(frag_depth)]] depth: f32;
+};
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let position = grid.inv_view_proj * vec4<f32>(ndc, 1.0);
+    return position.xyz / position.w;
+}
+
+// coverage of the grid lines every `spacing` units, about one pixel wide whatever the distance.
+fn grid_lines(position: vec2<f32>, spacing: f32) -> f32 {
+    let coord = position / spacing;
+    let derivative = fwidth(coord);
+    let lines = abs(fract(coord - 0.5) - 0.5) / derivative;
+    return 1.0 - min(min(lines.x, lines.y), 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> FragmentOutput {
+    // intersect the view ray with the y = 0 plane,
+    // the ray goes through a point halfway in depth rather than on the far plane, which may be infinitely far.
+    let near = unproject(vec3<f32>(in.ndc, grid.near_depth));
+    let ahead = unproject(vec3<f32>(in.ndc, 0.5));
+    let t = -near.y / (ahead.y - near.y);
+    let position = near + t * (ahead - near);
+
+    let minor = grid_lines(position.xz, grid.minor_spacing);
+    let major = grid_lines(position.xz, grid.minor_spacing * grid.major_every);
+    let minor_color = vec4<f32>(grid.minor_color.rgb, grid.minor_color.a * minor);
+    var color = mix(minor_color, grid.major_color, major);
+
+    // world axes: x in red, z in blue
+    let axis_width = fwidth(position.xz);
+    if (abs(position.z) < axis_width.y) {
+        color = vec4<f32>(0.9, 0.2, 0.2, 1.0);
+    }
+    if (abs(position.x) < axis_width.x) {
+        color = vec4<f32>(0.2, 0.4, 0.9, 1.0);
+    }
+
+    // fade out with the distance, which also hides the aliasing near the horizon
+    let fade = 1.0 - clamp(length(position.xz - grid.eye.xz) / grid.fade_distance, 0.0, 1.0);
+    color.a = color.a * fade * fade;
+
+    let clip = grid.view_proj * vec4<f32>(position, 1.0);
+
+    // the ray doesn't hit the plane (looking away from it), or nothing left to draw
+    if (t <= 0.0 || color.a <= 0.001) {
+        discard;
+    }
+
+    var out: FragmentOutput;
+    out.color = color;
+    out.depth = clamp(clip.z / clip.w, 0.0, 1.0);
+    return out;
+}
@@ -0,0 +1,117 @@
+/// Compute Shader
+
+struct FlareElement {
+    color: vec4<f32>; // rgb * a, added to the scene
+    params: vec4<f32>; // position along the flare axis, radius (fraction of the screen height), shape, unused
+};
+
+struct FlareUniform {
+    light: vec4<f32>; // light position in NDC (xy), 1.0 if it's in front of the camera (z), depth of the far plane (w)
+    light_color: vec4<f32>; // rgb, unused (w)
+    screen: vec4<f32>; // width & height in pixels, occlusion test radius in pixels, fade amount of this frame
+    elements: array<FlareElement, 16>;
+};
+
+struct Visibility {
+    value: f32; // 0.0 hidden ~ 1.0 fully visible, smoothed over frames
+};
+
+[[group(0), binding(0)]]
+var<uniform> flare: FlareUniform;
+[[group(0), binding(1)]]
+var t_depth: texture_depth_2d;
+[[group(0), binding(2)]]
+var<storage, read_write> visibility: Visibility;
+
+var<workgroup> visible_samples: atomic<u32>;
+
+// Occlusion test: 8x8 depth samples around the light, the light is only visible where nothing was drawn (depth cleared to the far plane).
+// Samples outside the screen count as hidden, so the flare fades out at the edges.
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn occlusion_main(
+    [[builtin(local_invocation_id)]] id: vec3<u32>,
+    [[builtin(local_invocation_index)]] index: u32
+) {
+    if (index == 0u) {
+        atomicStore(&visible_samples, 0u);
+    }
+    workgroupBarrier();
+
+    let size = flare.screen.xy;
+    let light_pixel = (flare.light.xy * vec2<f32>(0.5, -0.5) + 0.5) * size;
+    let offset = (vec2<f32>(id.xy) - 3.5) / 3.5 * flare.screen.z;
+    let pixel = light_pixel + offset;
+    if (flare.light.z > 0.5 && all(pixel >= vec2<f32>(0.0, 0.0)) && all(pixel < size)) {
+        if (textureLoad(t_depth, vec2<i32>(pixel), 0) == flare.light.w) {
+            atomicAdd(&visible_samples, 1u);
+        }
+    }
+    workgroupBarrier();
+
+    if (index == 0u) {
+        let visible = f32(atomicLoad(&visible_samples)) / 64.0;
+        visibility.value = mix(visibility.value, visible, flare.screen.w);
+    }
+}
+
+/// Vertex Shader
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] local: vec2<f32>; // -1.0 ~ 1.0 across the element
+    [[location(1)]] color: vec4<f32>;
+    [[location(2)]] shape: f32;
+};
+
+// one quad per element, spread on the axis from the light through the screen center.
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    [[builtin(instance_index)]] instance_index: u32
+) -> VertexOutput {
+    let element = flare.elements[instance_index];
+    // two triangles: (0, 1, 2) (2, 1, 3) on the corners of the quad
+    var corner_indices = array<u32, 6>(0u, 1u, 2u, 2u, 1u, 3u);
+    let corner_index = corner_indices[vertex_index];
+    let local = vec2<f32>(f32(corner_index & 1u), f32(corner_index >> 1u)) * 2.0 - 1.0;
+
+    // 0.0 on the light, 1.0 on the screen center, 2.0 mirrored on the other side
+    let center = flare.light.xy * (1.0 - element.params.x);
+    let aspect = flare.screen.x / flare.screen.y;
+    let radius = vec2<f32>(element.params.y * 2.0 / aspect, element.params.y * 2.0);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(center + local * radius, 0.0, 1.0);
+    out.local = local;
+    out.color = vec4<f32>(element.color.rgb * element.color.a * flare.light_color.rgb, 1.0);
+    out.shape = element.params.z;
+    return out;
+}
+
+/// Fragment Shader
+
+[[group(0), binding(2)]]
+var<storage, read> visibility_read: Visibility;
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    let r = length(in.local);
+    var mask: f32;
+    if (in.shape < 0.5) {
+        // disc: soft glow
+        let falloff = max(1.0 - r, 0.0);
+        mask = falloff * falloff;
+    } else if (in.shape < 1.5) {
+        // ring: thin band near the edge
+        mask = max(1.0 - abs(r - 0.85) / 0.15, 0.0);
+    } else {
+        // hexagon: aperture blade ghost with a soft edge
+        let p = abs(in.local);
+        let distance = max(p.x * 0.866 + p.y * 0.5, p.y);
+        mask = clamp((1.0 - distance) / 0.1, 0.0, 1.0) * 0.6;
+    }
+
+    return vec4<f32>(in.color.rgb * mask * visibility_read.value, 0.0);
+}
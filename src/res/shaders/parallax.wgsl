@@ -0,0 +1,65 @@
+/// Vertex Shader
+
+struct ParallaxLayer {
+    uv_center: vec2<f32>; // at the screen center
+    uv_scale: vec2<f32>; // per NDC unit
+    repeat: vec2<f32>; // 1.0 along the repeated axes
+    padding: vec2<f32>; // uniform arrays are laid out in 16 bytes blocks
+};
+struct ParallaxUniform {
+    layers: array<ParallaxLayer, 8>;
+};
+[[group(1), binding(0)]]
+var<uniform> parallax: ParallaxUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] repeat: vec2<f32>;
+};
+
+// full-screen triangle, the instance index is the layer
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    [[builtin(instance_index)]] instance_index: u32
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    let layer = parallax.layers[instance_index];
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.tex_coords = layer.uv_center + ndc * layer.uv_scale;
+    out.repeat = layer.repeat;
+    return out;
+}
+
+/// Fragment Shader
+
+// the layer's material, see shader.wgsl
+[[group(0), binding(0)]]
+var t_diffuse: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_diffuse: sampler;
+
+struct Material {
+    tint: vec4<f32>;
+    alpha_cutoff: f32;
+    specular: f32;
+    shininess: f32;
+    normal_map: f32;
+};
+[[group(0), binding(3)]]
+var<uniform> material: Material;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // transparent beyond the tiles along the axes which don't repeat
+    let outside = (in.tex_coords < vec2<f32>(0.0)) | (in.tex_coords > vec2<f32>(1.0));
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.tint;
+    if ((outside.x && in.repeat.x == 0.0) || (outside.y && in.repeat.y == 0.0)) {
+        return vec4<f32>(0.0);
+    }
+    return color;
+}
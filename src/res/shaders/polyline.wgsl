@@ -0,0 +1,72 @@
+/// Vertex Shader
+
+struct LineUniform {
+    view_proj: mat4x4<f32>;
+    eye: vec4<f32>; // camera position (w unused)
+    viewport: vec4<f32>; // viewport size in pixels (zw unused)
+};
+[[group(0), binding(0)]]
+var<uniform> line: LineUniform;
+
+// each segment is expanded into a quad (two triangles),
+// every vertex knows its end of the segment and which side of the line it's on.
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>; // end of the segment
+    [[location(1)]] direction: vec3<f32>; // segment end - segment start
+    [[location(2)]] side: f32; // -1.0 or 1.0
+    [[location(3)]] width: f32;
+    [[location(4)]] world_space: f32; // 1.0: width in world units, 0.0: width in pixels
+    [[location(5)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    vertex: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = vertex.color;
+
+    if (vertex.world_space > 0.5) {
+        // offset perpendicular to both the segment and the view direction, so the quad faces the camera.
+        let to_eye = line.eye.xyz - vertex.position;
+        let offset = cross(vertex.direction, to_eye);
+        var offset_dir = vec3<f32>(0.0);
+        if (length(offset) > 0.0) {
+            offset_dir = normalize(offset);
+        }
+        let position = vertex.position + offset_dir * vertex.side * vertex.width * 0.5;
+        out.clip_position = line.view_proj * vec4<f32>(position, 1.0);
+    } else {
+        // offset in screen space, so the line keeps the same width in pixels at any distance.
+        let clip = line.view_proj * vec4<f32>(vertex.position, 1.0);
+        let clip_next = line.view_proj * vec4<f32>(vertex.position + vertex.direction, 1.0);
+        let screen = clip.xy / clip.w * line.viewport.xy;
+        let screen_next = clip_next.xy / clip_next.w * line.viewport.xy;
+        let screen_dir = screen_next - screen;
+        var normal = vec2<f32>(0.0);
+        if (length(screen_dir) > 0.0) {
+            let dir = normalize(screen_dir);
+            normal = vec2<f32>(-dir.y, dir.x);
+        }
+        // NDC spans 2.0 over the viewport, so half the width in pixels is `width / viewport` in NDC.
+        let offset = normal * vertex.side * vertex.width / line.viewport.xy;
+        // offset before the perspective division
+        out.clip_position = vec4<f32>(clip.xy + offset * clip.w, clip.zw);
+    }
+
+    return out;
+}
+
+/// Fragment Shader
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,133 @@
+/// Vertex Shader
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// full-screen triangle
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // texture coordinates start at the top left
+    out.tex_coords = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+/// Fragment Shader
+
+// settings of the effect, see `PostEffect`
+struct PostProcessUniform {
+    params: vec4<f32>;
+    color: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var t_source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_source: sampler;
+[[group(0), binding(2)]]
+var<uniform> effect: PostProcessUniform;
+// the blurred bright parts, only read by the bloom composite
+[[group(0), binding(3)]]
+var t_bloom: texture_2d<f32>;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// params: threshold, soft knee
+// tips: rendered into a smaller target, the bilinear taps average 4x4 texels.
+[[stage(fragment)]]
+fn fs_bloom_prefilter(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_source, s_source, in.tex_coords).rgb;
+    let threshold = effect.params.x;
+    let knee = max(effect.params.y, 0.0001);
+    let brightness = max(color.r, max(color.g, color.b));
+    // quadratic curve between threshold - knee & threshold + knee, linear above
+    let knee_offset = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    let soft = knee_offset * knee_offset / (4.0 * knee);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+// params: intensity
+[[stage(fragment)]]
+fn fs_bloom_composite(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_source, s_source, in.tex_coords);
+    let bloom = textureSample(t_bloom, s_source, in.tex_coords).rgb;
+    return vec4<f32>(color.rgb + bloom * effect.params.x, color.a);
+}
+
+// ref: https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// params: operator (0 clamp, 1 reinhard, 2 ACES), gamma
+[[stage(fragment)]]
+fn fs_tonemap(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_source, s_source, in.tex_coords);
+    var mapped = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (effect.params.x > 1.5) {
+        mapped = aces(color.rgb);
+    } else {
+        if (effect.params.x > 0.5) {
+            // on the luminance, so the saturated colors keep their hue
+            mapped = clamp(color.rgb / (1.0 + luminance(color.rgb)), vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+    }
+    // on top of the display encoding: above 1.0 brightens the mid tones
+    mapped = pow(mapped, vec3<f32>(1.0 / max(effect.params.y, 0.01)));
+    return vec4<f32>(mapped, color.a);
+}
+
+// params: intensity, radius, smoothness, color: of the corners
+[[stage(fragment)]]
+fn fs_vignette(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_source, s_source, in.tex_coords);
+    let size = vec2<f32>(textureDimensions(t_source));
+    // round whatever the aspect ratio, 1.0 in the corners
+    let offset = (in.tex_coords - 0.5) * size / length(size) * 2.0;
+    let radius = effect.params.y;
+    let vignette = smoothStep(radius, radius + max(effect.params.z, 0.0001), length(offset)) * effect.params.x;
+    return vec4<f32>(mix(color.rgb, effect.color.rgb, clamp(vignette, 0.0, 1.0)), color.a);
+}
+
+fn srgb_from_linear(linear: vec3<f32>) -> vec3<f32> {
+    let cutoff = linear < vec3<f32>(0.0031308);
+    let lower = linear * vec3<f32>(12.92);
+    let higher = vec3<f32>(1.055) * pow(linear, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(higher, lower, cutoff);
+}
+
+// params: 1.0 if the target encodes sRGB itself
+[[stage(fragment)]]
+fn fs_output(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    // the frame is opaque
+    let color = clamp(textureSample(t_source, s_source, in.tex_coords).rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (effect.params.x > 0.5) {
+        return vec4<f32>(color, 1.0);
+    }
+    return vec4<f32>(srgb_from_linear(color), 1.0);
+}
@@ -2,6 +2,8 @@
 #version 450
 
 layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec4 v_color;
+layout(location=2) in vec3 v_world_position;
 // - out: the value is meant to be written to a buffer to be used outside the shader program.
 // - layout: specify a layout for the variable.
 // In this case, the value of `f_color` will be saved to whatever buffer is at location zero in our application
@@ -11,6 +13,29 @@ layout(location=0) out vec4 f_color;
 layout(set = 0, binding = 0) uniform texture2D t_diffuse;
 layout(set = 0, binding = 1) uniform sampler s_diffuse;
 
+layout(set = 1, binding = 0)
+uniform Camera {
+    mat4 u_view_proj;
+    vec4 u_view_position;
+};
+
+// global environment settings, sun & ambient are waiting for the lighting
+// they're pre-exposed: sun illuminance (lux) & sky luminance (nits) times the camera exposure, lambert diffuse is `albedo / PI * sun_color`
+layout(set = 2, binding = 0)
+uniform Environment {
+    vec4 u_sun_direction;
+    vec4 u_sun_color;
+    vec4 u_ambient_color;
+    vec3 u_fog_color;
+    float u_fog_density;
+};
+
 void main() {
-    f_color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+    // the vertex color is an albedo multiplier (tinting, baked AO...)
+    vec4 color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords) * v_color;
+
+    // exponential squared distance fog
+    float distance = length(v_world_position - u_view_position.xyz) * u_fog_density;
+    float visibility = exp(-distance * distance);
+    f_color = vec4(mix(u_fog_color, color.rgb, visibility), color.a);
 }
@@ -4,6 +4,7 @@
 // from Vertex Buffer
 layout(location=0) in vec3 a_position;
 layout(location=1) in vec2 a_tex_coords;
+layout(location=2) in vec4 a_color;
 // from Instance Buffer, this will be different when shader process another instance
 layout(location=5) in vec4 model_matrix_0;
 layout(location=6) in vec4 model_matrix_1;
@@ -11,14 +12,35 @@ layout(location=7) in vec4 model_matrix_2;
 layout(location=8) in vec4 model_matrix_3;
 
 layout(location=0) out vec2 v_tex_coords;
+layout(location=1) out vec4 v_color;
+layout(location=2) out vec3 v_world_position;
 
 layout(set=1, binding=0)
 uniform Camera {
     mat4 u_view_proj;
+    vec4 u_view_position;
 };
 
+// per-material transform of texture coordinates
+layout(set=0, binding=2)
+uniform UvTransform {
+    vec2 u_offset;
+    vec2 u_scale;
+    vec2 u_scroll; // UV per second
+    float u_rotation; // radians, around the texture center
+    float u_time; // seconds
+};
+
+vec2 transform_uv(vec2 uv) {
+    float c = cos(u_rotation);
+    float s = sin(u_rotation);
+    vec2 rotated = mat2(c, s, -s, c) * (uv - vec2(0.5)) + vec2(0.5);
+    return rotated * u_scale + u_offset + u_scroll * u_time;
+}
+
 void main() {
-    v_tex_coords = a_tex_coords;
+    v_tex_coords = transform_uv(a_tex_coords);
+    v_color = a_color;
 
     mat4 model_matrix = mat4(
         model_matrix_0,
@@ -26,5 +48,7 @@ void main() {
         model_matrix_2,
         model_matrix_3
     );
-    gl_Position = u_view_proj * model_matrix * vec4(a_position, 1.0);
+    vec4 world_position = model_matrix * vec4(a_position, 1.0);
+    v_world_position = world_position.xyz;
+    gl_Position = u_view_proj * world_position;
 }
@@ -9,15 +9,36 @@
 // Any structure used as a uniform must be annotated with [[block]]
 struct CameraUniform {
     view_proj: mat4x4<f32>;
+    view_position: vec4<f32>;
 };
 // bind group num & binding num
 [[group(1), binding(0)]]
 var<uniform> camera: CameraUniform;
 
+// per-material transform of texture coordinates
+struct UvTransform {
+    offset: vec2<f32>;
+    scale: vec2<f32>;
+    scroll: vec2<f32>; // UV per second
+    rotation: f32; // radians, around the texture center
+    time: f32; // seconds
+};
+[[group(0), binding(2)]]
+var<uniform> uv_transform: UvTransform;
+
+fn transform_uv(uv: vec2<f32>) -> vec2<f32> {
+    let c = cos(uv_transform.rotation);
+    let s = sin(uv_transform.rotation);
+    let rotated = mat2x2<f32>(vec2<f32>(c, s), vec2<f32>(-s, c)) * (uv - vec2<f32>(0.5)) + vec2<f32>(0.5);
+    return rotated * uv_transform.scale + uv_transform.offset + uv_transform.scroll * uv_transform.time;
+}
+
 // the input of vertex shader (from Vertex Buffer)
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] tex_coords: vec2<f32>;
+    [[location(2)]] color: vec4<f32>;
+    [[location(3)]] normal: vec3<f32>;
 };
 // from Instance Buffer, this will be different when shader process another instance
 struct InstanceInput {
@@ -33,6 +54,9 @@ struct VertexOutput {
     // which is analogous to GLSL's `gl_Position` variable.
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+    [[location(2)]] world_position: vec3<f32>;
+    [[location(3)]] world_normal: vec3<f32>;
 };
 
 // `[[stage(vertex)]]` mark this function as a valid entry point for a vertex shader.
@@ -52,8 +76,13 @@ fn vs_main(
         instance.model_matrix_3,
     );
 
-    out.tex_coords = vertex.tex_coords;
-    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(vertex.position, 1.0);
+    out.tex_coords = transform_uv(vertex.tex_coords);
+    out.color = vertex.color;
+    let world_position = model_matrix * vec4<f32>(vertex.position, 1.0);
+    out.world_position = world_position.xyz;
+    // tips: only right for uniformly scaled models, others need the inverse transpose of the model matrix.
+    out.world_normal = (model_matrix * vec4<f32>(vertex.normal, 0.0)).xyz;
+    out.clip_position = camera.view_proj * world_position;
 
     return out;
 }
@@ -65,12 +94,209 @@ fn vs_main(
 var t_diffuse: texture_2d<f32>;
 [[group(0), binding(1)]]
 var s_diffuse: sampler;
+// tangent space normals, flat without a normal map
+[[group(0), binding(4)]]
+var t_normal: texture_2d<f32>;
+
+// color tint, alpha cutoff of masked materials & Blinn-Phong highlights
+struct Material {
+    tint: vec4<f32>;
+    alpha_cutoff: f32;
+    specular: f32;
+    shininess: f32;
+    normal_map: f32; // 1.0 if the normal map is sampled
+};
+[[group(0), binding(3)]]
+var<uniform> material: Material;
+
+// global environment settings
+// they're pre-exposed: sun illuminance (lux) & sky luminance (nits) times the camera exposure, lambert diffuse is `albedo / PI * sun_color`
+struct Environment {
+    sun_direction: vec4<f32>;
+    sun_color: vec4<f32>;
+    ambient_color: vec4<f32>;
+    fog_color: vec3<f32>;
+    fog_density: f32;
+};
+[[group(2), binding(0)]]
+var<uniform> environment: Environment;
+
+// lights of the scene, pre-exposed like the environment
+struct Light {
+    position: vec4<f32>; // w: 2.0 for spot lights, 1.0 for point lights, 0.0 for directional lights (xyz is then the direction the light travels in)
+    color: vec4<f32>; // illuminance (lux) for directional lights, intensity (candela) for point & spot lights, w: their range
+    direction: vec4<f32>; // of spot lights, w: radius of the source casting occluder shadows, negative for none
+    cone: vec4<f32>; // of spot lights: cosines of the outer & inner angles
+};
+struct Lights {
+    count: u32;
+    lights: array<Light, 16>;
+};
+[[group(2), binding(1)]]
+var<uniform> lights: Lights;
+
+// edges of the 2D light occluders (x0, y0, x1, y1) in world space
+struct Occluders {
+    count: u32;
+    segments: array<vec4<f32>, 64>;
+};
+[[group(2), binding(2)]]
+var<uniform> occluders: Occluders;
+
+let PI: f32 = 3.14159265;
+
+// diffuse & specular light reflected towards `view_direction` from a light of `illuminance` coming from `light_direction`
+// tips: the directions are unit vectors pointing away from the surface.
+fn blinn_phong(normal: vec3<f32>, view_direction: vec3<f32>, light_direction: vec3<f32>, illuminance: vec3<f32>, albedo: vec3<f32>) -> vec3<f32> {
+    let n_dot_l = max(dot(normal, light_direction), 0.0);
+    let half_vector = normalize(light_direction + view_direction);
+    let n_dot_h = max(dot(normal, half_vector), 0.0);
+    // normalized, so tighter highlights get brighter instead of reflecting less light
+    let specular = material.specular * (material.shininess + 8.0) / (8.0 * PI) * pow(n_dot_h, material.shininess);
+    return (albedo / PI + vec3<f32>(specular)) * illuminance * n_dot_l;
+}
+
+// inverse square falloff, windowed to reach 0.0 at the range of the light
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / max(distance * distance, 0.0001);
+}
+
+// smooth falloff from the outer angle of a spot light's cone to its inner angle
+fn spot_factor(light: Light, light_direction: vec3<f32>) -> f32 {
+    let cos_angle = dot(-light_direction, light.direction.xyz);
+    return smoothStep(light.cone.x, light.cone.y, cos_angle);
+}
+
+// whether the segment from `a` to `b` crosses the segment from `c` to `d` (in the xy plane)
+fn segments_cross(a: vec2<f32>, b: vec2<f32>, c: vec2<f32>, d: vec2<f32>) -> bool {
+    let r = b - a;
+    let s = d - c;
+    let denominator = r.x * s.y - r.y * s.x;
+    if (abs(denominator) < 0.000001) {
+        return false;
+    }
+    let ac = c - a;
+    let t = (ac.x * s.y - ac.y * s.x) / denominator;
+    let u = (ac.x * r.y - ac.y * r.x) / denominator;
+    return t > 0.0 && t < 1.0 && u >= 0.0 && u <= 1.0;
+}
+
+// share of the light source (a disk of `radius` facing the fragment) seen past the occluders,
+// 0.0 ~ 1.0 with soft shadows, 0.0 or 1.0 with hard ones (radius 0.0)
+fn occluder_visibility(position: vec2<f32>, light_position: vec2<f32>, radius: f32) -> f32 {
+    let to_light = light_position - position;
+    let side = vec2<f32>(-to_light.y, to_light.x) / max(length(to_light), 0.0001) * radius;
+    var samples = 5;
+    if (radius <= 0.0) {
+        samples = 1;
+    }
+    var visible = 0.0;
+    for (var j = 0; j < samples; j = j + 1) {
+        // spread across the source, perpendicular to the light direction
+        var spread = 0.0;
+        if (samples > 1) {
+            spread = f32(j) / f32(samples - 1) * 2.0 - 1.0;
+        }
+        let source = light_position + side * spread;
+        var blocked = false;
+        for (var i: u32 = 0u; i < occluders.count; i = i + 1u) {
+            let segment = occluders.segments[i];
+            if (segments_cross(position, source, segment.xy, segment.zw)) {
+                blocked = true;
+                break;
+            }
+        }
+        if (!blocked) {
+            visible = visible + 1.0;
+        }
+    }
+    return visible / f32(samples);
+}
+
+// Normal of the fragment, from the normal map in the tangent space derived from the screen space derivatives
+// of the position & UVs (cotangent frame), so meshes need no vertex tangents.
+// The normal is flipped on the back faces of two-sided materials.
+// tips: textures derivatives need uniform control flow, so it's computed before discarding anything.
+fn surface_normal(in: VertexOutput, front_facing: bool) -> vec3<f32> {
+    var normal = normalize(in.world_normal);
+    if (!front_facing) {
+        normal = -normal;
+    }
+    let sampled = textureSample(t_normal, s_diffuse, in.tex_coords).xyz * 2.0 - 1.0;
+    let dp1 = dpdx(in.world_position);
+    let dp2 = dpdy(in.world_position);
+    let duv1 = dpdx(in.tex_coords);
+    let duv2 = dpdy(in.tex_coords);
+    if (material.normal_map == 0.0) {
+        return normal;
+    }
+    let dp2_perpendicular = cross(dp2, normal);
+    let dp1_perpendicular = cross(normal, dp1);
+    let tangent = dp2_perpendicular * duv1.x + dp1_perpendicular * duv2.x;
+    // the v axis of the textures points down, the green channel of normal maps up
+    let bitangent = -(dp2_perpendicular * duv1.y + dp1_perpendicular * duv2.y);
+    let scale = inverseSqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-20));
+    return normalize(mat3x3<f32>(tangent * scale, bitangent * scale, normal) * sampled);
+}
+
+// ambient + sun + scene lights
+fn shade(in: VertexOutput, normal: vec3<f32>, albedo: vec4<f32>) -> vec4<f32> {
+    let view_direction = normalize(camera.view_position.xyz - in.world_position);
+
+    // the sky lights the surface evenly, lambert diffuse then sums up to `albedo * luminance`
+    var color = albedo.rgb * environment.ambient_color.rgb;
+    color = color + blinn_phong(normal, view_direction, -environment.sun_direction.xyz, environment.sun_color.rgb, albedo.rgb);
+    for (var i: u32 = 0u; i < lights.count; i = i + 1u) {
+        let light = lights.lights[i];
+        if (light.position.w == 0.0) {
+            color = color + blinn_phong(normal, view_direction, -light.position.xyz, light.color.rgb, albedo.rgb);
+        } else {
+            let to_light = light.position.xyz - in.world_position;
+            let distance = length(to_light);
+            let light_direction = to_light / max(distance, 0.0001);
+            var illuminance = light.color.rgb * attenuation(distance, light.color.w);
+            if (light.position.w == 2.0) {
+                illuminance = illuminance * spot_factor(light, light_direction);
+            }
+            if (light.direction.w >= 0.0 && any(illuminance > vec3<f32>(0.0))) {
+                illuminance = illuminance * occluder_visibility(in.world_position.xy, light.position.xy, light.direction.w);
+            }
+            color = color + blinn_phong(normal, view_direction, light_direction, illuminance, albedo.rgb);
+        }
+    }
+    return vec4<f32>(color, albedo.a);
+}
+
+// exponential squared distance fog
+fn apply_fog(in: VertexOutput, color: vec4<f32>) -> vec4<f32> {
+    let distance = length(in.world_position - camera.view_position.xyz) * environment.fog_density;
+    let visibility = exp(-distance * distance);
+    return vec4<f32>(mix(environment.fog_color, color.rgb, visibility), color.a);
+}
+
+fn albedo(in: VertexOutput) -> vec4<f32> {
+    // the material tint & the vertex color are albedo multipliers (tinting, baked AO...)
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.tint * in.color;
+}
 
 // newer versions of the WGSL spec require these entry point names to be different.
 // we will spec the entry point when we create Render Pipeline in Application::new()
 // WGSL spec ref: https://www.w3.org/TR/WGSL/#declaration-and-scope
 [[stage(fragment)]]
-fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+fn fs_main(in: VertexOutput, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
     // sets the color of the current fragment
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return apply_fog(in, shade(in, surface_normal(in, front_facing), albedo(in)));
+}
+
+// entry point of masked materials: fragments below the alpha cutoff are thrown away.
+[[stage(fragment)]]
+fn fs_masked(in: VertexOutput, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
+    let color = albedo(in);
+    let normal = surface_normal(in, front_facing);
+    if (color.a < material.alpha_cutoff) {
+        discard;
+    }
+    return apply_fog(in, shade(in, normal, color));
 }
\ No newline at end of file
@@ -0,0 +1,51 @@
+/// Vertex Shader
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+// full-screen triangle, every pixel casts a ray into the cubemap.
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+/// Fragment Shader
+
+struct SkyboxUniform {
+    inv_view_proj: mat4x4<f32>;
+    intensity: f32; // multiplies the colors of the cubemap
+    near_depth: f32; // NDC depth of the near plane: 0.0, or 1.0 with reversed-Z
+};
+[[group(0), binding(0)]]
+var<uniform> skybox: SkyboxUniform;
+[[group(0), binding(1)]]
+var t_cubemap: texture_cube<f32>;
+[[group(0), binding(2)]]
+var s_cubemap: sampler;
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let position = skybox.inv_view_proj * vec4<f32>(ndc, 1.0);
+    return position.xyz / position.w;
+}
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    // two points of the view ray, the closer one first whatever the depth mode (the far plane may be infinite)
+    let near = mix(0.25, 0.75, skybox.near_depth);
+    let direction = unproject(vec3<f32>(in.ndc, 1.0 - near)) - unproject(vec3<f32>(in.ndc, near));
+    // cubemaps are looked up in a left-handed space
+    let color = textureSample(t_cubemap, s_cubemap, vec3<f32>(direction.x, direction.y, -direction.z)).rgb;
+    return vec4<f32>(color * skybox.intensity, 1.0);
+}
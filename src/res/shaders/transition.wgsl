@@ -0,0 +1,86 @@
+/// Vertex Shader
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+// full-screen triangle
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+/// Fragment Shader
+
+struct TransitionUniform {
+    color: vec4<f32>;
+    params: vec4<f32>; // coverage (0.0 ~ 1.0), effect (0 fade, 1 wipe, 2 iris), wipe angle, aspect ratio
+    loading: vec4<f32>; // shown (0.0 or 1.0), progress, spinner angle, unused
+};
+[[group(0), binding(0)]]
+var<uniform> transition: TransitionUniform;
+
+let PI: f32 = 3.14159265;
+// width of the soft edges, in NDC
+let EDGE: f32 = 0.1;
+
+// alpha of the cover at this pixel
+fn cover(ndc: vec2<f32>) -> f32 {
+    let coverage = transition.params.x;
+    let effect = transition.params.y;
+    if (effect < 0.5) {
+        return coverage;
+    }
+    // aspect corrected, so circles stay round
+    let position = vec2<f32>(ndc.x * transition.params.w, ndc.y);
+    if (effect < 1.5) {
+        // distance along the wipe direction, from the first corner reached (0.0) to the last one
+        let direction = vec2<f32>(cos(transition.params.z), sin(transition.params.z));
+        let extent = abs(direction.x) * transition.params.w + abs(direction.y);
+        let distance = dot(position, direction) + extent;
+        let front = coverage * (2.0 * extent + EDGE);
+        return clamp((front - distance) / EDGE, 0.0, 1.0);
+    }
+    // the iris closes from the screen corners to the center
+    let max_radius = length(vec2<f32>(transition.params.w, 1.0)) + EDGE;
+    let radius = (1.0 - coverage) * max_radius - EDGE;
+    return clamp((length(position) - radius) / EDGE, 0.0, 1.0);
+}
+
+// spinner ring around the screen center: the loaded part is filled, a dash turns around it
+fn spinner(ndc: vec2<f32>) -> f32 {
+    let position = vec2<f32>(ndc.x * transition.params.w, ndc.y);
+    let ring = 1.0 - clamp(abs(length(position) - 0.08) / 0.012, 0.0, 1.0);
+    // 0.0 ~ 1.0 clockwise from the top
+    let angle = fract(atan2(position.x, position.y) / (2.0 * PI) + 1.0);
+    let progress = select(0.25, 1.0, angle <= transition.loading.y);
+    let dash_angle = fract(transition.loading.z / (2.0 * PI));
+    let dash = clamp(1.0 - abs(fract(angle - dash_angle + 0.5) - 0.5) / 0.08, 0.0, 1.0);
+    return ring * max(progress, dash);
+}
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput
+) -> [[location(0)]] vec4<f32> {
+    let alpha = cover(in.ndc);
+    var color = transition.color.rgb;
+    // the spinner shows up with the cover, in the opposite brightness
+    if (transition.loading.x > 0.5) {
+        let spinner_alpha = spinner(in.ndc) * transition.params.x;
+        let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+        let spinner_color = select(vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.0, 0.0, 0.0), luminance > 0.5);
+        color = mix(color, spinner_color, spinner_alpha);
+        return vec4<f32>(color, max(alpha, spinner_alpha));
+    }
+    return vec4<f32>(color, alpha);
+}
@@ -0,0 +1,489 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use legion::{Entity, IntoQuery, Resources, Schedule, World};
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use super::camera::CameraRig;
+use super::cloth::{cloth_system, Cloth, ClothMeshes};
+use super::debug_draw::{DebugDraw, DebugDrawCategory, DebugDrawSettings};
+use super::floating_origin::{PreciseTransform, WorldOrigin};
+use super::fracture::{Debris, Destructible, FracturedMesh, Random};
+use super::light::{Light, LightKind, LightOccluder};
+use super::mesh::{Mesh, MeshDraw};
+use super::material::Material;
+use super::material_params::{material_param_animation_system, MaterialParamOverrides};
+use super::model::Model;
+use super::parallax::ParallaxLayer;
+use super::pipeline_cache::ScenePipelineKey;
+use super::sprite::Sprite;
+use super::steering::steering_system;
+use super::tags::Tags;
+use super::time::Time;
+use super::transform::{transform_propagation_system, Parent, Transform};
+use super::validation::{SceneIssue, SceneReport, SceneStats};
+
+// Component drawing a mesh registered with `Scene::add_mesh()`, placed by the entity's `Transform`.
+// tips: components must be `Send + Sync`, so entities refer to the meshes (`Rc<Mesh>`) by index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshHandle(usize);
+
+// Component drawing an entity's mesh with a material registered with `Scene::add_material()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(usize);
+
+// ECS world of the application, owned by the event loop (see `Application::start_with_scene()`).
+// Every frame its schedule runs, the material parameters are animated (see `MaterialParamAnimation`),
+// the steering agents are moved (see `SteeringAgent`), the cloths are simulated (see `Cloth`), the global transforms are propagated from the parents (see `Parent`),
+// then the entities with a `Transform` & a `MeshHandle` are drawn, lit by the entities with a `Light` & shadowed in 2D by the ones with a `LightOccluder`.
+// The entities with `DebugDrawSettings` get their bounds drawn as debug shapes: the box & sphere around their mesh, the range of their light.
+pub struct Scene {
+    pub world: World,
+    pub resources: Resources,
+    schedule: Schedule,
+    // the engine's systems, run after `schedule`
+    engine_schedule: Schedule,
+    meshes: Vec<Rc<Mesh>>,
+    materials: Vec<Rc<Material>>,
+    // written by the cloth system
+    cloth_meshes: ClothMeshes,
+    // None keeps the origin where it is
+    origin: Option<WorldOrigin>
+}
+
+impl Scene {
+    pub fn new(world: World, schedule: Schedule) -> Self {
+        let cloth_meshes = ClothMeshes::default();
+        Self {
+            world,
+            resources: Resources::default(),
+            schedule,
+            engine_schedule: Schedule::builder()
+                .add_system(material_param_animation_system())
+                .add_system(steering_system())
+                .add_thread_local_fn(cloth_system(cloth_meshes.clone()))
+                .add_system(transform_propagation_system())
+                .build(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            cloth_meshes,
+            origin: None
+        }
+    }
+
+    // shared with the application's resources, e.g. settings read by the systems
+    pub fn with_resources(mut self, resources: Resources) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    // Shift the scene & the camera back to the origin whenever the camera is further than `threshold`, for large worlds.
+    // The `WorldOrigin` is in the resources for the systems.
+    pub fn with_floating_origin(mut self, threshold: f32) -> Self {
+        self.origin = Some(WorldOrigin::new(threshold));
+        self
+    }
+
+    // None without floating origin
+    pub fn world_origin(&self) -> Option<&WorldOrigin> {
+        self.origin.as_ref()
+    }
+
+    // replace the systems run every frame
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+    }
+
+    pub fn add_mesh(&mut self, mesh: &Rc<Mesh>) -> MeshHandle {
+        self.meshes.push(mesh.clone());
+        MeshHandle(self.meshes.len() - 1)
+    }
+
+    pub fn add_material(&mut self, material: &Rc<Material>) -> MaterialHandle {
+        self.materials.push(material.clone());
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    pub fn mesh(&self, handle: MeshHandle) -> Option<&Rc<Mesh>> {
+        self.meshes.get(handle.0)
+    }
+
+    pub fn material(&self, handle: MaterialHandle) -> Option<&Rc<Material>> {
+        self.materials.get(handle.0)
+    }
+
+    // Spawn one entity per mesh of the model, all placed by `transform`.
+    pub fn spawn_model(&mut self, model: &Model, transform: Transform) {
+        for model_mesh in model.meshes() {
+            let mesh = self.add_mesh(&model_mesh.mesh);
+            match &model_mesh.material {
+                Some(material) => {
+                    let material = self.add_material(material);
+                    self.world.push((transform, mesh, material));
+                },
+                None => {
+                    self.world.push((transform, mesh));
+                }
+            }
+        }
+    }
+
+    // Register the pieces of `fractured`, for the entities which break into them (see `Scene::destroy()`).
+    pub fn add_destructible(&mut self, fractured: &FracturedMesh) -> Destructible {
+        Destructible {
+            pieces: fractured.pieces().iter().map(|piece| (self.add_mesh(&piece.mesh), piece.center)).collect(),
+            debris: Default::default()
+        }
+    }
+
+    // Swap the entity with a `Destructible` & a `Transform` for its pieces, thrown away from `impact` (world space) by `force`.
+    // The pieces are entities with a `Debris` & the entity's material, moved by the scene until the end of their lifetime.
+    // Return false (leaving the entity as it is) if it's not destructible.
+    pub fn destroy(&mut self, entity: Entity, impact: Point3<f32>, force: f32) -> bool {
+        let (transform, destructible, material) = match self.world.entry(entity) {
+            Some(entry) => match (entry.get_component::<Transform>(), entry.get_component::<Destructible>()) {
+                (Ok(transform), Ok(destructible)) => (*transform, destructible.clone(), entry.get_component::<MaterialHandle>().ok().copied()),
+                _ => return false
+            },
+            None => return false
+        };
+        self.world.remove(entity);
+
+        // the spins differ from an entity to the other
+        let mut hasher = DefaultHasher::new();
+        entity.hash(&mut hasher);
+        let mut random = Random::new(hasher.finish() as u32);
+        for (mesh, center) in destructible.pieces {
+            let piece_mesh = match self.mesh(mesh) {
+                Some(piece_mesh) => piece_mesh,
+                None => continue
+            };
+            let debris = Debris::new(piece_mesh, center, &transform.global, &impact, force, destructible.debris, &mut random);
+            let transform = debris.transform();
+            match material {
+                Some(material) => self.world.push((transform, mesh, material, debris)),
+                None => self.world.push((transform, mesh, debris))
+            };
+        }
+        true
+    }
+
+    // Attach `child` to `parent`, its local transform is then relative to the parent's, or detach it with None.
+    // Return false if `child` doesn't exist.
+    pub fn set_parent(&mut self, child: Entity, parent: Option<Entity>) -> bool {
+        let mut entry = match self.world.entry(child) {
+            Some(entry) => entry,
+            None => return false
+        };
+        match parent {
+            Some(parent) => entry.add_component(Parent(parent)),
+            None => entry.remove_component::<Parent>()
+        }
+        true
+    }
+
+    // the entities whose `Tags` have `tag` or one of its descendants, e.g. every "enemy.*" for "enemy"
+    pub fn tagged(&self, tag: &str) -> Vec<Entity> {
+        let mut query = <(Entity, &Tags)>::query();
+        query
+            .iter(&self.world)
+            .filter(|(_, tags)| tags.has(tag))
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    // The closest entity with `tag` & a `Transform` within `max_distance` of `position`, with its distance, e.g. an AI's target.
+    // tips: the transforms are the ones of the last frame while the systems run.
+    pub fn nearest_tagged(&self, tag: &str, position: Point3<f32>, max_distance: f32) -> Option<(Entity, f32)> {
+        let mut query = <(Entity, &Tags, &Transform)>::query();
+        query
+            .iter(&self.world)
+            .filter(|(_, tags, _)| tags.has(tag))
+            .map(|(entity, _, transform)| (*entity, nalgebra::distance(&transform.global.transform_point(&Point3::origin()), &position)))
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    // Scan the scene for content errors (unregistered handles, untextured sprites, broken meshes, dark lights...) with its stats,
+    // e.g. once it's loaded. The application prints the issues of its scene on start.
+    pub fn validate(&self) -> SceneReport {
+        let mut stats = SceneStats {
+            entities: self.world.len(),
+            meshes: self.meshes.len(),
+            materials: self.materials.len(),
+            ..SceneStats::default()
+        };
+        let mut issues = Vec::new();
+
+        for (index, mesh) in self.meshes.iter().enumerate() {
+            let vertices = mesh.vertices().len();
+            if let Some(out_of_range) = mesh.indices().iter().find(|index| **index as usize >= vertices) {
+                issues.push(SceneIssue::IndexOutOfRange { mesh: MeshHandle(index), index: *out_of_range, vertices });
+            }
+            if mesh.indices().len() % 3 != 0 {
+                issues.push(SceneIssue::IncompleteTriangle { mesh: MeshHandle(index), indices: mesh.indices().len() });
+            }
+        }
+
+        let mut query = <(Entity, &MeshHandle, Option<&Transform>)>::query();
+        for (entity, mesh, transform) in query.iter(&self.world) {
+            match self.mesh(*mesh) {
+                Some(mesh) if transform.is_some() => {
+                    stats.mesh_instances += 1;
+                    stats.vertices += mesh.vertices().len();
+                    stats.triangles += mesh.indices().len() / 3;
+                },
+                Some(_) => {},
+                None => issues.push(SceneIssue::MissingMesh { entity: *entity, mesh: *mesh })
+            }
+        }
+
+        let mut query = <(Entity, &MaterialHandle, Option<&Sprite>, Option<&ParallaxLayer>)>::query();
+        for (entity, material, sprite, layer) in query.iter(&self.world) {
+            stats.sprites += sprite.is_some() as usize;
+            stats.parallax_layers += layer.is_some() as usize;
+            match self.material(*material) {
+                // sprites & layers are drawn with the engine's shader
+                Some(material) if (sprite.is_some() || layer.is_some()) && material.shader().is_some() => {
+                    issues.push(SceneIssue::CustomMaterialOnSprite { entity: *entity, material: material.name().to_string() });
+                },
+                // sprites & layers are textured by their material
+                Some(material) if (sprite.is_some() || layer.is_some()) && material.albedo().is_none() => {
+                    issues.push(SceneIssue::MissingTexture { entity: *entity, material: material.name().to_string() });
+                },
+                Some(_) => {},
+                None => issues.push(SceneIssue::MissingMaterial { entity: *entity, material: *material })
+            }
+        }
+
+        let mut query = <(Entity, &MaterialHandle, &MaterialParamOverrides)>::query();
+        for (entity, material, param_overrides) in query.iter(&self.world) {
+            let material = match self.material(*material) {
+                Some(material) => material,
+                None => continue
+            };
+            let params = material.shader().map_or(&[][..], |shader| shader.params());
+            for (name, value) in param_overrides.iter() {
+                if !params.iter().any(|param| param.name == name && param.ty == value.ty()) {
+                    issues.push(SceneIssue::UnknownMaterialParam {
+                        entity: *entity,
+                        material: material.name().to_string(),
+                        param: name.to_string()
+                    });
+                }
+            }
+        }
+
+        let mut query = <(Entity, &Light)>::query();
+        for (entity, light) in query.iter(&self.world) {
+            stats.lights += 1;
+            if light.intensity <= 0.0 || light.color.iter().all(|channel| *channel <= 0.0) {
+                issues.push(SceneIssue::DarkLight { entity: *entity });
+            }
+            match light.kind {
+                LightKind::Point { range } | LightKind::Spot { range, .. } if range <= 0.0 => {
+                    issues.push(SceneIssue::LightWithoutRange { entity: *entity });
+                },
+                _ => {}
+            }
+        }
+        stats.light_occluders = <&LightOccluder>::query().iter(&self.world).count();
+
+        SceneReport { stats, issues }
+    }
+
+    // the registered materials drawn with one of the `failed` render states
+    pub(crate) fn pipeline_issues(&self, failed: &[ScenePipelineKey]) -> Vec<SceneIssue> {
+        self.materials
+            .iter()
+            .filter(|material| failed.contains(&material.pipeline_key(material.render_state())))
+            .map(|material| SceneIssue::MaterialWithoutPipeline {
+                material: material.name().to_string(),
+                render_state: material.render_state()
+            })
+            .collect()
+    }
+
+    // Move the origin under the camera if it's too far: the root entities (the children follow them),
+    // the debris & the camera are shifted back, before the application & the systems run.
+    pub(crate) fn recenter(&mut self, camera_rig: &mut CameraRig) {
+        let origin = match &mut self.origin {
+            Some(origin) => origin,
+            None => return
+        };
+        let shift = origin.recenter(&camera_rig.camera().eye);
+        self.resources.insert(*origin);
+        let shift = match shift {
+            Some(shift) => shift,
+            None => return
+        };
+        camera_rig.shift_origin(&shift);
+        let translation = Matrix4::new_translation(&-shift);
+        // the roots of the propagation: no parent, or a parent without transform
+        let placed = <(Entity, &Transform)>::query()
+            .iter(&self.world)
+            .map(|(entity, _)| *entity)
+            .collect::<HashSet<_>>();
+        <(Option<&Parent>, &mut Transform)>::query().for_each_mut(&mut self.world, |(parent, transform)| {
+            if !parent.is_some_and(|parent| placed.contains(&parent.0)) {
+                transform.translation -= shift;
+            }
+        });
+        // the global transforms are propagated again after the systems
+        <&mut Transform>::query().for_each_mut(&mut self.world, |transform| {
+            transform.global = translation * transform.global;
+        });
+        <&mut Debris>::query().for_each_mut(&mut self.world, |debris| debris.shift_origin(&shift));
+        <&mut Cloth>::query().for_each_mut(&mut self.world, |cloth| cloth.shift_origin(shift));
+        for mesh in self.cloth_meshes.borrow_mut().values_mut() {
+            mesh.transform(&translation);
+        }
+        <&mut Destructible>::query().for_each_mut(&mut self.world, |destructible| destructible.debris.shift_origin(&shift));
+    }
+
+    // run the systems, move the debris, convert the precise transforms, then update the global transforms
+    pub(crate) fn execute(&mut self) {
+        self.schedule.execute(&mut self.world, &mut self.resources);
+        self.update_debris();
+        self.update_precise_transforms();
+        self.engine_schedule.execute(&mut self.world, &mut self.resources);
+    }
+
+    // the `Transform` of the entities placed in double precision, relative to the origin
+    fn update_precise_transforms(&mut self) {
+        let origin = self.origin.map(|origin| origin.offset()).unwrap_or_else(Vector3::zeros);
+        <(&PreciseTransform, &mut Transform)>::query().for_each_mut(&mut self.world, |(precise, transform)| {
+            precise.to_transform(&origin, transform);
+        });
+    }
+
+    // step the pieces of the destroyed entities by the frame's delta time, removing the expired ones
+    fn update_debris(&mut self) {
+        let dt = match self.resources.get::<Time>() {
+            Some(time) => time.delta(),
+            None => return
+        };
+        let mut expired = Vec::new();
+        <(Entity, &mut Debris, &mut Transform)>::query().for_each_mut(&mut self.world, |(entity, debris, transform)| {
+            debris.step(dt);
+            if debris.is_alive() {
+                *transform = debris.transform();
+            } else {
+                expired.push(*entity);
+            }
+        });
+        for entity in expired {
+            self.world.remove(entity);
+        }
+    }
+
+    pub(crate) fn draw_meshes(&self, mesh_draws: &mut Vec<MeshDraw>) {
+        let mut query = <(&Transform, &MeshHandle, Option<&MaterialHandle>, Option<&Debris>, Option<&MaterialParamOverrides>)>::query();
+        for (transform, mesh, material, debris, param_overrides) in query.iter(&self.world) {
+            let mesh = match self.mesh(*mesh) {
+                Some(mesh) => mesh,
+                None => continue
+            };
+            let matrix = match debris {
+                Some(debris) => transform.global * debris.shape(),
+                None => transform.global
+            };
+            let mut mesh_draw = MeshDraw::new(mesh, matrix);
+            mesh_draw.material = material.and_then(|material| self.material(*material)).cloned();
+            mesh_draw.param_overrides = param_overrides.filter(|param_overrides| !param_overrides.is_empty()).cloned();
+            mesh_draws.push(mesh_draw);
+        }
+
+        // the cloths, in world space
+        let cloth_meshes = self.cloth_meshes.borrow();
+        let mut query = <(Entity, &Cloth, Option<&MaterialHandle>)>::query();
+        for (entity, _, material) in query.iter(&self.world) {
+            if let Some(mesh) = cloth_meshes.get(entity) {
+                let mut mesh_draw = MeshDraw::new(&mesh.mesh(), Matrix4::identity());
+                mesh_draw.material = material.and_then(|material| self.material(*material)).cloned();
+                mesh_draws.push(mesh_draw);
+            }
+        }
+    }
+
+    // the entities with a `ParallaxLayer` & a `MaterialHandle`
+    pub(crate) fn parallax_layers(&self, layers: &mut Vec<(ParallaxLayer, Rc<Material>)>) {
+        let mut query = <(&ParallaxLayer, &MaterialHandle)>::query();
+        for (layer, material) in query.iter(&self.world) {
+            if let Some(material) = self.material(*material) {
+                layers.push((*layer, material.clone()));
+            }
+        }
+    }
+
+    // the entities with a `Sprite` & a `MaterialHandle`, with their world transform (identity without one)
+    pub(crate) fn sprites(&self, sprites: &mut Vec<(Sprite, Matrix4<f32>, Rc<Material>)>) {
+        let mut query = <(&Sprite, &MaterialHandle, Option<&Transform>)>::query();
+        for (sprite, material, transform) in query.iter(&self.world) {
+            if let Some(material) = self.material(*material) {
+                let transform = transform.map_or_else(Matrix4::identity, |transform| transform.global);
+                sprites.push((*sprite, transform, material.clone()));
+            }
+        }
+    }
+
+    // the entities with a `Light`, placed by their `Transform` (at the origin without one)
+    pub(crate) fn lights(&self, lights: &mut Vec<(Light, Point3<f32>)>) {
+        let mut query = <(&Light, Option<&Transform>)>::query();
+        for (light, transform) in query.iter(&self.world) {
+            let position = transform.map_or_else(Point3::origin, |transform| transform.global.transform_point(&Point3::origin()));
+            lights.push((*light, position));
+        }
+    }
+
+    // The debug shapes of the entities with `DebugDrawSettings` & a `Transform`, in the categories enabled for them.
+    pub(crate) fn debug_draw(&self, debug_draw: &mut DebugDraw) {
+        const BOUNDS_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+        let mut query = <(&Transform, &DebugDrawSettings, Option<&MeshHandle>, Option<&Light>)>::query();
+        for (transform, settings, mesh, light) in query.iter(&self.world) {
+            let mesh = mesh.and_then(|mesh| self.mesh(*mesh)).filter(|mesh| !mesh.vertices().is_empty());
+            if let Some(mesh) = mesh {
+                // around the world space vertices, rather than the transformed local bounds
+                let points = mesh.vertices()
+                    .iter()
+                    .map(|vertex| transform.global.transform_point(&Point3::from(vertex.position)))
+                    .collect::<Vec<_>>();
+                let (min, max) = points.iter().fold((points[0], points[0]), |(min, max), point| (min.inf(point), max.sup(point)));
+                if settings.is_enabled(DebugDrawCategory::Aabbs) {
+                    debug_draw.aabb(min.into(), max.into(), BOUNDS_COLOR);
+                }
+                if settings.is_enabled(DebugDrawCategory::Spheres) {
+                    let center = nalgebra::center(&min, &max);
+                    let radius = points.iter().map(|point| nalgebra::distance(&center, point)).fold(0.0, f32::max);
+                    debug_draw.sphere(center.into(), radius, BOUNDS_COLOR);
+                }
+            }
+            if let (Some(light), true) = (light, settings.is_enabled(DebugDrawCategory::Lights)) {
+                let range = match light.kind {
+                    LightKind::Point { range } | LightKind::Spot { range, .. } => range,
+                    LightKind::Directional { .. } => continue
+                };
+                let position = transform.global.transform_point(&Point3::origin());
+                let [r, g, b] = light.color;
+                debug_draw.point_light(position.into(), range, [r, g, b, 1.0]);
+            }
+        }
+    }
+
+    // the world space edges of the entities with a `LightOccluder`
+    pub(crate) fn occluder_segments(&self, segments: &mut Vec<[f32; 4]>) {
+        let mut query = <(&LightOccluder, Option<&Transform>)>::query();
+        for (occluder, transform) in query.iter(&self.world) {
+            occluder.segments(&transform.map_or_else(Matrix4::identity, |transform| transform.global), segments);
+        }
+    }
+}
+
+impl Default for Scene {
+    // an empty world without systems
+    fn default() -> Self {
+        Self::new(World::default(), Schedule::builder().build())
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::assets::AssetRoot;
+use super::model::{Model, ModelData};
+
+// How far a `SceneLoad` is, e.g. for `Transition::set_loading()`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+    // name of the asset being loaded, None before the first one & once done
+    pub current: Option<String>
+}
+
+impl LoadProgress {
+    // 0.0 ~ 1.0
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+}
+
+// shared with `AssetLoader`
+pub(crate) enum AssetRequest {
+    Model(String, PathBuf),
+    Image(String, PathBuf)
+}
+
+impl AssetRequest {
+    pub(crate) fn resolve(self, asset_root: &AssetRoot) -> Self {
+        match self {
+            AssetRequest::Model(name, path) => AssetRequest::Model(name, asset_root.resolve(path)),
+            AssetRequest::Image(name, path) => AssetRequest::Image(name, asset_root.resolve(path))
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            AssetRequest::Model(name, _) | AssetRequest::Image(name, _) => name
+        }
+    }
+
+    pub(crate) fn load(&self) -> Result<AssetData> {
+        match self {
+            AssetRequest::Model(name, path) => Ok(AssetData::Model(name.clone(), ModelData::load(path)?)),
+            AssetRequest::Image(name, path) => {
+                let image = image::open(path).with_context(|| format!("failed to load image {}", path.display()))?;
+                Ok(AssetData::Image(name.clone(), image.to_rgba8()))
+            }
+        }
+    }
+}
+
+pub(crate) enum AssetData {
+    Model(String, ModelData),
+    Image(String, image::RgbaImage)
+}
+
+// The assets of a scene, named by the application, to load in the background with `start()`.
+pub struct SceneLoader {
+    requests: Vec<AssetRequest>,
+    // None: the paths are used as given, i.e. relative to the working directory
+    asset_root: Option<AssetRoot>
+}
+
+impl SceneLoader {
+    pub fn new() -> Self {
+        Self {
+            requests: Vec::new(),
+            asset_root: None
+        }
+    }
+
+    // resolve the relative paths of the assets against `asset_root`, e.g. `AssetRoot::default()`
+    pub fn with_asset_root(mut self, asset_root: AssetRoot) -> Self {
+        self.asset_root = Some(asset_root);
+        self
+    }
+
+    // an OBJ model, see `Model::load()`
+    pub fn with_model<P: Into<PathBuf>>(mut self, name: &str, path: P) -> Self {
+        self.requests.push(AssetRequest::Model(name.to_string(), path.into()));
+        self
+    }
+
+    // an image decoded to RGBA, e.g. for `PaintCanvas::from_image()`
+    pub fn with_image<P: Into<PathBuf>>(mut self, name: &str, path: P) -> Self {
+        self.requests.push(AssetRequest::Image(name.to_string(), path.into()));
+        self
+    }
+
+    // Load the assets in order on a worker thread, the first failure stops the load.
+    pub fn start(self) -> SceneLoad {
+        let requests: Vec<AssetRequest> = match &self.asset_root {
+            Some(asset_root) => self.requests.into_iter().map(|request| request.resolve(asset_root)).collect(),
+            None => self.requests
+        };
+        let progress = Arc::new(Mutex::new(LoadProgress {
+            total: requests.len(),
+            ..LoadProgress::default()
+        }));
+        let (sender, receiver) = mpsc::channel();
+
+        let worker_progress = progress.clone();
+        std::thread::spawn(move || {
+            let set_progress = |loaded: usize, current: Option<&str>| {
+                let mut progress = worker_progress.lock().unwrap_or_else(|error| error.into_inner());
+                progress.loaded = loaded;
+                progress.current = current.map(str::to_string);
+            };
+            let mut assets = Vec::with_capacity(requests.len());
+            for (loaded, request) in requests.iter().enumerate() {
+                set_progress(loaded, Some(request.name()));
+                match request.load() {
+                    Ok(asset) => assets.push(asset),
+                    Err(error) => {
+                        // the receiver may be gone if the load was dropped
+                        let _ = sender.send(Err(error));
+                        return;
+                    }
+                }
+            }
+            set_progress(assets.len(), None);
+            let _ = sender.send(Ok(assets));
+        });
+
+        SceneLoad {
+            progress,
+            receiver,
+            finished: false
+        }
+    }
+}
+
+impl Default for SceneLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Handle of a scene loading in the background.
+// tips: dropping it doesn't stop the worker thread, its assets are dropped once loaded.
+pub struct SceneLoad {
+    progress: Arc<Mutex<LoadProgress>>,
+    receiver: mpsc::Receiver<Result<Vec<AssetData>>>,
+    finished: bool
+}
+
+impl SceneLoad {
+    pub fn progress(&self) -> LoadProgress {
+        self.progress.lock().unwrap_or_else(|error| error.into_inner()).clone()
+    }
+
+    // Some once, when every asset is loaded or one failed: swap the active scene then, so nothing is missing from its first frame.
+    // The meshes & textures are uploaded to the GPU when they're first drawn, before that frame is rendered.
+    pub fn poll(&mut self) -> Option<Result<LoadedScene>> {
+        if self.finished {
+            return None;
+        }
+        let result = match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => Err(anyhow!("the scene loading thread panicked"))
+        };
+        self.finished = true;
+        Some(result.map(LoadedScene::new))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+// Assets of a loaded scene, by name.
+pub struct LoadedScene {
+    models: HashMap<String, Model>,
+    images: HashMap<String, image::RgbaImage>
+}
+
+impl LoadedScene {
+    // the models are built on the main thread, they're shared with `Rc`
+    fn new(assets: Vec<AssetData>) -> Self {
+        let mut models = HashMap::new();
+        let mut images = HashMap::new();
+        for asset in assets {
+            match asset {
+                AssetData::Model(name, data) => {
+                    models.insert(name, Model::from_data(data));
+                },
+                AssetData::Image(name, image) => {
+                    images.insert(name, image);
+                }
+            }
+        }
+        Self {
+            models,
+            images
+        }
+    }
+
+    pub fn model(&self, name: &str) -> Option<&Model> {
+        self.models.get(name)
+    }
+
+    pub fn take_model(&mut self, name: &str) -> Option<Model> {
+        self.models.remove(name)
+    }
+
+    pub fn image(&self, name: &str) -> Option<&image::RgbaImage> {
+        self.images.get(name)
+    }
+
+    pub fn take_image(&mut self, name: &str) -> Option<image::RgbaImage> {
+        self.images.remove(name)
+    }
+}
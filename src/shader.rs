@@ -0,0 +1,281 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use anyhow::{anyhow, bail, Result};
+
+// Type of a material parameter, i.e. a member of the material uniform block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MaterialParamType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Mat4
+}
+
+impl MaterialParamType {
+    // whether a reflected WGSL type is this parameter type.
+    fn matches(&self, inner: &naga::TypeInner) -> bool {
+        use naga::{ScalarKind, TypeInner, VectorSize};
+
+        matches!(
+            (self, inner),
+            (Self::Float, TypeInner::Scalar { kind: ScalarKind::Float, width: 4 })
+                | (Self::Vec2, TypeInner::Vector { size: VectorSize::Bi, kind: ScalarKind::Float, width: 4 })
+                | (Self::Vec3, TypeInner::Vector { size: VectorSize::Tri, kind: ScalarKind::Float, width: 4 })
+                | (Self::Vec4, TypeInner::Vector { size: VectorSize::Quad, kind: ScalarKind::Float, width: 4 })
+                | (Self::Mat4, TypeInner::Matrix { columns: VectorSize::Quad, rows: VectorSize::Quad, width: 4 })
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialParam {
+    pub name: String,
+    pub ty: MaterialParamType
+}
+
+// Layout of a material shader resources, reflected from its WGSL source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialShaderLayout {
+    // size in bytes of the uniform block, None if the material has no parameters.
+    pub uniform_size: Option<u64>,
+    // offset in bytes of each parameter within the uniform block, in declaration order.
+    pub param_offsets: Vec<u32>
+}
+
+// A user authored WGSL material shader, drawn on the meshes of its materials (see `Material::custom()`).
+// The material resources live in bind group `MaterialShader::GROUP`:
+// * binding 0: uniform block whose members are the declared parameters (in declaration order), bound at a dynamic offset
+// * binding 1 + 2 * i: `t_<name>` texture of the i-th declared texture slot
+// * binding 2 + 2 * i: `s_<name>` sampler of the i-th declared texture slot
+// Other bind groups are provided by the engine (group 1: camera, group 2: environment, lights & occluders, as in "shader.wgsl").
+// The vertex entry gets the engine's vertex attributes (locations 0 ~ 3: position, tex_coords, color & normal)
+// & the model matrix of the instance (locations 5 ~ 8), the fragment entry writes the HDR color of the scene.
+// tips: the render pipelines are compiled in the background, one per shader & render state, like the engine's.
+#[derive(Clone, Debug)]
+pub struct MaterialShader {
+    label: String,
+    source: String,
+    params: Vec<MaterialParam>,
+    textures: Vec<String>,
+    // hash of the source & declarations, materials with equal shaders share their pipelines
+    key: u64
+}
+
+impl MaterialShader {
+    // bind group index of the material resources
+    pub const GROUP: u32 = 0;
+    // entry points every material shader has to provide
+    pub const VERTEX_ENTRY: &'static str = "vs_main";
+    pub const FRAGMENT_ENTRY: &'static str = "fs_main";
+
+    pub fn new(label: &str, source: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            source: source.to_string(),
+            params: Vec::new(),
+            textures: Vec::new(),
+            key: 0
+        }
+        .rehashed()
+    }
+
+    // declare a member of the uniform block.
+    pub fn with_param(mut self, name: &str, ty: MaterialParamType) -> Self {
+        self.params.push(MaterialParam { name: name.to_string(), ty });
+        self.rehashed()
+    }
+
+    // declare a texture slot (a `t_<name>` texture and its `s_<name>` sampler).
+    pub fn with_texture(mut self, name: &str) -> Self {
+        self.textures.push(name.to_string());
+        self.rehashed()
+    }
+
+    fn rehashed(mut self) -> Self {
+        let mut hasher = DefaultHasher::new();
+        (&self.source, &self.params, &self.textures).hash(&mut hasher);
+        self.key = hasher.finish();
+        self
+    }
+
+    // what the pipelines of this shader are cached by
+    pub(crate) fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn params(&self) -> &[MaterialParam] {
+        &self.params
+    }
+
+    pub fn textures(&self) -> &[String] {
+        &self.textures
+    }
+
+    pub(crate) fn texture_binding(slot: usize) -> u32 {
+        1 + 2 * slot as u32
+    }
+
+    pub(crate) fn sampler_binding(slot: usize) -> u32 {
+        2 + 2 * slot as u32
+    }
+
+    // Parse the WGSL source and check the declared parameters & texture slots against it,
+    // so a mismatch is reported with a clear message instead of a wgpu validation panic.
+    pub fn reflect(&self) -> Result<MaterialShaderLayout> {
+        let module = naga::front::wgsl::parse_str(&self.source)
+            .map_err(|error| anyhow!("[{}] {}", self.label, error.emit_to_string(&self.source)))?;
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+            .validate(&module)
+            .map_err(|error| anyhow!("[{}] invalid shader: {:?}", self.label, error))?;
+
+        for entry in [Self::VERTEX_ENTRY, Self::FRAGMENT_ENTRY] {
+            if !module.entry_points.iter().any(|entry_point| entry_point.name == entry) {
+                bail!("[{}] missing entry point `{}`", self.label, entry);
+            }
+        }
+
+        let mut layout = MaterialShaderLayout {
+            uniform_size: None,
+            param_offsets: Vec::new()
+        };
+
+        // collect every resource the shader binds in the material group
+        let mut declared_bindings = Vec::new();
+        if !self.params.is_empty() {
+            declared_bindings.push(0);
+        }
+        for slot in 0..self.textures.len() {
+            declared_bindings.push(Self::texture_binding(slot));
+            declared_bindings.push(Self::sampler_binding(slot));
+        }
+
+        for (_, global) in module.global_variables.iter() {
+            let binding = match &global.binding {
+                Some(binding) if binding.group == Self::GROUP => binding.binding,
+                _ => continue
+            };
+            let name = global.name.as_deref().unwrap_or("<unnamed>");
+            if !declared_bindings.contains(&binding) {
+                bail!("[{}] `{}` at binding {} is not a declared parameter or texture slot", self.label, name, binding);
+            }
+        }
+
+        let find_global = |binding: u32| {
+            module.global_variables.iter().map(|(_, global)| global).find(|global| {
+                matches!(&global.binding, Some(resource) if resource.group == Self::GROUP && resource.binding == binding)
+            })
+        };
+
+        // uniform block
+        if !self.params.is_empty() {
+            let global = find_global(0)
+                .ok_or_else(|| anyhow!("[{}] missing uniform block at group {} binding 0", self.label, Self::GROUP))?;
+            if global.class != naga::StorageClass::Uniform {
+                bail!("[{}] binding 0 must be a `var<uniform>`", self.label);
+            }
+            let (members, span) = match &module.types[global.ty].inner {
+                naga::TypeInner::Struct { members, span } => (members, *span),
+                _ => bail!("[{}] the uniform block at binding 0 must be a struct", self.label)
+            };
+            if members.len() != self.params.len() {
+                bail!("[{}] uniform block has {} members, but {} parameters are declared", self.label, members.len(), self.params.len());
+            }
+            for (member, param) in members.iter().zip(self.params.iter()) {
+                if member.name.as_deref() != Some(param.name.as_str()) {
+                    bail!("[{}] uniform member `{}` doesn't match declared parameter `{}`", self.label, member.name.as_deref().unwrap_or("<unnamed>"), param.name);
+                }
+                if !param.ty.matches(&module.types[member.ty].inner) {
+                    bail!("[{}] uniform member `{}` isn't a {:?}", self.label, param.name, param.ty);
+                }
+                layout.param_offsets.push(member.offset);
+            }
+            layout.uniform_size = Some(span as u64);
+        }
+
+        // texture slots
+        for (slot, name) in self.textures.iter().enumerate() {
+            let texture = find_global(Self::texture_binding(slot))
+                .ok_or_else(|| anyhow!("[{}] missing texture `t_{}` at binding {}", self.label, name, Self::texture_binding(slot)))?;
+            if texture.name.as_deref() != Some(format!("t_{}", name).as_str()) {
+                bail!("[{}] binding {} should be texture `t_{}`", self.label, Self::texture_binding(slot), name);
+            }
+            match &module.types[texture.ty].inner {
+                naga::TypeInner::Image {
+                    dim: naga::ImageDimension::D2,
+                    arrayed: false,
+                    class: naga::ImageClass::Sampled { kind: naga::ScalarKind::Float, multi: false }
+                } => {},
+                _ => bail!("[{}] `t_{}` must be a `texture_2d<f32>`", self.label, name)
+            }
+
+            let sampler = find_global(Self::sampler_binding(slot))
+                .ok_or_else(|| anyhow!("[{}] missing sampler `s_{}` at binding {}", self.label, name, Self::sampler_binding(slot)))?;
+            if sampler.name.as_deref() != Some(format!("s_{}", name).as_str()) {
+                bail!("[{}] binding {} should be sampler `s_{}`", self.label, Self::sampler_binding(slot), name);
+            }
+            match &module.types[sampler.ty].inner {
+                naga::TypeInner::Sampler { comparison: false } => {},
+                _ => bail!("[{}] `s_{}` must be a `sampler`", self.label, name)
+            }
+        }
+
+        Ok(layout)
+    }
+
+    // Generate the entries of the material bind group layout from the declared parameters & texture slots.
+    pub(crate) fn bind_group_layout_entries(&self) -> Vec<wgpu::BindGroupLayoutEntry> {
+        let mut entries = Vec::new();
+
+        if !self.params.is_empty() {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    // a block per instance overriding the parameters, see `MaterialParamOverrides`
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+        }
+
+        for slot in 0..self.textures.len() {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: Self::texture_binding(slot),
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: Self::sampler_binding(slot),
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+
+        entries
+    }
+
+    pub(crate) fn create_bind_group_layout(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} bind group layout", self.label)),
+            entries: &self.bind_group_layout_entries()
+        })
+    }
+}
@@ -0,0 +1,284 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use nalgebra::Vector3;
+
+// how strongly the unlocked open edges resist moving, relative to the surface
+const BORDER_WEIGHT: f64 = 10.0;
+
+// Symmetric 4x4 matrix summing squared distances to planes (upper triangle row by row),
+// with the total weight of the planes to turn it into an average.
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric {
+    matrix: [f64; 10],
+    weight: f64
+}
+
+impl Quadric {
+    // squared distance to the plane `dot(normal, p) + d == 0`, scaled by `weight`
+    fn plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Self {
+            matrix: [
+                a * a, a * b, a * c, a * d,
+                b * b, b * c, b * d,
+                c * c, c * d,
+                d * d
+            ].map(|value| value * weight),
+            weight
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other) in self.matrix.iter_mut().zip(other.matrix.iter()) {
+            *value += other;
+        }
+        self.weight += other.weight;
+    }
+
+    // weighted average of the squared distances from `p` to the planes
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let q = &self.matrix;
+        let (x, y, z) = (p.x, p.y, p.z);
+        let error = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9];
+        if self.weight > 0.0 {
+            error.max(0.0) / self.weight
+        } else {
+            0.0
+        }
+    }
+}
+
+// candidate edge collapse, ordered by lowest error first
+#[derive(Clone, Copy, Debug)]
+struct Collapse {
+    error: f64,
+    from: u32,
+    to: u32,
+    // vertex versions when the error was computed, the collapse is stale once either changed
+    from_version: u32,
+    to_version: u32
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, `BinaryHeap` is a max-heap
+        other.error.total_cmp(&self.error)
+    }
+}
+
+// Mesh simplification by edge collapses with quadric error metrics, to generate LODs automatically.
+// ref: https://www.cs.cmu.edu/~garland/Papers/quadrics.pdf
+//
+// Vertices collapse onto one of their neighbors instead of a new optimal position,
+// so simplified meshes are index buffers sharing the vertex buffer (& attributes) of the original mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshSimplifier {
+    // keep the open edges in place, which also keeps UV seams (vertices duplicated with other UVs) closed
+    pub lock_border: bool,
+    // stop before any collapse moving the surface further than this, even if the triangle budget isn't reached
+    pub max_error: f32
+}
+
+impl MeshSimplifier {
+    pub fn new() -> Self {
+        Self {
+            lock_border: true,
+            max_error: f32::INFINITY
+        }
+    }
+
+    // Simplify a triangle list down to about `target_triangles` triangles, returns its new indices.
+    pub fn simplify(&self, positions: &[[f32; 3]], indices: &[u32], target_triangles: usize) -> Vec<u32> {
+        let position = |vertex: u32| {
+            let [x, y, z] = positions[vertex as usize];
+            Vector3::new(x as f64, y as f64, z as f64)
+        };
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .filter(|&[a, b, c]| a != b && b != c && c != a)
+            .collect();
+        let mut triangles_alive = vec![true; triangles.len()];
+        let mut triangle_count = triangles.len();
+        if triangle_count <= target_triangles {
+            return triangles.concat();
+        }
+
+        // quadrics: the planes of the triangles around each vertex, weighted by area
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+        let mut edges: HashMap<(u32, u32), (usize, usize)> = HashMap::new(); // (triangle count, last triangle) by edge
+        for (triangle_index, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(position);
+            let cross = (b - a).cross(&(c - a));
+            let area = cross.magnitude() * 0.5;
+            if let Some(normal) = cross.try_normalize(f64::EPSILON) {
+                let quadric = Quadric::plane(normal, -normal.dot(&a), area);
+                for &vertex in triangle {
+                    quadrics[vertex as usize].add(&quadric);
+                }
+            }
+            for (corner, &vertex) in triangle.iter().enumerate() {
+                vertex_triangles[vertex as usize].push(triangle_index);
+                let next = triangle[(corner + 1) % 3];
+                let edge = edges.entry((vertex.min(next), vertex.max(next))).or_insert((0, triangle_index));
+                edge.0 += 1;
+                edge.1 = triangle_index;
+            }
+        }
+
+        // open edges (a single triangle): lock them, or keep them from drifting with planes perpendicular to their triangle
+        let mut locked = vec![false; positions.len()];
+        for (&(a, b), &(count, triangle_index)) in &edges {
+            if count != 1 {
+                continue;
+            }
+            if self.lock_border {
+                locked[a as usize] = true;
+                locked[b as usize] = true;
+                continue;
+            }
+            let [p0, p1, p2] = triangles[triangle_index].map(position);
+            let edge = position(b) - position(a);
+            let plane_normal = (p1 - p0).cross(&(p2 - p0)).cross(&edge).try_normalize(f64::EPSILON);
+            if let Some(plane_normal) = plane_normal {
+                let quadric = Quadric::plane(plane_normal, -plane_normal.dot(&position(a)), edge.magnitude_squared() * BORDER_WEIGHT);
+                quadrics[a as usize].add(&quadric);
+                quadrics[b as usize].add(&quadric);
+            }
+        }
+
+        let mut versions = vec![0u32; positions.len()];
+        let mut removed = vec![false; positions.len()];
+        let mut collapses = BinaryHeap::new();
+        let cheapest_collapse = |a: u32, b: u32, quadrics: &[Quadric], versions: &[u32]| {
+            let mut quadric = quadrics[a as usize];
+            quadric.add(&quadrics[b as usize]);
+            [(a, b), (b, a)]
+                .into_iter()
+                .filter(|&(from, _)| !locked[from as usize])
+                .map(|(from, to)| Collapse {
+                    error: quadric.error(position(to)),
+                    from,
+                    to,
+                    from_version: versions[from as usize],
+                    to_version: versions[to as usize]
+                })
+                .min_by(|x, y| x.error.total_cmp(&y.error))
+        };
+        for &(a, b) in edges.keys() {
+            if let Some(collapse) = cheapest_collapse(a, b, &quadrics, &versions) {
+                collapses.push(collapse);
+            }
+        }
+
+        while triangle_count > target_triangles {
+            let Collapse { error, from, to, from_version, to_version } = match collapses.pop() {
+                Some(collapse) => collapse,
+                None => break
+            };
+            if removed[from as usize] || removed[to as usize]
+                || versions[from as usize] != from_version || versions[to as usize] != to_version {
+                continue;
+            }
+            if error > self.max_error as f64 * self.max_error as f64 {
+                break;
+            }
+
+            // the triangles kept around `from` must not flip over once it moved to `to`
+            let flips = vertex_triangles[from as usize].iter().any(|&triangle_index| {
+                let triangle = triangles[triangle_index];
+                if !triangles_alive[triangle_index] || triangle.contains(&to) {
+                    return false;
+                }
+                let [a, b, c] = triangle.map(position);
+                let [a_moved, b_moved, c_moved] = triangle.map(|vertex| position(if vertex == from { to } else { vertex }));
+                let before = (b - a).cross(&(c - a));
+                let after = (b_moved - a_moved).cross(&(c_moved - a_moved));
+                before.dot(&after) <= 0.0
+            });
+            if flips {
+                continue;
+            }
+
+            // collapse: `from` is replaced by `to`, the triangles with both disappear
+            removed[from as usize] = true;
+            versions[to as usize] += 1;
+            let from_quadric = quadrics[from as usize];
+            quadrics[to as usize].add(&from_quadric);
+            for triangle_index in std::mem::take(&mut vertex_triangles[from as usize]) {
+                if !triangles_alive[triangle_index] {
+                    continue;
+                }
+                let triangle = &mut triangles[triangle_index];
+                if triangle.contains(&to) {
+                    triangles_alive[triangle_index] = false;
+                    triangle_count -= 1;
+                } else {
+                    for vertex in triangle.iter_mut().filter(|vertex| **vertex == from) {
+                        *vertex = to;
+                    }
+                    vertex_triangles[to as usize].push(triangle_index);
+                }
+            }
+            vertex_triangles[to as usize].retain(|&triangle_index| triangles_alive[triangle_index]);
+
+            // errors of the edges around `to` changed with its quadric
+            let mut neighbors: Vec<u32> = vertex_triangles[to as usize]
+                .iter()
+                .flat_map(|&triangle_index| triangles[triangle_index])
+                .filter(|&vertex| vertex != to)
+                .collect();
+            neighbors.sort_unstable();
+            neighbors.dedup();
+            for neighbor in neighbors {
+                if let Some(collapse) = cheapest_collapse(neighbor, to, &quadrics, &versions) {
+                    collapses.push(collapse);
+                }
+            }
+        }
+
+        triangles
+            .iter()
+            .zip(triangles_alive)
+            .filter(|(_, alive)| *alive)
+            .flat_map(|(triangle, _)| *triangle)
+            .collect()
+    }
+
+    // One index buffer per triangle budget, e.g. `&[2000, 500, 100]` for LOD 1 ~ 3.
+    // Each LOD is simplified from the previous one, so they're cheap to generate & visually consistent.
+    pub fn generate_lods(&self, positions: &[[f32; 3]], indices: &[u32], triangle_budgets: &[usize]) -> Vec<Vec<u32>> {
+        let mut lods: Vec<Vec<u32>> = Vec::with_capacity(triangle_budgets.len());
+        for &budget in triangle_budgets {
+            let previous = lods.last().map(Vec::as_slice).unwrap_or(indices);
+            let lod = self.simplify(positions, previous, budget);
+            lods.push(lod);
+        }
+        lods
+    }
+}
+
+impl Default for MeshSimplifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
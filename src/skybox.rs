@@ -0,0 +1,206 @@
+use std::rc::Rc;
+
+use super::hot_reload::load_shader;
+use super::texture::Cubemap;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct SkyboxUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    intensity: f32,
+    near_depth: f32, // NDC depth of the near plane, see `DepthMode`
+    _padding: [f32; 2]
+}
+
+// The cubemap of `Application::skybox()` drawn behind the scene, instead of clearing it to the sky color.
+// It's a full-screen shader casting a ray per pixel, drawn first so everything else (parallax layers included) goes over it:
+// the sky is at an infinite distance, it doesn't need the depth buffer.
+pub(crate) struct SkyboxPass {
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // the cubemap drawn & its bind group, None without a skybox
+    cubemap: Option<(Rc<Cubemap>, wgpu::BindGroup)>,
+    render_pipeline: Option<wgpu::RenderPipeline>, // None if the pipeline failed to build
+    intensity: f32,
+    near_depth: f32
+}
+
+impl SkyboxPass {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32, // of the scene's color target
+        depth_mode: super::app_config::DepthMode,
+        error_overlay: &mut super::error_overlay::ErrorOverlay
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Skybox Uniform Buffer"),
+            size: std::mem::size_of::<SkyboxUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Skybox BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let render_pipeline = super::error_overlay::catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Skybox Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("skybox.wgsl", include_str!("res/shaders/skybox.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Skybox Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the full-screen triangle is generated from the vertex index
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: None, // covers the whole frame
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let render_pipeline = render_pipeline
+            .map_err(|error| error_overlay.report("Skybox Render Pipeline", &error))
+            .ok();
+
+        Self {
+            uniform_buffer,
+            bind_group_layout,
+            cubemap: None,
+            render_pipeline,
+            intensity: 1.0,
+            near_depth: depth_mode.near_depth()
+        }
+    }
+
+    // the cubemap of this frame & its brightness (see `Environment::sky_intensity`), uploaded the first time it's drawn
+    pub(crate) fn set_skybox(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, cubemap: Option<Rc<Cubemap>>, intensity: f32) {
+        self.intensity = intensity;
+        let cubemap = match cubemap {
+            Some(cubemap) => cubemap,
+            None => {
+                self.cubemap = None;
+                return;
+            }
+        };
+        if self.cubemap.as_ref().is_some_and(|(current, _)| Rc::ptr_eq(current, &cubemap)) {
+            return;
+        }
+        let texture = cubemap.texture(device, queue);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Skybox Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler)
+                }
+            ]
+        });
+        self.cubemap = Some((cubemap, bind_group));
+    }
+
+    // upload the camera data of this frame
+    pub(crate) fn update(&self, queue: &wgpu::Queue, view_proj: nalgebra::Matrix4<f32>) {
+        let uniform = SkyboxUniform {
+            inv_view_proj: view_proj.try_inverse().unwrap_or_else(nalgebra::Matrix4::identity).into(),
+            intensity: self.intensity,
+            near_depth: self.near_depth,
+            _padding: [0.0; 2]
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // draw the sky over the whole frame, false (nothing done) without a skybox
+    pub(crate) fn render(&self, texture_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) -> bool {
+        let (render_pipeline, bind_group) = match (&self.render_pipeline, &self.cubemap) {
+            (Some(render_pipeline), Some((_, bind_group))) => (render_pipeline, bind_group),
+            _ => return false
+        };
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Skybox Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // every pixel is written
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: None
+        });
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        true
+    }
+}
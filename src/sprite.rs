@@ -0,0 +1,205 @@
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+use wgpu::util::DeviceExt; // for `create_buffer_init`
+
+use super::material::MaterialBinding;
+use super::mesh::Vertex;
+use super::pipeline_cache::ScenePipelines;
+use super::render_state::RenderState;
+
+// 2D sprite component of a `Scene` entity: a `size` quad in the entity's xy plane, placed by its `Transform` (at the origin without one).
+// Its texture is the albedo of the entity's material (`MaterialHandle`), lit & shaded like the meshes (see `LightOccluder` for 2D shadows).
+// Sprites sharing a material & at the same depth are batched into a single draw call,
+// the batches are drawn from the lowest world z (the farthest from a 2D camera looking along -z) to the highest.
+// tips: give sprites with transparent pixels a masked material (`AlphaMode::Mask`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    // in world units
+    pub size: [f32; 2],
+    // left, top, right & bottom texture coordinates shown, e.g. a frame of a sprite sheet or an atlas entry
+    pub uv_rect: [f32; 4],
+    // multiplied with the albedo
+    pub color: [f32; 4],
+    // point of the quad on the entity's origin, from [0.0, 0.0] (bottom left) to [1.0, 1.0] (top right)
+    pub anchor: [f32; 2],
+    pub flip_x: bool,
+    pub flip_y: bool
+}
+
+impl Sprite {
+    // the whole texture, centered & untinted
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            size: [width, height],
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            color: Vertex::WHITE,
+            anchor: [0.5, 0.5],
+            flip_x: false,
+            flip_y: false
+        }
+    }
+
+    pub fn with_uv_rect(mut self, left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        self.uv_rect = [left, top, right, bottom];
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    // e.g. [0.5, 0.0] for a character standing on its origin
+    pub fn with_anchor(mut self, x: f32, y: f32) -> Self {
+        self.anchor = [x, y];
+        self
+    }
+
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    // the 4 corners in world space, counter-clockwise from the bottom left
+    fn vertices(&self, transform: &Matrix4<f32>) -> [Vertex; 4] {
+        let [left, top, right, bottom] = self.uv_rect;
+        let (left, right) = if self.flip_x { (right, left) } else { (left, right) };
+        let (top, bottom) = if self.flip_y { (bottom, top) } else { (top, bottom) };
+        let x = [-self.anchor[0] * self.size[0], (1.0 - self.anchor[0]) * self.size[0]];
+        let y = [-self.anchor[1] * self.size[1], (1.0 - self.anchor[1]) * self.size[1]];
+        // meshes are transformed in the vertex shader, the sprites are drawn with an identity transform
+        let normal = transform
+            .transform_vector(&Vector3::z())
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+        let corner = |x: f32, y: f32, u: f32, v: f32| {
+            let position = transform.transform_point(&Point3::new(x, y, 0.0));
+            Vertex {
+                position: position.into(),
+                tex_coords: [u, v],
+                color: self.color,
+                normal: normal.into()
+            }
+        };
+        [
+            corner(x[0], y[0], left, bottom),
+            corner(x[1], y[0], right, bottom),
+            corner(x[1], y[1], right, top),
+            corner(x[0], y[1], left, top)
+        ]
+    }
+}
+
+// this frame's sprites in a few draw calls: their quads in a single vertex buffer, one index range per material
+pub(crate) struct SpriteBatch {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    // in sprites
+    capacity: usize,
+    // a single identity transform, the vertices are in world space
+    instance_buffer: wgpu::Buffer,
+    batches: Vec<(Rc<MaterialBinding>, RenderState, std::ops::Range<u32>)>
+}
+
+impl SpriteBatch {
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        let capacity = 64;
+        let (vertex_buffer, index_buffer) = Self::create_buffers(device, capacity);
+        let identity: [[f32; 4]; 4] = Matrix4::<f32>::identity().into();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Instance Buffer"),
+            contents: bytemuck::cast_slice(&[identity]),
+            usage: wgpu::BufferUsages::VERTEX
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            capacity,
+            instance_buffer,
+            batches: Vec::new()
+        }
+    }
+
+    fn create_buffers(device: &wgpu::Device, capacity: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Vertex Buffer"),
+            size: (std::mem::size_of::<Vertex>() * 4 * capacity) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Index Buffer"),
+            size: (std::mem::size_of::<u32>() * 6 * capacity) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        (vertex_buffer, index_buffer)
+    }
+
+    // sprites with their world transform, material & render state, in any order
+    pub(crate) fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut sprites: Vec<(Sprite, Matrix4<f32>, Rc<MaterialBinding>, RenderState)>
+    ) {
+        // back to front, then grouped by material at the same depth
+        sprites.sort_by(|(_, a_transform, a_binding, _), (_, b_transform, b_binding, _)| {
+            a_transform[(2, 3)]
+                .total_cmp(&b_transform[(2, 3)])
+                .then_with(|| Rc::as_ptr(a_binding).cmp(&Rc::as_ptr(b_binding)))
+        });
+        if sprites.len() > self.capacity {
+            self.capacity = sprites.len().next_power_of_two();
+            let (vertex_buffer, index_buffer) = Self::create_buffers(device, self.capacity);
+            self.vertex_buffer = vertex_buffer;
+            self.index_buffer = index_buffer;
+        }
+
+        let mut vertices = Vec::with_capacity(sprites.len() * 4);
+        let mut indices = Vec::with_capacity(sprites.len() * 6);
+        self.batches.clear();
+        for (sprite, transform, material_binding, render_state) in sprites {
+            let first = vertices.len() as u32;
+            vertices.extend_from_slice(&sprite.vertices(&transform));
+            // a mirroring transform turns the quad around, keep it facing the camera
+            let quad = if transform.fixed_slice::<3, 3>(0, 0).determinant() < 0.0 { [0, 2, 1, 0, 3, 2] } else { [0, 1, 2, 0, 2, 3] };
+            let start = indices.len() as u32;
+            indices.extend(quad.iter().map(|index| first + index));
+            let end = indices.len() as u32;
+            match self.batches.last_mut() {
+                Some((batch_binding, batch_state, range)) if Rc::ptr_eq(batch_binding, &material_binding) && *batch_state == render_state => {
+                    range.end = end;
+                },
+                _ => self.batches.push((material_binding, render_state, start..end))
+            }
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+    }
+
+    // the render states of this frame's batches, to compile their pipelines
+    pub(crate) fn render_states(&self) -> impl Iterator<Item = RenderState> + '_ {
+        self.batches.iter().map(|(_, render_state, _)| *render_state)
+    }
+
+    // in the scene render pass, the camera & environment bind groups are set already
+    pub(crate) fn render<'a>(&'a self, scene_pipelines: &'a ScenePipelines, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.batches.is_empty() {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for (material_binding, render_state, range) in &self.batches {
+            // a batch is skipped until its pipeline is compiled
+            if let Some(render_pipeline) = scene_pipelines.get(&(*render_state).into()) {
+                render_pass.set_pipeline(render_pipeline);
+                render_pass.set_bind_group(0, &material_binding.bind_group, &[]);
+                render_pass.draw_indexed(range.clone(), 0, 0..1);
+            }
+        }
+    }
+}
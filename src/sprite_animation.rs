@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use super::texture::UvTransform;
+
+// Grid of equally sized frames in a texture, numbered row by row from the top left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteSheet {
+    pub columns: u32,
+    pub rows: u32
+}
+
+impl SpriteSheet {
+    pub fn new(columns: u32, rows: u32) -> Self {
+        Self {
+            columns: columns.max(1),
+            rows: rows.max(1)
+        }
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    // UVs showing only `frame`, e.g. for `Material::set_uv_transform()`
+    pub fn frame_uv_transform(&self, frame: u32) -> UvTransform {
+        let frame = frame % self.frame_count();
+        let scale = [1.0 / self.columns as f32, 1.0 / self.rows as f32];
+        UvTransform {
+            offset: [(frame % self.columns) as f32 * scale[0], (frame / self.columns) as f32 * scale[1]],
+            scale,
+            ..UvTransform::new()
+        }
+    }
+}
+
+// Frames of a sprite sheet played in order, `fps` frames per second.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteClip {
+    frames: Vec<u32>,
+    fps: f32,
+    looping: bool
+}
+
+impl SpriteClip {
+    // looping, e.g. `SpriteClip::new(8..16, 12.0)` for the second row of an 8 columns sheet
+    pub fn new<I: IntoIterator<Item = u32>>(frames: I, fps: f32) -> Self {
+        Self {
+            frames: frames.into_iter().collect(),
+            fps: fps.max(f32::EPSILON),
+            looping: true
+        }
+    }
+
+    // a clip which doesn't loop holds its last frame once it's over, see `SpriteCondition::Finished`
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn frames(&self) -> &[u32] {
+        &self.frames
+    }
+
+    // in seconds
+    pub fn duration(&self) -> f32 {
+        self.frames.len() as f32 / self.fps
+    }
+
+    // frame shown `time` seconds after the clip started
+    fn frame_at(&self, time: f32) -> u32 {
+        if self.frames.is_empty() {
+            return 0;
+        }
+        let index = (time * self.fps) as usize;
+        let index = if self.looping {
+            index % self.frames.len()
+        } else {
+            index.min(self.frames.len() - 1)
+        };
+        self.frames[index]
+    }
+
+    fn is_finished(&self, time: f32) -> bool {
+        !self.looping && time >= self.duration()
+    }
+}
+
+// Condition of a `SpriteTransition`, on the parameters of the animator (set from the input, physics...).
+// tips: parameters which were never set are false & 0.0.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpriteCondition {
+    // a bool parameter, e.g. "grounded"
+    IsTrue(String),
+    IsFalse(String),
+    // a float parameter compared with a threshold, e.g. "speed" or "vertical_speed"
+    Above(String, f32),
+    Below(String, f32),
+    // the clip of the current state played to its end (never for looping clips)
+    Finished
+}
+
+// Switch from the `from` state (or any state) to the `to` state once all its conditions hold.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteTransition {
+    from: Option<String>,
+    to: String,
+    conditions: Vec<SpriteCondition>
+}
+
+impl SpriteTransition {
+    pub fn new(from: &str, to: &str) -> Self {
+        Self {
+            from: Some(from.to_string()),
+            to: to.to_string(),
+            conditions: Vec::new()
+        }
+    }
+
+    // from every state but `to`, e.g. jumping whatever the character was doing
+    pub fn from_any(to: &str) -> Self {
+        Self {
+            from: None,
+            ..Self::new("", to)
+        }
+    }
+
+    // tips: a transition without conditions is taken right away.
+    pub fn when(mut self, condition: SpriteCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+}
+
+// Animation state machine of a 2D character: named states (idle, run, jump...) playing sprite sheet clips,
+// switching between them with the first transition whose conditions hold, checked in order every update.
+// tips: the animator drives the UVs of a material (see `Material::set_uv_transform()`), so each animated sprite needs its own material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteAnimator {
+    sheet: SpriteSheet,
+    states: Vec<(String, SpriteClip)>,
+    transitions: Vec<SpriteTransition>,
+    bools: HashMap<String, bool>,
+    floats: HashMap<String, f32>,
+    current: usize,
+    // since the current state started, in seconds
+    time: f32
+}
+
+impl SpriteAnimator {
+    pub fn new(sheet: SpriteSheet) -> Self {
+        Self {
+            sheet,
+            states: Vec::new(),
+            transitions: Vec::new(),
+            bools: HashMap::new(),
+            floats: HashMap::new(),
+            current: 0,
+            time: 0.0
+        }
+    }
+
+    // the first state is the initial one, a state with the same name is replaced
+    pub fn with_state(mut self, name: &str, clip: SpriteClip) -> Self {
+        match self.state_index(name) {
+            Some(index) => self.states[index].1 = clip,
+            None => self.states.push((name.to_string(), clip))
+        }
+        self
+    }
+
+    // tips: transitions between unknown states are never taken.
+    pub fn with_transition(mut self, transition: SpriteTransition) -> Self {
+        self.transitions.push(transition);
+        self
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bools.insert(name.to_string(), value);
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.floats.insert(name.to_string(), value);
+    }
+
+    // switch to `state` right away & restart its clip, false if there's no such state
+    pub fn play(&mut self, state: &str) -> bool {
+        match self.state_index(state) {
+            Some(index) => {
+                self.current = index;
+                self.time = 0.0;
+                true
+            }
+            None => false
+        }
+    }
+
+    // None without states
+    pub fn state(&self) -> Option<&str> {
+        self.states.get(self.current).map(|(name, _)| name.as_str())
+    }
+
+    pub fn sheet(&self) -> SpriteSheet {
+        self.sheet
+    }
+
+    // frame of the sprite sheet shown now
+    pub fn frame(&self) -> u32 {
+        self.states
+            .get(self.current)
+            .map_or(0, |(_, clip)| clip.frame_at(self.time))
+    }
+
+    // UVs of the current frame, for the sprite's material
+    pub fn uv_transform(&self) -> UvTransform {
+        self.sheet.frame_uv_transform(self.frame())
+    }
+
+    // advance the current clip by `delta` seconds (e.g. `Time::delta()`), then take at most one transition
+    pub fn update(&mut self, delta: f32) {
+        if self.states.is_empty() {
+            return;
+        }
+        self.time += delta;
+
+        let next = self.transitions
+            .iter()
+            .filter(|transition| match &transition.from {
+                Some(from) => *from == self.states[self.current].0,
+                None => transition.to != self.states[self.current].0
+            })
+            .find(|transition| transition.conditions.iter().all(|condition| self.holds(condition)))
+            .and_then(|transition| self.state_index(&transition.to));
+        if let Some(next) = next {
+            self.current = next;
+            self.time = 0.0;
+        }
+    }
+
+    fn holds(&self, condition: &SpriteCondition) -> bool {
+        let boolean = |name: &String| self.bools.get(name).copied().unwrap_or(false);
+        let float = |name: &String| self.floats.get(name).copied().unwrap_or(0.0);
+        match condition {
+            SpriteCondition::IsTrue(name) => boolean(name),
+            SpriteCondition::IsFalse(name) => !boolean(name),
+            SpriteCondition::Above(name, threshold) => float(name) > *threshold,
+            SpriteCondition::Below(name, threshold) => float(name) < *threshold,
+            SpriteCondition::Finished => self.states[self.current].1.is_finished(self.time)
+        }
+    }
+
+    fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|(state, _)| state == name)
+    }
+}
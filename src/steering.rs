@@ -0,0 +1,426 @@
+use std::collections::hash_map::DefaultHasher;
+use std::f32::consts::TAU;
+use std::hash::{Hash, Hasher};
+
+use legion::systems::{Runnable, SystemBuilder};
+use legion::world::SubWorld;
+use legion::{Entity, IntoQuery};
+use nalgebra::Vector3;
+
+use super::time::Time;
+use super::transform::Transform;
+
+// Anything moved by steering behaviors: NPCs, crowds, flocks of birds...
+// Behaviors return a steering force, which `apply()` turns into a new velocity & position.
+// As a component, it's moved by the `steering_system()` with the `Wander` & `Flocking` of its entity.
+// ref: https://www.red3d.com/cwr/steer/gdc99/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SteeringAgent {
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub max_speed: f32,
+    // max steering force, the lower the wider the turns
+    pub max_force: f32,
+    // force added by the application for the next step of the `steering_system()` (e.g. `seek()`), cleared by it
+    pub steering: Vector3<f32>
+}
+
+fn truncate(vector: Vector3<f32>, max_length: f32) -> Vector3<f32> {
+    let length = vector.magnitude();
+    if length > max_length && length > 0.0 {
+        vector * (max_length / length)
+    } else {
+        vector
+    }
+}
+
+impl SteeringAgent {
+    pub fn new(position: Vector3<f32>, max_speed: f32, max_force: f32) -> Self {
+        Self {
+            position,
+            velocity: Vector3::zeros(),
+            max_speed,
+            max_force,
+            steering: Vector3::zeros()
+        }
+    }
+
+    // Integrate a steering force (e.g. the weighted sum of several behaviors) over `dt` seconds.
+    pub fn apply(&mut self, steering: Vector3<f32>, dt: f32) {
+        let steering = truncate(steering, self.max_force);
+        self.velocity = truncate(self.velocity + steering * dt, self.max_speed);
+        self.position += self.velocity * dt;
+    }
+
+    // steer towards the velocity `desired`
+    fn steer_towards(&self, desired: Vector3<f32>) -> Vector3<f32> {
+        truncate(desired - self.velocity, self.max_force)
+    }
+
+    // head to `target` at full speed.
+    pub fn seek(&self, target: Vector3<f32>) -> Vector3<f32> {
+        match (target - self.position).try_normalize(f32::EPSILON) {
+            Some(direction) => self.steer_towards(direction * self.max_speed),
+            None => Vector3::zeros()
+        }
+    }
+
+    // run away from `threat`, as long as it's closer than `panic_distance`.
+    pub fn flee(&self, threat: Vector3<f32>, panic_distance: f32) -> Vector3<f32> {
+        let offset = self.position - threat;
+        if offset.magnitude() > panic_distance {
+            return Vector3::zeros();
+        }
+        match offset.try_normalize(f32::EPSILON) {
+            Some(direction) => self.steer_towards(direction * self.max_speed),
+            None => Vector3::zeros()
+        }
+    }
+
+    // like `seek()`, but slow down within `slowing_radius` to stop at `target`.
+    pub fn arrive(&self, target: Vector3<f32>, slowing_radius: f32) -> Vector3<f32> {
+        let offset = target - self.position;
+        let distance = offset.magnitude();
+        if distance <= f32::EPSILON {
+            return self.steer_towards(Vector3::zeros());
+        }
+        let speed = if distance < slowing_radius {
+            self.max_speed * distance / slowing_radius
+        } else {
+            self.max_speed
+        };
+        self.steer_towards(offset / distance * speed)
+    }
+
+    // Steer away from the spherical obstacles (center, radius) in the way of the next `look_ahead` seconds.
+    pub fn avoid_obstacles(&self, obstacles: &[(Vector3<f32>, f32)], look_ahead: f32) -> Vector3<f32> {
+        let speed = self.velocity.magnitude();
+        let direction = match self.velocity.try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => return Vector3::zeros()
+        };
+        let reach = speed * look_ahead;
+
+        // the closest obstacle crossing the path
+        let mut closest: Option<(f32, Vector3<f32>)> = None;
+        for &(center, radius) in obstacles {
+            let offset = center - self.position;
+            let along = offset.dot(&direction);
+            if along < 0.0 || along > reach + radius {
+                continue;
+            }
+            let lateral = offset - direction * along;
+            let is_closer = match closest {
+                Some((distance, _)) => along < distance,
+                None => true
+            };
+            if lateral.magnitude() < radius && is_closer {
+                closest = Some((along, lateral));
+            }
+        }
+
+        match closest {
+            Some((_, lateral)) => {
+                // turn away from the obstacle center, pick any side when heading straight into it
+                let away = match (-lateral).try_normalize(f32::EPSILON) {
+                    Some(away) => away,
+                    None => direction.cross(&Vector3::y()).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::x)
+                };
+                away * self.max_force
+            },
+            None => Vector3::zeros()
+        }
+    }
+
+    // Keep away from the neighbors closer than `radius`, the closer the stronger.
+    // `neighbors` are the agents of the flock with their entity, the agent itself (`entity`) is skipped:
+    // the ones at the same position are pushed apart along a direction picked from their entities.
+    pub fn separation(&self, entity: Entity, neighbors: &[(Entity, SteeringAgent)], radius: f32) -> Vector3<f32> {
+        let mut push = Vector3::zeros();
+        for (neighbor_entity, neighbor) in neighbors {
+            let offset = self.position - neighbor.position;
+            let distance = offset.magnitude();
+            if *neighbor_entity == entity || distance >= radius {
+                continue;
+            }
+            let direction = match offset.try_normalize(f32::EPSILON) {
+                Some(direction) => direction,
+                None => coincident_direction(entity, *neighbor_entity)
+            };
+            // the coincident ones push the hardest
+            let distance = distance.max(radius * 1e-3);
+            push += direction / (distance * distance);
+        }
+        match push.try_normalize(f32::EPSILON) {
+            Some(direction) => self.steer_towards(direction * self.max_speed),
+            None => Vector3::zeros()
+        }
+    }
+
+    // match the average heading of the neighbors within `radius`, see `separation()` for `entity` & `neighbors`.
+    pub fn alignment(&self, entity: Entity, neighbors: &[(Entity, SteeringAgent)], radius: f32) -> Vector3<f32> {
+        let nearby = self.nearby(entity, neighbors, radius);
+        if nearby.is_empty() {
+            return Vector3::zeros();
+        }
+        let average = nearby.iter().map(|neighbor| neighbor.velocity).sum::<Vector3<f32>>() / nearby.len() as f32;
+        match average.try_normalize(f32::EPSILON) {
+            Some(direction) => self.steer_towards(direction * self.max_speed),
+            None => Vector3::zeros()
+        }
+    }
+
+    // move to the center of the neighbors within `radius`, see `separation()` for `entity` & `neighbors`.
+    pub fn cohesion(&self, entity: Entity, neighbors: &[(Entity, SteeringAgent)], radius: f32) -> Vector3<f32> {
+        let nearby = self.nearby(entity, neighbors, radius);
+        if nearby.is_empty() {
+            return Vector3::zeros();
+        }
+        let center = nearby.iter().map(|neighbor| neighbor.position).sum::<Vector3<f32>>() / nearby.len() as f32;
+        self.seek(center)
+    }
+
+    // the other agents within `radius`, excluding the agent itself (`entity`)
+    fn nearby<'a>(&self, entity: Entity, neighbors: &'a [(Entity, SteeringAgent)], radius: f32) -> Vec<&'a SteeringAgent> {
+        neighbors
+            .iter()
+            .filter(|(neighbor_entity, neighbor)| {
+                *neighbor_entity != entity && (neighbor.position - self.position).magnitude() < radius
+            })
+            .map(|(_, neighbor)| neighbor)
+            .collect()
+    }
+}
+
+// Direction pushing `entity` away from `other` at the same position, the opposite one pushes `other`.
+// It's in the ground (xz) plane, at an angle picked from the hashes of both entities.
+fn coincident_direction(entity: Entity, other: Entity) -> Vector3<f32> {
+    let hash = |entity: Entity| {
+        let mut hasher = DefaultHasher::new();
+        entity.hash(&mut hasher);
+        hasher.finish()
+    };
+    let (entity_hash, other_hash) = (hash(entity), hash(other));
+    let angle = (entity_hash ^ other_hash) as f32 / u64::MAX as f32 * TAU;
+    let direction = Vector3::new(angle.cos(), 0.0, angle.sin());
+    if entity_hash < other_hash {
+        direction
+    } else {
+        -direction
+    }
+}
+
+// Random meandering on the ground (xz) plane: steer towards a point moving randomly on a circle ahead of the agent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wander {
+    // distance of the circle ahead of the agent
+    pub distance: f32,
+    pub radius: f32,
+    // max angle change per second, in radians
+    pub jitter: f32,
+    angle: f32,
+    seed: u32
+}
+
+impl Wander {
+    pub fn new(distance: f32, radius: f32, jitter: f32, seed: u32) -> Self {
+        Self {
+            distance,
+            radius,
+            jitter,
+            angle: 0.0,
+            seed: seed.max(1) // xorshift never leaves 0
+        }
+    }
+
+    // uniform random number in -1.0 ~ 1.0 (xorshift32)
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    pub fn steer(&mut self, agent: &SteeringAgent, dt: f32) -> Vector3<f32> {
+        self.angle += self.random() * self.jitter * dt;
+
+        let heading = Vector3::new(agent.velocity.x, 0.0, agent.velocity.z)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+        let target = agent.position
+            + heading * self.distance
+            + Vector3::new(self.angle.cos(), 0.0, self.angle.sin()) * self.radius;
+        agent.seek(target)
+    }
+}
+
+// Boids: separation, alignment & cohesion weighted together.
+// ref: https://www.red3d.com/cwr/boids/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flocking {
+    pub separation_weight: f32,
+    pub alignment_weight: f32,
+    pub cohesion_weight: f32,
+    // agents closer than this are part of the local flock
+    pub neighbor_radius: f32,
+    // agents closer than this push each other away
+    pub separation_radius: f32
+}
+
+impl Flocking {
+    pub fn new() -> Self {
+        Self {
+            separation_weight: 1.5,
+            alignment_weight: 1.0,
+            cohesion_weight: 1.0,
+            neighbor_radius: 5.0,
+            separation_radius: 1.5
+        }
+    }
+
+    // flocking force of `agent` (of `entity`) among `flock`, which may contain the agent itself.
+    pub fn steer(&self, entity: Entity, agent: &SteeringAgent, flock: &[(Entity, SteeringAgent)]) -> Vector3<f32> {
+        agent.separation(entity, flock, self.separation_radius) * self.separation_weight
+            + agent.alignment(entity, flock, self.neighbor_radius) * self.alignment_weight
+            + agent.cohesion(entity, flock, self.neighbor_radius) * self.cohesion_weight
+    }
+
+    // steer the whole flock for `dt` seconds, plus an extra force per agent (e.g. seeking a goal).
+    pub fn update(&self, flock: &mut [(Entity, SteeringAgent)], dt: f32, extra: impl Fn(&SteeringAgent) -> Vector3<f32>) {
+        // every agent reacts to the state of the flock at the beginning of the step
+        let snapshot = flock.to_vec();
+        for (entity, agent) in flock.iter_mut() {
+            let steering = self.steer(*entity, agent, &snapshot) + extra(agent);
+            agent.apply(steering, dt);
+        }
+    }
+}
+
+impl Default for Flocking {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// System moving every entity with a `SteeringAgent` by the frame's delta time, run by the scene after its schedule:
+// the force of its `Wander`, its `Flocking` (among the other flocking agents) & its `steering` is applied,
+// then its `Transform` (if any) is moved to the agent's position.
+// tips: the agent starts from the translation of its transform, so it's relative to the `Parent` if any.
+pub fn steering_system() -> impl Runnable {
+    SystemBuilder::new("steering")
+        .read_resource::<Time>()
+        .with_query(<(Entity, &mut SteeringAgent, Option<&mut Wander>, Option<&Flocking>, Option<&mut Transform>)>::query())
+        .build(|_, world, time, agents| {
+            steer_agents(world, time.delta(), agents);
+        })
+}
+
+type AgentQuery = legion::Query<(
+    Entity,
+    &'static mut SteeringAgent,
+    Option<&'static mut Wander>,
+    Option<&'static Flocking>,
+    Option<&'static mut Transform>
+)>;
+
+fn steer_agents(world: &mut SubWorld, dt: f32, agents: &mut AgentQuery) {
+    // moved by the application or the other systems since the last step
+    for (_, agent, _, _, transform) in agents.iter_mut(world) {
+        if let Some(transform) = transform {
+            agent.position = transform.translation;
+        }
+    }
+    // every agent reacts to the state of the flock at the beginning of the step
+    let flock = agents
+        .iter_mut(world)
+        .filter(|(_, _, _, flocking, _)| flocking.is_some())
+        .map(|(entity, agent, _, _, _)| (*entity, *agent))
+        .collect::<Vec<_>>();
+    for (entity, agent, wander, flocking, transform) in agents.iter_mut(world) {
+        let mut steering = agent.steering;
+        if let Some(wander) = wander {
+            steering += wander.steer(agent, dt);
+        }
+        if let Some(flocking) = flocking {
+            steering += flocking.steer(*entity, agent, &flock);
+        }
+        agent.apply(steering, dt);
+        agent.steering = Vector3::zeros();
+        if let Some(transform) = transform {
+            transform.translation = agent.position;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::World;
+
+    use super::*;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::default();
+        (0..count).map(|_| world.push(())).collect()
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn separation_pushes_away_from_close_neighbors() {
+        let entities = entities(4);
+        let agent = SteeringAgent::new(Vector3::zeros(), 2.0, 100.0);
+        let flock = [
+            (entities[0], agent),
+            (entities[1], SteeringAgent::new(Vector3::new(1.0, 0.0, 0.0), 2.0, 100.0)),
+            (entities[2], SteeringAgent::new(Vector3::new(0.0, 0.0, 2.0), 2.0, 100.0)),
+            // out of the radius
+            (entities[3], SteeringAgent::new(Vector3::new(-4.0, 0.0, 0.0), 2.0, 100.0))
+        ];
+
+        // inverse square of the distance: 1 from the first neighbor, 1/4 from the second
+        let expected = Vector3::new(-1.0, 0.0, -0.25).normalize() * 2.0;
+        assert_close(agent.separation(entities[0], &flock, 3.0), expected);
+        // limited by the max force
+        let slow = SteeringAgent { max_force: 0.5, ..agent };
+        assert_close(slow.separation(entities[0], &flock, 3.0), expected.normalize() * 0.5);
+        // alone
+        assert_eq!(agent.separation(entities[0], &flock[..1], 3.0), Vector3::zeros());
+    }
+
+    #[test]
+    fn separation_splits_coincident_agents() {
+        let entities = entities(2);
+        let agent = SteeringAgent::new(Vector3::new(1.0, 2.0, 3.0), 1.0, 100.0);
+        let flock = [(entities[0], agent), (entities[1], agent)];
+
+        let first = agent.separation(entities[0], &flock, 1.0);
+        let second = agent.separation(entities[1], &flock, 1.0);
+        assert!((first.magnitude() - 1.0).abs() < 1e-5);
+        assert_eq!(first.y, 0.0);
+        assert_close(second, -first);
+        // the same every frame
+        assert_eq!(agent.separation(entities[0], &flock, 1.0), first);
+    }
+
+    #[test]
+    fn alignment_matches_neighbor_heading() {
+        let entities = entities(4);
+        let mut agent = SteeringAgent::new(Vector3::zeros(), 2.0, 100.0);
+        agent.velocity = Vector3::new(0.0, 1.0, 0.0);
+        let neighbor = |position: Vector3<f32>, velocity: Vector3<f32>| SteeringAgent { velocity, ..SteeringAgent::new(position, 2.0, 100.0) };
+        let flock = [
+            (entities[0], agent),
+            (entities[1], neighbor(Vector3::new(1.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0))),
+            (entities[2], neighbor(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0))),
+            // out of the radius
+            (entities[3], neighbor(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -10.0)))
+        ];
+
+        // the average heading at full speed, minus the current velocity
+        let expected = Vector3::new(1.0, 0.0, 1.0).normalize() * 2.0 - agent.velocity;
+        assert_close(agent.alignment(entities[0], &flock, 2.0), expected);
+        assert_eq!(agent.alignment(entities[0], &flock[..1], 2.0), Vector3::zeros());
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+// Hierarchical gameplay tag: lower-case names separated by dots, from the most general to the most specific,
+// e.g. "enemy.flying" is a kind of "enemy".
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameplayTag(String);
+
+impl GameplayTag {
+    // names are ascii letters, digits & '_', they're lower-cased
+    pub fn new(tag: &str) -> Result<Self> {
+        let tag = tag.trim().to_ascii_lowercase();
+        if tag.is_empty() {
+            bail!("empty gameplay tag");
+        }
+        for name in tag.split('.') {
+            if name.is_empty() {
+                bail!("gameplay tag `{}`: empty name between dots", tag);
+            }
+            if let Some(c) = name.chars().find(|c| !c.is_ascii_alphanumeric() && *c != '_') {
+                bail!("gameplay tag `{}`: unexpected character `{}`", tag, c);
+            }
+        }
+        Ok(Self(tag))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // "enemy" for "enemy.flying", None for a root tag
+    pub fn parent(&self) -> Option<GameplayTag> {
+        self.0.rsplit_once('.').map(|(parent, _)| GameplayTag(parent.to_string()))
+    }
+
+    // from the root, e.g. ["enemy", "flying"]
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.split('.')
+    }
+
+    // `tag` itself or one of its descendants, e.g. "enemy.flying" matches "enemy" but not "enemy.fly" nor "enemy.flying.bat"
+    pub fn matches(&self, tag: &str) -> bool {
+        match self.0.strip_prefix(tag) {
+            Some(rest) => rest.is_empty() || rest.starts_with('.'),
+            None => false
+        }
+    }
+}
+
+impl fmt::Display for GameplayTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Gameplay tags component of a `Scene` entity, classifying it for AI targeting, triggers & scripts without a component type per tag.
+// Queries are hierarchical: an entity tagged "enemy.flying" has "enemy" (see `Scene::tagged()`).
+// tips: serialized as the comma separated list of its tags (`to_string()` & `Tags::parse()`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tags {
+    // sorted & without duplicates
+    tags: Vec<GameplayTag>
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // e.g. `Tags::new().with("enemy.flying")?.with("boss")?`
+    pub fn with(mut self, tag: &str) -> Result<Self> {
+        self.insert(GameplayTag::new(tag)?);
+        Ok(self)
+    }
+
+    // parse "enemy.flying, boss", the tags are separated by commas or whitespaces
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut tags = Self::new();
+        for tag in source.split(|c: char| c == ',' || c.is_whitespace()).filter(|tag| !tag.is_empty()) {
+            tags.insert(GameplayTag::new(tag)?);
+        }
+        Ok(tags)
+    }
+
+    // false if the tag was there already
+    pub fn insert(&mut self, tag: GameplayTag) -> bool {
+        match self.tags.binary_search(&tag) {
+            Ok(_) => false,
+            Err(index) => {
+                self.tags.insert(index, tag);
+                true
+            }
+        }
+    }
+
+    // remove `tag` exactly, its descendants stay
+    pub fn remove(&mut self, tag: &str) -> bool {
+        let length = self.tags.len();
+        self.tags.retain(|own| own.as_str() != tag);
+        self.tags.len() != length
+    }
+
+    // `tag` or one of its descendants, e.g. "enemy" for an entity tagged "enemy.flying"
+    pub fn has(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.matches(tag))
+    }
+
+    pub fn has_exact(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.as_str() == tag)
+    }
+
+    pub fn has_any(&self, tags: &[&str]) -> bool {
+        tags.iter().any(|tag| self.has(tag))
+    }
+
+    pub fn has_all(&self, tags: &[&str]) -> bool {
+        tags.iter().all(|tag| self.has(tag))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GameplayTag> {
+        self.tags.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+}
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, tag) in self.tags.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(tag.as_str())?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,13 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
 use image::GenericImageView;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use nalgebra::Vector3;
+
+use super::app_config::DepthMode;
+use super::paint::PixelRegion;
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -18,13 +26,47 @@ impl Texture {
         Self::from_image(device, queue, &img, label)
     }
 
+    // Load an image file (PNG, JPEG...) at runtime, e.g. from `AssetRoot::resolve()`.
+    pub fn from_path<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: P,
+        label: Option<&str>
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read texture {}", path.display()))?;
+        let img = image::load_from_memory(&bytes).with_context(|| format!("failed to decode texture {}", path.display()))?;
+        Self::from_image(device, queue, &img, label)
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>
     ) -> Result<Self> {
-        let rgba = img.as_rgba8().unwrap(); // convert image into Vec of RGBA bytes.
+        // Most images are stored using sRGB so we need to set that here.
+        Self::from_image_with_format(device, queue, img, wgpu::TextureFormat::Rgba8UnormSrgb, label)
+    }
+
+    // Image holding data rather than colors (normal maps...), sampled as is.
+    pub fn from_linear_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>
+    ) -> Result<Self> {
+        Self::from_image_with_format(device, queue, img, wgpu::TextureFormat::Rgba8Unorm, label)
+    }
+
+    fn from_image_with_format(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        format: wgpu::TextureFormat,
+        label: Option<&str>
+    ) -> Result<Self> {
+        let rgba = img.to_rgba8(); // convert image into Vec of RGBA bytes, whatever its color type (RGB JPEG...).
         let dimensions = img.dimensions(); // get width and height of this image.
 
         let texutre_size = wgpu::Extent3d {
@@ -41,8 +83,7 @@ impl Texture {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                // Most images are stored using sRGB so we need to set that here.
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                format,
                 // TEXTURE_BINDING : tells wgpu that we want to use this texture in shaders
                 // COPY_DST : means that we want to copy data to this texture
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
@@ -59,7 +100,7 @@ impl Texture {
                 aspect: wgpu::TextureAspect::All,
             },
             // the actual pixel data
-            rgba, 
+            &rgba, 
             // the layout of the texture
             wgpu::ImageDataLayout {
                 offset: 0,
@@ -77,9 +118,10 @@ impl Texture {
             &wgpu::SamplerDescriptor {
                 // address_mode_* :
                 // determine what to do if the sampler gets a texture coordinate that's outside the texture itself
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                // `Repeat`: tile the texture, so materials can scale their UVs (see `UvTransform`)
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
                 // describe what to do when a fragment covers multiple pixels or there are multiple fragments for a single pixel,
                 // this often comes into play when viewing a surface from up close, or from far away.
                 // `Linear`: Attempt to blend the in-between fragments so that they seem to flow together.
@@ -102,10 +144,42 @@ impl Texture {
         })
     }
 
+    // Overwrite a region of the texture with RGBA8 pixels, row by row, e.g. the dirty region of a `PaintCanvas`.
+    // The region must be inside the texture.
+    pub fn write_region(&self, queue: &wgpu::Queue, region: PixelRegion, rgba: &[u8]) -> Result<()> {
+        if rgba.len() != (region.width * region.height * 4) as usize {
+            bail!("expected {} bytes for a {}x{} region, got {}", region.width * region.height * 4, region.width, region.height, rgba.len());
+        }
+        if region.width == 0 || region.height == 0 {
+            return Ok(());
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: region.x, y: region.y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * region.width),
+                rows_per_image: std::num::NonZeroU32::new(region.height)
+            },
+            wgpu::Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1
+            }
+        );
+        Ok(())
+    }
+
     // Depth Format for creating the depth stage of the render_pipeline and the depth texture itself.
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, depth_mode: DepthMode, label: &str) -> Self {
         // create Texture
         let size = wgpu::Extent3d {
             // depth texture needs to be the same size as our screen
@@ -138,9 +212,9 @@ impl Texture {
                 mag_filter: wgpu::FilterMode::Linear,
                 min_filter: wgpu::FilterMode::Linear,
                 mipmap_filter: wgpu::FilterMode::Nearest,
-                // If we do decide to render our depth texture, we need to use CompareFunction::LessEqual
+                // If we do decide to render our depth texture, we need to use CompareFunction::LessEqual (GreaterEqual with reversed-Z)
                 // This is due to how the samplerShadow and sampler2DShadow() interacts with the texture() function in GLSL.
-                compare: Some(wgpu::CompareFunction::LessEqual), 
+                compare: Some(depth_mode.far_compare()), 
                 lod_min_clamp: -100.0,
                 lod_max_clamp: 100.0,
                 ..Default::default()
@@ -149,4 +223,366 @@ impl Texture {
 
         return Self { texture, view, sampler }
     }
-}
\ No newline at end of file
+
+    // Color texture to render into & sample from afterwards, `usage` adds to RENDER_ATTACHMENT | TEXTURE_BINDING.
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        label: &str
+    ) -> Self {
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    // textures can't be empty, e.g. while the window is minimized
+                    width: width.max(1),
+                    height: height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | usage,
+            }
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // effects read neighbor texels, don't wrap around at the edges
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Self { texture, view, sampler }
+    }
+}
+
+// Six square faces of HDR colors (linear, stored as half floats) around a point: a sky, or a captured environment for reflections & image based lighting.
+// The faces are uploaded the first time the cubemap is drawn, see `Application::skybox()`.
+// tips: faces are in the order +x (right), -x (left), +y (top), -y (bottom), -z (front, where the default camera looks), +z (back).
+pub struct Cubemap {
+    size: u32,
+    faces: Vec<Vec<[u16; 4]>>, // row by row, from the top left
+    texture: RefCell<Option<Rc<Texture>>>
+}
+
+impl Cubemap {
+    // Six square sRGB images of the same size, e.g. a skybox pack (right, left, top, bottom, front, back).
+    pub fn from_faces(faces: [&image::RgbaImage; 6]) -> Result<Self> {
+        let size = faces[0].width();
+        if let Some(face) = faces.iter().find(|face| face.dimensions() != (size, size)) {
+            bail!("cubemap faces must be square & of the same size, expected {}x{}, got {}x{}", size, size, face.width(), face.height());
+        }
+        if size == 0 {
+            bail!("cubemap faces can't be empty");
+        }
+        let faces = faces
+            .iter()
+            .map(|face| {
+                face.pixels()
+                    .map(|pixel| {
+                        let [r, g, b, a] = pixel.0.map(|channel| channel as f32 / 255.0);
+                        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a].map(f16_from_f32)
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(Self::new(size, faces))
+    }
+
+    // Load the six face images, see `from_faces()`.
+    pub fn from_face_paths<P: AsRef<Path>>(paths: [P; 6]) -> Result<Self> {
+        let mut faces = Vec::with_capacity(6);
+        for path in &paths {
+            let path = path.as_ref();
+            let face = image::open(path).with_context(|| format!("failed to decode cubemap face {}", path.display()))?;
+            faces.push(face.to_rgba8());
+        }
+        Self::from_faces([&faces[0], &faces[1], &faces[2], &faces[3], &faces[4], &faces[5]])
+    }
+
+    // Project an equirectangular (latitude / longitude) panorama on `face_size` x `face_size` faces.
+    // `width` x `height` linear colors, row by row: the middle of the image is in front, the top row is straight up.
+    pub fn from_equirectangular(width: u32, height: u32, colors: &[[f32; 3]], face_size: u32) -> Result<Self> {
+        if width == 0 || height == 0 || colors.len() != (width * height) as usize {
+            bail!("expected {} colors for a {}x{} panorama, got {}", width * height, width, height, colors.len());
+        }
+        let face_size = face_size.max(1);
+        let sample = |u: f32, v: f32| {
+            // bilinear, wrapping around horizontally
+            let x = u * width as f32 - 0.5;
+            let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+            let (x0, y0) = (x.floor(), y.floor());
+            let (tx, ty) = (x - x0, y - y0);
+            let texel = |x: f32, y: f32| {
+                let x = (x as i64).rem_euclid(width as i64) as u32;
+                let y = (y as u32).min(height - 1);
+                Vector3::from(colors[(y * width + x) as usize])
+            };
+            let top = texel(x0, y0).lerp(&texel(x0 + 1.0, y0), tx);
+            let bottom = texel(x0, y0 + 1.0).lerp(&texel(x0 + 1.0, y0 + 1.0), tx);
+            top.lerp(&bottom, ty)
+        };
+
+        let faces = (0..6)
+            .map(|face| {
+                let mut texels = Vec::with_capacity((face_size * face_size) as usize);
+                for y in 0..face_size {
+                    for x in 0..face_size {
+                        let s = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                        let t = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                        let direction = face_direction(face, s, t).normalize();
+                        let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * std::f32::consts::PI);
+                        let v = 0.5 - direction.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI;
+                        let color = sample(u, v);
+                        texels.push([color.x, color.y, color.z, 1.0].map(f16_from_f32));
+                    }
+                }
+                texels
+            })
+            .collect();
+        Ok(Self::new(face_size, faces))
+    }
+
+    // Load an equirectangular panorama: Radiance HDR (.hdr) for a real sky brightness, or any sRGB image, see `from_equirectangular()`.
+    pub fn from_equirectangular_path<P: AsRef<Path>>(path: P, face_size: u32) -> Result<Self> {
+        let path = path.as_ref();
+        let is_hdr = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+        let (width, height, colors) = if is_hdr {
+            let file = std::fs::File::open(path).with_context(|| format!("failed to read panorama {}", path.display()))?;
+            let decoder = image::codecs::hdr::HdrDecoder::new(std::io::BufReader::new(file))
+                .with_context(|| format!("failed to decode panorama {}", path.display()))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder.read_image_hdr().with_context(|| format!("failed to decode panorama {}", path.display()))?;
+            (metadata.width, metadata.height, pixels.into_iter().map(|pixel| pixel.0).collect::<Vec<_>>())
+        } else {
+            let image = image::open(path).with_context(|| format!("failed to decode panorama {}", path.display()))?.to_rgb8();
+            let colors = image.pixels().map(|pixel| pixel.0.map(|channel| srgb_to_linear(channel as f32 / 255.0))).collect();
+            (image.width(), image.height(), colors)
+        };
+        Self::from_equirectangular(width, height, &colors, face_size)
+    }
+
+    fn new(size: u32, faces: Vec<Vec<[u16; 4]>>) -> Self {
+        Self {
+            size,
+            faces,
+            texture: RefCell::new(None)
+        }
+    }
+
+    // width & height of a face, in texels
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // the linear color seen in `direction` (world space), e.g. to integrate the sky lighting
+    pub fn sample(&self, direction: Vector3<f32>) -> [f32; 3] {
+        let (face, s, t) = match face_coords(direction) {
+            Some(coords) => coords,
+            None => return [0.0; 3]
+        };
+        let texel = |coord: f32| (((coord + 1.0) * 0.5 * self.size as f32) as u32).min(self.size - 1);
+        let color = self.faces[face][(texel(t) * self.size + texel(s)) as usize];
+        [f32_from_f16(color[0]), f32_from_f16(color[1]), f32_from_f16(color[2])]
+    }
+
+    // upload the faces the first time, then reuse the texture
+    pub(crate) fn texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Rc<Texture> {
+        self.texture
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(self.upload(device, queue)))
+            .clone()
+    }
+
+    fn upload(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+        let size = wgpu::Extent3d {
+            width: self.size,
+            height: self.size,
+            // one layer per face
+            depth_or_array_layers: 6
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Cubemap Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+        });
+        let texels = self.faces.iter().flatten().copied().collect::<Vec<_>>();
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            bytemuck::cast_slice(&texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(8 * self.size),
+                rows_per_image: std::num::NonZeroU32::new(self.size)
+            },
+            size
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Cubemap Texture View"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            // no seams between the faces
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Texture { texture, view, sampler }
+    }
+
+    // half floats, filterable & enough range for the sun
+    pub(crate) const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+}
+
+// World space direction of the texel at `s`, `t` (-1.0 ~ 1.0, from the top left) of a face.
+// tips: the GPU picks the faces in a left-handed space, the world's z axis is flipped (see `skybox.wgsl`).
+fn face_direction(face: usize, s: f32, t: f32) -> Vector3<f32> {
+    let cube = match face {
+        0 => Vector3::new(1.0, -t, -s),
+        1 => Vector3::new(-1.0, -t, s),
+        2 => Vector3::new(s, 1.0, t),
+        3 => Vector3::new(s, -1.0, -t),
+        4 => Vector3::new(s, -t, 1.0),
+        _ => Vector3::new(-s, -t, -1.0)
+    };
+    Vector3::new(cube.x, cube.y, -cube.z)
+}
+
+// the face a world space direction points at, with the `s`, `t` coordinates on it, see `face_direction()`
+fn face_coords(direction: Vector3<f32>) -> Option<(usize, f32, f32)> {
+    let cube = Vector3::new(direction.x, direction.y, -direction.z);
+    let axis = cube.iamax();
+    let major = cube[axis];
+    if major == 0.0 {
+        return None;
+    }
+    let (face, s, t) = match (axis, major > 0.0) {
+        (0, true) => (0, -cube.z, -cube.y),
+        (0, false) => (1, cube.z, -cube.y),
+        (1, true) => (2, cube.x, cube.z),
+        (1, false) => (3, cube.x, -cube.z),
+        (_, true) => (4, cube.x, -cube.y),
+        (_, false) => (5, -cube.x, -cube.y)
+    };
+    Some((face, s / major.abs(), t / major.abs()))
+}
+
+fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// IEEE half float bits, clamped to its range (65504) rather than infinite
+fn f16_from_f32(value: f32) -> u16 {
+    let value = if value.is_nan() { 0.0 } else { value.clamp(-65504.0, 65504.0) };
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        // too small for a normal half float
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = (mantissa | 0x80_0000) >> (1 - exponent);
+        return sign | ((mantissa + 0x1000) >> 13) as u16;
+    }
+    // rounded to the nearest, a carry goes into the exponent
+    sign | (((exponent as u32) << 10) + ((mantissa + 0x1000) >> 13)) as u16
+}
+
+fn f32_from_f16(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 => sign * f32::INFINITY,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15)
+    }
+}
+
+// Per-material transform of texture coordinates, applied in the vertex shader:
+// rotate around the texture center, then scale (tiling), then offset.
+// `scroll` keeps moving the offset (UV per second), e.g. for animated conveyor belts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvTransform {
+    pub offset: [f32; 2],
+    pub scale: [f32; 2],
+    pub rotation: f32, // radians
+    pub scroll: [f32; 2]
+}
+
+impl UvTransform {
+    pub fn new() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            scale: [1.0, 1.0],
+            rotation: 0.0,
+            scroll: [0.0, 0.0]
+        }
+    }
+
+    // repeat the texture `u` x `v` times
+    pub fn tiled(u: f32, v: f32) -> Self {
+        Self {
+            scale: [u, v],
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn to_uniform(self, time: f32) -> UvTransformUniform {
+        UvTransformUniform {
+            offset: self.offset,
+            scale: self.scale,
+            scroll: self.scroll,
+            rotation: self.rotation,
+            time
+        }
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// `UvTransform` layout in the shader uniform buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct UvTransformUniform {
+    offset: [f32; 2],
+    scale: [f32; 2],
+    scroll: [f32; 2],
+    rotation: f32,
+    time: f32 // seconds since start, drives `scroll`
+}
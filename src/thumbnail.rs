@@ -0,0 +1,453 @@
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use nalgebra::{Matrix4, Point3, Vector3};
+use wgpu::util::DeviceExt;
+
+use super::app_config::DepthMode;
+use super::assets::AssetRoot;
+use super::camera::{Camera, PerspectiveCamera, Projection};
+use super::environment::{Environment, Fog};
+use super::gpu::{
+    create_camera_bind_group_layout, create_environment_bind_group_layout, create_texture_bind_group_layout, CameraUniform, InstanceRaw
+};
+use super::light::{Light, LightsUniform, OccludersUniform};
+use super::material::{Material, MaterialBinding, ShaderMaterialBinding};
+use super::mesh::{Mesh, MeshBuffers, MeshDraw};
+use super::pipeline_cache::ScenePipelines;
+use super::scene::Scene;
+use super::texture::Texture;
+
+// What a thumbnail shows, framed to fit.
+pub enum ThumbnailSubject<'a> {
+    // with a plain white material
+    Mesh(&'a Rc<Mesh>),
+    // on a sphere
+    Material(&'a Rc<Material>),
+    // the meshes of the scene with their materials
+    // tips: sprites, parallax layers & the scene's own lights aren't drawn.
+    Scene(&'a Scene)
+}
+
+// GPU copy of a thumbnail's material: the engine's, or a custom one drawn with its shader
+enum ThumbnailMaterial {
+    Standard(MaterialBinding),
+    Shader(ShaderMaterialBinding)
+}
+
+impl ThumbnailMaterial {
+    fn bind_group(&self) -> &wgpu::BindGroup {
+        match self {
+            ThumbnailMaterial::Standard(binding) => &binding.bind_group,
+            ThumbnailMaterial::Shader(binding) => &binding.bind_group
+        }
+    }
+
+    // the material's own parameters
+    fn dynamic_offset(&self) -> Option<u32> {
+        match self {
+            ThumbnailMaterial::Standard(_) => None,
+            ThumbnailMaterial::Shader(binding) => binding.dynamic_offset(0)
+        }
+    }
+}
+
+// Headless renderer of standardized asset thumbnails, for the editor & external tools.
+// It owns its own GPU device (no window needed), frames the subject from a fixed three-quarter view
+// & lights it with a neutral rig: a key light (the sun), a fill & a rim light over a gray background.
+// tips: the meshes & materials are uploaded again for every thumbnail, the window's copies live on another device.
+pub struct ThumbnailRenderer {
+    device: Arc<wgpu::Device>,
+    queue: wgpu::Queue,
+    size: u32,
+    background: [f32; 3],
+    target: Texture,
+    depth_texture: Texture,
+    staging_buffer: wgpu::Buffer,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    environment_bind_group: wgpu::BindGroup,
+    scene_pipelines: ScenePipelines,
+    // drawn on meshes without a material
+    default_material: Material,
+    // drawn with materials
+    sphere: Rc<Mesh>
+}
+
+impl ThumbnailRenderer {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    // `size` x `size` pixels thumbnails, fails if there's no GPU adapter
+    pub fn new(size: u32) -> Result<Self> {
+        let size = size.max(1);
+
+        /* Device & Queue */
+        // any adapter works, nothing is presented
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: false
+        }))
+        .context("no GPU adapter to render thumbnails with")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+                label: Some("Thumbnail Device")
+            },
+            None
+        ))
+        .context("failed to create the thumbnail device")?;
+        // shared with the thread compiling the scene pipelines
+        let device = Arc::new(device);
+
+        /* Targets */
+        let target = Texture::create_render_target(&device, size, size, Self::FORMAT, wgpu::TextureUsages::COPY_SRC, "Thumbnail Target");
+        let depth_texture = Texture::create_render_target(
+            &device,
+            size,
+            size,
+            Texture::DEPTH_FORMAT,
+            wgpu::TextureUsages::empty(),
+            "Thumbnail Depth Texture"
+        );
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Thumbnail Staging Buffer"),
+            size: (padded_bytes_per_row(size) * size) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+
+        /* Bind Groups */
+        let texture_bind_group_layout = create_texture_bind_group_layout(&device);
+        let camera_bind_group_layout = create_camera_bind_group_layout(&device);
+        let environment_bind_group_layout = create_environment_bind_group_layout(&device);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Thumbnail Camera Bind Group"),
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        // Lighting Rig: the environment is the same for every thumbnail
+        let environment = neutral_environment();
+        let exposure = environment.exposure.multiplier();
+        let rig = [
+            // fill, from the right
+            (Light::directional(Vector3::new(-1.0, -0.2, -0.3), [0.9, 0.95, 1.0], 30000.0), Point3::origin()),
+            // rim, from behind & above
+            (Light::directional(Vector3::new(0.2, -0.6, 1.0), [1.0, 1.0, 1.0], 60000.0), Point3::origin())
+        ];
+        let environment_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Environment Buffer"),
+            contents: bytemuck::cast_slice(&[environment.to_uniform()]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let lights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Lights Buffer"),
+            contents: bytemuck::cast_slice(&[LightsUniform::new(&rig, exposure)]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let occluders_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Occluders Buffer"),
+            contents: bytemuck::cast_slice(&[OccludersUniform::new(&[])]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let environment_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Thumbnail Environment Bind Group"),
+            layout: &environment_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: environment_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: lights_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: occluders_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        /* Pipelines */
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Thumbnail Pipeline Layout"),
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &camera_bind_group_layout,
+                &environment_bind_group_layout
+            ],
+            push_constant_ranges: &[]
+        });
+        let scene_pipelines = ScenePipelines::new(device.clone(), render_pipeline_layout, Self::FORMAT, 1, DepthMode::Standard);
+
+        Ok(Self {
+            device,
+            queue,
+            size,
+            background: environment.sky_color,
+            target,
+            depth_texture,
+            staging_buffer,
+            texture_bind_group_layout,
+            camera_buffer,
+            camera_bind_group,
+            environment_bind_group,
+            scene_pipelines,
+            default_material: Material::new("thumbnail"),
+            sphere: Rc::new(Mesh::sphere(1.0, 48, 24).with_smooth_normals())
+        })
+    }
+
+    // background color (linear) instead of the neutral gray
+    pub fn with_background(mut self, background: [f32; 3]) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // the thumbnail of `subject`, fails if it has nothing to draw
+    pub fn render(&mut self, subject: &ThumbnailSubject) -> Result<image::RgbaImage> {
+        let mesh_draws = match subject {
+            ThumbnailSubject::Mesh(mesh) => vec![MeshDraw::new(mesh, Matrix4::identity())],
+            ThumbnailSubject::Material(material) => vec![MeshDraw::new(&self.sphere, Matrix4::identity()).with_material(material)],
+            ThumbnailSubject::Scene(scene) => {
+                let mut mesh_draws = Vec::new();
+                scene.draw_meshes(&mut mesh_draws);
+                mesh_draws
+            }
+        };
+        let (min, max) = match bounds(&mesh_draws) {
+            Some(bounds) => bounds,
+            None => bail!("nothing to draw in the thumbnail")
+        };
+
+        // Camera: three-quarter view from the front, the bounding sphere fits the frame
+        let center = nalgebra::center(&min, &max);
+        let radius = (nalgebra::distance(&min, &max) * 0.5).max(0.001);
+        let fovy = 30f32.to_radians();
+        let distance = radius / (fovy * 0.5).sin();
+        let mut camera = Camera::perspective(center + Vector3::new(0.8, 0.6, 1.0).normalize() * distance, center);
+        camera.projection = Projection::Perspective(PerspectiveCamera {
+            fovy,
+            znear: (distance - radius * 1.1).max(radius * 0.01),
+            zfar: distance + radius * 1.1
+        });
+        camera.set_viewport(self.size, self.size);
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(&camera);
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+        // Upload: once per mesh & material of the subject
+        let instance_data = mesh_draws
+            .iter()
+            .map(|mesh_draw| InstanceRaw { model: mesh_draw.transform.into() })
+            .collect::<Vec<_>>();
+        let instance_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Instance Buffer"),
+            contents: bytemuck::cast_slice(&instance_data),
+            usage: wgpu::BufferUsages::VERTEX
+        });
+        let mut meshes: Vec<(Rc<Mesh>, MeshBuffers)> = Vec::new();
+        let mut materials: Vec<(Option<Rc<Material>>, ThumbnailMaterial)> = Vec::new();
+        let mut draws = Vec::with_capacity(mesh_draws.len());
+        for mesh_draw in &mesh_draws {
+            let mesh_index = match meshes.iter().position(|(mesh, _)| Rc::ptr_eq(mesh, &mesh_draw.mesh)) {
+                Some(index) => index,
+                None => {
+                    meshes.push((mesh_draw.mesh.clone(), mesh_draw.mesh.upload(&self.device)));
+                    meshes.len() - 1
+                }
+            };
+            let same_material = |material: &Option<Rc<Material>>| match (material, &mesh_draw.material) {
+                (Some(material), Some(draw_material)) => Rc::ptr_eq(material, draw_material),
+                (None, None) => true,
+                _ => false
+            };
+            let material_index = match materials.iter().position(|(material, _)| same_material(material)) {
+                Some(index) => index,
+                None => {
+                    let material = mesh_draw.material.as_deref().unwrap_or(&self.default_material);
+                    // custom materials are drawn with their shader & their own parameters
+                    let binding = match material.shader() {
+                        Some(_) => ThumbnailMaterial::Shader(ShaderMaterialBinding::new(material, &self.device, &self.queue)?),
+                        None => ThumbnailMaterial::Standard(MaterialBinding::new(material, &self.device, &self.queue, &self.texture_bind_group_layout)?)
+                    };
+                    materials.push((mesh_draw.material.clone(), binding));
+                    materials.len() - 1
+                }
+            };
+            let material = mesh_draw.material.as_deref().unwrap_or(&self.default_material);
+            let render_state = mesh_draw.render_state.unwrap_or_else(|| material.render_state());
+            // compiled before the render pass borrows the pipelines
+            self.scene_pipelines.wait(material.shader(), render_state)?;
+            draws.push((mesh_index, material_index, material.pipeline_key(render_state)));
+        }
+
+        // Render
+        let mut command_encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Thumbnail Encoder")
+        });
+        {
+            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Thumbnail Render Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &self.target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: self.background[0] as f64,
+                            g: self.background[1] as f64,
+                            b: self.background[2] as f64,
+                            a: 1.0
+                        }),
+                        store: true
+                    }
+                }],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(DepthMode::Standard.far_depth()),
+                        store: true
+                    }),
+                    stencil_ops: None
+                })
+            });
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            for (instance, (mesh_index, material_index, pipeline_key)) in draws.iter().enumerate() {
+                let instance = instance as u32;
+                let render_pipeline = match self.scene_pipelines.get(pipeline_key) {
+                    Some(render_pipeline) => render_pipeline,
+                    None => continue
+                };
+                let mesh_buffers = &meshes[*mesh_index].1;
+                render_pass.set_pipeline(render_pipeline);
+                let material = &materials[*material_index].1;
+                render_pass.set_bind_group(0, material.bind_group(), material.dynamic_offset().as_slice());
+                render_pass.set_vertex_buffer(0, mesh_buffers.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh_buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..mesh_buffers.indices_num, 0, instance..instance + 1);
+            }
+        }
+
+        // Readback: rows of the staging buffer are padded
+        command_encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.target.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row(self.size)),
+                    rows_per_image: std::num::NonZeroU32::new(self.size)
+                }
+            },
+            wgpu::Extent3d {
+                width: self.size,
+                height: self.size,
+                depth_or_array_layers: 1
+            }
+        );
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+        self.read_image()
+    }
+
+    // Render `subject` to `<asset cache>/thumbnails/<name>.png`, e.g. with the asset's path as `name`, and return the file's path.
+    pub fn render_to_cache(&mut self, assets: &AssetRoot, name: &str, subject: &ThumbnailSubject) -> Result<PathBuf> {
+        let image = self.render(subject)?;
+        let path = cache_path(assets, name);
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).with_context(|| format!("failed to create {}", directory.display()))?;
+        }
+        image.save(&path).with_context(|| format!("failed to save {}", path.display()))?;
+        Ok(path)
+    }
+
+    // the cached thumbnail of `name`, if it was rendered already
+    pub fn cached(assets: &AssetRoot, name: &str) -> Option<PathBuf> {
+        let path = cache_path(assets, name);
+        path.exists().then_some(path)
+    }
+
+    fn read_image(&self) -> Result<image::RgbaImage> {
+        let slice = self.staging_buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).context("failed to read the thumbnail back")?;
+
+        let padded_bytes_per_row = padded_bytes_per_row(self.size) as usize;
+        let bytes_per_row = self.size as usize * 4;
+        let mut pixels = Vec::with_capacity(bytes_per_row * self.size as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row).take(self.size as usize) {
+                pixels.extend_from_slice(&row[..bytes_per_row]);
+            }
+        }
+        self.staging_buffer.unmap();
+        image::RgbaImage::from_raw(self.size, self.size, pixels).context("thumbnail size mismatch")
+    }
+}
+
+fn cache_path(assets: &AssetRoot, name: &str) -> PathBuf {
+    assets.cache_directory().join("thumbnails").join(format!("{}.png", name))
+}
+
+// rows of a texture copy are aligned to 256 bytes
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+// soft white key light from the upper left, no fog & a mid gray background
+fn neutral_environment() -> Environment {
+    Environment {
+        sky_color: [0.18, 0.18, 0.18],
+        sun_direction: Vector3::new(0.5, -0.8, -0.6),
+        sun_color: [1.0, 1.0, 1.0],
+        sun_intensity: 90000.0,
+        ambient_color: [1.0, 1.0, 1.0],
+        ambient_intensity: 15000.0,
+        fog: Fog::new([0.18, 0.18, 0.18], 0.0),
+        ..Environment::new()
+    }
+}
+
+// world space bounding box of the meshes, None if they have no vertices
+fn bounds(mesh_draws: &[MeshDraw]) -> Option<(Point3<f32>, Point3<f32>)> {
+    let mut bounds: Option<(Point3<f32>, Point3<f32>)> = None;
+    for mesh_draw in mesh_draws {
+        for vertex in mesh_draw.mesh.vertices() {
+            let position = mesh_draw.transform.transform_point(&Point3::from(vertex.position));
+            bounds = Some(match bounds {
+                Some((min, max)) => (min.inf(&position), max.sup(&position)),
+                None => (position, position)
+            });
+        }
+    }
+    bounds
+}
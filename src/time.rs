@@ -0,0 +1,93 @@
+use std::time::Instant;
+
+// Frame timing, passed to `Application::update()` & inserted in the scene's resources every frame.
+#[derive(Clone, Copy, Debug)]
+pub struct Time {
+    delta: f32,
+    elapsed: f32,
+    frame_count: u64,
+    fixed_timestep: f32,
+    accumulator: f32,
+    last_tick: Option<Instant>
+}
+
+impl Time {
+    // longest frame taken into account, so a hitch (breakpoint, window drag...) doesn't run hundreds of fixed steps
+    pub const MAX_DELTA: f32 = 0.25;
+
+    // `fixed_timestep` in seconds, see `Application::fixed_update()`
+    pub fn new(fixed_timestep: f32) -> Self {
+        Self {
+            delta: 0.0,
+            elapsed: 0.0,
+            frame_count: 0,
+            fixed_timestep: fixed_timestep.max(f32::EPSILON),
+            accumulator: 0.0,
+            last_tick: None
+        }
+    }
+
+    // seconds since the previous frame, 0.0 on the first frame
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    // seconds since the first frame
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    // index of the current frame, 0 for the first one
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    // seconds simulated by each `Application::fixed_update()` call
+    pub fn fixed_timestep(&self) -> f32 {
+        self.fixed_timestep
+    }
+
+    // 0.0 ~ 1.0 how far the current frame is between two fixed steps, to interpolate their states when drawing
+    pub fn fixed_alpha(&self) -> f32 {
+        self.accumulator / self.fixed_timestep
+    }
+
+    // start a new frame
+    pub(crate) fn tick(&mut self) {
+        let now = Instant::now();
+        let delta = self.last_tick.map(|last_tick| now.duration_since(last_tick).as_secs_f32().min(Self::MAX_DELTA));
+        self.last_tick = Some(now);
+        self.advance(delta);
+    }
+
+    // start a new frame `delta` seconds after the previous one whatever the clock says, see `AppConfig::fixed_frame_delta`
+    pub(crate) fn tick_fixed(&mut self, delta: f32) {
+        let delta = self.last_tick.map(|_| delta);
+        self.last_tick = Some(Instant::now());
+        self.advance(delta);
+    }
+
+    // None on the first frame
+    fn advance(&mut self, delta: Option<f32>) {
+        if let Some(delta) = delta {
+            self.delta = delta;
+            self.elapsed += self.delta;
+            self.frame_count += 1;
+        }
+        self.accumulator += self.delta;
+    }
+
+    // number of fixed steps due this frame, consumed from the accumulated time
+    pub(crate) fn take_fixed_steps(&mut self) -> u32 {
+        let steps = (self.accumulator / self.fixed_timestep).floor();
+        self.accumulator -= steps * self.fixed_timestep;
+        steps as u32
+    }
+}
+
+impl Default for Time {
+    // 60 fixed steps per second
+    fn default() -> Self {
+        Self::new(1.0 / 60.0)
+    }
+}
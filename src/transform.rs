@@ -1,16 +1,285 @@
-use nalgebra::Matrix4;
+use std::collections::HashMap;
 
+use legion::systems::{CommandBuffer, Runnable};
+use legion::world::SubWorld;
+use legion::{Entity, IntoQuery, SystemBuilder};
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+
+// Component placing an entity: its local translation, rotation & scale (relative to its `Parent`, if any),
+// and the global matrix drawn by the renderer, computed from them every frame (see `transform_propagation_system()`).
+// Right-handed like the camera: an entity looks along its -z axis, +y up.
+// tips: the constructors set `global` to the local matrix, which is right for the entities without parent.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Transform {
-    pub local: Matrix4<f32>,
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
     pub global: Matrix4<f32>
 }
 
 impl Transform {
     pub fn new() -> Transform {
+        Self::from_parts(Vector3::zeros(), UnitQuaternion::identity(), Vector3::repeat(1.0))
+    }
+
+    pub fn from_translation(translation: Vector3<f32>) -> Transform {
+        Self::from_parts(translation, UnitQuaternion::identity(), Vector3::repeat(1.0))
+    }
+
+    fn from_parts(translation: Vector3<f32>, rotation: UnitQuaternion<f32>, scale: Vector3<f32>) -> Transform {
+        let mut transform = Transform {
+            translation,
+            rotation,
+            scale,
+            global: Matrix4::identity()
+        };
+        transform.global = transform.to_matrix();
+        transform
+    }
+
+    pub fn with_rotation(mut self, rotation: UnitQuaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self.global = self.to_matrix();
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector3<f32>) -> Self {
+        self.scale = scale;
+        self.global = self.to_matrix();
+        self
+    }
+
+    // the local matrix: scaled, then rotated, then translated, e.g. for `MeshDraw::new()`
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation) * self.rotation.to_homogeneous() * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    // Turn to face `target` (in the parent's space), with the +y axis as close as possible to `up`.
+    // Nothing changes if `target` is on the translation or straight along `up`.
+    pub fn look_at(&mut self, target: &Point3<f32>, up: &Vector3<f32>) {
+        let direction = target.coords - self.translation;
+        if direction.norm_squared() <= f32::EPSILON || direction.cross(up).norm_squared() <= f32::EPSILON {
+            return;
+        }
+        // face_towards() turns +z toward the direction
+        self.rotation = UnitQuaternion::face_towards(&-direction, up);
+    }
+
+    // the local -z axis, in the parent's space
+    pub fn forward(&self) -> Vector3<f32> {
+        self.rotation * -Vector3::z()
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.rotation * Vector3::x()
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.rotation * Vector3::y()
+    }
+
+    // Where `child` ends up in the space of `self`'s parent, i.e. `self.to_matrix() * child.to_matrix()` as a transform.
+    // Its `global` is the one `child` would get attached to `self`.
+    // tips: exact when `self` has a uniform scale, a rotated child of a non-uniformly scaled transform would need a shear.
+    pub fn compose(&self, child: &Transform) -> Transform {
         Transform {
-            local: Matrix4::<f32>::identity(),
-            global: Matrix4::<f32>::identity()
+            translation: self.translation + self.rotation * self.scale.component_mul(&child.translation),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale.component_mul(&child.scale),
+            global: self.global * child.to_matrix()
         }
     }
-}
\ No newline at end of file
+
+    // Between `self` (0.0) & `other` (1.0), with the rotation linearly interpolated & normalized (nlerp):
+    // cheap & fine for close rotations, e.g. between the fixed updates.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Self::from_parts(
+            self.translation.lerp(&other.translation, t),
+            self.rotation.nlerp(&other.rotation, t),
+            self.scale.lerp(&other.scale, t)
+        )
+    }
+
+    // Between `self` (0.0) & `other` (1.0), rotating at a constant speed (slerp): for animations between distant rotations.
+    pub fn slerp(&self, other: &Transform, t: f32) -> Transform {
+        // opposite rotations have no shortest path, the linear one is taken then
+        let rotation = self.rotation
+            .try_slerp(&other.rotation, t, f32::EPSILON)
+            .unwrap_or_else(|| self.rotation.nlerp(&other.rotation, t));
+        Self::from_parts(self.translation.lerp(&other.translation, t), rotation, self.scale.lerp(&other.scale, t))
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Component attaching an entity to another one (a turret on a tank...): its `Transform` is relative to the parent's,
+// so it follows the parent when it moves. See `Scene::set_parent()`.
+// tips: an entity whose parent is gone (or has no `Transform`) is placed by its local transform alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Parent(pub Entity);
+
+// Component listing the entities attached to this one, kept in sync with their `Parent` by the transform propagation.
+// tips: it's added to a parent the frame after its first child is attached.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Children(pub Vec<Entity>);
+
+// System computing `Transform::global` of every entity from its local transform & the ones of its parents, run by the scene after its schedule.
+// Add it to the schedule as well for systems reading the global transforms of the frame, e.g. before aiming at a target.
+pub fn transform_propagation_system() -> impl Runnable {
+    SystemBuilder::new("transform_propagation")
+        .with_query(<(Entity, &Parent)>::query())
+        .with_query(<(Entity, &mut Transform)>::query())
+        .with_query(<(Entity, &mut Children)>::query())
+        .build(|commands, world, _, (parents, transforms, children)| {
+            propagate_transforms(commands, world, parents, transforms, children);
+        })
+}
+
+type ParentQuery = legion::Query<(Entity, &'static Parent)>;
+type TransformQuery = legion::Query<(Entity, &'static mut Transform)>;
+type ChildrenQuery = legion::Query<(Entity, &'static mut Children)>;
+
+fn propagate_transforms(
+    commands: &mut CommandBuffer,
+    world: &mut SubWorld,
+    parents: &mut ParentQuery,
+    transforms: &mut TransformQuery,
+    children: &mut ChildrenQuery
+) {
+    // in the query order, which stays the same from a frame to the other
+    let parent_list = parents
+        .iter(world)
+        .map(|(entity, parent)| (*entity, parent.0))
+        .collect::<Vec<_>>();
+    let parents = parent_list.iter().copied().collect::<HashMap<_, _>>();
+    let locals = transforms
+        .iter_mut(world)
+        .map(|(entity, transform)| (*entity, transform.to_matrix()))
+        .collect::<HashMap<_, _>>();
+
+    // parents before their children: walk up to a known ancestor (or a root), then back down
+    let mut globals: HashMap<Entity, Matrix4<f32>> = HashMap::with_capacity(locals.len());
+    for entity in locals.keys() {
+        let mut chain = vec![*entity];
+        while let Some(parent) = parents.get(chain.last().unwrap()) {
+            // an ancestor without transform ends the chain, a cycle too
+            if globals.contains_key(parent) || !locals.contains_key(parent) || chain.contains(parent) {
+                break;
+            }
+            chain.push(*parent);
+        }
+        for entity in chain.into_iter().rev() {
+            if globals.contains_key(&entity) {
+                continue;
+            }
+            let parent_global = parents.get(&entity).and_then(|parent| globals.get(parent));
+            let global = match parent_global {
+                Some(parent_global) => parent_global * locals[&entity],
+                None => locals[&entity]
+            };
+            globals.insert(entity, global);
+        }
+    }
+    for (entity, transform) in transforms.iter_mut(world) {
+        transform.global = globals[entity];
+    }
+
+    // the children of every parent
+    let mut attached: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for (child, parent) in parent_list {
+        attached.entry(parent).or_default().push(child);
+    }
+    for (entity, children) in children.iter_mut(world) {
+        children.0 = attached.remove(entity).unwrap_or_default();
+    }
+    // the parent may be gone
+    for (parent, children) in attached {
+        commands.exec_mut(move |world, _| {
+            if let Some(mut entry) = world.entry(parent) {
+                entry.add_component(Children(children.clone()));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::{Resources, Schedule, World};
+
+    use super::*;
+
+    fn propagate(world: &mut World) {
+        let mut schedule = Schedule::builder().add_system(transform_propagation_system()).build();
+        schedule.execute(world, &mut Resources::default());
+    }
+
+    fn global(world: &mut World, entity: Entity) -> Matrix4<f32> {
+        world.entry(entity).unwrap().get_component::<Transform>().unwrap().global
+    }
+
+    fn children(world: &mut World, entity: Entity) -> Vec<Entity> {
+        world.entry(entity).unwrap().get_component::<Children>().unwrap().0.clone()
+    }
+
+    fn assert_close(a: Matrix4<f32>, b: Matrix4<f32>) {
+        assert!((a - b).abs().max() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn propagates_through_parent_chain() {
+        let mut world = World::default();
+        let root = Transform::from_translation(Vector3::new(1.0, 0.0, 0.0))
+            .with_rotation(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_2));
+        let child = Transform::from_translation(Vector3::new(0.0, 0.0, -2.0)).with_scale(Vector3::repeat(2.0));
+        let grandchild = Transform::from_translation(Vector3::new(0.0, 1.0, 0.0))
+            .with_rotation(UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.5));
+
+        // spawned children first, so the chain isn't in query order
+        let grandchild_entity = world.push((grandchild,));
+        let child_entity = world.push((child,));
+        let root_entity = world.push((root,));
+        world.entry(grandchild_entity).unwrap().add_component(Parent(child_entity));
+        world.entry(child_entity).unwrap().add_component(Parent(root_entity));
+        propagate(&mut world);
+
+        assert_close(global(&mut world, root_entity), root.to_matrix());
+        assert_close(global(&mut world, child_entity), root.to_matrix() * child.to_matrix());
+        assert_close(global(&mut world, grandchild_entity), root.to_matrix() * child.to_matrix() * grandchild.to_matrix());
+        assert_close(global(&mut world, grandchild_entity), root.compose(&child).compose(&grandchild).to_matrix());
+
+        // the children lists are added by the command buffer, flushed at the end of the schedule
+        assert_eq!(children(&mut world, root_entity), vec![child_entity]);
+        assert_eq!(children(&mut world, child_entity), vec![grandchild_entity]);
+    }
+
+    #[test]
+    fn orphan_is_placed_by_its_local_transform() {
+        let mut world = World::default();
+        let root = Transform::from_translation(Vector3::new(0.0, 5.0, 0.0));
+        let child = Transform::from_translation(Vector3::new(3.0, 0.0, 0.0));
+        let grandchild = Transform::from_translation(Vector3::new(0.0, 0.0, 1.0)).with_scale(Vector3::repeat(0.5));
+
+        let root_entity = world.push((root,));
+        let child_entity = world.push((child, Parent(root_entity)));
+        let grandchild_entity = world.push((grandchild, Parent(child_entity)));
+        propagate(&mut world);
+        assert_close(global(&mut world, grandchild_entity), root.to_matrix() * child.to_matrix() * grandchild.to_matrix());
+
+        // the parent is gone: the child becomes a root, its own child still follows it
+        world.remove(root_entity);
+        propagate(&mut world);
+        assert_close(global(&mut world, child_entity), child.to_matrix());
+        assert_close(global(&mut world, grandchild_entity), child.to_matrix() * grandchild.to_matrix());
+
+        // a parent without transform is ignored the same way
+        let group = world.push((Children::default(),));
+        world.entry(child_entity).unwrap().add_component(Parent(group));
+        propagate(&mut world);
+        assert_close(global(&mut world, child_entity), child.to_matrix());
+        assert_close(global(&mut world, grandchild_entity), child.to_matrix() * grandchild.to_matrix());
+    }
+}
@@ -0,0 +1,284 @@
+use super::error_overlay::{catch_validation_error, ErrorOverlay};
+use super::hot_reload::load_shader;
+
+// How the screen gets covered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionEffect {
+    // the whole screen fades to the color
+    Fade,
+    // a soft edge sweeps across the screen, towards `angle` (radians, 0.0 left to right, counter-clockwise)
+    Wipe { angle: f32 },
+    // a circle closes on the screen center
+    Iris
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TransitionState {
+    Covering,
+    Revealing
+}
+
+// Screen-space transition between scenes, returned by `Application::transition()` & drawn over everything.
+// Typical use: `cover()`, swap the scene once `advance()` returns true (showing the loading indicator meanwhile), then `reveal()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    pub effect: TransitionEffect,
+    pub color: [f32; 3],
+    // seconds to cover or reveal the screen
+    pub duration: f32,
+    state: TransitionState,
+    coverage: f32,
+    loading: Option<f32>,
+    time: f32
+}
+
+impl Transition {
+    // starts revealed
+    pub fn new(effect: TransitionEffect, color: [f32; 3], duration: f32) -> Self {
+        Self {
+            effect,
+            color,
+            duration,
+            state: TransitionState::Revealing,
+            coverage: 0.0,
+            loading: None,
+            time: 0.0
+        }
+    }
+
+    // fade to black in half a second
+    pub fn fade() -> Self {
+        Self::new(TransitionEffect::Fade, [0.0; 3], 0.5)
+    }
+
+    // start at `coverage` instead of revealed, e.g. 1.0 to fade in from the color at startup
+    pub fn with_coverage(mut self, coverage: f32) -> Self {
+        self.coverage = coverage.clamp(0.0, 1.0);
+        self
+    }
+
+    // start covering the screen, from the current coverage
+    pub fn cover(&mut self) {
+        self.state = TransitionState::Covering;
+    }
+
+    // start revealing the screen, from the current coverage
+    pub fn reveal(&mut self) {
+        self.state = TransitionState::Revealing;
+    }
+
+    // Advance the transition by `dt` seconds, returns true on the frame the screen becomes fully covered.
+    pub fn advance(&mut self, dt: f32) -> bool {
+        self.time += dt;
+        let step = if self.duration > 0.0 { dt / self.duration } else { 1.0 };
+        match self.state {
+            TransitionState::Covering => {
+                let was_covered = self.is_covered();
+                self.coverage = (self.coverage + step).min(1.0);
+                !was_covered && self.is_covered()
+            },
+            TransitionState::Revealing => {
+                self.coverage = (self.coverage - step).max(0.0);
+                false
+            }
+        }
+    }
+
+    // 0.0 the scene is fully visible ~ 1.0 fully covered
+    pub fn coverage(&self) -> f32 {
+        self.coverage
+    }
+
+    pub fn is_covered(&self) -> bool {
+        self.coverage >= 1.0
+    }
+
+    // no coverage & not covering
+    pub fn is_idle(&self) -> bool {
+        self.coverage <= 0.0 && self.state == TransitionState::Revealing
+    }
+
+    // Show a spinner at the screen center while covered, with the loading `progress` (0.0 ~ 1.0) as a filling ring.
+    // None hides it, e.g. once the next scene is ready.
+    pub fn set_loading(&mut self, progress: Option<f32>) {
+        self.loading = progress.map(|progress| progress.clamp(0.0, 1.0));
+    }
+
+    pub fn loading(&self) -> Option<f32> {
+        self.loading
+    }
+
+    fn to_uniform(self, aspect: f32) -> TransitionUniform {
+        let (effect, angle) = match self.effect {
+            TransitionEffect::Fade => (0.0, 0.0),
+            TransitionEffect::Wipe { angle } => (1.0, angle),
+            TransitionEffect::Iris => (2.0, 0.0)
+        };
+        let [r, g, b] = self.color;
+        TransitionUniform {
+            color: [r, g, b, 1.0],
+            params: [self.coverage, effect, angle, aspect],
+            loading: [
+                if self.loading.is_some() { 1.0 } else { 0.0 },
+                self.loading.unwrap_or(0.0),
+                // one turn per second
+                self.time.fract() * std::f32::consts::TAU,
+                0.0
+            ]
+        }
+    }
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self::fade()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct TransitionUniform {
+    color: [f32; 4],
+    params: [f32; 4], // coverage, effect, wipe angle, aspect ratio
+    loading: [f32; 4] // shown, progress, spinner angle, unused
+}
+
+// Full-screen pass drawing the transition of the frame, if any.
+pub(crate) struct TransitionPass {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    render_pipeline: Option<wgpu::RenderPipeline>, // None if the pipeline failed to build
+    visible: bool
+}
+
+impl TransitionPass {
+    pub(crate) fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, error_overlay: &mut ErrorOverlay) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Transition Uniform Buffer"),
+            size: std::mem::size_of::<TransitionUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Transition BindGroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ]
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Transition Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Transition Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let render_pipeline = catch_validation_error(device, || {
+            let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Transition Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_shader("transition.wgsl", include_str!("res/shaders/transition.wgsl")))
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Transition Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[], // the full-screen triangle is generated from the vertex index
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[
+                        wgpu::ColorTargetState {
+                            format: config.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL
+                        }
+                    ]
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // screen space, over everything
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None
+            })
+        });
+        let render_pipeline = render_pipeline
+            .map_err(|error| error_overlay.report("Transition Render Pipeline", &error))
+            .ok();
+
+        Self {
+            uniform_buffer,
+            bind_group,
+            render_pipeline,
+            visible: false
+        }
+    }
+
+    // upload the transition of this frame, None or an idle transition draws nothing
+    pub(crate) fn update(&mut self, queue: &wgpu::Queue, transition: Option<Transition>, width: u32, height: u32) {
+        self.visible = match transition {
+            Some(transition) if !transition.is_idle() => {
+                let aspect = width as f32 / height as f32;
+                queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[transition.to_uniform(aspect)]));
+                true
+            },
+            _ => false
+        };
+    }
+
+    pub(crate) fn render(&self, texture_view: &wgpu::TextureView, command_encoder: &mut wgpu::CommandEncoder) {
+        let render_pipeline = match &self.render_pipeline {
+            Some(render_pipeline) if self.visible => render_pipeline,
+            _ => return
+        };
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transition Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: None
+        });
+
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
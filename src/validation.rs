@@ -0,0 +1,109 @@
+use std::fmt;
+
+use legion::Entity;
+
+use super::render_state::RenderState;
+use super::scene::{MaterialHandle, MeshHandle};
+
+// Counts of what a `Scene` holds, see `Scene::validate()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SceneStats {
+    pub entities: usize,
+    // registered with `Scene::add_mesh()` & `Scene::add_material()`
+    pub meshes: usize,
+    pub materials: usize,
+    // entities drawing a mesh, with the vertices & triangles of their meshes
+    pub mesh_instances: usize,
+    pub vertices: usize,
+    pub triangles: usize,
+    pub sprites: usize,
+    pub parallax_layers: usize,
+    pub lights: usize,
+    pub light_occluders: usize
+}
+
+// Content error found in a `Scene`, which would otherwise show up as a missing object, a black sprite or a GPU validation error at runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneIssue {
+    // the entity's `MeshHandle` isn't registered in this scene, it's not drawn
+    MissingMesh { entity: Entity, mesh: MeshHandle },
+    // the entity's `MaterialHandle` isn't registered in this scene, it's drawn with the default material (or not at all for sprites & layers)
+    MissingMaterial { entity: Entity, material: MaterialHandle },
+    // the entity's sprite or parallax layer is textured by its material, which has no albedo
+    MissingTexture { entity: Entity, material: String },
+    // the entity's sprite or parallax layer has a custom material (see `Material::custom()`), it's not drawn
+    CustomMaterialOnSprite { entity: Entity, material: String },
+    // the entity's `MaterialParamOverrides` has a value which isn't a parameter of its material's shader (or not of that type), it's ignored
+    UnknownMaterialParam { entity: Entity, material: String, param: String },
+    // the pipeline of the material's render state failed to build, what uses it isn't drawn
+    MaterialWithoutPipeline { material: String, render_state: RenderState },
+    // an index of the mesh points past its vertices
+    IndexOutOfRange { mesh: MeshHandle, index: u32, vertices: usize },
+    // the index count of the mesh isn't a multiple of 3, the last triangle is dropped
+    IncompleteTriangle { mesh: MeshHandle, indices: usize },
+    // zero intensity or black color
+    DarkLight { entity: Entity },
+    // a point or spot light reaching nothing
+    LightWithoutRange { entity: Entity }
+}
+
+impl fmt::Display for SceneIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneIssue::MissingMesh { entity, mesh } => write!(f, "{:?}: {:?} isn't registered in the scene", entity, mesh),
+            SceneIssue::MissingMaterial { entity, material } => write!(f, "{:?}: {:?} isn't registered in the scene", entity, material),
+            SceneIssue::MissingTexture { entity, material } => write!(f, "{:?}: material `{}` has no albedo texture", entity, material),
+            SceneIssue::CustomMaterialOnSprite { entity, material } => {
+                write!(f, "{:?}: material `{}` has its own shader, sprites & parallax layers only draw the engine's materials", entity, material)
+            },
+            SceneIssue::UnknownMaterialParam { entity, material, param } => {
+                write!(f, "{:?}: material `{}` has no parameter `{}` of that type", entity, material, param)
+            },
+            SceneIssue::MaterialWithoutPipeline { material, render_state } => {
+                write!(f, "material `{}`: the pipeline of {:?} failed to build", material, render_state)
+            },
+            SceneIssue::IndexOutOfRange { mesh, index, vertices } => write!(f, "{:?}: index {} out of its {} vertices", mesh, index, vertices),
+            SceneIssue::IncompleteTriangle { mesh, indices } => write!(f, "{:?}: {} indices isn't a multiple of 3", mesh, indices),
+            SceneIssue::DarkLight { entity } => write!(f, "{:?}: light with zero intensity or a black color", entity),
+            SceneIssue::LightWithoutRange { entity } => write!(f, "{:?}: light with a zero range", entity)
+        }
+    }
+}
+
+// Result of `Scene::validate()`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneReport {
+    pub stats: SceneStats,
+    pub issues: Vec<SceneIssue>
+}
+
+impl SceneReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for SceneReport {
+    // the stats on a line, then an issue per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.stats;
+        write!(
+            f,
+            "{} entities, {} meshes ({} instances, {} vertices, {} triangles), {} materials, {} sprites, {} parallax layers, {} lights, {} occluders",
+            stats.entities,
+            stats.meshes,
+            stats.mesh_instances,
+            stats.vertices,
+            stats.triangles,
+            stats.materials,
+            stats.sprites,
+            stats.parallax_layers,
+            stats.lights,
+            stats.light_occluders
+        )?;
+        for issue in &self.issues {
+            write!(f, "\n{}", issue)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+// Renders every example with its harness (`examples/common`) & compares its last frame with its reference image
+// in `examples/reference/`, failing if one of them differs:
+//
+//     cargo test --test examples -- --ignored
+//
+// Ignored by default: it needs a GPU & a window, and builds every example.
+// After a change meant to alter the rendering, check the new frames & commit them as the references:
+//
+//     EYENGINE_BLESS=1 cargo test --test examples -- --ignored
+//
+// tips: the references are rendered on one GPU, others may differ slightly. Raise `TOLERANCE` rather than re-rendering them.
+
+use std::path::Path;
+use std::process::Command;
+
+// the examples & the features they require
+const EXAMPLES: [(&str, &[&str]); 5] = [
+    ("instancing", &[]),
+    ("lighting", &[]),
+    ("physics", &[]),
+    ("simple", &[]),
+    ("ui", &["egui"])
+];
+const FRAMES: &str = "120";
+// mean difference of the color channels, 0.0 ~ 1.0
+const TOLERANCE: &str = "0.01";
+
+#[test]
+#[ignore]
+fn examples_match_references() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let bless = std::env::var_os("EYENGINE_BLESS").is_some();
+
+    let mut failures = Vec::new();
+    for (example, features) in EXAMPLES {
+        let reference = root.join("examples/reference").join(format!("{}.png", example));
+        if !bless && !reference.exists() {
+            failures.push(format!("{}: no reference image {}, render it with EYENGINE_BLESS=1", example, reference.display()));
+            continue;
+        }
+
+        let mut command = Command::new(env!("CARGO"));
+        command.current_dir(root).args(["run", "--example", example]);
+        if !features.is_empty() {
+            command.arg("--features").arg(features.join(","));
+        }
+        command.args(["--", "--frames", FRAMES]);
+        if bless {
+            let _ = std::fs::remove_file(&reference);
+            command.arg("--screenshot").arg(&reference);
+        } else {
+            command.arg("--compare").arg(&reference).args(["--tolerance", TOLERANCE]);
+        }
+
+        // the harness exits with 1 if the frame differs, 2 if the example failed
+        match command.status() {
+            Ok(status) if status.success() && (!bless || reference.exists()) => {},
+            Ok(status) if status.code() == Some(1) && !bless => failures.push(format!("{}: differs from {}", example, reference.display())),
+            Ok(status) => failures.push(format!("{}: failed to render ({})", example, status)),
+            Err(error) => failures.push(format!("{}: failed to run cargo: {}", example, error))
+        }
+    }
+    assert!(failures.is_empty(), "{} example(s) failed:\n{}", failures.len(), failures.join("\n"));
+}